@@ -9,6 +9,7 @@ use std::fmt;
 use std::ops::Range;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum MachiKind {
     /// 両面待ち。
     ///
@@ -467,7 +468,7 @@ impl AgariTilesets {
     }
 
     /// 英語で表示する
-    pub fn display_en(&self) -> AgariTilesetsDisplayEn {
+    pub fn display_en(&self) -> AgariTilesetsDisplayEn<'_> {
         AgariTilesetsDisplayEn(self)
     }
 }
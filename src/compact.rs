@@ -0,0 +1,399 @@
+//! 天鳳や雀魂などで使われる短い手牌の表記を読み書きする。
+//!
+//! 表記は次の要素を並べたもので、要素の間の空白は省略してよい。
+//!
+//! - 手牌: `234m067p55s1z` のように数字を並べて最後に種類 (m, p, s, z) を書く。 0 は赤ドラの 5 、
+//!   1z から 7z は東南西北白發中を表す。
+//! - 副露: `[789s]` のように括弧で囲む。同じ牌 3 枚ならポン、 4 枚なら明槓、連続した 3 枚ならチーと
+//!   なる。括弧の中の最初に `<` (上家) 、 `^` (対面) 、 `>` (下家) を書くと鳴いた相手を表す。チー
+//!   は上家からしかできない。牌集合の集合は鳴いた相手を持たないので、鳴いた相手は読むときに確かめる
+//!   だけで、 `format` では書かれない。
+//! - 鳴きの種類の明示: `1111z(ankan)` のように牌の後ろに括弧で種類 (chi, pon, kan, ankan) を書く。
+//!   括弧は直前の一つの種類の牌にだけかかるので、 `5s1111z(ankan)` の `5s` は手牌になる。
+//! - アガリ牌: `+5p` はロン、 `+5p(tsumo)` はツモ。
+//!
+//! これ以外の語は、コンテキストの表記 (例: `南家` `リーチ`) か、従来の牌集合の表記 (例: `ドラ表示1m`)
+//! として読む。
+
+use crate::context::{Context, ParseContextError};
+use crate::tile::Tile;
+use crate::tiles::Tiles;
+use crate::tileset::{Error as TilesetError, ParseError as ParseTilesetError, Tag, Tileset};
+use crate::tilesets::{Tilesets, TilesetsError};
+use std::iter::Peekable;
+use std::str::Chars;
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, CompactError>;
+
+#[derive(Debug, Error)]
+pub enum CompactError {
+    /// 牌として読めない。
+    #[error("不明な牌です: {0}")]
+    UnknownTile(String),
+
+    /// 数字の後に種類が書かれていない。
+    #[error("数字の後に種類 (m, p, s, z) がありません: {0}")]
+    UnterminatedTiles(String),
+
+    /// 括弧が閉じていない。
+    #[error("括弧が閉じていません: {0}")]
+    UnclosedBracket(String),
+
+    /// 鳴きの種類や鳴いた相手の表記が読めない。
+    #[error("不明な鳴きの表記です: {0}")]
+    UnknownCall(String),
+
+    /// 上家以外からチーした。
+    #[error("チーは上家からしかできません: {0}")]
+    ChiNotFromShangjia(String),
+
+    /// 牌集合として正しくない。
+    #[error("{0}")]
+    Tileset(#[from] TilesetError),
+
+    /// 従来の表記として読めない。
+    #[error("{0}")]
+    ParseTileset(#[from] ParseTilesetError),
+
+    /// コンテキストの表記が正しくない。
+    #[error("{0}")]
+    Context(#[from] ParseContextError),
+
+    /// 牌集合の集合として正しくない。
+    #[error("{0}")]
+    Tilesets(#[from] TilesetsError),
+}
+
+/// 鳴いた相手。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Source {
+    /// 上家。
+    Shangjia,
+
+    /// 対面。
+    Duimian,
+
+    /// 下家。
+    Xiajia,
+}
+
+/// 種類の文字から、その種類の 1 の牌の番号を得る。
+fn base_index(suit: char) -> Option<usize> {
+    match suit {
+        's' => Some(0),
+        'm' => Some(9),
+        'p' => Some(18),
+        'z' => Some(27),
+        _ => None,
+    }
+}
+
+/// `234m067p` のような牌の並びを読む。並び順は保たれる。
+pub fn parse_tiles(s: &str) -> Result<Vec<Tile>> {
+    let mut tiles = Vec::new();
+    let mut pending = Vec::new();
+
+    for ch in s.chars() {
+        if let Some(number) = ch.to_digit(10) {
+            pending.push(number as usize);
+            continue;
+        }
+
+        let base = base_index(ch).ok_or_else(|| CompactError::UnknownTile(s.to_string()))?;
+        if pending.is_empty() {
+            return Err(CompactError::UnknownTile(s.to_string()));
+        }
+
+        for number in pending.drain(..) {
+            let tile = match (number, base) {
+                (0, 27) => return Err(CompactError::UnknownTile(format!("0{}", ch))),
+                (0, _) => Tile::from_index(base + 4)
+                    .with_red(true)
+                    .expect("a five of a suit can be red."),
+                (1..=7, 27) => Tile::from_index(base + number - 1),
+                (1..=9, _) if base != 27 => Tile::from_index(base + number - 1),
+                _ => return Err(CompactError::UnknownTile(format!("{}{}", number, ch))),
+            };
+            tiles.push(tile);
+        }
+    }
+
+    if !pending.is_empty() {
+        return Err(CompactError::UnterminatedTiles(s.to_string()));
+    }
+
+    Ok(tiles)
+}
+
+/// 牌を種類ごとにまとめて `234m067p55s1z` のように書く。種類は m, p, s, z の順とする。
+pub fn format_tiles(tiles: &[Tile]) -> String {
+    let mut res = String::new();
+    for &(suit, base) in &[('m', 9), ('p', 18), ('s', 0), ('z', 27)] {
+        let len = if suit == 'z' { 7 } else { 9 };
+        let mut numbers: Vec<_> = (tiles.iter())
+            .filter(|t| (base..base + len).contains(&t.index()))
+            .collect();
+        if numbers.is_empty() {
+            continue;
+        }
+
+        numbers.sort_by_key(|t| t.index());
+        for tile in numbers {
+            let number = if tile.is_red() {
+                0
+            } else {
+                tile.index() - base + 1
+            };
+            res.push_str(&number.to_string());
+        }
+        res.push(suit);
+    }
+
+    res
+}
+
+/// 文字の並びを読み進める。
+struct Scanner<'a> {
+    chars: Peekable<Chars<'a>>,
+}
+
+impl Scanner<'_> {
+    /// `pred` を満たす文字が続く限り読む。
+    fn take_while(&mut self, pred: impl Fn(char) -> bool) -> String {
+        let mut res = String::new();
+        while let Some(&ch) = self.chars.peek() {
+            if !pred(ch) {
+                break;
+            }
+            res.push(ch);
+            self.chars.next();
+        }
+        res
+    }
+
+    /// `close` までを読み、 `close` は読み捨てる。
+    fn until(&mut self, open: char, close: char) -> Result<String> {
+        let mut res = String::new();
+        for ch in &mut self.chars {
+            if ch == close {
+                return Ok(res);
+            }
+            res.push(ch);
+        }
+        Err(CompactError::UnclosedBracket(format!("{}{}", open, res)))
+    }
+
+    /// 直後に `(...)` があれば、その中身を読む。
+    fn annotation(&mut self) -> Result<Option<String>> {
+        if self.chars.peek() != Some(&'(') {
+            return Ok(None);
+        }
+        self.chars.next();
+        self.until('(', ')').map(Some)
+    }
+
+    /// 数字の並びと、その後の種類の文字一つまでを読む。
+    fn tiles(&mut self) -> String {
+        let mut res = self.take_while(|ch| ch.is_ascii_digit());
+        if let Some(&suit) = self.chars.peek().filter(|&&ch| base_index(ch).is_some()) {
+            res.push(suit);
+            self.chars.next();
+        }
+        res
+    }
+}
+
+/// 鳴きの種類の表記を読む。
+fn call_tag(annotation: &str) -> Result<Tag> {
+    match annotation {
+        "chi" | "チー" => Ok(Tag::Chi),
+        "pon" | "ポン" => Ok(Tag::Peng),
+        "kan" | "minkan" | "明槓" => Ok(Tag::Minggang),
+        "ankan" | "暗槓" => Ok(Tag::Angang),
+        _ => Err(CompactError::UnknownCall(annotation.to_string())),
+    }
+}
+
+/// `[789s]` の中身から副露を作る。
+fn bracket_meld(content: &str) -> Result<Tileset> {
+    let (source, rest) = match content.chars().next() {
+        Some('<') => (Some(Source::Shangjia), &content[1..]),
+        Some('^') => (Some(Source::Duimian), &content[1..]),
+        Some('>') => (Some(Source::Xiajia), &content[1..]),
+        _ => (None, content),
+    };
+
+    let tiles = parse_tiles(rest)?;
+    let is_same = tiles.iter().all(|&t| Some(&t) == tiles.first());
+    let tag = match (tiles.len(), is_same) {
+        (3, true) => Tag::Peng,
+        (4, true) => Tag::Minggang,
+        _ => Tag::Chi,
+    };
+
+    if tag == Tag::Chi && source.map_or(false, |s| s != Source::Shangjia) {
+        return Err(CompactError::ChiNotFromShangjia(content.to_string()));
+    }
+
+    Ok(Tileset::new(tag, Tiles::new(tiles))?)
+}
+
+/// 短い表記から牌集合の集合を作る。
+pub fn parse(s: &str) -> Result<Tilesets> {
+    let mut scanner = Scanner {
+        chars: s.chars().peekable(),
+    };
+    let mut context = Context::default();
+    let mut hand = Vec::new();
+    let mut tilesets = Vec::new();
+
+    while let Some(&ch) = scanner.chars.peek() {
+        if ch.is_whitespace() {
+            scanner.chars.next();
+        } else if ch == '[' {
+            scanner.chars.next();
+            let content = scanner.until('[', ']')?;
+            tilesets.push(bracket_meld(&content)?);
+        } else if ch == '+' {
+            scanner.chars.next();
+            let tiles = Tiles::new(parse_tiles(&scanner.tiles())?);
+            let tag = match scanner.annotation()?.as_deref() {
+                None | Some("ron") | Some("ロン") => Tag::Ronghe,
+                Some("tsumo") | Some("ツモ") => Tag::Zimo,
+                Some(other) => return Err(CompactError::UnknownCall(other.to_string())),
+            };
+            tilesets.push(Tileset::new(tag, tiles)?);
+        } else if ch.is_ascii_digit() {
+            let tiles = parse_tiles(&scanner.tiles())?;
+            match scanner.annotation()? {
+                Some(annotation) => {
+                    tilesets.push(Tileset::new(call_tag(&annotation)?, Tiles::new(tiles))?)
+                }
+                None => hand.extend(tiles),
+            }
+        } else {
+            let word = scanner.take_while(|ch| !ch.is_whitespace());
+            if !context.annotate(&word)? {
+                tilesets.push(word.parse()?);
+            }
+        }
+    }
+
+    tilesets.push(Tileset::new(Tag::Hand, Tiles::new(hand))?);
+
+    Ok(Tilesets::new(context, tilesets)?)
+}
+
+/// 牌集合の集合を短い表記にする。ドラとコンテキスト、副露の鳴いた相手は含まない。
+pub fn format(tilesets: &Tilesets) -> String {
+    let mut res = vec![format_tiles(&tilesets.hand)];
+
+    for chi in &tilesets.chis {
+        res.push(format!("[{}]", format_tiles(chi)));
+    }
+
+    for peng in &tilesets.pengs {
+        res.push(format!("[{}]", format_tiles(peng)));
+    }
+
+    for minggang in &tilesets.minggangs {
+        res.push(format!("[{}]", format_tiles(minggang)));
+    }
+
+    for angang in &tilesets.angangs {
+        res.push(format!("{}(ankan)", format_tiles(angang)));
+    }
+
+    let last = format_tiles(&[tilesets.last]);
+    if tilesets.is_zimo {
+        res.push(format!("+{}(tsumo)", last));
+    } else {
+        res.push(format!("+{}", last));
+    }
+
+    res.join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::Direction;
+
+    #[test]
+    fn tiles() {
+        let tiles = parse_tiles("067p12z").unwrap();
+        let names: Vec<_> = tiles.iter().map(|t| t.to_string()).collect();
+        assert_eq!(names, vec!["5P", "6p", "7p", "東", "南"]);
+        assert_eq!(format_tiles(&tiles), "067p12z");
+
+        assert!(matches!(
+            parse_tiles("0z"),
+            Err(CompactError::UnknownTile(_))
+        ));
+        assert!(matches!(
+            parse_tiles("8z"),
+            Err(CompactError::UnknownTile(_))
+        ));
+        assert!(matches!(
+            parse_tiles("123"),
+            Err(CompactError::UnterminatedTiles(_))
+        ));
+    }
+
+    #[test]
+    fn parse_hand() {
+        let tilesets = parse("234m067p5s 1111z(ankan) [789s] +5s").unwrap();
+        assert_eq!(tilesets.hand.len(), 7);
+        assert_eq!(tilesets.angangs.len(), 1);
+        assert_eq!(tilesets.chis.len(), 1);
+        assert_eq!(tilesets.last.to_string(), "5s");
+        assert!(!tilesets.is_zimo);
+
+        // 空白を省略し、コンテキストや従来の表記を混ぜる。
+        let tilesets = parse("南家 234m067p5s[^555z][<789s]+5s(tsumo) ドラ表示4s").unwrap();
+        assert_eq!(tilesets.context.player, Direction::South);
+        assert_eq!(tilesets.pengs.len(), 1);
+        assert!(tilesets.is_zimo);
+        assert_eq!(tilesets.doras.to_string(), "5s");
+
+        // 鳴きの種類の明示は直前の種類の牌にだけかかる。
+        let tilesets = parse("234m067p5s1111z(ankan)[789s]+5s").unwrap();
+        assert_eq!(tilesets.hand.len(), 7);
+        assert_eq!(tilesets.angangs.len(), 1);
+        assert_eq!(tilesets.chis.len(), 1);
+        assert_eq!(format(&tilesets), "234m067p5s [789s] 1111z(ankan) +5s");
+
+        assert!(matches!(
+            parse("234m067p55s [>789s] 555z(pon) +5p"),
+            Err(CompactError::ChiNotFromShangjia(_))
+        ));
+        assert!(matches!(
+            parse("234m067p55s [789s 555z(pon) +5p"),
+            Err(CompactError::UnclosedBracket(_))
+        ));
+        assert!(matches!(
+            parse("234m067p55s 555z(daiminkan) [789s] +5p"),
+            Err(CompactError::UnknownCall(_))
+        ));
+    }
+
+    #[test]
+    fn round_trip() {
+        // 鳴いた相手は残らない。
+        let tilesets = parse("1m [^555z] [<123s] [789s] [0555p] +1m").unwrap();
+        assert_eq!(format(&tilesets), "1m [123s] [789s] [555z] [0555p] +1m");
+
+        for hand in &[
+            "234m067p5s [789s] 1111z(ankan) +5s",
+            "1m [123s] [789s] [666z] [0555p] +1m(tsumo)",
+        ] {
+            let tilesets = parse(hand).unwrap();
+            assert_eq!(format(&tilesets), *hand);
+            assert_eq!(
+                parse(&format(&tilesets)).unwrap().to_string(),
+                tilesets.to_string()
+            );
+        }
+    }
+}
@@ -1,15 +1,18 @@
 //! リーチやコンテキストなどを定義する。
 
 use crate::form::Form;
+use crate::tile::Tile;
 use num_derive::FromPrimitive;
 use std::fmt;
 use std::str::FromStr;
 use thiserror::Error;
 
 /// 場風や自風を表す。例 : 東家、東場
-#[derive(Debug, Clone, Copy, PartialEq, Eq, FromPrimitive)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, FromPrimitive)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Direction {
     /// 東場 / 東家。
+    #[default]
     East,
 
     /// 南場 / 南家。
@@ -23,14 +26,18 @@ pub enum Direction {
 }
 
 impl Direction {
-    pub fn display_en(self) -> DirectionDisplayEn {
-        DirectionDisplayEn(self)
+    /// 次の方角 (下家) 。北の次は東に戻る。
+    pub fn next(self) -> Direction {
+        match self {
+            Direction::East => Direction::South,
+            Direction::South => Direction::West,
+            Direction::West => Direction::North,
+            Direction::North => Direction::East,
+        }
     }
-}
 
-impl Default for Direction {
-    fn default() -> Direction {
-        Direction::East
+    pub fn display_en(self) -> DirectionDisplayEn {
+        DirectionDisplayEn(self)
     }
 }
 
@@ -60,7 +67,7 @@ impl fmt::Display for DirectionDisplayEn {
 }
 
 #[derive(Debug, Error)]
-#[error("不明な方角です: {}", 0)]
+#[error("不明な方角です: {0}")]
 pub struct UnknownDirection(String);
 
 impl FromStr for Direction {
@@ -77,9 +84,11 @@ impl FromStr for Direction {
 }
 
 /// どの種類のリーチか。
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Lizhi {
     /// 立直なし
+    #[default]
     None,
 
     /// 立直
@@ -95,40 +104,443 @@ pub enum Lizhi {
     DoubleLizhiIppatsu,
 }
 
-impl Default for Lizhi {
-    fn default() -> Lizhi {
-        Lizhi::None
+impl Lizhi {
+    /// 一発が有効なら一発つきの立直にする。立直していなければそのまま。
+    pub fn with_ippatsu(self, ippatsu: bool) -> Lizhi {
+        match (self, ippatsu) {
+            (Lizhi::Lizhi, true) => Lizhi::LizhiIppatsu,
+            (Lizhi::DoubleLizhi, true) => Lizhi::DoubleLizhiIppatsu,
+            (lizhi, _) => lizhi,
+        }
     }
 }
 
 /// アガリ牌がどういうものだったか。
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, Default)]
 pub enum LastDraw {
     /// ツモ
+    #[default]
     Zimo,
 
     /// ロン
     Ronghe,
 }
 
-impl Default for LastDraw {
-    fn default() -> LastDraw {
-        LastDraw::Zimo
+/// 偶然役 (海底・河底・嶺上・槍槓・天和・地和・人和) がつく状況かどうか。
+///
+/// 手牌の状態と矛盾しないかは `Tilesets` を作るときに確かめる。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Situation {
+    /// 海底牌でツモアガリした。
+    pub haidimoyue: bool,
+
+    /// 河底牌でロンアガリした。
+    pub hedilaoyu: bool,
+
+    /// 槓をして嶺上牌でツモアガリした。
+    pub lingshangkaihua: bool,
+
+    /// 他家の加槓した牌でロンアガリした。
+    pub chenggang: bool,
+
+    /// 親が配牌でアガった。
+    pub tianhe: bool,
+
+    /// 子が鳴きの入っていない最初のツモでアガった。
+    pub dihe: bool,
+
+    /// 子が鳴きの入っていない最初のツモより前にロンアガリした。
+    pub renhe: bool,
+}
+
+impl Situation {
+    /// 偶然役に対応するフラグ。偶然役でなければ None 。
+    fn flag_mut(&mut self, form: Form) -> Option<&mut bool> {
+        match form {
+            Form::Haidimoyue => Some(&mut self.haidimoyue),
+            Form::Hedilaoyu => Some(&mut self.hedilaoyu),
+            Form::Lingshangkaihua => Some(&mut self.lingshangkaihua),
+            Form::Chenggang => Some(&mut self.chenggang),
+            Form::Tianhe => Some(&mut self.tianhe),
+            Form::Dihe => Some(&mut self.dihe),
+            Form::Renhe => Some(&mut self.renhe),
+            _ => None,
+        }
+    }
+
+    /// 偶然役 `form` がつく状況かどうか。
+    pub fn contains(mut self, form: Form) -> bool {
+        matches!(self.flag_mut(form), Some(&mut true))
+    }
+
+    /// 偶然役 `form` がつく状況にする。 `form` が偶然役でなければ何もせずに false を返す。
+    pub fn insert(&mut self, form: Form) -> bool {
+        match self.flag_mut(form) {
+            Some(flag) => {
+                *flag = true;
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// つく偶然役を列挙する。
+    pub fn forms(self) -> impl Iterator<Item = Form> {
+        (LUCKY_FORMS.iter())
+            .map(|&(form, _, _)| form)
+            .filter(move |&form| self.contains(form))
+    }
+
+    pub fn is_empty(self) -> bool {
+        self.forms().next().is_none()
+    }
+}
+
+/// アガったときの局の進み具合。局を進める `Round` と記録を再生する `paipu`・ `mjai` で共通の、偶然役
+/// を決めるための情報。
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct HuleProgress {
+    /// ツモかどうか。
+    pub is_zimo: bool,
+
+    /// 親かどうか。
+    pub is_parent: bool,
+
+    /// 槓の後の嶺上牌をツモったところかどうか。
+    pub is_lingshang: bool,
+
+    /// 山が残っていないかどうか。
+    pub is_haidi: bool,
+
+    /// 加槓された牌でのロンかどうか。
+    pub is_chenggang: bool,
+
+    /// 鳴きの入っていない最初の一巡で、まだ捨て牌がないかどうか。
+    pub is_first: bool,
+}
+
+impl HuleProgress {
+    /// 偶然役がつく状況。嶺上牌は海底牌にならず、槍槓は河底にならない。
+    pub fn situation(self) -> Situation {
+        let HuleProgress {
+            is_zimo,
+            is_parent,
+            is_lingshang,
+            is_haidi,
+            is_chenggang,
+            is_first,
+        } = self;
+
+        Situation {
+            haidimoyue: is_zimo && is_haidi && !is_lingshang,
+            hedilaoyu: !is_zimo && is_haidi && !is_chenggang,
+            lingshangkaihua: is_zimo && is_lingshang,
+            chenggang: is_chenggang,
+            tianhe: is_zimo && is_first && is_parent,
+            dihe: is_zimo && is_first && !is_parent,
+            renhe: !is_zimo && is_first,
+        }
     }
 }
 
 /// 牌を解釈する状況。
 #[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
 pub struct Context {
     pub lizhi: Lizhi,
-    pub lucky_forms: Vec<Form>,
+
+    /// 偶然役がつく状況。
+    pub situation: Situation,
+
     pub place: Direction,
     pub player: Direction,
     pub player_name: String,
+
+    /// 本場 (積み棒の数) 。
+    pub benchang: u32,
+
+    /// 供託 (場に出ているリーチ棒の本数) 。
+    pub gongtuo: u32,
+
+    /// 自分の捨て牌。鳴かれた牌も含む。
+    pub discards: Vec<Tile>,
+
+    /// 最後に自分が打牌してから見逃した牌。同巡内のフリテンの判定に使う。
+    pub passed: Vec<Tile>,
+
+    /// 立直してから見逃した牌。立直後のフリテンの判定に使う。
+    pub passed_after_lizhi: Vec<Tile>,
+}
+
+/// 偶然役と、その短い表記 (日本語・英語) 。
+const LUCKY_FORMS: [(Form, &str, &str); 7] = [
+    (Form::Haidimoyue, "海底", "Haitei"),
+    (Form::Hedilaoyu, "河底", "Houtei"),
+    (Form::Lingshangkaihua, "嶺上", "Rinshan"),
+    (Form::Chenggang, "槍槓", "Chankan"),
+    (Form::Tianhe, "天和", "Tenhou"),
+    (Form::Dihe, "地和", "Chiihou"),
+    (Form::Renhe, "人和", "Renhou"),
+];
+
+#[derive(Debug, Error)]
+pub enum ParseContextError {
+    /// コンテキストの表記として読めない。
+    #[error("不明なコンテキストの表記です: {0}")]
+    UnknownAnnotation(String),
+
+    /// 立直していないのに一発が指定された。
+    #[error("一発は立直の後に指定してください。")]
+    IppatsuWithoutLizhi,
 }
 
 impl Context {
     pub fn is_parent(&self) -> bool {
         self.player == Direction::East
     }
+
+    /// コンテキストの表記の一語 (例: "東場", "South-seat", "リーチ", "一発", "海底", "2本場") を反映す
+    /// る。一発は立直の後に書く。コンテキストの表記でなければ `Ok(false)` を返す。
+    pub fn annotate(&mut self, word: &str) -> Result<bool, ParseContextError> {
+        let number = |s: Option<&str>| s.and_then(|s| s.parse::<u32>().ok());
+
+        if let Some(benchang) =
+            number(word.strip_suffix("本場")).or_else(|| number(word.strip_prefix("Honba")))
+        {
+            self.benchang = benchang;
+        } else if let Some(gongtuo) =
+            number(word.strip_prefix("供託")).or_else(|| number(word.strip_prefix("Deposit")))
+        {
+            self.gongtuo = gongtuo;
+        } else if let Some(place) = (word.strip_suffix('場'))
+            .or_else(|| word.strip_suffix("-round"))
+            .and_then(|dir| dir.parse().ok())
+        {
+            self.place = place;
+        } else if let Some(player) = (word.strip_suffix('家'))
+            .or_else(|| word.strip_suffix("-seat"))
+            .and_then(|dir| dir.parse().ok())
+        {
+            self.player = player;
+        } else {
+            match word {
+                "リーチ" | "立直" | "Riichi" => self.lizhi = Lizhi::Lizhi,
+                "ダブルリーチ" | "ダブル立直" | "DoubleRiichi" | "Double-Riichi" => {
+                    self.lizhi = Lizhi::DoubleLizhi
+                }
+                "一発" | "Ippatsu" => {
+                    self.lizhi = match self.lizhi {
+                        Lizhi::Lizhi | Lizhi::LizhiIppatsu => Lizhi::LizhiIppatsu,
+                        Lizhi::DoubleLizhi | Lizhi::DoubleLizhiIppatsu => Lizhi::DoubleLizhiIppatsu,
+                        Lizhi::None => return Err(ParseContextError::IppatsuWithoutLizhi),
+                    }
+                }
+                _ => {
+                    let found = (LUCKY_FORMS.iter())
+                        .find(|&&(form, ja, en)| word == ja || word == en || word == form.name());
+                    match found {
+                        Some(&(form, _, _)) => {
+                            self.situation.insert(form);
+                        }
+                        None => return Ok(false),
+                    }
+                }
+            }
+        }
+
+        Ok(true)
+    }
+
+    pub fn display_en(&self) -> ContextDisplayEn<'_> {
+        ContextDisplayEn(self)
+    }
+}
+
+/// 空白で区切られたコンテキストの表記 (例: `東場 南家 リーチ 一発 海底`) を読む。
+impl FromStr for Context {
+    type Err = ParseContextError;
+
+    fn from_str(s: &str) -> Result<Context, ParseContextError> {
+        let mut context = Context::default();
+        for word in s.split_whitespace() {
+            if !context.annotate(word)? {
+                return Err(ParseContextError::UnknownAnnotation(word.to_string()));
+            }
+        }
+
+        Ok(context)
+    }
+}
+
+/// 偶然役の短い表記。
+fn lucky_form_names(form: Form) -> (&'static str, &'static str) {
+    (LUCKY_FORMS.iter())
+        .find(|&&(f, _, _)| f == form)
+        .map_or((form.name(), form.name_en()), |&(_, ja, en)| (ja, en))
+}
+
+impl fmt::Display for Context {
+    fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
+        write!(b, "{}場 {}家", self.place, self.player)?;
+
+        match self.lizhi {
+            Lizhi::None => {}
+            Lizhi::Lizhi => write!(b, " リーチ")?,
+            Lizhi::LizhiIppatsu => write!(b, " リーチ 一発")?,
+            Lizhi::DoubleLizhi => write!(b, " ダブルリーチ")?,
+            Lizhi::DoubleLizhiIppatsu => write!(b, " ダブルリーチ 一発")?,
+        }
+
+        for form in self.situation.forms() {
+            write!(b, " {}", lucky_form_names(form).0)?;
+        }
+
+        if self.benchang > 0 {
+            write!(b, " {}本場", self.benchang)?;
+        }
+
+        if self.gongtuo > 0 {
+            write!(b, " 供託{}", self.gongtuo)?;
+        }
+
+        Ok(())
+    }
+}
+
+pub struct ContextDisplayEn<'a>(&'a Context);
+
+impl fmt::Display for ContextDisplayEn<'_> {
+    fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
+        let ContextDisplayEn(context) = self;
+        write!(
+            b,
+            "{}-round {}-seat",
+            context.place.display_en(),
+            context.player.display_en()
+        )?;
+
+        match context.lizhi {
+            Lizhi::None => {}
+            Lizhi::Lizhi => write!(b, " Riichi")?,
+            Lizhi::LizhiIppatsu => write!(b, " Riichi Ippatsu")?,
+            Lizhi::DoubleLizhi => write!(b, " DoubleRiichi")?,
+            Lizhi::DoubleLizhiIppatsu => write!(b, " DoubleRiichi Ippatsu")?,
+        }
+
+        for form in context.situation.forms() {
+            write!(b, " {}", lucky_form_names(form).1)?;
+        }
+
+        if context.benchang > 0 {
+            write!(b, " Honba{}", context.benchang)?;
+        }
+
+        if context.gongtuo > 0 {
+            write!(b, " Deposit{}", context.gongtuo)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hule_progress() {
+        let forms = |progress: HuleProgress| progress.situation().forms().collect::<Vec<_>>();
+
+        // 最後の嶺上牌は海底にならず、最後の牌の加槓への槍槓は河底にならない。
+        let zimo = HuleProgress {
+            is_zimo: true,
+            is_lingshang: true,
+            is_haidi: true,
+            ..HuleProgress::default()
+        };
+        assert_eq!(forms(zimo), vec![Form::Lingshangkaihua]);
+
+        let ronghe = HuleProgress {
+            is_haidi: true,
+            is_chenggang: true,
+            ..HuleProgress::default()
+        };
+        assert_eq!(forms(ronghe), vec![Form::Chenggang]);
+
+        let first = HuleProgress {
+            is_first: true,
+            ..HuleProgress::default()
+        };
+        assert_eq!(forms(first), vec![Form::Renhe]);
+        let zimo = |is_parent| HuleProgress {
+            is_zimo: true,
+            is_parent,
+            ..first
+        };
+        assert_eq!(forms(zimo(true)), vec![Form::Tianhe]);
+        assert_eq!(forms(zimo(false)), vec![Form::Dihe]);
+
+        assert_eq!(Lizhi::Lizhi.with_ippatsu(true), Lizhi::LizhiIppatsu);
+        assert_eq!(Lizhi::None.with_ippatsu(true), Lizhi::None);
+    }
+
+    #[test]
+    fn parse() {
+        let context: Context = "南場 西家 リーチ 一発 海底 2本場 供託1".parse().unwrap();
+        assert_eq!(context.place, Direction::South);
+        assert_eq!(context.player, Direction::West);
+        assert_eq!(context.lizhi, Lizhi::LizhiIppatsu);
+        assert_eq!(
+            context.situation.forms().collect::<Vec<_>>(),
+            vec![Form::Haidimoyue]
+        );
+        assert_eq!(context.benchang, 2);
+        assert_eq!(context.gongtuo, 1);
+
+        let context: Context = "East-round South-seat DoubleRiichi Rinshan"
+            .parse()
+            .unwrap();
+        assert_eq!(context.player, Direction::South);
+        assert_eq!(context.lizhi, Lizhi::DoubleLizhi);
+        assert!(context.situation.lingshangkaihua);
+
+        let context: Context = "西家 人和".parse().unwrap();
+        assert!(context.situation.renhe);
+        assert_eq!(
+            context.display_en().to_string(),
+            "East-round West-seat Renhou"
+        );
+        assert!(!context.situation.contains(Form::Haidimoyue));
+
+        assert!(matches!(
+            "一発".parse::<Context>(),
+            Err(ParseContextError::IppatsuWithoutLizhi)
+        ));
+        assert!(matches!(
+            "中場".parse::<Context>(),
+            Err(ParseContextError::UnknownAnnotation(_))
+        ));
+    }
+
+    #[test]
+    fn round_trip() {
+        let context: Context = "南場 西家 ダブルリーチ 一発 河底 槍槓 3本場"
+            .parse()
+            .unwrap();
+        assert_eq!(
+            context.to_string(),
+            "南場 西家 ダブルリーチ 一発 河底 槍槓 3本場"
+        );
+        assert_eq!(
+            context.display_en().to_string(),
+            "South-round West-seat DoubleRiichi Ippatsu Houtei Chankan Honba3"
+        );
+
+        let en: Context = context.display_en().to_string().parse().unwrap();
+        assert_eq!(en.to_string(), context.to_string());
+        assert_eq!(Context::default().to_string(), "東場 東家");
+    }
 }
@@ -0,0 +1,359 @@
+//! 役の判定の理由を表す。
+//!
+//! 判定のときに、成立した役とそれを成立させた面子、成立しなかった役の候補とその理由を集めておく。
+//! これを見れば、なぜその点数になったのかを説明できる。
+
+use crate::agaritilesets::MachiKind;
+use crate::form::Form;
+use crate::tile::Tile;
+use crate::tiles::Tiles;
+use log::debug;
+use std::fmt;
+
+/// 成立した役。
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Established {
+    /// 役。
+    pub form: Form,
+
+    /// 役を成立させた面子・雀頭。状況による役やドラでは空になる。
+    pub melds: Vec<Tiles>,
+}
+
+/// 成立しなかった役の候補。
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Rejected {
+    /// 候補になった役。清一色・混一色のようにまとめて判定するものは複数になる。
+    pub forms: Vec<Form>,
+
+    /// 成立しなかった理由。
+    pub reason: Reason,
+}
+
+/// 役が成立しなかった理由。
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Reason {
+    /// 副露または暗槓がある。
+    HasCalls,
+
+    /// 門前でない。
+    NotMenqian,
+
+    /// ツモでない。
+    NotZimo,
+
+    /// 喰いタンなしのルールで副露している。
+    NoKuitan,
+
+    /// 人和なしのルールである。
+    NoRenhe,
+
+    /// 満貫・倍満の人和は他の役と複合しない。
+    RenheAlone,
+
+    /// 幺九牌がある。
+    HasYaojiu,
+
+    /// 中張牌がある。
+    HasZhongzhang,
+
+    /// 中張牌がない。
+    NoZhongzhang,
+
+    /// 字牌しかない。
+    NoSuitedTiles,
+
+    /// 字牌以外の牌の種類が二つ以上ある。
+    MixedSuits,
+
+    /// 二枚組でない牌がある。
+    NotAllPairs,
+
+    /// 決まった形と一致しない。
+    ShapeMismatch,
+
+    /// 緑色でない牌がある。
+    NotAllGreen,
+
+    /// 字牌以外の牌がある。
+    NotAllZipai,
+
+    /// 1, 9 以外の牌がある。
+    NotAllTerminals,
+
+    /// 役牌の刻子がない。
+    NoFanpaiKezi,
+
+    /// 順子以外の面子がある。
+    NotAllShunzi,
+
+    /// 雀頭が役牌である。
+    FanpaiQuetou(Tile),
+
+    /// 両面待ちでない。
+    NotLiangmian(MachiKind),
+
+    /// 同じ順子がない。
+    NoSameShunzi,
+
+    /// 索子・萬子・筒子の揃った順子がない。
+    NoSanshokuShunzi,
+
+    /// 索子・萬子・筒子の揃った刻子がない。
+    NoSanshokuKezi,
+
+    /// 123 456 789 が揃っていない。
+    NoStraight,
+
+    /// 暗刻が足りない。
+    TooFewAnkes(usize),
+
+    /// 刻子が足りない。
+    TooFewKezis(usize),
+
+    /// 中張牌だけの面子・雀頭がある。
+    ZhongzhangMeld(Tiles),
+
+    /// 槓が足りない。
+    TooFewGangs(usize),
+
+    /// 雀頭が三元牌でない。
+    QuetouNotSanyuan,
+
+    /// 三元牌の刻子が足りない。
+    TooFewSanyuanKezis(usize),
+
+    /// 風牌が揃っていない。
+    TooFewWinds,
+
+    /// 役満があるので数えない。
+    Yiman,
+}
+
+impl Reason {
+    pub fn display_en(&self) -> ReasonDisplayEn<'_> {
+        ReasonDisplayEn(self)
+    }
+}
+
+impl fmt::Display for Reason {
+    fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Reason::HasCalls => write!(b, "副露または暗槓があります。"),
+            Reason::NotMenqian => write!(b, "門前ではありません。"),
+            Reason::NotZimo => write!(b, "ツモではありません。"),
+            Reason::NoKuitan => write!(b, "喰いタンなしのルールで副露しています。"),
+            Reason::NoRenhe => write!(b, "人和なしのルールです。"),
+            Reason::RenheAlone => write!(b, "人和は他の役と複合しません。"),
+            Reason::HasYaojiu => write!(b, "幺九牌があります。"),
+            Reason::HasZhongzhang => write!(b, "中張牌があります。"),
+            Reason::NoZhongzhang => write!(b, "中張牌がありません。"),
+            Reason::NoSuitedTiles => write!(b, "字牌しかありません。"),
+            Reason::MixedSuits => write!(b, "字牌以外の牌の種類が二つ以上あります。"),
+            Reason::NotAllPairs => write!(b, "二枚組でない牌があります。"),
+            Reason::ShapeMismatch => write!(b, "形が一致しませんでした。"),
+            Reason::NotAllGreen => write!(b, "緑色でない牌が混ざっています。"),
+            Reason::NotAllZipai => write!(b, "字牌以外の牌が混ざっています。"),
+            Reason::NotAllTerminals => write!(b, "1, 9 以外の牌が混ざっています。"),
+            Reason::NoFanpaiKezi => write!(b, "役牌の刻子がありません。"),
+            Reason::NotAllShunzi => write!(b, "順子以外の面子があります。"),
+            Reason::FanpaiQuetou(tile) => write!(b, "雀頭の{}が役牌です。", tile),
+            Reason::NotLiangmian(machi) => write!(b, "両面待ちではありません ({}) 。", machi),
+            Reason::NoSameShunzi => write!(b, "同じ順子がありません。"),
+            Reason::NoSanshokuShunzi => write!(b, "全ての種類が揃っている順子はありません。"),
+            Reason::NoSanshokuKezi => write!(b, "全ての種類が揃っている刻子はありません。"),
+            Reason::NoStraight => write!(b, "123 456 789 を達成している牌はありません。"),
+            Reason::TooFewAnkes(n) => write!(b, "暗刻は{}つしかありません。", n),
+            Reason::TooFewKezis(n) => write!(b, "刻子は{}つしかありません。", n),
+            Reason::ZhongzhangMeld(tiles) => {
+                write!(b, "全てが中張牌で構成された面子{}があります。", tiles)
+            }
+            Reason::TooFewGangs(n) => write!(b, "槓は{}回しか行われていません。", n),
+            Reason::QuetouNotSanyuan => write!(b, "雀頭が三元牌ではありません。"),
+            Reason::TooFewSanyuanKezis(n) => write!(b, "三元牌の刻子は{}つしかありません。", n),
+            Reason::TooFewWinds => write!(b, "方位が足りません。"),
+            Reason::Yiman => write!(b, "役満があるので数えません。"),
+        }
+    }
+}
+
+/// 役が成立しなかった理由 (英語) 。
+pub struct ReasonDisplayEn<'a>(&'a Reason);
+
+impl fmt::Display for ReasonDisplayEn<'_> {
+    fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Reason::HasCalls => write!(b, "hand has calls or concealed kongs"),
+            Reason::NotMenqian => write!(b, "hand is open"),
+            Reason::NotZimo => write!(b, "not won by self-draw"),
+            Reason::NoKuitan => write!(b, "open all simples is not allowed"),
+            Reason::NoRenhe => write!(b, "blessing of man is not allowed"),
+            Reason::RenheAlone => write!(b, "blessing of man does not combine with other yaku"),
+            Reason::HasYaojiu => write!(b, "hand has terminals or honors"),
+            Reason::HasZhongzhang => write!(b, "hand has simples"),
+            Reason::NoZhongzhang => write!(b, "hand has no simples"),
+            Reason::NoSuitedTiles => write!(b, "hand has only honors"),
+            Reason::MixedSuits => write!(b, "hand has more than one suit"),
+            Reason::NotAllPairs => write!(b, "not every tile is in a pair"),
+            Reason::ShapeMismatch => write!(b, "shape does not match"),
+            Reason::NotAllGreen => write!(b, "hand has non-green tiles"),
+            Reason::NotAllZipai => write!(b, "hand has suited tiles"),
+            Reason::NotAllTerminals => write!(b, "hand has tiles other than 1 and 9"),
+            Reason::NoFanpaiKezi => write!(b, "no pung of value tiles"),
+            Reason::NotAllShunzi => write!(b, "not every group is a chow"),
+            Reason::FanpaiQuetou(tile) => write!(b, "pair is a value tile ({})", tile),
+            Reason::NotLiangmian(machi) => {
+                write!(b, "wait is not open ({})", machi.display_en())
+            }
+            Reason::NoSameShunzi => write!(b, "no identical chows"),
+            Reason::NoSanshokuShunzi => write!(b, "no chow in all three suits"),
+            Reason::NoSanshokuKezi => write!(b, "no pung in all three suits"),
+            Reason::NoStraight => write!(b, "no 123 456 789 in one suit"),
+            Reason::TooFewAnkes(n) => write!(b, "only {} concealed pungs", n),
+            Reason::TooFewKezis(n) => write!(b, "only {} pungs", n),
+            Reason::ZhongzhangMeld(tiles) => write!(b, "{} has only simples", tiles),
+            Reason::TooFewGangs(n) => write!(b, "only {} kongs", n),
+            Reason::QuetouNotSanyuan => write!(b, "pair is not a dragon"),
+            Reason::TooFewSanyuanKezis(n) => write!(b, "only {} dragon pungs", n),
+            Reason::TooFewWinds => write!(b, "not all four winds"),
+            Reason::Yiman => write!(b, "not counted with yakuman"),
+        }
+    }
+}
+
+/// 判定の説明。成立した役と、成立しなかった役の候補を判定した順に並べる。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Explanation {
+    /// 成立した役。
+    pub established: Vec<Established>,
+
+    /// 成立しなかった役の候補。
+    pub rejected: Vec<Rejected>,
+}
+
+impl Explanation {
+    pub fn new() -> Explanation {
+        Explanation::default()
+    }
+
+    /// 役が成立したことを記録し、その役を返す。
+    pub(crate) fn establish<'a, I>(&mut self, form: Form, melds: I) -> Form
+    where
+        I: IntoIterator<Item = &'a Tiles>,
+    {
+        debug!("    {}です。", form.name());
+        let melds = melds.into_iter().cloned().collect();
+        self.established.push(Established { form, melds });
+        form
+    }
+
+    /// 役の候補が成立しなかったことを記録する。
+    pub(crate) fn reject(&mut self, forms: &[Form], reason: Reason) {
+        debug!("    {}", reason);
+        self.rejected.push(Rejected {
+            forms: forms.to_vec(),
+            reason,
+        });
+    }
+
+    /// 手牌全体で決まる役 (断么九や清一色など) に、面子・雀頭を割り当てる。
+    pub(crate) fn fill_melds(&mut self, melds: &[Tiles]) {
+        for established in &mut self.established {
+            if established.melds.is_empty() && is_whole_hand(established.form) {
+                established.melds = melds.to_vec();
+            }
+        }
+    }
+
+    /// 最終的に数える役 `forms` に合わせる。数えない役は成立しなかった候補に移す。
+    pub(crate) fn fix(&mut self, forms: &[Form]) {
+        let (established, dropped): (Vec<_>, Vec<_>) =
+            (self.established.drain(..)).partition(|established| forms.contains(&established.form));
+
+        self.established = established;
+        self.established
+            .sort_by_key(|established| forms.iter().position(|&f| f == established.form));
+
+        for Established { form, .. } in dropped {
+            self.reject(&[form], Reason::Yiman);
+        }
+    }
+
+    /// 成立した役で、`form` を成立させた面子・雀頭。
+    pub fn melds(&self, form: Form) -> Option<&[Tiles]> {
+        (self.established.iter())
+            .find(|established| established.form == form)
+            .map(|established| &established.melds[..])
+    }
+
+    pub fn display_en(&self) -> ExplanationDisplayEn<'_> {
+        ExplanationDisplayEn(self)
+    }
+}
+
+/// 手牌全体の形で決まる役かどうか。
+fn is_whole_hand(form: Form) -> bool {
+    matches!(
+        form,
+        Form::Duanyaojiu
+            | Form::Qingyise(_)
+            | Form::Hungyise(_)
+            | Form::Hunlaotou
+            | Form::Luyise
+            | Form::Ziyise
+            | Form::Qinglaotou
+    )
+}
+
+fn write_melds(b: &mut fmt::Formatter, melds: &[Tiles]) -> fmt::Result {
+    for (i, meld) in melds.iter().enumerate() {
+        write!(b, "{}{}", if i == 0 { ": " } else { " " }, meld)?;
+    }
+    Ok(())
+}
+
+impl fmt::Display for Explanation {
+    fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
+        for established in &self.established {
+            write!(b, "{} 成立", established.form.name())?;
+            write_melds(b, &established.melds)?;
+            writeln!(b)?;
+        }
+
+        for rejected in &self.rejected {
+            let names: Vec<_> = rejected.forms.iter().map(|form| form.name()).collect();
+            writeln!(b, "{} 不成立: {}", names.join("・"), rejected.reason)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// 判定の説明 (英語) 。
+pub struct ExplanationDisplayEn<'a>(&'a Explanation);
+
+impl fmt::Display for ExplanationDisplayEn<'_> {
+    fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
+        for established in &self.0.established {
+            write!(b, "{} established", established.form.name_en())?;
+            write_melds(b, &established.melds)?;
+            writeln!(b)?;
+        }
+
+        for rejected in &self.0.rejected {
+            let names: Vec<_> = rejected.forms.iter().map(|form| form.name_en()).collect();
+            writeln!(
+                b,
+                "{} rejected: {}",
+                names.join(" / "),
+                rejected.reason.display_en()
+            )?;
+        }
+
+        Ok(())
+    }
+}
@@ -2,6 +2,8 @@
 
 use crate::agaritilesets::{AgariTilesets, MachiKind};
 use crate::context::Lizhi;
+use crate::explanation::{Explanation, Reason};
+use crate::ruleset::{Renhe, Ruleset};
 use crate::tile::{Order, Tile, TileKind, Zipai};
 use crate::tiles::Tiles;
 use crate::tilesets::Tilesets;
@@ -16,6 +18,7 @@ type SmallVec = smallvec::SmallVec<[Form; 4]>;
 
 /// 翻数・符数。
 #[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Point {
     /// 翻数。
     pub fan: u32,
@@ -45,10 +48,15 @@ impl Point {
     }
 
     pub fn new_yiman() -> Point {
+        Point::new_multiple_yiman(1)
+    }
+
+    /// `n` 倍役満を作る。
+    pub fn new_multiple_yiman(n: u32) -> Point {
         Point {
-            fan: 13,
+            fan: 13 * n,
             fu: 0,
-            yiman: 1,
+            yiman: n,
         }
     }
 
@@ -60,88 +68,86 @@ impl Point {
         self.yiman > 0
     }
 
-    pub fn value(self, is_parent: bool) -> u32 {
-        debug!("-> 点数計算を行います。");
+    /// 基本点を計算する。
+    ///
+    /// 基本点は 符 × 2^(翻 + 2) で、満貫以上は翻数に応じた固定の値になる。実際に支払う点数はこれに
+    /// 親子・ツモロンに応じた倍率をかけて百点単位に切り上げたものである。
+    pub fn base_value(self, ruleset: &Ruleset) -> u32 {
+        debug!("-> 基本点の計算を行います。");
 
-        let calc_few = |fan: u32, fu: u32| {
-            debug!("--> 少翻の点数計算を行います。");
-
-            // 符の倍率
-            let mul = if is_parent { 6 } else { 4 };
-            debug!(
-                "    符の基本の倍率は{}{}倍です。",
-                if is_parent { "親なので" } else { "" },
-                mul
-            );
-
-            debug!("    比較のため満貫の点数を計算します...");
-            let manguan = Point::new_manguan().value(is_parent);
-            debug!("    満貫の点数の計算は終わりました。");
-
-            // 最後の +2 は場ゾロあるいはバンバンと呼ばれる。
-            let raw = fu * mul * 2u32.pow(fan + 2);
-            debug!("    補正なしの点数は{}点です。", raw);
-
-            if raw > manguan {
-                // 満貫を越えていたら満貫に強制。
-                debug!("    満貫の点数を越えているため、満貫に強制します。");
-                manguan
-            } else {
-                // それ以外の場合は定義の計算式に従う。百の位以下を切り上げる。
-                let ceiled = crate::utils::ceil_at(raw, 100);
-                debug!("    切り上げて{}点です。", ceiled);
-                ceiled
-            }
-        };
+        let manguan = 2000;
 
         let value = match self.yiman {
-            0 => match (self.fan, is_parent) {
-                (0..=4, is_parent) => {
-                    let manguan = || Point::new_manguan().value(is_parent);
-                    match (self.fan, self.fu) {
-                        // 4翻30符と3翻60符は切り上げ満貫
-                        (fan @ 4, fu @ 30) | (fan @ 3, fu @ 60) => {
-                            debug!("    {}翻{}符のため、切り上げ満貫です。", fan, fu);
-                            manguan()
-                        }
-
-                        // それ以外は通常の計算ルールに従う
-                        (fan, fu) => calc_few(fan, fu),
-                    }
+            0 => match (self.fan, self.fu) {
+                // 4翻30符と3翻60符は切り上げ満貫
+                (fan @ 4, fu @ 30) | (fan @ 3, fu @ 60) if ruleset.kiriage_manguan => {
+                    debug!("    {}翻{}符のため、切り上げ満貫です。", fan, fu);
+                    manguan
                 }
 
-                (5, true) => 12000,
-                (5, false) => 8000,
+                (fan @ 0..=4, fu) => {
+                    debug!("--> 少翻の点数計算を行います。");
 
-                (6..=7, true) => 18000,
-                (6..=7, false) => 12000,
+                    // 最後の +2 は場ゾロあるいはバンバンと呼ばれる。
+                    let raw = fu * 2u32.pow(fan + 2);
+                    debug!("    補正なしの基本点は{}点です。", raw);
 
-                (8..=10, true) => 24000,
-                (8..=10, false) => 16000,
-
-                (11..=12, true) => 36000,
-                (11..=12, false) => 24000,
+                    if raw > manguan {
+                        // 満貫を越えていたら満貫に強制。
+                        debug!("    満貫の点数を越えているため、満貫に強制します。");
+                        manguan
+                    } else {
+                        raw
+                    }
+                }
 
-                (_, is_parent) => Point::new_yiman().value(is_parent),
+                (5, _) => manguan,
+                (6..=7, _) => 3000,
+                (8..=10, _) => 4000,
+                (11..=12, _) => 6000,
+                _ if ruleset.kazoe_yiman => Point::new_yiman().base_value(ruleset),
+                _ => {
+                    debug!("    数え役満なしのため、三倍満に留めます。");
+                    6000
+                }
             },
 
-            n => n * if is_parent { 48000 } else { 32000 },
+            n => n * 8000,
         };
 
+        debug!("    基本点は{}点です。", value);
+
+        value
+    }
+
+    /// ロンしたときの点数 (ツモの場合は支払いの合計とずれることがある) 。
+    pub fn value(self, is_parent: bool, ruleset: &Ruleset) -> u32 {
+        debug!("-> 点数計算を行います。");
+
+        // 基本点の倍率
+        let mul = if is_parent { 6 } else { 4 };
+        debug!(
+            "    基本点の倍率は{}{}倍です。",
+            if is_parent { "親なので" } else { "" },
+            mul
+        );
+
+        // 百の位以下を切り上げる。
+        let value = crate::utils::ceil_at(self.base_value(ruleset) * mul, 100);
         debug!("    結果は{}点です。", value);
 
         value
     }
 
-    pub fn rank(self, is_parent: bool) -> Option<Rank> {
+    pub fn rank(self, is_parent: bool, ruleset: &Ruleset) -> Option<Rank> {
         let calc_few = || {
-            let value = self.value(is_parent);
-            let manguan = Point::new_manguan().value(is_parent);
+            let value = self.value(is_parent, ruleset);
+            let manguan = Point::new_manguan().value(is_parent, ruleset);
 
             assert!(value <= manguan, "4翻以下で満貫を越えることはありません。");
 
             if value == manguan {
-                Point::new_manguan().rank(is_parent)
+                Point::new_manguan().rank(is_parent, ruleset)
             } else {
                 // 満貫もないときは特に何も表示しない
                 None
@@ -155,7 +161,8 @@ impl Point {
                 6..=7 => Some(Rank::Haneman),
                 8..=10 => Some(Rank::Baiman),
                 11..=12 => Some(Rank::Sanbaiman),
-                x if x >= 13 => Point::new_yiman().rank(is_parent),
+                x if x >= 13 && ruleset.kazoe_yiman => Point::new_yiman().rank(is_parent, ruleset),
+                x if x >= 13 => Some(Rank::Sanbaiman),
                 _ => unreachable!(),
             },
             1 => Some(Rank::Yiman),
@@ -167,25 +174,27 @@ impl Point {
         }
     }
 
-    pub fn rank_en(self, is_parent: bool) -> Option<RankEn> {
-        self.rank(is_parent).map(RankEn)
+    pub fn rank_en(self, is_parent: bool, ruleset: &Ruleset) -> Option<RankEn> {
+        self.rank(is_parent, ruleset).map(RankEn)
     }
 
     pub fn display_en(self) -> PointDisplayEn {
         PointDisplayEn { point: self }
     }
 
-    pub fn display_full(self, is_parent: bool) -> PointDisplayFull {
+    pub fn display_full(self, is_parent: bool, ruleset: &Ruleset) -> PointDisplayFull {
         PointDisplayFull {
             point: self,
             is_parent,
+            ruleset: *ruleset,
         }
     }
 
-    pub fn display_full_en(self, is_parent: bool) -> PointDisplayFullEn {
+    pub fn display_full_en(self, is_parent: bool, ruleset: &Ruleset) -> PointDisplayFullEn {
         PointDisplayFullEn {
             point: self,
             is_parent,
+            ruleset: *ruleset,
         }
     }
 }
@@ -244,19 +253,24 @@ impl Sum for Point {
 pub struct PointDisplayFull {
     point: Point,
     is_parent: bool,
+    ruleset: Ruleset,
 }
 
 impl fmt::Display for PointDisplayFull {
     fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
-        let &PointDisplayFull { point, is_parent } = self;
+        let &PointDisplayFull {
+            point,
+            is_parent,
+            ref ruleset,
+        } = self;
 
         if !point.is_true_yiman() {
             write!(b, "{} ", point)?;
         }
 
-        write!(b, "{}点", point.value(is_parent))?;
+        write!(b, "{}点", point.value(is_parent, ruleset))?;
 
-        if let Some(rank) = point.rank(is_parent) {
+        if let Some(rank) = point.rank(is_parent, ruleset) {
             write!(b, " {}", rank)?;
         }
 
@@ -285,19 +299,24 @@ impl fmt::Display for PointDisplayEn {
 pub struct PointDisplayFullEn {
     point: Point,
     is_parent: bool,
+    ruleset: Ruleset,
 }
 
 impl fmt::Display for PointDisplayFullEn {
     fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
-        let &PointDisplayFullEn { point, is_parent } = self;
+        let &PointDisplayFullEn {
+            point,
+            is_parent,
+            ref ruleset,
+        } = self;
 
         if !point.is_true_yiman() {
             write!(b, "{} ", point.display_en())?;
         }
 
-        write!(b, "{} Points", point.value(is_parent))?;
+        write!(b, "{} Points", point.value(is_parent, ruleset))?;
 
-        if let Some(rank) = point.rank_en(is_parent) {
+        if let Some(rank) = point.rank_en(is_parent, ruleset) {
             write!(b, " {}", rank)?;
         }
 
@@ -375,6 +394,7 @@ impl fmt::Display for RankEn {
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Form {
     /// 立直
     Lizhi,
@@ -504,6 +524,11 @@ pub enum Form {
     /// `bool`: 純正かどうか
     Jiulianbaodeng(bool),
 
+    /// 人和
+    ///
+    /// 満貫・倍満のルールでは他の役と複合せず、人和だけで数えた場合と比べて高い方を採る。
+    Renhe,
+
     /// 地和
     Dihe,
 
@@ -514,6 +539,16 @@ pub enum Form {
     ///
     /// `u32`: ドラの枚数
     Dora(u32),
+
+    /// 赤ドラ
+    ///
+    /// `u32`: 赤ドラの枚数
+    Akadora(u32),
+
+    /// 裏ドラ
+    ///
+    /// `u32`: 裏ドラの枚数
+    Uradora(u32),
 }
 
 impl Form {
@@ -573,9 +608,12 @@ impl Form {
                     "九蓮宝燈"
                 }
             }
+            Form::Renhe => "人和",
             Form::Dihe => "地和",
             Form::Tianhe => "天和",
             Form::Dora(_) => "ドラ",
+            Form::Akadora(_) => "赤ドラ",
+            Form::Uradora(_) => "裏ドラ",
         }
     }
 
@@ -635,13 +673,25 @@ impl Form {
                     "Nine Gates"
                 }
             }
+            Form::Renhe => "Blessing of Man",
             Form::Dihe => "Blessing of Earth",
             Form::Tianhe => "Blessing of Heaven",
             Form::Dora(_) => "Dora",
+            Form::Akadora(_) => "Akadora",
+            Form::Uradora(_) => "Uradora",
         }
     }
 
-    pub fn point(self) -> Point {
+    pub fn point(self, ruleset: &Ruleset) -> Point {
+        // ダブル役満を認めるなら、条件を満たすときは2倍役満とする。
+        let yiman = |is_double: bool| {
+            if is_double && ruleset.double_yiman {
+                Point::new_multiple_yiman(2)
+            } else {
+                Point::new_yiman()
+            }
+        };
+
         match self {
             Form::Lizhi => Point::new(1),
             Form::Ippatsu => Point::new(1),
@@ -669,35 +719,43 @@ impl Form {
             Form::Shousangen => Point::new(2),
             Form::Hunlaotou => Point::new(2),
             Form::Qingyise(is_menqian) => Point::new(if is_menqian { 6 } else { 5 }),
-            Form::Sianke(_) => Point::new_yiman(),
+            Form::Sianke(is_danqi) => yiman(is_danqi),
             Form::Daisangen => Point::new_yiman(),
-            Form::Kokushimuso(_) => Point::new_yiman(),
+            Form::Kokushimuso(is_genuine) => yiman(is_genuine),
             Form::Luyise => Point::new_yiman(),
             Form::Ziyise => Point::new_yiman(),
             Form::Qinglaotou => Point::new_yiman(),
             Form::Sigangzi => Point::new_yiman(),
             Form::Shousushi => Point::new_yiman(),
-            Form::Daisushi => Point::new_yiman(),
-            Form::Jiulianbaodeng(_) => Point::new_yiman(),
+            Form::Daisushi => yiman(true),
+            Form::Jiulianbaodeng(is_genuine) => yiman(is_genuine),
+            Form::Renhe => match ruleset.renhe {
+                Renhe::None => Point::new(0),
+                Renhe::Manguan => Point::new_manguan(),
+                Renhe::Beiman => Point::new(8),
+                Renhe::Yiman => Point::new_yiman(),
+            },
             Form::Dihe => Point::new_yiman(),
             Form::Tianhe => Point::new_yiman(),
             Form::Dora(n) => Point::new(n),
+            Form::Akadora(n) => Point::new(n),
+            Form::Uradora(n) => Point::new(n),
         }
     }
 
     pub fn is_dora(self) -> bool {
-        matches!(self, Form::Dora(_))
+        matches!(self, Form::Dora(_) | Form::Akadora(_) | Form::Uradora(_))
     }
 
-    pub fn display(self) -> FormDisplay {
+    pub fn display(self, ruleset: &Ruleset) -> FormDisplay {
         let name = self.name();
-        let point = self.point();
+        let point = self.point(ruleset);
         FormDisplay { name, point }
     }
 
-    pub fn display_en(self) -> FormDisplayEn {
+    pub fn display_en(self, ruleset: &Ruleset) -> FormDisplayEn {
         let name = self.name_en();
-        let point = self.point();
+        let point = self.point(ruleset);
         FormDisplayEn { name, point }
     }
 }
@@ -735,63 +793,79 @@ impl fmt::Display for FormDisplayEn {
 }
 
 /// [1]立直・[2]ダブルリーチ・[1]一発
-pub fn special_check_lizhi(tilesets: &Tilesets) -> SmallVec {
+pub fn special_check_lizhi(tilesets: &Tilesets, explanation: &mut Explanation) -> SmallVec {
     debug!("--> 立直類を判定...");
     // 立直類は最初から指定されており、 Context として渡されている。
-    match tilesets.context.lizhi {
+    let forms: &[Form] = match tilesets.context.lizhi {
         Lizhi::None => {
             debug!("   立直ではありません。");
-            SmallVec::new()
-        }
-        Lizhi::Lizhi => {
-            debug!("    立直です。");
-            SmallVec::from_elem(Form::Lizhi, 1)
+            &[]
         }
-        Lizhi::LizhiIppatsu => {
-            debug!("    立直・一発です。");
-            let mut v = SmallVec::new();
-            v.push(Form::Lizhi);
-            v.push(Form::Ippatsu);
-            v
-        }
-        Lizhi::DoubleLizhi => {
-            debug!("    ダブル立直です。");
-            SmallVec::from_elem(Form::Doublelizhi, 1)
-        }
-        Lizhi::DoubleLizhiIppatsu => {
-            debug!("    ダブル立直・一発です。");
-            let mut v = SmallVec::new();
-            v.push(Form::Doublelizhi);
-            v.push(Form::Ippatsu);
-            v
-        }
-    }
+        Lizhi::Lizhi => &[Form::Lizhi],
+        Lizhi::LizhiIppatsu => &[Form::Lizhi, Form::Ippatsu],
+        Lizhi::DoubleLizhi => &[Form::Doublelizhi],
+        Lizhi::DoubleLizhiIppatsu => &[Form::Doublelizhi, Form::Ippatsu],
+    };
+
+    (forms.iter())
+        .map(|&form| explanation.establish(form, &[]))
+        .collect()
 }
 
-pub fn special_check_dora(tilesets: &Tilesets) -> Option<Form> {
+/// ドラ・赤ドラ・裏ドラ
+///
+/// - 裏ドラは立直しているときのみ数える。
+pub fn special_check_dora(
+    tilesets: &Tilesets,
+    ruleset: &Ruleset,
+    explanation: &mut Explanation,
+) -> SmallVec {
     debug!("--> ドラを判定...");
-    let count_dora = |tile: Tile| {
-        let num_dora = tilesets.doras.iter().filter(|&&dora| tile == dora).count();
-        let red_dora = if tile.is_red() { 1 } else { 0 };
-        num_dora + red_dora
+    let count = |doras: &Tiles| {
+        tilesets
+            .tiles_without_doras()
+            .map(|tile| doras.iter().filter(|&&dora| tile == dora).count() as u32)
+            .sum::<u32>()
     };
 
-    let num_dora: usize = tilesets.tiles_without_doras().map(count_dora).sum();
+    let mut res = SmallVec::new();
 
+    let num_dora = count(&tilesets.doras);
     debug!("    ドラは {} 枚です。", num_dora);
     if num_dora > 0 {
-        Some(Form::Dora(num_dora as _))
+        res.push(explanation.establish(Form::Dora(num_dora), &[]));
+    }
+
+    if ruleset.akadora > 0 {
+        let num_akadora = tilesets
+            .tiles_without_doras()
+            .filter(|t| t.is_red())
+            .count() as u32;
+        debug!("    赤ドラは {} 枚です。", num_akadora);
+        if num_akadora > 0 {
+            res.push(explanation.establish(Form::Akadora(num_akadora), &[]));
+        }
+    }
+
+    if tilesets.context.lizhi != Lizhi::None {
+        let num_uradora = count(&tilesets.uradoras);
+        debug!("    裏ドラは {} 枚です。", num_uradora);
+        if num_uradora > 0 {
+            res.push(explanation.establish(Form::Uradora(num_uradora), &[]));
+        }
     } else {
-        None
+        debug!("    立直していないので裏ドラは数えません。");
     }
+
+    res
 }
 
 /// [2.5]七対子
-pub fn special_check_qiduizi(tilesets: &Tilesets) -> Option<Form> {
+pub fn special_check_qiduizi(tilesets: &Tilesets, explanation: &mut Explanation) -> Option<Form> {
     debug!("--> 七対子を判定...");
     // ポン・チー・カンをしていたら七対子にならないので終了。
     if tilesets.did_fulou() || !tilesets.angangs.is_empty() {
-        debug!("    副露または暗槓があります。");
+        explanation.reject(&[Form::Qiduizi], Reason::HasCalls);
         return None;
     }
 
@@ -809,48 +883,62 @@ pub fn special_check_qiduizi(tilesets: &Tilesets) -> Option<Form> {
 
     // 一つでも2枚じゃない牌があれば七対子ではない。
     if map.iter().any(|(_, &cnt)| cnt != 2) {
-        debug!("    二枚組でない牌があります。");
+        explanation.reject(&[Form::Qiduizi], Reason::NotAllPairs);
         return None;
     }
 
     // そうであれば七対子
-    debug!("    七対子です。");
-    Some(Form::Qiduizi)
+    let pairs: Vec<Tiles> = (tiles.chunks(2))
+        .map(|pair| Tiles::new(pair.to_vec()))
+        .collect();
+    Some(explanation.establish(Form::Qiduizi, &pairs))
 }
 
 /// [1]門前清自摸和
 ///
 /// - 門前でツモ上がりをした。
-pub fn special_check_menqianqingzimohu(tilesets: &Tilesets) -> Option<Form> {
+pub fn special_check_menqianqingzimohu(
+    tilesets: &Tilesets,
+    explanation: &mut Explanation,
+) -> Option<Form> {
     debug!("--> 門前清自摸和を判定...");
     if !tilesets.is_zimo {
-        debug!("    ツモではありません。");
+        explanation.reject(&[Form::Menqianqingzimohu], Reason::NotZimo);
         return None;
     }
 
     if !tilesets.is_menqian() {
-        debug!("    門前ではありません。");
+        explanation.reject(&[Form::Menqianqingzimohu], Reason::NotMenqian);
         return None;
     }
 
-    debug!("    門前清自摸和です。");
-    Some(Form::Menqianqingzimohu)
+    let last = Tiles::new(vec![tilesets.last]);
+    Some(explanation.establish(Form::Menqianqingzimohu, once(&last)))
 }
 
 /// [1]断么九
 ///
 /// - 手牌が全て中張牌である。
-pub fn special_check_duanyaojiu(tilesets: &Tilesets) -> Option<Form> {
+/// - 喰いタンなしのルールでは門前である。
+pub fn special_check_duanyaojiu(
+    tilesets: &Tilesets,
+    ruleset: &Ruleset,
+    explanation: &mut Explanation,
+) -> Option<Form> {
     debug!("--> 断么九を判定...");
+    if !ruleset.kuitan && !tilesets.is_menqian() {
+        explanation.reject(&[Form::Duanyaojiu], Reason::NoKuitan);
+        return None;
+    }
+
     let has_yaojiu = tilesets.tiles_without_doras().all(|t| t.is_zhongzhang());
 
     if !has_yaojiu {
-        debug!("    幺九牌があります。");
+        explanation.reject(&[Form::Duanyaojiu], Reason::HasYaojiu);
         return None;
     }
 
-    debug!("    断么九です。");
-    Some(Form::Duanyaojiu)
+    Some(explanation.establish(Form::Duanyaojiu, &[]))
 }
 
 /// [6/5]清一色・[3/2]混一色
@@ -860,8 +948,13 @@ pub fn special_check_duanyaojiu(tilesets: &Tilesets) -> Option<Form> {
 ///
 /// 〈混一色〉
 /// - どれか一種類の牌と字牌だけで構成する。
-pub fn special_check_hungyise_qingyise(tilesets: &Tilesets) -> Option<Form> {
+pub fn special_check_hungyise_qingyise(
+    tilesets: &Tilesets,
+    explanation: &mut Explanation,
+) -> Option<Form> {
     debug!("--> 清一色・混一色を判定...");
+    let is_menqian = tilesets.is_menqian();
+    let candidates = [Form::Qingyise(is_menqian), Form::Hungyise(is_menqian)];
 
     // 各面子・雀頭の種類
     let kinds = || tilesets.tiles_without_doras().map(|tile| tile.kind());
@@ -877,7 +970,13 @@ pub fn special_check_hungyise_qingyise(tilesets: &Tilesets) -> Option<Form> {
     let kinds_not_zipai = || kinds().filter(|&kind| kind != TileKind::Zipai);
 
     // 対象となる種類
-    let target_kind = kinds_not_zipai().next()?;
+    let target_kind = match kinds_not_zipai().next() {
+        Some(kind) => kind,
+        None => {
+            explanation.reject(&candidates, Reason::NoSuitedTiles);
+            return None;
+        }
+    };
     debug!("    字牌でない牌の種類の一つは {} です。", target_kind);
 
     // 全てが同じかどうか
@@ -892,16 +991,10 @@ pub fn special_check_hungyise_qingyise(tilesets: &Tilesets) -> Option<Form> {
     );
 
     match (all_same, has_zipai) {
-        (true, false) => {
-            debug!("    清一色です。");
-            Some(Form::Qingyise(tilesets.is_menqian()))
-        }
-        (true, true) => {
-            debug!("    混一色です。");
-            Some(Form::Hungyise(tilesets.is_menqian()))
-        }
+        (true, false) => Some(explanation.establish(Form::Qingyise(is_menqian), &[])),
+        (true, true) => Some(explanation.establish(Form::Hungyise(is_menqian), &[])),
         _ => {
-            debug!("    清一色・混一色ではありません。");
+            explanation.reject(&candidates, Reason::MixedSuits);
             None
         }
     }
@@ -910,17 +1003,16 @@ pub fn special_check_hungyise_qingyise(tilesets: &Tilesets) -> Option<Form> {
 /// [2]混老頭
 ///
 /// - 全ての面子が幺九牌で構成されている。
-pub fn special_check_hunlaotou(tilesets: &Tilesets) -> Option<Form> {
+pub fn special_check_hunlaotou(tilesets: &Tilesets, explanation: &mut Explanation) -> Option<Form> {
     debug!("--> 混老頭を判定...");
     let has_zhongzhang = tilesets.tiles_without_doras().all(|tile| tile.is_yaojiu());
 
     if !has_zhongzhang {
-        debug!("    中張牌があります。");
+        explanation.reject(&[Form::Hunlaotou], Reason::HasZhongzhang);
         return None;
     }
 
-    debug!("    混老頭です。");
-    Some(Form::Hunlaotou)
+    Some(explanation.establish(Form::Hunlaotou, &[]))
 }
 
 /// 特別な形のある役 (国士無双、九蓮宝燈など)
@@ -928,15 +1020,17 @@ pub fn special_check_hunlaotou(tilesets: &Tilesets) -> Option<Form> {
 /// `target` はベースとなる形 (国士無双なら 1s9s1m9m1p9p東南西北白發中 など) で、これプラスその形の
 /// どれか一つの牌だけがダブっている状態がアガリとなる。 `name_genuine` は純正の場合、つまり最初から
 /// `target` がそろっていて最後に引いた牌がダブっている場合につく。たとえば国士無双13面待ちなど。
+///
+/// 成立しなければその理由を返す。
 pub fn special_check_certadebugrm(
     tilesets: &Tilesets,
     mut target: Tiles,
     form_constructor: fn(bool) -> Form,
-) -> Option<Form> {
+) -> Result<Form, Reason> {
     // ポン・チー・カンをしていたらならないので終了。
     if tilesets.did_fulou() || !tilesets.angangs.is_empty() {
         debug!("    副露があるため所定の形にできません。");
-        return None;
+        return Err(Reason::HasCalls);
     }
 
     // 手札に最後に引いてきた牌を追加する。
@@ -952,7 +1046,7 @@ pub fn special_check_certadebugrm(
         if target == hand {
             let form = form_constructor(add == tilesets.last);
             debug!("    形が一致したので {} 成立です。", form.name());
-            return Some(form);
+            return Ok(form);
         }
 
         target.remove(
@@ -964,13 +1058,16 @@ pub fn special_check_certadebugrm(
     }
 
     debug!("    形が一致しませんでした。");
-    None
+    Err(Reason::ShapeMismatch)
 }
 
 /// [13]国士無双
-pub fn special_check_kokushimuso(tilesets: &Tilesets) -> Option<Form> {
+pub fn special_check_kokushimuso(
+    tilesets: &Tilesets,
+    explanation: &mut Explanation,
+) -> Option<Form> {
     debug!("--> 国士無双を判定...");
-    special_check_certadebugrm(
+    let res = special_check_certadebugrm(
         tilesets,
         Tiles::new(vec![
             Tile::Suozi(Order::new(1).unwrap()),
@@ -988,11 +1085,37 @@ pub fn special_check_kokushimuso(tilesets: &Tilesets) -> Option<Form> {
             Tile::Zipai(Zipai::Zhong),
         ]),
         Form::Kokushimuso,
-    )
+    );
+
+    explain_certadebugrm(tilesets, res, Form::Kokushimuso(false), explanation)
+}
+
+/// 特別な形のある役の判定結果を記録する。面子は手牌全体とする。
+fn explain_certadebugrm(
+    tilesets: &Tilesets,
+    res: Result<Form, Reason>,
+    candidate: Form,
+    explanation: &mut Explanation,
+) -> Option<Form> {
+    match res {
+        Ok(form) => {
+            let hand: Tiles = (tilesets.hand.iter().copied())
+                .chain(once(tilesets.last))
+                .collect();
+            Some(explanation.establish(form, once(&hand)))
+        }
+        Err(reason) => {
+            explanation.reject(&[candidate], reason);
+            None
+        }
+    }
 }
 
 /// [13]九蓮宝燈
-pub fn special_check_jiulianbaodeng(tilesets: &Tilesets) -> Option<Form> {
+pub fn special_check_jiulianbaodeng(
+    tilesets: &Tilesets,
+    explanation: &mut Explanation,
+) -> Option<Form> {
     debug!("--> 九蓮宝燈を判定...");
     let constructors: Vec<fn(Order) -> Tile> = vec![Tile::Suozi, Tile::Wanzi, Tile::Tongzi];
 
@@ -1012,56 +1135,61 @@ pub fn special_check_jiulianbaodeng(tilesets: &Tilesets) -> Option<Form> {
         Order::new(9).unwrap(),
     ];
 
-    constructors
-        .into_iter()
-        .filter_map(move |ctor| {
-            special_check_certadebugrm(
-                tilesets,
-                orders.iter().map(|&o| ctor(o)).collect(),
-                Form::Jiulianbaodeng,
-            )
-        })
-        .next()
+    let mut res = Err(Reason::ShapeMismatch);
+    for ctor in constructors {
+        res = special_check_certadebugrm(
+            tilesets,
+            orders.iter().map(|&o| ctor(o)).collect(),
+            Form::Jiulianbaodeng,
+        );
+
+        if res != Err(Reason::ShapeMismatch) {
+            break;
+        }
+    }
+
+    explain_certadebugrm(tilesets, res, Form::Jiulianbaodeng(false), explanation)
 }
 
 /// [13]緑一色
 ///
 /// - 全ての牌が緑一色を構成する牌である。
-pub fn special_check_luyise(tilesets: &Tilesets) -> Option<Form> {
+pub fn special_check_luyise(tilesets: &Tilesets, explanation: &mut Explanation) -> Option<Form> {
     debug!("--> 緑一色を判定...");
     let all_green = tilesets.tiles_without_doras().all(|tile| tile.is_green());
 
     if !all_green {
-        debug!("    緑色でない牌が混ざっています。");
+        explanation.reject(&[Form::Luyise], Reason::NotAllGreen);
         return None;
     }
 
-    debug!("    緑一色です。");
-    Some(Form::Luyise)
+    Some(explanation.establish(Form::Luyise, &[]))
 }
 
 /// [13]字一色
 ///
 /// - 全ての牌が字牌である。
-pub fn special_check_ziyise(tilesets: &Tilesets) -> Option<Form> {
+pub fn special_check_ziyise(tilesets: &Tilesets, explanation: &mut Explanation) -> Option<Form> {
     debug!("--> 字一色を判定...");
     let all_zipai = tilesets
         .tiles_without_doras()
         .all(|tile| tile.kind() == TileKind::Zipai);
 
     if !all_zipai {
-        debug!("    字牌以外の牌が混ざっています。");
+        explanation.reject(&[Form::Ziyise], Reason::NotAllZipai);
         return None;
     }
 
-    debug!("    字一色です。");
-    Some(Form::Ziyise)
+    Some(explanation.establish(Form::Ziyise, &[]))
 }
 
 /// [13]清老頭
 ///
 /// - 全ての牌が 1,9 牌のみである。
-pub fn special_check_qinglaotou(tilesets: &Tilesets) -> Option<Form> {
+pub fn special_check_qinglaotou(
+    tilesets: &Tilesets,
+    explanation: &mut Explanation,
+) -> Option<Form> {
     debug!("--> 清老頭を判定...");
 
     let all_19 = tilesets
@@ -1069,33 +1197,36 @@ pub fn special_check_qinglaotou(tilesets: &Tilesets) -> Option<Form> {
         .all(|tile| tile.kind() != TileKind::Zipai && tile.is_yaojiu());
 
     if !all_19 {
-        debug!("    1, 9 以外の牌が混ざっています。");
+        explanation.reject(&[Form::Qinglaotou], Reason::NotAllTerminals);
         return None;
     }
 
-    debug!("    清老頭です。");
-    Some(Form::Qinglaotou)
+    Some(explanation.establish(Form::Qinglaotou, &[]))
 }
 
 /// [n]役牌
 ///
 /// - 刻子・槓子が役牌である。一つにつき1翻。
-pub fn check_fanpai(agari: &AgariTilesets) -> Option<Form> {
+pub fn check_fanpai(agari: &AgariTilesets, explanation: &mut Explanation) -> Option<Form> {
     debug!("--> 役牌を判定...");
 
-    let sum = agari
+    let fanpais: Vec<(&Tiles, u32)> = agari
         .kezis()
         .map(|tile| {
             let num = tile.first().num_fan(agari.context());
             debug!("    {}の役は{}翻です。", tile, num);
-            num
+            (tile, num)
         })
-        .sum();
+        .filter(|&(_, num)| num != 0)
+        .collect();
 
+    let sum = fanpais.iter().map(|&(_, num)| num).sum();
     debug!("    役の合計は{}翻です。", sum);
     if sum != 0 {
-        Some(Form::Fanpai(sum))
+        let melds = fanpais.into_iter().map(|(tiles, _)| tiles);
+        Some(explanation.establish(Form::Fanpai(sum), melds))
     } else {
+        explanation.reject(&[Form::Fanpai(1)], Reason::NoFanpaiKezi);
         None
     }
 }
@@ -1105,31 +1236,32 @@ pub fn check_fanpai(agari: &AgariTilesets) -> Option<Form> {
 /// - 4面子が順子である。
 /// - 雀頭が役牌でない。
 /// - 両面待ちである。
-pub fn check_pinghe(agari: &AgariTilesets) -> Option<Form> {
+pub fn check_pinghe(agari: &AgariTilesets, explanation: &mut Explanation) -> Option<Form> {
     debug!("--> 平和を判定...");
 
     if !agari.is_menqian() {
-        debug!("    門前ではありません。");
+        explanation.reject(&[Form::Pinghe], Reason::NotMenqian);
         return None;
     }
 
     if agari.shunzis().count() != 4 {
-        debug!("    順子以外の面子があります。");
+        explanation.reject(&[Form::Pinghe], Reason::NotAllShunzi);
         return None;
     }
 
-    if agari.quetou().first().num_fan(agari.context()) >= 1 {
-        debug!("    雀頭が役牌です。");
+    let quetou = agari.quetou().first();
+    if quetou.num_fan(agari.context()) >= 1 {
+        explanation.reject(&[Form::Pinghe], Reason::FanpaiQuetou(quetou));
         return None;
     }
 
     if agari.machi() != MachiKind::Liangmian {
-        debug!("    両面待ちではありません。");
+        explanation.reject(&[Form::Pinghe], Reason::NotLiangmian(agari.machi()));
         return None;
     }
 
-    debug!("    平和です。");
-    Some(Form::Pinghe)
+    let melds = agari.shunzis().chain(once(agari.quetou()));
+    Some(explanation.establish(Form::Pinghe, melds))
 }
 
 /// [1/0]一盃口・[3/0]二盃口
@@ -1141,49 +1273,51 @@ pub fn check_pinghe(agari: &AgariTilesets) -> Option<Form> {
 ///
 /// 〈二盃口〉
 /// - 同種の牌で同じ順序の順子が2面子、これが2組ある。一盃口二つ。
-pub fn check_yibeikou_liangbeigou(agari: &AgariTilesets) -> Option<Form> {
+pub fn check_yibeikou_liangbeigou(
+    agari: &AgariTilesets,
+    explanation: &mut Explanation,
+) -> Option<Form> {
     debug!("--> 一盃口・二盃口を判定...");
+    let candidates = [Form::Yibeikou, Form::Liangbeigou];
     if !agari.is_menqian() {
-        debug!("    門前ではありません。");
+        explanation.reject(&candidates, Reason::NotMenqian);
         return None;
     }
 
-    let mut map = HashMap::new();
+    let mut map: HashMap<Tile, Vec<&Tiles>> = HashMap::new();
     for shunzi in agari.shunzis() {
-        *map.entry(shunzi.first()).or_default() += 1;
+        map.entry(shunzi.first()).or_default().push(shunzi);
     }
 
     let mut cnt = 0;
-    for (_, num) in map {
-        match num {
+    let mut melds = Vec::new();
+    for (_, shunzis) in map {
+        match shunzis.len() {
             // 同じ順子が 2 組あるならそれで二盃口が構成される。
             4 => {
                 debug!("    同じ順子が2組ありました。");
                 cnt += 2;
+                melds.extend(shunzis);
             }
             // 同じ順子が 2 または 3 あるならそれで一盃口が構成される。
             // 今後他の牌についてまた一盃口が構成されれば二盃口となる。
             2 | 3 => {
                 debug!("    同じ順子が1組ありました。");
                 cnt += 1;
+                melds.extend(&shunzis[..2]);
             }
             _ => {}
         }
     }
+    melds.sort_by_key(|tiles| tiles.first());
 
     match cnt {
         0 => {
-            debug!("    一盃口・二盃口ではありません。");
+            explanation.reject(&candidates, Reason::NoSameShunzi);
             None
         }
-        1 => {
-            debug!("    一盃口です。");
-            Some(Form::Yibeikou)
-        }
-        2 => {
-            debug!("    二盃口です。");
-            Some(Form::Liangbeigou)
-        }
+        1 => Some(explanation.establish(Form::Yibeikou, melds)),
+        2 => Some(explanation.establish(Form::Liangbeigou, melds)),
         _ => panic!("二盃口以上があります。"),
     }
 }
@@ -1191,58 +1325,54 @@ pub fn check_yibeikou_liangbeigou(agari: &AgariTilesets) -> Option<Form> {
 /// [2/1]三色同順
 ///
 /// - 索子・萬子・筒子で同じ数字から始まる順子を作る。
-pub fn check_sanshoku_dojun(agari: &AgariTilesets) -> Option<Form> {
+pub fn check_sanshoku_dojun(agari: &AgariTilesets, explanation: &mut Explanation) -> Option<Form> {
     debug!("--> 三色同順を判定...");
+    // 喰い下がりがあるので注意。
+    let form = Form::Sanshokudojun(agari.is_menqian());
 
-    // 「その順序から始まる順子にはどの種類の牌があるか」を集める
-    let mut map: HashMap<Option<Order>, HashSet<TileKind>> = HashMap::new();
-    for tile in agari.shunzis().map(|t| t.first()) {
-        map.entry(tile.order()).or_default().insert(tile.kind());
-    }
-
-    // そのなかのある一つの順序について、索子も萬子も筒子もあるということなら三色同順
-    let does_match = map.into_iter().any(|(_, kinds)| {
-        kinds.contains(&TileKind::Suozi)
-            && kinds.contains(&TileKind::Wanzi)
-            && kinds.contains(&TileKind::Tongzi)
-    });
-
-    if !does_match {
-        debug!("    全ての種類が揃っている順子はありませんでした。");
-        return None;
+    match find_sanshoku(agari.shunzis()) {
+        Some(melds) => Some(explanation.establish(form, melds)),
+        None => {
+            explanation.reject(&[form], Reason::NoSanshokuShunzi);
+            None
+        }
     }
+}
 
-    debug!("    三色同順です。");
-    // 喰い下がりがあるので注意。
-    Some(Form::Sanshokudojun(agari.is_menqian()))
+/// 索子・萬子・筒子で同じ順序から始まる面子を探す。
+fn find_sanshoku<'a, I: Iterator<Item = &'a Tiles>>(mianzis: I) -> Option<Vec<&'a Tiles>> {
+    // 「その順序から始まる面子にはどの種類の牌があるか」を集める
+    let mut map: HashMap<Option<Order>, HashMap<TileKind, &Tiles>> = HashMap::new();
+    for tiles in mianzis {
+        let tile = tiles.first();
+        map.entry(tile.order())
+            .or_default()
+            .entry(tile.kind())
+            .or_insert(tiles);
+    }
+
+    // そのなかのある一つの順序について、索子も萬子も筒子もあるということなら三色
+    map.into_iter().find_map(|(_, kinds)| {
+        [TileKind::Suozi, TileKind::Wanzi, TileKind::Tongzi]
+            .iter()
+            .map(|kind| kinds.get(kind).copied())
+            .collect()
+    })
 }
 
 /// [2]三色同刻
 ///
 /// - 索子・萬子・筒子で同じ数字からなる刻子を作る。
-pub fn check_sanshoku_doko(agari: &AgariTilesets) -> Option<Form> {
+pub fn check_sanshoku_doko(agari: &AgariTilesets, explanation: &mut Explanation) -> Option<Form> {
     debug!("--> 三色同刻を判定...");
 
-    // 「その順序から始まる刻子にはどの種類の牌があるか」を集める
-    let mut map: HashMap<Option<Order>, HashSet<TileKind>> = HashMap::new();
-    for tile in agari.kezis().map(|t| t.first()) {
-        map.entry(tile.order()).or_default().insert(tile.kind());
-    }
-
-    // そのなかのある一つの順序について、索子も萬子も筒子もあるということなら三色同刻
-    let does_match = map.into_iter().any(|(_, kinds)| {
-        kinds.contains(&TileKind::Suozi)
-            && kinds.contains(&TileKind::Wanzi)
-            && kinds.contains(&TileKind::Tongzi)
-    });
-
-    if !does_match {
-        debug!("    全ての種類が揃っている刻子はありませんでした。");
-        return None;
+    match find_sanshoku(agari.kezis()) {
+        Some(melds) => Some(explanation.establish(Form::Sanshokudoko, melds)),
+        None => {
+            explanation.reject(&[Form::Sanshokudoko], Reason::NoSanshokuKezi);
+            None
+        }
     }
-
-    debug!("    三色同刻です。");
-    Some(Form::Sanshokudoko)
 }
 
 /// [13]四暗刻・[2]三暗刻
@@ -1252,19 +1382,19 @@ pub fn check_sanshoku_doko(agari: &AgariTilesets) -> Option<Form> {
 ///
 /// 〈三暗刻〉
 /// - 暗刻が3つある
-pub fn check_sananke_sianke(agari: &AgariTilesets) -> Option<Form> {
+pub fn check_sananke_sianke(agari: &AgariTilesets, explanation: &mut Explanation) -> Option<Form> {
     debug!("--> 四暗刻・三暗刻を判定...");
 
     let count = agari.ankes().count();
 
     if count == 4 {
-        debug!("    四暗刻です。");
-        Some(Form::Sianke(agari.machi() == MachiKind::Danqi))
+        let form = Form::Sianke(agari.machi() == MachiKind::Danqi);
+        Some(explanation.establish(form, agari.ankes()))
     } else if count == 3 {
-        debug!("    三暗刻です。");
-        Some(Form::Sananke)
+        Some(explanation.establish(Form::Sananke, agari.ankes()))
     } else {
-        debug!("    暗刻は{}つしかありません。", count);
+        let candidates = [Form::Sianke(false), Form::Sananke];
+        explanation.reject(&candidates, Reason::TooFewAnkes(count));
         None
     }
 }
@@ -1272,54 +1402,68 @@ pub fn check_sananke_sianke(agari: &AgariTilesets) -> Option<Form> {
 /// [2/1]一気通貫
 ///
 /// - どれか一種類の牌で 123 456 789 を達成する
-pub fn check_ikki_tukan(agari: &AgariTilesets) -> Option<Form> {
+pub fn check_ikki_tukan(agari: &AgariTilesets, explanation: &mut Explanation) -> Option<Form> {
     debug!("--> 一気通貫を判定...");
+    let form = Form::Ikkitsukan(agari.is_menqian());
 
-    let mut map: HashMap<TileKind, HashSet<Option<Order>>> = HashMap::new();
-    for tile in agari.shunzis().map(|t| t.first()) {
-        map.entry(tile.kind()).or_default().insert(tile.order());
+    let mut map: HashMap<TileKind, HashMap<Option<Order>, &Tiles>> = HashMap::new();
+    for tiles in agari.shunzis() {
+        let tile = tiles.first();
+        map.entry(tile.kind())
+            .or_default()
+            .entry(tile.order())
+            .or_insert(tiles);
     }
 
-    let does_match = map.into_iter().any(|(_, orders)| {
-        orders.contains(&Some(Order::new(1).unwrap()))
-            && orders.contains(&Some(Order::new(4).unwrap()))
-            && orders.contains(&Some(Order::new(7).unwrap()))
+    let found: Option<Vec<&Tiles>> = map.into_iter().find_map(|(_, orders)| {
+        [1, 4, 7]
+            .iter()
+            .map(|&n| orders.get(&Some(Order::new(n).unwrap())).copied())
+            .collect()
     });
 
-    if !does_match {
-        debug!("    123 456 789 を達成している牌はありませんでした。");
-        return None;
+    match found {
+        Some(melds) => Some(explanation.establish(form, melds)),
+        None => {
+            explanation.reject(&[form], Reason::NoStraight);
+            None
+        }
     }
-
-    debug!("    一気通貫です。");
-    Some(Form::Ikkitsukan(agari.is_menqian()))
 }
 
 /// [2]対々和
 ///
 /// - 刻子が4つある。
-pub fn check_duiduihe(agari: &AgariTilesets) -> Option<Form> {
+pub fn check_duiduihe(agari: &AgariTilesets, explanation: &mut Explanation) -> Option<Form> {
     debug!("--> 対々和を判定...");
 
     let count = agari.kezis().count();
 
     if count != 4 {
-        debug!("    刻子が{}個しかありません。", count);
+        explanation.reject(&[Form::Duiduihe], Reason::TooFewKezis(count));
         return None;
     }
 
-    debug!("    対々和です。");
-    Some(Form::Duiduihe)
+    Some(explanation.establish(Form::Duiduihe, agari.kezis()))
 }
 
 /// [2/1]混全帯幺九・[3/2]純全帯公九
 ///
 /// 〈混全帯幺九〉
 /// - 全ての面子と雀頭に幺九牌が絡んでいる。
+///
 /// 〈純全帯公九〉
 /// - 全ての面子と雀頭に 1, 9 が絡んでいる。
-pub fn check_hunquandaiyaojiu_chunquandaiyaojiu(agari: &AgariTilesets) -> Option<Form> {
+pub fn check_hunquandaiyaojiu_chunquandaiyaojiu(
+    agari: &AgariTilesets,
+    explanation: &mut Explanation,
+) -> Option<Form> {
     debug!("--> 混全帯幺九・純全帯公九を判定...");
+    let is_menqian = agari.is_menqian();
+    let candidates = [
+        Form::Chunquandaiyaojiu(is_menqian),
+        Form::Hunquandaiyaojiu(is_menqian),
+    ];
 
     let mut has_zipai = false;
     let mut has_zhongzhang = false;
@@ -1327,10 +1471,7 @@ pub fn check_hunquandaiyaojiu_chunquandaiyaojiu(agari: &AgariTilesets) -> Option
     for tiles in agari.mianzis().chain(once(agari.quetou())) {
         // その面子の牌の全てが中張牌であれば対象の役のどれも成立しえないので放置。
         if tiles.iter().all(|&tile| tile.is_zhongzhang()) {
-            debug!(
-                "    全てが中張牌で構成された面子{}があったため、いずれも成立しません。",
-                tiles
-            );
+            explanation.reject(&candidates, Reason::ZhongzhangMeld(tiles.clone()));
             return None;
         }
 
@@ -1348,18 +1489,13 @@ pub fn check_hunquandaiyaojiu_chunquandaiyaojiu(agari: &AgariTilesets) -> Option
         }
     );
 
+    let melds = agari.mianzis().chain(once(agari.quetou()));
     match (has_zipai, has_zhongzhang) {
-        (false, true) => {
-            debug!("    純全帯公九です。");
-            Some(Form::Chunquandaiyaojiu(agari.is_menqian()))
-        }
-        (true, true) => {
-            debug!("    混全帯幺九です。");
-            Some(Form::Hunquandaiyaojiu(agari.is_menqian()))
-        }
+        (false, true) => Some(explanation.establish(candidates[0], melds)),
+        (true, true) => Some(explanation.establish(candidates[1], melds)),
         // 混老頭は別扱いのため、ここでは None
         _ => {
-            debug!("    純全帯公九でも混全帯幺九でもありません。");
+            explanation.reject(&candidates, Reason::NoZhongzhang);
             None
         }
     }
@@ -1369,21 +1505,21 @@ pub fn check_hunquandaiyaojiu_chunquandaiyaojiu(agari: &AgariTilesets) -> Option
 ///
 /// 〈四槓子〉
 /// - 槓を4回行う
+///
 /// 〈三槓子〉
 /// - 槓を3回行う
-pub fn check_sangangzi_sigangzi(agari: &AgariTilesets) -> Option<Form> {
+pub fn check_sangangzi_sigangzi(
+    agari: &AgariTilesets,
+    explanation: &mut Explanation,
+) -> Option<Form> {
     debug!("--> 四槓子・三槓子を判定...");
-    match agari.angangs().count() + agari.minggangs().count() {
-        4 => {
-            debug!("    四槓子です。");
-            Some(Form::Sigangzi)
-        }
-        3 => {
-            debug!("    三槓子です。");
-            Some(Form::Sangangzi)
-        }
+    let gangs = || agari.minggangs().chain(agari.angangs());
+    match gangs().count() {
+        4 => Some(explanation.establish(Form::Sigangzi, gangs())),
+        3 => Some(explanation.establish(Form::Sangangzi, gangs())),
         n => {
-            debug!("    槓は{}回しか行われていません。", n);
+            let candidates = [Form::Sigangzi, Form::Sangangzi];
+            explanation.reject(&candidates, Reason::TooFewGangs(n));
             None
         }
     }
@@ -1393,28 +1529,28 @@ pub fn check_sangangzi_sigangzi(agari: &AgariTilesets) -> Option<Form> {
 ///
 /// - 雀頭が三元牌になっている。
 /// - 遺りの二つを刻子または槓子で揃える。
-pub fn check_shousanyuan(agari: &AgariTilesets) -> Option<Form> {
+pub fn check_shousanyuan(agari: &AgariTilesets, explanation: &mut Explanation) -> Option<Form> {
     debug!("--> 小三元を判定...");
 
     // まず雀頭が三元牌でないならアウト。
     if !agari.quetou().first().is_sanyuan() {
-        debug!("    雀頭が三元牌ではありません。");
+        explanation.reject(&[Form::Shousangen], Reason::QuetouNotSanyuan);
         return None;
     }
 
-    let num_sanyuan = agari
-        .kezis()
-        .filter(|tiles| tiles.first().is_sanyuan())
-        .count();
+    let sanyuans = || agari.kezis().filter(|tiles| tiles.first().is_sanyuan());
+    let num_sanyuan = sanyuans().count();
 
     if num_sanyuan >= 2 {
         debug!(
             "    三元牌が雀頭を除いて {} 枚あるので小三元です。",
             num_sanyuan
         );
-        Some(Form::Shousangen)
+        let melds = sanyuans().chain(once(agari.quetou()));
+        Some(explanation.establish(Form::Shousangen, melds))
     } else {
-        debug!("    三元牌は雀頭を除いて{}枚しかありません。", num_sanyuan);
+        let reason = Reason::TooFewSanyuanKezis(num_sanyuan);
+        explanation.reject(&[Form::Shousangen], reason);
         None
     }
 }
@@ -1422,19 +1558,17 @@ pub fn check_shousanyuan(agari: &AgariTilesets) -> Option<Form> {
 /// [13]大三元
 ///
 /// - 三元牌全てについてそれぞれ刻子を作る。
-pub fn check_daisanyuan(agari: &AgariTilesets) -> Option<Form> {
+pub fn check_daisanyuan(agari: &AgariTilesets, explanation: &mut Explanation) -> Option<Form> {
     debug!("--> 大三元を判定...");
-    let num_sanyuan = agari
-        .kezis()
-        .filter(|tiles| tiles.first().is_sanyuan())
-        .count();
+    let sanyuans = || agari.kezis().filter(|tiles| tiles.first().is_sanyuan());
+    let num_sanyuan = sanyuans().count();
 
     // 刻子が3つあれば自動的に全種類で刻子を作っていることになるのでOK。そもそも数がないため。
     if num_sanyuan == 3 {
-        debug!("    三元牌が{}枚あるので大三元です。", num_sanyuan);
-        Some(Form::Daisangen)
+        Some(explanation.establish(Form::Daisangen, sanyuans()))
     } else {
-        debug!("    三元牌が雀頭を除いて{}枚しかありません。", num_sanyuan);
+        let reason = Reason::TooFewSanyuanKezis(num_sanyuan);
+        explanation.reject(&[Form::Daisangen], reason);
         None
     }
 }
@@ -1446,35 +1580,32 @@ pub fn check_daisanyuan(agari: &AgariTilesets) -> Option<Form> {
 ///
 /// 〈小四喜〉
 /// - 雀頭と3面子が風牌
-pub fn check_shousushi_daisushi(agari: &AgariTilesets) -> Option<Form> {
+pub fn check_shousushi_daisushi(
+    agari: &AgariTilesets,
+    explanation: &mut Explanation,
+) -> Option<Form> {
     debug!("--> 大四喜・小四喜を判定...");
 
-    let extract_zipai_kind = |tiles: &Tiles| match tiles.first() {
-        Tile::Zipai(kind) => Some(kind),
-        _ => None,
+    let is_wind = |tiles: &&Tiles| tiles.first().is_feng();
+    let covers_all = |melds: &[&Tiles]| {
+        let set: HashSet<Tile> = melds.iter().map(|tiles| tiles.first()).collect();
+        set.len() == 4
     };
 
-    let check = |set: &HashSet<Zipai>, form: Form| {
-        let ok = [Zipai::East, Zipai::South, Zipai::West, Zipai::North]
-            .iter()
-            .all(|d| set.contains(d));
-        if ok {
-            debug!("   全方位を含んでいるので{}成立です。", form.name());
+    // まずここで大四喜を確認。
+    let mut melds: Vec<&Tiles> = agari.kezis().filter(is_wind).collect();
+    if covers_all(&melds) {
+        return Some(explanation.establish(Form::Daisushi, melds));
+    }
 
-            Some(form)
-        } else {
-            debug!("    方位が足りず{}は成立しませんでした。", form.name());
-            None
-        }
-    };
+    // 続いて雀頭を追加し、小四喜を確認。
+    melds.extend(Some(agari.quetou()).filter(is_wind));
+    if covers_all(&melds) {
+        return Some(explanation.establish(Form::Shousushi, melds));
+    }
 
-    let mut set: HashSet<Zipai> = agari.kezis().filter_map(extract_zipai_kind).collect();
-    // まずここで大四喜を確認。
-    check(&set, Form::Daisushi).or_else(|| {
-        // 続いて雀頭を追加し、小四喜を確認。
-        set.insert(extract_zipai_kind(agari.quetou())?);
-        check(&set, Form::Shousushi)
-    })
+    explanation.reject(&[Form::Daisushi, Form::Shousushi], Reason::TooFewWinds);
+    None
 }
 
 #[cfg(test)]
@@ -1485,7 +1616,8 @@ pub mod tests {
     #[test]
     fn judge_order() {
         assert_eq!(
-            (Form::Ikkitsukan(false).point()).cmp(&Form::Hungyise(false).point()),
+            (Form::Ikkitsukan(false).point(&Ruleset::default()))
+                .cmp(&Form::Hungyise(false).point(&Ruleset::default())),
             Ordering::Less
         );
     }
@@ -0,0 +1,418 @@
+//! 符計算を行い、その内訳を表す。
+
+use crate::agaritilesets::{AgariTilesets, MachiKind};
+use crate::form::Form;
+use crate::tiles::Tiles;
+use log::debug;
+use std::fmt;
+
+/// 符の内訳。
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FuBreakdown {
+    /// 符の項目。
+    pub items: Vec<FuItem>,
+
+    /// 項目の合計。
+    pub sum: u32,
+
+    /// 合計を 10 符単位に切り上げたもの。
+    pub ceiled: u32,
+
+    /// 切り上げた符に代えて決まった符にする場合。
+    pub special: Option<FuSpecial>,
+
+    /// 最終的な符数。
+    pub total: u32,
+}
+
+/// 符の項目の一つ。
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FuItem {
+    /// 何による符か。
+    pub kind: FuKind,
+
+    /// 符数。
+    pub fu: u32,
+}
+
+/// 符のつく理由。
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FuKind {
+    /// 副底。
+    Fudi,
+
+    /// ツモ。
+    Zimo,
+
+    /// 門前でのロン。
+    MenqianRonghe,
+
+    /// 刻子・槓子。
+    Mianzi(MianziKind, Tiles),
+
+    /// 役牌の雀頭。
+    Quetou(Tiles),
+
+    /// 待ち。
+    Machi(MachiKind),
+}
+
+/// 符のつく面子の種類。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum MianziKind {
+    /// 明刻。ロンによる明刻を含む。
+    Mingke,
+
+    /// 暗刻。
+    Anke,
+
+    /// 明槓。
+    Minggang,
+
+    /// 暗槓。
+    Angang,
+}
+
+/// 切り上げた符に代えて決まった符にする場合。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum FuSpecial {
+    /// 平和ツモは一律 20 符。
+    PingheZimo,
+
+    /// 喰い平和形は 20 符を 30 符に引き上げる。
+    KuiPinghe,
+
+    /// 七対子は一律 25 符。
+    Qiduizi,
+}
+
+impl FuBreakdown {
+    /// 面子に分解しない形 (七対子・国士無双など) の符。七対子なら 25 符、それ以外は 0 符とする。
+    pub(crate) fn without_agari(forms: &[Form]) -> FuBreakdown {
+        if forms.contains(&Form::Qiduizi) {
+            FuBreakdown {
+                special: Some(FuSpecial::Qiduizi),
+                total: 25,
+                ..FuBreakdown::default()
+            }
+        } else {
+            FuBreakdown::default()
+        }
+    }
+
+    pub fn display_en(&self) -> FuBreakdownDisplayEn<'_> {
+        FuBreakdownDisplayEn(self)
+    }
+}
+
+impl fmt::Display for FuItem {
+    fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            FuKind::Fudi => write!(b, "副底 {}符", self.fu),
+            FuKind::Zimo => write!(b, "ツモ {}符", self.fu),
+            FuKind::MenqianRonghe => write!(b, "門前ロン {}符", self.fu),
+            FuKind::Mianzi(kind, tiles) => {
+                let kind = match kind {
+                    MianziKind::Mingke => "明刻",
+                    MianziKind::Anke => "暗刻",
+                    MianziKind::Minggang => "明槓",
+                    MianziKind::Angang => "暗槓",
+                };
+                let yaojiu = if tiles.first().is_yaojiu() {
+                    "么九牌"
+                } else {
+                    "中張牌"
+                };
+                write!(b, "{} {} {}符 ({})", kind, yaojiu, self.fu, tiles)
+            }
+            FuKind::Quetou(tiles) => write!(b, "雀頭 役牌 {}符 ({})", self.fu, tiles),
+            FuKind::Machi(machi) => write!(b, "{} {}符", machi, self.fu),
+        }
+    }
+}
+
+impl FuItem {
+    pub fn display_en(&self) -> FuItemDisplayEn<'_> {
+        FuItemDisplayEn(self)
+    }
+}
+
+/// 符の項目 (英語) 。
+pub struct FuItemDisplayEn<'a>(&'a FuItem);
+
+impl fmt::Display for FuItemDisplayEn<'_> {
+    fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
+        let FuItemDisplayEn(item) = self;
+        match &item.kind {
+            FuKind::Fudi => write!(b, "Base {} Minipoints", item.fu),
+            FuKind::Zimo => write!(b, "Tsumo {} Minipoints", item.fu),
+            FuKind::MenqianRonghe => write!(b, "Concealed Ron {} Minipoints", item.fu),
+            FuKind::Mianzi(kind, tiles) => {
+                let kind = match kind {
+                    MianziKind::Mingke => "Open Pung",
+                    MianziKind::Anke => "Concealed Pung",
+                    MianziKind::Minggang => "Open Kong",
+                    MianziKind::Angang => "Concealed Kong",
+                };
+                let yaojiu = if tiles.first().is_yaojiu() {
+                    "Terminals/Honors"
+                } else {
+                    "Simples"
+                };
+                write!(b, "{} {} {} Minipoints ({})", kind, yaojiu, item.fu, tiles)
+            }
+            FuKind::Quetou(tiles) => {
+                write!(b, "Value Pair {} Minipoints ({})", item.fu, tiles)
+            }
+            FuKind::Machi(machi) => write!(b, "{} {} Minipoints", machi.display_en(), item.fu),
+        }
+    }
+}
+
+impl fmt::Display for FuBreakdown {
+    fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
+        for item in &self.items {
+            writeln!(b, "{}", item)?;
+        }
+
+        if !self.items.is_empty() {
+            write!(b, "合計 {}符 → 切り上げ {}符", self.sum, self.ceiled)?;
+        }
+
+        if let Some(special) = self.special {
+            if !self.items.is_empty() {
+                writeln!(b)?;
+            }
+            let name = match special {
+                FuSpecial::PingheZimo => "平和ツモ",
+                FuSpecial::KuiPinghe => "喰い平和形",
+                FuSpecial::Qiduizi => "七対子",
+            };
+            write!(b, "{} {}符", name, self.total)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// 符の内訳 (英語) 。
+pub struct FuBreakdownDisplayEn<'a>(&'a FuBreakdown);
+
+impl fmt::Display for FuBreakdownDisplayEn<'_> {
+    fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
+        let FuBreakdownDisplayEn(breakdown) = self;
+        for item in &breakdown.items {
+            writeln!(b, "{}", item.display_en())?;
+        }
+
+        if !breakdown.items.is_empty() {
+            write!(
+                b,
+                "Total {} Minipoints, Rounded Up to {} Minipoints",
+                breakdown.sum, breakdown.ceiled
+            )?;
+        }
+
+        if let Some(special) = breakdown.special {
+            if !breakdown.items.is_empty() {
+                writeln!(b)?;
+            }
+            let name = match special {
+                FuSpecial::PingheZimo => "Pinfu Tsumo",
+                FuSpecial::KuiPinghe => "Open Pinfu",
+                FuSpecial::Qiduizi => "Seven Pairs",
+            };
+            write!(b, "{} {} Minipoints", name, breakdown.total)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// 面子に分解したアガリ形について符を計算する。
+pub(crate) struct FuCalculator<'a> {
+    agari: &'a AgariTilesets,
+    forms: &'a [Form],
+    items: Vec<FuItem>,
+}
+
+impl<'a> FuCalculator<'a> {
+    pub(crate) fn new(agari: &'a AgariTilesets, forms: &'a [Form]) -> FuCalculator<'a> {
+        FuCalculator {
+            agari,
+            forms,
+            items: Vec::new(),
+        }
+    }
+
+    fn push(&mut self, kind: FuKind, fu: u32) {
+        if fu != 0 {
+            self.items.push(FuItem { kind, fu });
+        }
+    }
+
+    pub(crate) fn calculate(mut self) -> FuBreakdown {
+        debug!("符計算を行います。");
+
+        // アガリ基本符
+        self.push(FuKind::Fudi, 20);
+
+        // 平和ツモは一律 20 符
+        if self.is_pinghe_zimo() {
+            debug!("-> 平和ツモのため 20 符です。");
+            return FuBreakdown {
+                items: self.items,
+                sum: 20,
+                ceiled: 20,
+                special: Some(FuSpecial::PingheZimo),
+                total: 20,
+            };
+        }
+
+        // アガリ方による符
+        self.calc_agari_fu();
+
+        // 刻子、槓によるボーナス
+        self.calc_kezi_fu();
+
+        // 雀頭によるボーナス
+        self.calc_quetou_fu();
+
+        // 待ちによるボーナス
+        self.calc_machi_fu();
+
+        for item in &self.items {
+            debug!("-> {}", item);
+        }
+
+        let sum = self.items.iter().map(|item| item.fu).sum();
+        debug!("-> 従って、基本の合計が {} 符", sum);
+
+        let ceiled = crate::utils::ceil_at(sum, 10);
+        debug!("-> これを切り上げると {} 符", ceiled);
+
+        // 喰い平和形では 20 符となるが、このときは 30 符に引き上げる
+        let (special, total) = if !self.agari.is_zimo() && ceiled == 20 {
+            debug!("-> これは喰い平和形なので 30 符に切り上げます。");
+            (Some(FuSpecial::KuiPinghe), 30)
+        } else {
+            (None, ceiled)
+        };
+
+        FuBreakdown {
+            items: self.items,
+            sum,
+            ceiled,
+            special,
+            total,
+        }
+    }
+
+    fn is_pinghe_zimo(&self) -> bool {
+        self.agari.is_zimo() && self.forms.contains(&Form::Pinghe)
+    }
+
+    fn calc_agari_fu(&mut self) {
+        if self.agari.is_zimo() {
+            self.push(FuKind::Zimo, 2);
+        } else if self.agari.is_menqian() {
+            self.push(FuKind::MenqianRonghe, 10);
+        }
+    }
+
+    fn calc_kezi_fu(&mut self) {
+        let agari = self.agari;
+
+        // 明刻 (槓を除く) は 2 符、暗刻は 4 符、明槓は 8 符、暗槓は 16 符。么九牌なら倍。
+        let mianzis = (agari.pengs().chain(agari.ronghe_mingke()))
+            .map(|tiles| (MianziKind::Mingke, tiles, 2))
+            .chain(
+                agari
+                    .kezis_in_hand()
+                    .map(|tiles| (MianziKind::Anke, tiles, 4)),
+            )
+            .chain(
+                agari
+                    .minggangs()
+                    .map(|tiles| (MianziKind::Minggang, tiles, 8)),
+            )
+            .chain(agari.angangs().map(|tiles| (MianziKind::Angang, tiles, 16)));
+
+        for (kind, tiles, fu) in mianzis {
+            let fu = if tiles.first().is_zhongzhang() {
+                fu
+            } else {
+                fu * 2
+            };
+            self.push(FuKind::Mianzi(kind, tiles.clone()), fu);
+        }
+    }
+
+    fn calc_quetou_fu(&mut self) {
+        // 役牌の雀頭は 2 符。連風牌なら 4 符。
+        let quetou = self.agari.quetou();
+        let fu = quetou.first().num_fan(self.agari.context()) * 2;
+        self.push(FuKind::Quetou(quetou.clone()), fu);
+    }
+
+    fn calc_machi_fu(&mut self) {
+        let machi = self.agari.machi();
+        match machi {
+            MachiKind::Liangmian | MachiKind::Shuangpeng => {}
+            _ => self.push(FuKind::Machi(machi), 2),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::judge::judge;
+    use crate::ruleset::Ruleset;
+
+    fn breakdown(hand: &str) -> FuBreakdown {
+        let tilesets = hand.parse().unwrap();
+        let judge = judge(&tilesets, &Ruleset::default()).unwrap();
+        assert_eq!(judge.total().fu, judge.fu_breakdown().total);
+        judge.fu_breakdown().clone()
+    }
+
+    #[test]
+    fn items() {
+        let fu = breakdown("東東東2m2m2m5p6p7p白白2s4s ロン3s");
+        assert_eq!(
+            fu.to_string(),
+            "副底 20符\n門前ロン 10符\n暗刻 中張牌 4符 (2m2m2m)\n暗刻 么九牌 8符 (東東東)\n雀頭 役牌 2符 (白白)\nカンチャン 2符\n合計 46符 → 切り上げ 50符"
+        );
+        assert_eq!(
+            fu.display_en().to_string(),
+            "Base 20 Minipoints\nConcealed Ron 10 Minipoints\nConcealed Pung Simples 4 Minipoints (2m2m2m)\nConcealed Pung Terminals/Honors 8 Minipoints (東東東)\nValue Pair 2 Minipoints (白白)\nClosed wait 2 Minipoints\nTotal 46 Minipoints, Rounded Up to 50 Minipoints"
+        );
+        assert_eq!(
+            (fu.sum, fu.ceiled, fu.special, fu.total),
+            (46, 50, None, 50)
+        );
+    }
+
+    #[test]
+    fn special() {
+        let fu = breakdown("チー2m3m4m 4p5p6p6s7s8s2s2s3m4m ロン5m");
+        assert_eq!(fu.special, Some(FuSpecial::KuiPinghe));
+        assert_eq!((fu.ceiled, fu.total), (20, 30));
+        assert!(fu.to_string().ends_with("\n喰い平和形 30符"));
+
+        let fu = breakdown("2m3m4m4p5p6p7s8s9s3s3s5m6m ツモ7m");
+        assert_eq!(fu.special, Some(FuSpecial::PingheZimo));
+        assert_eq!(fu.items.len(), 1);
+        assert_eq!(fu.total, 20);
+
+        let fu = breakdown("3s3s5s5s1p6p6p東東白白中中 ツモ1p");
+        assert_eq!(fu.to_string(), "七対子 25符");
+        assert_eq!(fu.display_en().to_string(), "Seven Pairs 25 Minipoints");
+    }
+}
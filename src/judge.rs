@@ -1,9 +1,21 @@
 use crate::agaritilesets::AgariTilesets;
+use crate::context::Lizhi;
+use crate::explanation::{Explanation, Reason};
 use crate::form::{Form, Point};
-use crate::tilesets::Tilesets;
+use crate::fu::{FuBreakdown, FuCalculator};
+use crate::payment::Payment;
+use crate::ruleset::{Renhe, Ruleset};
+use crate::shanten::shanten;
+use crate::tile::Tile;
+use crate::tiles::Tiles;
+use crate::tilesets::{Tilesets, TilesetsError, TingpaiTilesets};
+use crate::tingpai::machi_tiles;
+use crate::zhenting::Zhenting;
 use log::debug;
+use std::cmp::Ordering;
 use std::fmt;
 use std::iter::once;
+use thiserror::Error;
 
 #[derive(Debug, Clone)]
 enum JudgeTilesets {
@@ -16,10 +28,20 @@ pub struct Judge {
     forms: Vec<Form>,
     total: Point,
     tilesets: JudgeTilesets,
+    ruleset: Ruleset,
+    explanation: Explanation,
+    fu: FuBreakdown,
 }
 
 impl Judge {
-    fn new(forms: Vec<Form>, total: Point, tilesets: JudgeTilesets) -> Option<Judge> {
+    fn new(
+        forms: Vec<Form>,
+        total: Point,
+        tilesets: JudgeTilesets,
+        ruleset: &Ruleset,
+        explanation: Explanation,
+        fu: FuBreakdown,
+    ) -> Option<Judge> {
         if forms.is_empty() {
             None
         } else {
@@ -27,47 +49,66 @@ impl Judge {
                 forms,
                 total,
                 tilesets,
+                ruleset: *ruleset,
+                explanation,
+                fu,
             })
         }
     }
 
-    fn fix_forms(forms: &mut Vec<Form>) {
-        // 役を翻数の順に並べる。
-        forms.sort_by_key(|f| f.point());
+    fn fix_forms(forms: &mut Vec<Form>, ruleset: &Ruleset, explanation: &mut Explanation) {
+        // 役を翻数の順に並べる。同じ翻数ならドラ類を後ろにする。
+        forms.sort_by_key(|f| (f.point(ruleset), f.is_dora()));
 
         // もし真の役満が含まれているなら、それ以外を除く。
-        if forms.iter().any(|form| form.point().is_true_yiman()) {
-            forms.retain(|form| form.point().is_true_yiman());
+        if forms.iter().any(|form| form.point(ruleset).is_true_yiman()) {
+            forms.retain(|form| form.point(ruleset).is_true_yiman());
         }
 
         // もし役がドラのみであれば、それでは上がれないので役を空にする
         if forms.iter().all(|form| form.is_dora()) {
             forms.clear();
         }
+
+        explanation.fix(forms);
     }
 
-    fn from_tilesets(tilesets: Tilesets, mut forms: Vec<Form>) -> Option<Judge> {
+    fn from_tilesets(
+        tilesets: Tilesets,
+        mut forms: Vec<Form>,
+        ruleset: &Ruleset,
+        mut explanation: Explanation,
+    ) -> Option<Judge> {
         // 役を補正する。
-        Judge::fix_forms(&mut forms);
+        Judge::fix_forms(&mut forms, ruleset, &mut explanation);
 
         // 合計の翻数を計算する。
-        let total: Point = forms.iter().map(|f| f.point()).sum();
+        let total: Point = forms.iter().map(|f| f.point(ruleset)).sum();
 
-        Judge::new(forms, total, JudgeTilesets::Tilesets(tilesets))
+        let fu = FuBreakdown::without_agari(&forms);
+        let tilesets = JudgeTilesets::Tilesets(tilesets);
+        Judge::new(forms, total, tilesets, ruleset, explanation, fu)
     }
 
-    fn from_agaritilesets(agari: AgariTilesets, mut forms: Vec<Form>) -> Option<Judge> {
+    fn from_agaritilesets(
+        agari: AgariTilesets,
+        mut forms: Vec<Form>,
+        ruleset: &Ruleset,
+        mut explanation: Explanation,
+    ) -> Option<Judge> {
         // 役を補正する。
-        Judge::fix_forms(&mut forms);
+        Judge::fix_forms(&mut forms, ruleset, &mut explanation);
 
         // 合計の翻数を計算する。
-        let mut total: Point = forms.iter().map(|f| f.point()).sum();
+        let mut total: Point = forms.iter().map(|f| f.point(ruleset)).sum();
 
         // 符計算をする。
         assert_eq!(total.fu, 0);
-        total.fu = FuCalculator::new(&agari, &forms).calculate();
+        let fu = FuCalculator::new(&agari, &forms).calculate();
+        total.fu = fu.total;
 
-        Judge::new(forms, total, JudgeTilesets::AgariTilesets(agari))
+        let tilesets = JudgeTilesets::AgariTilesets(agari);
+        Judge::new(forms, total, tilesets, ruleset, explanation, fu)
     }
 
     pub fn tilesets(&self) -> &Tilesets {
@@ -92,20 +133,43 @@ impl Judge {
         &self.forms
     }
 
-    pub fn display_en(&self) -> JudgeDisplayEn {
+    /// 成立した役とそれを成立させた面子、成立しなかった役の候補とその理由。
+    pub fn explanation(&self) -> &Explanation {
+        &self.explanation
+    }
+
+    /// 符の内訳。
+    pub fn fu_breakdown(&self) -> &FuBreakdown {
+        &self.fu
+    }
+
+    /// 判定に使ったルール。
+    pub fn ruleset(&self) -> &Ruleset {
+        &self.ruleset
+    }
+
+    /// 誰がいくら支払うか。本場と供託の分も含む。
+    pub fn payment(&self) -> Payment {
+        let context = &self.tilesets().context;
+        Payment::new(
+            self.total,
+            context.is_parent(),
+            self.tilesets().is_zimo,
+            &self.ruleset,
+        )
+        .with_benchang(context.benchang)
+        .with_gongtuo(context.gongtuo)
+    }
+
+    pub fn display_en(&self) -> JudgeDisplayEn<'_> {
         JudgeDisplayEn(self)
     }
 }
 
 impl fmt::Display for Judge {
     fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
-        writeln!(
-            b,
-            "{}場 {}家 {}",
-            self.tilesets().context.place,
-            self.tilesets().context.player,
-            self.tilesets().context.player_name
-        )?;
+        let context = &self.tilesets().context;
+        writeln!(b, "{} {}", context, context.player_name)?;
 
         writeln!(b, "{}", self.tilesets())?;
 
@@ -114,14 +178,22 @@ impl fmt::Display for Judge {
         }
 
         for form in &self.forms {
-            writeln!(b, "{}", form.display())?;
+            writeln!(b, "{}", form.display(&self.ruleset))?;
         }
 
         write!(
             b,
             "{}",
-            self.total.display_full(self.tilesets().context.is_parent())
-        )
+            self.total
+                .display_full(self.tilesets().context.is_parent(), &self.ruleset)
+        )?;
+
+        let payment = self.payment();
+        if payment.has_bonus() {
+            write!(b, "\n{}", payment)?;
+        }
+
+        Ok(())
     }
 }
 
@@ -130,13 +202,8 @@ pub struct JudgeDisplayEn<'a>(&'a Judge);
 impl fmt::Display for JudgeDisplayEn<'_> {
     fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
         let JudgeDisplayEn(judge) = self;
-        writeln!(
-            b,
-            "{} {} {}",
-            judge.tilesets().context.place.display_en(),
-            judge.tilesets().context.player.display_en(),
-            judge.tilesets().context.player_name
-        )?;
+        let context = &judge.tilesets().context;
+        writeln!(b, "{} {}", context.display_en(), context.player_name)?;
 
         writeln!(b, "{}", judge.tilesets().display_en())?;
 
@@ -145,7 +212,7 @@ impl fmt::Display for JudgeDisplayEn<'_> {
         }
 
         for form in &judge.forms {
-            writeln!(b, "{}", form.display_en())?;
+            writeln!(b, "{}", form.display_en(&judge.ruleset))?;
         }
 
         write!(
@@ -153,20 +220,25 @@ impl fmt::Display for JudgeDisplayEn<'_> {
             "{}",
             judge
                 .total
-                .display_full_en(judge.tilesets().context.is_parent())
-        )
+                .display_full_en(judge.tilesets().context.is_parent(), &judge.ruleset)
+        )?;
+
+        let payment = judge.payment();
+        if payment.has_bonus() {
+            write!(b, "\n{}", payment.display_en())?;
+        }
+
+        Ok(())
     }
 }
 
-pub fn judge(tilesets: &Tilesets) -> Option<Judge> {
+pub fn judge(tilesets: &Tilesets, ruleset: &Ruleset) -> Option<Judge> {
     debug!("判定を開始します。");
     debug!("対象: {}", tilesets);
-    let res = judge_all(tilesets).max_by(|x, y| {
-        // まずは得点で比較し、等しければ役の数が少ない方をとる。
-        (x.total)
-            .cmp(&y.total)
-            .then((x.forms.len()).cmp(&y.forms.len()).reverse())
-    });
+    let res = judge_ranked(tilesets, ruleset)
+        .into_iter()
+        .next()
+        .map(|interpretation| interpretation.judge);
 
     debug!(
         "判定が終わりました。結論は: {:?}",
@@ -175,200 +247,639 @@ pub fn judge(tilesets: &Tilesets) -> Option<Judge> {
     res
 }
 
-fn judge_all(tilesets: &Tilesets) -> impl Iterator<Item = Judge> {
-    let qiduizi = judge_qiduizi(&tilesets);
-    let kokushimuso = judge_kokushimuso(&tilesets);
-    let jiulianbaodeng = judge_jiulianbaodeng(&tilesets);
-    let agari = (AgariTilesets::enumerate(tilesets).into_iter()).filter_map(judge_agari);
+/// 二つの判定結果を比べる。まずは得点で比較し、等しければ役の数が少ない方を上とする。
+fn compare(x: &Judge, y: &Judge) -> Ordering {
+    (x.total)
+        .cmp(&y.total)
+        .then((x.forms.len()).cmp(&y.forms.len()).reverse())
+}
 
-    agari
-        .chain(qiduizi)
-        .chain(kokushimuso)
-        .chain(jiulianbaodeng)
+/// 手牌の解釈の一つ。
+#[derive(Debug, Clone)]
+pub struct Interpretation {
+    /// この解釈での判定結果。分解・待ち・役・符・点数を含む。
+    pub judge: Judge,
+
+    /// 最上位の解釈がこの解釈より上になった理由。最上位の解釈では None となる。
+    pub beaten_by: Option<Preference>,
 }
 
-fn forms_for_all_base(tilesets: &Tilesets) -> impl Iterator<Item = Form> {
-    use crate::form::*;
-    (tilesets.context.lucky_forms.clone().into_iter())
-        .chain(special_check_lizhi(tilesets))
-        .chain(special_check_menqianqingzimohu(tilesets))
-        .chain(special_check_duanyaojiu(tilesets))
-        .chain(special_check_ziyise(tilesets))
-        .chain(special_check_luyise(tilesets))
-        .chain(special_check_hungyise_qingyise(tilesets))
-        .chain(special_check_qinglaotou(tilesets))
-        .chain(special_check_hunlaotou(tilesets))
-        .chain(special_check_dora(tilesets))
-}
-
-fn judge_agari(agari: AgariTilesets) -> Option<Judge> {
-    debug!("-> 次のアガリ形について判定: {}", agari);
-    use crate::form::*;
+/// ある解釈が他の解釈より上になった理由。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Preference {
+    /// 役満の数が多い。
+    Yiman,
 
-    let mut forms = Vec::with_capacity(10);
-    forms.extend(forms_for_all_base(agari.tilesets()));
-    forms.extend(check_fanpai(&agari));
-    forms.extend(check_pinghe(&agari));
-    forms.extend(check_yibeikou_liangbeigou(&agari));
-    forms.extend(check_sanshoku_dojun(&agari));
-    forms.extend(check_sanshoku_doko(&agari));
-    forms.extend(check_sananke_sianke(&agari));
-    forms.extend(check_ikki_tukan(&agari));
-    forms.extend(check_duiduihe(&agari));
-    forms.extend(check_hunquandaiyaojiu_chunquandaiyaojiu(&agari));
-    forms.extend(check_sangangzi_sigangzi(&agari));
-    forms.extend(check_shousanyuan(&agari));
-    forms.extend(check_daisanyuan(&agari));
-    forms.extend(check_shousushi_daisushi(&agari));
-
-    Judge::from_agaritilesets(agari, forms)
-}
-
-fn judge_qiduizi(tilesets: &Tilesets) -> Option<Judge> {
-    debug!("-> 七対子を判定...");
-    crate::form::special_check_qiduizi(tilesets)
-        .map(|q| forms_for_all_base(tilesets).chain(once(q)).collect())
-        .and_then(|forms| Judge::from_tilesets(tilesets.clone(), forms))
+    /// 翻数が多い。
+    Fan,
+
+    /// 符数が多い。
+    Fu,
+
+    /// 得点が同じで、役の数が少ない。
+    FewerForms,
+
+    /// 得点も役の数も同じ。
+    Tie,
 }
 
-fn judge_kokushimuso(tilesets: &Tilesets) -> Option<Judge> {
-    debug!("-> 国士無双を判定...");
-    crate::form::special_check_kokushimuso(tilesets)
-        .map(|k| forms_for_all_base(tilesets).chain(once(k)).collect())
-        .and_then(|forms| Judge::from_tilesets(tilesets.clone(), forms))
+impl Preference {
+    fn between(winner: &Judge, loser: &Judge) -> Preference {
+        let (w, l) = (winner.total, loser.total);
+        if w.yiman != l.yiman {
+            Preference::Yiman
+        } else if w.fan != l.fan {
+            Preference::Fan
+        } else if w.fu != l.fu {
+            Preference::Fu
+        } else if winner.forms.len() != loser.forms.len() {
+            Preference::FewerForms
+        } else {
+            Preference::Tie
+        }
+    }
+
+    pub fn display_en(self) -> PreferenceDisplayEn {
+        PreferenceDisplayEn(self)
+    }
 }
 
-fn judge_jiulianbaodeng(tilesets: &Tilesets) -> Option<Judge> {
-    debug!("-> 九蓮宝燈を判定...");
-    crate::form::special_check_jiulianbaodeng(tilesets)
-        .map(|j| forms_for_all_base(tilesets).chain(once(j)).collect())
-        .and_then(|forms| Judge::from_tilesets(tilesets.clone(), forms))
+impl fmt::Display for Preference {
+    fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Preference::Yiman => write!(b, "役満の数が多い"),
+            Preference::Fan => write!(b, "翻数が多い"),
+            Preference::Fu => write!(b, "符数が多い"),
+            Preference::FewerForms => write!(b, "得点が同じで役の数が少ない"),
+            Preference::Tie => write!(b, "得点も役の数も同じ"),
+        }
+    }
+}
+
+pub struct PreferenceDisplayEn(Preference);
+
+impl fmt::Display for PreferenceDisplayEn {
+    fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Preference::Yiman => write!(b, "more yakuman"),
+            Preference::Fan => write!(b, "more han"),
+            Preference::Fu => write!(b, "more minipoints"),
+            Preference::FewerForms => write!(b, "same score with fewer yaku"),
+            Preference::Tie => write!(b, "same score and number of yaku"),
+        }
+    }
+}
+
+/// 役のある全ての解釈を、上位のものから順に並べる。先頭が `judge` の結果と同じになる。
+pub fn judge_ranked(tilesets: &Tilesets, ruleset: &Ruleset) -> Vec<Interpretation> {
+    let mut judges: Vec<Judge> = judge_all(tilesets, ruleset).collect();
+
+    // 同点なら後に見つかった解釈を上にする。
+    judges.reverse();
+    judges.sort_by(|x, y| compare(y, x));
+
+    let best = match judges.first() {
+        Some(best) => best.clone(),
+        None => return Vec::new(),
+    };
+
+    (judges.into_iter().enumerate())
+        .map(|(i, judge)| Interpretation {
+            beaten_by: if i == 0 {
+                None
+            } else {
+                Some(Preference::between(&best, &judge))
+            },
+            judge,
+        })
+        .collect()
 }
 
-struct FuCalculator<'a> {
-    agari: &'a AgariTilesets,
-    forms: &'a [Form],
+/// 役のある点数にならなかった理由。
+#[derive(Debug, Error)]
+pub enum JudgeError {
+    /// 牌の枚数などがおかしい。
+    #[error("{0}")]
+    InvalidTiles(TilesetsError),
+
+    /// フリテンなのにロンしている。
+    #[error("{0}なのでロンできません。")]
+    Zhenting(Zhenting),
+
+    /// アガリの形になっていない。 `machi` はアガリ牌を除いた手牌の待ち牌で、聴牌していなければ空。
+    #[error("アガリの形になっていません。{}", NotAgariDetail { shanten: *.shanten, machi })]
+    NotAgari { shanten: i32, machi: Vec<Tile> },
+
+    /// アガリの形だが役がない。ドラだけがつく場合は `doras` に入る。
+    #[error("役がありません{}。{}", if doras.is_empty() { "" } else { " (ドラのみ) " }, Hints(hints))]
+    NoYaku { doras: Vec<Form>, hints: Vec<Hint> },
+}
+
+impl JudgeError {
+    pub fn display_en(&self) -> JudgeErrorDisplayEn<'_> {
+        JudgeErrorDisplayEn(self)
+    }
+}
+
+impl From<TilesetsError> for JudgeError {
+    fn from(err: TilesetsError) -> JudgeError {
+        match err {
+            TilesetsError::Zhenting(zhenting) => JudgeError::Zhenting(zhenting),
+            err => JudgeError::InvalidTiles(err),
+        }
+    }
 }
 
-impl<'a> FuCalculator<'a> {
-    fn new(agari: &'a AgariTilesets, forms: &'a [Form]) -> FuCalculator<'a> {
-        FuCalculator { agari, forms }
+struct NotAgariDetail<'a> {
+    shanten: i32,
+    machi: &'a [Tile],
+}
+
+impl fmt::Display for NotAgariDetail<'_> {
+    fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
+        if self.machi.is_empty() {
+            write!(b, " ({}向聴)", self.shanten)
+        } else {
+            let machi: Tiles = self.machi.iter().copied().collect();
+            write!(b, "待ち牌は {} です。", machi)
+        }
+    }
+}
+
+struct Hints<'a>(&'a [Hint]);
+
+impl fmt::Display for Hints<'_> {
+    fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
+        for hint in self.0 {
+            write!(b, "\n{}", hint)?;
+        }
+        Ok(())
     }
+}
 
-    fn calculate(&self) -> u32 {
-        debug!("符計算を行います。");
-        // 平和ツモは一律 20 符
-        if self.is_pinghe_zimo() {
-            debug!("-> 平和ツモのため 20 符です。");
-            return 20;
+pub struct JudgeErrorDisplayEn<'a>(&'a JudgeError);
+
+impl fmt::Display for JudgeErrorDisplayEn<'_> {
+    fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            JudgeError::InvalidTiles(err) => write!(b, "Invalid Tiles: {}", err),
+            JudgeError::Zhenting(zhenting) => {
+                write!(b, "Cannot Ron in {}", zhenting.display_en())
+            }
+            JudgeError::NotAgari { shanten, machi } if machi.is_empty() => {
+                write!(b, "Not a Winning Hand ({}-shanten)", shanten)
+            }
+            JudgeError::NotAgari { machi, .. } => {
+                let machi: Tiles = machi.iter().copied().collect();
+                write!(b, "Not a Winning Hand (waiting: {})", machi)
+            }
+            JudgeError::NoYaku { doras, hints } => {
+                write!(b, "No Yaku")?;
+                if !doras.is_empty() {
+                    write!(b, " (Dora Only)")?;
+                }
+                for hint in hints {
+                    write!(b, "\n{}", hint.display_en())?;
+                }
+                Ok(())
+            }
         }
+    }
+}
+
+/// 役がないときに、条件が違えばつく役。
+#[derive(Debug, Clone, PartialEq)]
+pub struct Hint {
+    /// 違っていた条件。
+    pub condition: Condition,
+
+    /// その条件のもとでつく役。ドラは含まない。
+    pub forms: Vec<Form>,
+}
 
-        // アガリ基本符
-        let fudi = 20;
-        debug!("-> 副底は {} 符", fudi);
+/// 役がつくかを確かめ直す条件。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Condition {
+    /// 副露せずに門前だった。
+    Menqian,
 
-        // アガリ方による符
-        let agari_fu = self.calc_agari_fu();
-        debug!("-> アガリ方による符は {} 符", agari_fu);
+    /// ロンではなくツモだった。
+    Zimo,
 
-        // 刻子、槓によるボーナス
-        let kezi_fu = self.calc_kezi_fu();
-        debug!("-> 刻子・槓によるボーナスが {} 符", kezi_fu);
+    /// 立直していた。
+    Lizhi,
 
-        // 雀頭によるボーナス
-        let quetou_fu = self.calc_quetou_fu();
-        debug!("-> 雀頭によるボーナスが {} 符", quetou_fu);
+    /// 喰いタンありのルールだった。
+    Kuitan,
+}
 
-        // 待ちによるボーナス
-        let machi_fu = self.calc_machi_fu();
-        debug!("-> 待ちによるボーナスが {} 符", machi_fu);
+impl Hint {
+    pub fn display_en(&self) -> HintDisplayEn<'_> {
+        HintDisplayEn(self)
+    }
+}
 
-        let base = fudi + agari_fu + kezi_fu + quetou_fu + machi_fu;
-        debug!("-> 従って、基本の合計が {} 符", base);
+impl fmt::Display for Hint {
+    fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
+        let condition = match self.condition {
+            Condition::Menqian => "門前なら",
+            Condition::Zimo => "ツモなら",
+            Condition::Lizhi => "立直していれば",
+            Condition::Kuitan => "喰いタンありなら",
+        };
+        let forms: Vec<_> = self.forms.iter().map(|form| form.name()).collect();
+        write!(b, "{}{}がつきます。", condition, forms.join("・"))
+    }
+}
 
-        let ceiled = crate::utils::ceil_at(base, 10);
-        debug!("-> これを切り上げると {} 符", ceiled);
+pub struct HintDisplayEn<'a>(&'a Hint);
 
-        // 喰い平和形では 20 符となるが、このときは 30 符に引き上げる
-        if !self.agari.is_zimo() && ceiled == 20 {
-            debug!("-> これは喰い平和形なので 30 符に切り上げます。");
-            return 30;
+impl fmt::Display for HintDisplayEn<'_> {
+    fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
+        let condition = match self.0.condition {
+            Condition::Menqian => "if concealed",
+            Condition::Zimo => "with tsumo",
+            Condition::Lizhi => "with riichi",
+            Condition::Kuitan => "if open tanyao were allowed",
+        };
+        let forms: Vec<_> = self.0.forms.iter().map(|form| form.name_en()).collect();
+        write!(b, "would be {} {}", forms.join(", "), condition)
+    }
+}
+
+/// `judge` と同様に判定するが、点数にならなければその理由を返す。
+///
+/// 牌の枚数とフリテンを確かめ直したうえで、アガリの形でなければ `NotAgari` を、役がなければ
+/// `NoYaku` を返す。役がない場合は、門前・ツモ・立直・喰いタンの条件を変えて判定し直し、つく役を
+/// ヒントとして添える。
+pub fn try_judge(tilesets: &Tilesets, ruleset: &Ruleset) -> Result<Judge, JudgeError> {
+    // `Tilesets` はフィールドを直接書き換えて作ることもできるので、ここでも確かめる。
+    let tingpai = tilesets.without_last();
+    tingpai.with_last(tilesets.last, tilesets.is_zimo)?;
+    tilesets.check_zhenting()?;
+
+    if let Some(judge) = judge(tilesets, ruleset) {
+        return Ok(judge);
+    }
+
+    let mut hand = tilesets.hand.clone();
+    hand.push(tilesets.last);
+    let shanten = shanten(&hand, tingpai.num_mianzis());
+    if shanten >= 0 {
+        let machi = machi_tiles(&tingpai);
+        return Err(JudgeError::NotAgari { shanten, machi });
+    }
+
+    let doras = crate::form::special_check_dora(tilesets, ruleset, &mut Explanation::new());
+    Err(JudgeError::NoYaku {
+        doras: doras.into_iter().collect(),
+        hints: hints(tilesets, ruleset),
+    })
+}
+
+/// 条件を一つずつ変えて判定し直し、役がつくものを集める。
+fn hints(tilesets: &Tilesets, ruleset: &Ruleset) -> Vec<Hint> {
+    let mut cands = Vec::new();
+    if tilesets.did_fulou() {
+        let mut menqian = tilesets.clone();
+        for mianzi in menqian.pengs.drain(..).chain(menqian.chis.drain(..)) {
+            for &tile in mianzi.iter() {
+                menqian.hand.push(tile);
+            }
         }
+        menqian.angangs.append(&mut menqian.minggangs);
+        cands.push((Condition::Menqian, menqian, *ruleset));
+
+        if !ruleset.kuitan {
+            let kuitan = Ruleset {
+                kuitan: true,
+                ..*ruleset
+            };
+            cands.push((Condition::Kuitan, tilesets.clone(), kuitan));
+        }
+    }
 
-        ceiled
+    if !tilesets.is_zimo {
+        let mut zimo = tilesets.clone();
+        zimo.is_zimo = true;
+        zimo.context.situation.hedilaoyu = false;
+        zimo.context.situation.chenggang = false;
+        zimo.context.situation.renhe = false;
+        cands.push((Condition::Zimo, zimo, *ruleset));
     }
 
-    fn is_pinghe_zimo(&self) -> bool {
-        self.agari.is_zimo() && self.forms.iter().any(|&form| form == Form::Pinghe)
+    if tilesets.is_menqian() && tilesets.context.lizhi == Lizhi::None {
+        let mut lizhi = tilesets.clone();
+        lizhi.context.lizhi = Lizhi::Lizhi;
+        cands.push((Condition::Lizhi, lizhi, *ruleset));
     }
 
-    fn calc_agari_fu(&self) -> u32 {
-        if self.agari.is_zimo() {
-            2
-        } else if self.agari.is_menqian() {
-            10
-        } else {
-            0
+    (cands.into_iter())
+        .filter_map(|(condition, tilesets, ruleset)| {
+            let judge = judge(&tilesets, &ruleset)?;
+            let forms = (judge.forms.into_iter())
+                .filter(|form| !form.is_dora())
+                .collect();
+            Some(Hint { condition, forms })
+        })
+        .collect()
+}
+
+/// アガリ牌の選び方の一つと、その判定結果。
+#[derive(Debug)]
+pub struct LastChoice {
+    /// アガリ牌とみなした牌。
+    pub last: Tile,
+
+    /// ツモかどうか。
+    pub is_zimo: bool,
+
+    /// 判定結果。点数にならなければその理由。
+    pub result: Result<Judge, JudgeError>,
+}
+
+/// アガリ牌の選び方ごとの判定結果を並べたもの。
+#[derive(Debug)]
+pub struct LastChoices {
+    /// 手牌の牌の順に、同じ牌ならロン・ツモの順に並ぶ。
+    pub choices: Vec<LastChoice>,
+}
+
+impl LastChoices {
+    /// 最も得点の高い選び方。点数になるものがなければ None 。同点なら先に並ぶものを選ぶ。
+    pub fn best(&self) -> Option<&LastChoice> {
+        self.scored().min_by(|x, y| compare(y.1, x.1)).map(|x| x.0)
+    }
+
+    /// 点数になるものの中で最も得点の低い選び方。同点なら先に並ぶものを選ぶ。
+    pub fn worst(&self) -> Option<&LastChoice> {
+        self.scored().min_by(|x, y| compare(x.1, y.1)).map(|x| x.0)
+    }
+
+    fn scored(&self) -> impl Iterator<Item = (&LastChoice, &Judge)> {
+        (self.choices.iter()).filter_map(|choice| Some((choice, choice.result.as_ref().ok()?)))
+    }
+
+    pub fn display_en(&self) -> LastChoicesDisplayEn<'_> {
+        LastChoicesDisplayEn(self)
+    }
+}
+
+impl fmt::Display for LastChoice {
+    fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
+        let tag = if self.is_zimo { "ツモ" } else { "ロン" };
+        write!(b, "{}{}: ", tag, self.last)?;
+        match &self.result {
+            Ok(judge) => {
+                let is_parent = judge.tilesets().context.is_parent();
+                let forms: Vec<_> = judge.forms.iter().map(|form| form.name()).collect();
+                write!(
+                    b,
+                    "{} {}",
+                    judge.total.display_full(is_parent, &judge.ruleset),
+                    forms.join("・")
+                )
+            }
+            Err(err) => write!(b, "{}", err.to_string().lines().next().unwrap_or_default()),
         }
     }
+}
 
-    fn calc_kezi_fu(&self) -> u32 {
-        let mut res = 0;
+impl fmt::Display for LastChoices {
+    fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
+        for choice in &self.choices {
+            writeln!(b, "{}", choice)?;
+        }
 
-        // 明刻 (槓を除く)
-        res += (self.agari.pengs())
-            .chain(self.agari.ronghe_mingke())
-            .map(|tiles| tiles.first())
-            .map(|tile| if tile.is_zhongzhang() { 2 } else { 4 })
-            .sum::<u32>();
+        match (self.best(), self.worst()) {
+            (Some(best), Some(worst)) => write!(b, "最高 {}\n最低 {}", best, worst),
+            _ => write!(b, "どのアガリ牌でも点数になりません。"),
+        }
+    }
+}
+
+pub struct LastChoicesDisplayEn<'a>(&'a LastChoices);
 
-        // 暗刻 (槓を除く)
-        res += (self.agari.kezis_in_hand())
-            .map(|tiles| tiles.first())
-            .map(|tile| if tile.is_zhongzhang() { 4 } else { 8 })
-            .sum::<u32>();
+struct LastChoiceDisplayEn<'a>(&'a LastChoice);
 
-        // 明槓
-        res += (self.agari.minggangs())
-            .map(|tiles| tiles.first())
-            .map(|tile| if tile.is_zhongzhang() { 8 } else { 16 })
-            .sum::<u32>();
+impl fmt::Display for LastChoiceDisplayEn<'_> {
+    fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
+        let LastChoiceDisplayEn(choice) = self;
+        let tag = if choice.is_zimo { "Tsumo" } else { "Ron" };
+        write!(b, "{}{}: ", tag, choice.last)?;
+        match &choice.result {
+            Ok(judge) => {
+                let is_parent = judge.tilesets().context.is_parent();
+                let forms: Vec<_> = judge.forms.iter().map(|form| form.name_en()).collect();
+                write!(
+                    b,
+                    "{} {}",
+                    judge.total.display_full_en(is_parent, &judge.ruleset),
+                    forms.join(", ")
+                )
+            }
+            Err(err) => {
+                let err = err.display_en().to_string();
+                write!(b, "{}", err.lines().next().unwrap_or_default())
+            }
+        }
+    }
+}
 
-        // 暗槓
-        res += (self.agari.angangs())
-            .map(|tiles| tiles.first())
-            .map(|tile| if tile.is_zhongzhang() { 16 } else { 32 })
-            .sum::<u32>();
+impl fmt::Display for LastChoicesDisplayEn<'_> {
+    fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
+        let LastChoicesDisplayEn(choices) = self;
+        for choice in &choices.choices {
+            writeln!(b, "{}", LastChoiceDisplayEn(choice))?;
+        }
 
-        res
+        match (choices.best(), choices.worst()) {
+            (Some(best), Some(worst)) => write!(
+                b,
+                "Best {}\nWorst {}",
+                LastChoiceDisplayEn(best),
+                LastChoiceDisplayEn(worst)
+            ),
+            _ => write!(b, "No Winning Tile Scores"),
+        }
     }
+}
 
-    fn calc_quetou_fu(&self) -> u32 {
-        (self.agari.quetou()).first().num_fan(&self.agari.context())
+/// アガリ牌が区別されていない 14 枚の手牌について、手牌の牌を一種類ずつアガリ牌とみなして判定する。
+///
+/// `is_zimo` が None ならロンとツモの両方を試す。候補は `TingpaiTilesets::each_last` で作る。
+pub fn judge_each_last(
+    cands: &[(Tile, TingpaiTilesets)],
+    is_zimo: Option<bool>,
+    ruleset: &Ruleset,
+) -> LastChoices {
+    let zimos = match is_zimo {
+        Some(is_zimo) => vec![is_zimo],
+        None => vec![false, true],
+    };
+
+    let mut choices = Vec::new();
+    for (last, tingpai) in cands {
+        for &is_zimo in &zimos {
+            let result = (tingpai.with_last(*last, is_zimo))
+                .map_err(JudgeError::from)
+                .and_then(|tilesets| try_judge(&tilesets, ruleset));
+            choices.push(LastChoice {
+                last: *last,
+                is_zimo,
+                result,
+            });
+        }
     }
 
-    fn calc_machi_fu(&self) -> u32 {
-        use crate::agaritilesets::MachiKind;
-        match self.agari.machi() {
-            MachiKind::Liangmian | MachiKind::Shuangpeng => 0,
-            _ => 2,
+    LastChoices { choices }
+}
+
+pub(crate) fn judge_all<'a>(
+    tilesets: &Tilesets,
+    ruleset: &'a Ruleset,
+) -> impl Iterator<Item = Judge> + 'a {
+    let qiduizi = judge_qiduizi(tilesets, ruleset);
+    let kokushimuso = judge_kokushimuso(tilesets, ruleset);
+    let jiulianbaodeng = judge_jiulianbaodeng(tilesets, ruleset);
+    let renhe = judge_renhe(tilesets, ruleset);
+    let agari = (AgariTilesets::enumerate(tilesets).into_iter())
+        .filter_map(move |agari| judge_agari(agari, ruleset));
+
+    agari
+        .chain(qiduizi)
+        .chain(kokushimuso)
+        .chain(jiulianbaodeng)
+        .chain(renhe)
+}
+
+fn forms_for_all_base(
+    tilesets: &Tilesets,
+    ruleset: &Ruleset,
+    explanation: &mut Explanation,
+) -> Vec<Form> {
+    use crate::form::*;
+    let mut forms = Vec::with_capacity(10);
+    for form in tilesets.context.situation.forms() {
+        if form == Form::Renhe {
+            match ruleset.renhe {
+                Renhe::None => {
+                    explanation.reject(&[form], Reason::NoRenhe);
+                    continue;
+                }
+                // 人和だけの判定は `judge_renhe` で別に作る。
+                Renhe::Manguan | Renhe::Beiman => {
+                    explanation.reject(&[form], Reason::RenheAlone);
+                    continue;
+                }
+                Renhe::Yiman => {}
+            }
         }
+        forms.push(explanation.establish(form, &[]));
+    }
+    forms.extend(special_check_lizhi(tilesets, explanation));
+    forms.extend(special_check_menqianqingzimohu(tilesets, explanation));
+    forms.extend(special_check_duanyaojiu(tilesets, ruleset, explanation));
+    forms.extend(special_check_ziyise(tilesets, explanation));
+    forms.extend(special_check_luyise(tilesets, explanation));
+    forms.extend(special_check_hungyise_qingyise(tilesets, explanation));
+    forms.extend(special_check_qinglaotou(tilesets, explanation));
+    forms.extend(special_check_hunlaotou(tilesets, explanation));
+    forms.extend(special_check_dora(tilesets, ruleset, explanation));
+    forms
+}
+
+fn judge_agari(agari: AgariTilesets, ruleset: &Ruleset) -> Option<Judge> {
+    debug!("-> 次のアガリ形について判定: {}", agari);
+    use crate::form::*;
+
+    let mut explanation = Explanation::new();
+    let e = &mut explanation;
+    let mut forms = forms_for_all_base(agari.tilesets(), ruleset, e);
+    forms.extend(check_fanpai(&agari, e));
+    forms.extend(check_pinghe(&agari, e));
+    forms.extend(check_yibeikou_liangbeigou(&agari, e));
+    forms.extend(check_sanshoku_dojun(&agari, e));
+    forms.extend(check_sanshoku_doko(&agari, e));
+    forms.extend(check_sananke_sianke(&agari, e));
+    forms.extend(check_ikki_tukan(&agari, e));
+    forms.extend(check_duiduihe(&agari, e));
+    forms.extend(check_hunquandaiyaojiu_chunquandaiyaojiu(&agari, e));
+    forms.extend(check_sangangzi_sigangzi(&agari, e));
+    forms.extend(check_shousanyuan(&agari, e));
+    forms.extend(check_daisanyuan(&agari, e));
+    forms.extend(check_shousushi_daisushi(&agari, e));
+    let melds: Vec<_> = agari
+        .mianzis()
+        .chain(once(agari.quetou()))
+        .cloned()
+        .collect();
+    explanation.fill_melds(&melds);
+
+    Judge::from_agaritilesets(agari, forms, ruleset, explanation)
+}
+
+/// 七対子・国士無双・九蓮宝燈のように、面子に分解しない形を判定する。
+fn judge_special(
+    tilesets: &Tilesets,
+    ruleset: &Ruleset,
+    check: fn(&Tilesets, &mut Explanation) -> Option<Form>,
+) -> Option<Judge> {
+    let mut explanation = Explanation::new();
+    let form = check(tilesets, &mut explanation)?;
+    let mut forms = forms_for_all_base(tilesets, ruleset, &mut explanation);
+    forms.push(form);
+
+    let melds = explanation.melds(form).unwrap_or_default().to_vec();
+    explanation.fill_melds(&melds);
+    Judge::from_tilesets(tilesets.clone(), forms, ruleset, explanation)
+}
+
+/// 満貫・倍満の人和は他の役と複合しないので、人和だけで数えた判定を作る。
+///
+/// 他の役で数えた判定と並べて、高い方が採られる。
+fn judge_renhe(tilesets: &Tilesets, ruleset: &Ruleset) -> Option<Judge> {
+    if !tilesets.context.situation.renhe || !matches!(ruleset.renhe, Renhe::Manguan | Renhe::Beiman)
+    {
+        return None;
+    }
+
+    debug!("-> 人和を判定...");
+    let mut hand = tilesets.hand.clone();
+    hand.push(tilesets.last);
+    if shanten(&hand, tilesets.without_last().num_mianzis()) >= 0 {
+        return None;
     }
+
+    let mut explanation = Explanation::new();
+    let forms = vec![explanation.establish(Form::Renhe, &[])];
+    Judge::from_tilesets(tilesets.clone(), forms, ruleset, explanation)
+}
+
+fn judge_qiduizi(tilesets: &Tilesets, ruleset: &Ruleset) -> Option<Judge> {
+    debug!("-> 七対子を判定...");
+    judge_special(tilesets, ruleset, crate::form::special_check_qiduizi)
+}
+
+fn judge_kokushimuso(tilesets: &Tilesets, ruleset: &Ruleset) -> Option<Judge> {
+    debug!("-> 国士無双を判定...");
+    judge_special(tilesets, ruleset, crate::form::special_check_kokushimuso)
+}
+
+fn judge_jiulianbaodeng(tilesets: &Tilesets, ruleset: &Ruleset) -> Option<Judge> {
+    debug!("-> 九蓮宝燈を判定...");
+    judge_special(tilesets, ruleset, crate::form::special_check_jiulianbaodeng)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::context::{Context, Direction};
+    use crate::payment::PaymentKind;
     use crate::tilesets::Tilesets;
 
     fn parse(from: &str) -> Tilesets {
-        let tilesets = from
-            .split_whitespace()
-            .map(|tileset| tileset.parse().unwrap())
-            .collect();
-
-        Tilesets::new(Context::default(), tilesets).unwrap()
+        from.parse().unwrap()
     }
 
     fn with_direction(tilesets: Tilesets, player: Direction, place: Direction) -> Tilesets {
@@ -387,14 +898,14 @@ mod tests {
         crate::logger::init_once();
 
         let tilesets = parse("1p1p1p2p2p2p3p3p3p4p4p4p5p ツモ5p");
-        let res = dbg!(judge(&tilesets)).unwrap();
+        let res = dbg!(judge(&tilesets, &Ruleset::default())).unwrap();
         assert_eq!(
             res.to_string(),
             "東場 東家 \n1p1p1p2p2p2p3p3p3p4p4p4p5p ツモ5p\n(1p1p1p 2p2p2p 3p3p3p 4p4p4p 5p5p 待ち: 単騎)\n13翻 四暗刻単騎\n48000点 役満"
         );
         assert_eq!(
             res.display_en().to_string(),
-            "East East \n1p1p1p2p2p2p3p3p3p4p4p4p5p Tsumo5p\n(1p1p1p 2p2p2p 3p3p3p 4p4p4p 5p5p waiting: Single wait)\n13 Han Four Concealed Pungs (Single)\n48000 Points Yakuman"
+            "East-round East-seat \n1p1p1p2p2p2p3p3p3p4p4p4p5p Tsumo5p\n(1p1p1p 2p2p2p 3p3p3p 4p4p4p 5p5p waiting: Single wait)\n13 Han Four Concealed Pungs (Single)\n48000 Points Yakuman"
         );
     }
 
@@ -403,14 +914,14 @@ mod tests {
         crate::logger::init_once();
 
         let tilesets = parse("1s9s1m9m1p9p東南西北白發中 ツモ中");
-        let res = dbg!(judge(&tilesets)).unwrap();
+        let res = dbg!(judge(&tilesets, &Ruleset::default())).unwrap();
         assert_eq!(
             res.to_string(),
             "東場 東家 \n1s9s1m9m1p9p東南西北白發中 ツモ中\n13翻 国士無双13面待ち\n48000点 役満"
         );
         assert_eq!(
             res.display_en().to_string(),
-            "East East \n1s9s1m9m1p9p東南西北白發中 Tsumo中\n13 Han Thirteen Orphans (13)\n48000 Points Yakuman"
+            "East-round East-seat \n1s9s1m9m1p9p東南西北白發中 Tsumo中\n13 Han Thirteen Orphans (13)\n48000 Points Yakuman"
         );
     }
 
@@ -419,14 +930,14 @@ mod tests {
         crate::logger::init_once();
 
         let tilesets = parse("3s3s5s5s1p6p6p東東白白中中 ツモ1p");
-        let res = dbg!(judge(&tilesets)).unwrap();
+        let res = dbg!(judge(&tilesets, &Ruleset::default())).unwrap();
         assert_eq!(
             res.to_string(),
             "東場 東家 \n3s3s5s5s1p6p6p東東白白中中 ツモ1p\n1翻 門前清自摸和\n2翻25符 七対子\n3翻25符 4800点"
         );
         assert_eq!(
             res.display_en().to_string(),
-            "East East \n3s3s5s5s1p6p6p東東白白中中 Tsumo1p\n1 Han Fully Concealed Hand\n2 Han 25 Minipoints Seven Pairs\n3 Han 25 Minipoints 4800 Points"
+            "East-round East-seat \n3s3s5s5s1p6p6p東東白白中中 Tsumo1p\n1 Han Fully Concealed Hand\n2 Han 25 Minipoints Seven Pairs\n3 Han 25 Minipoints 4800 Points"
         );
     }
 
@@ -435,14 +946,14 @@ mod tests {
         crate::logger::init_once();
 
         let tilesets = parse("1p1p2p2p3p3p4p4p5p5p6p6p7p ツモ7p");
-        let res = dbg!(judge(&tilesets)).unwrap();
+        let res = dbg!(judge(&tilesets, &Ruleset::default())).unwrap();
         assert_eq!(
             res.to_string(),
             "東場 東家 \n1p1p2p2p3p3p4p4p5p5p6p6p7p ツモ7p\n(1p2p3p 1p2p3p 5p6p7p 5p6p7p 4p4p 待ち: 両面)\n1翻 門前清自摸和\n1翻 平和\n3翻 二盃口\n6翻 清一色\n11翻 36000点 三倍満"
         );
         assert_eq!(
             res.display_en().to_string(),
-            "East East \n1p1p2p2p3p3p4p4p5p5p6p6p7p Tsumo7p\n(1p2p3p 1p2p3p 5p6p7p 5p6p7p 4p4p waiting: Open wait)\n1 Han Fully Concealed Hand\n1 Han Pinfu\n3 Han Twice Pure Double Chows\n6 Han Full Flush\n11 Han 36000 Points Sanbaiman"
+            "East-round East-seat \n1p1p2p2p3p3p4p4p5p5p6p6p7p Tsumo7p\n(1p2p3p 1p2p3p 5p6p7p 5p6p7p 4p4p waiting: Open wait)\n1 Han Fully Concealed Hand\n1 Han Pinfu\n3 Han Twice Pure Double Chows\n6 Han Full Flush\n11 Han 36000 Points Sanbaiman"
         );
     }
 
@@ -451,14 +962,14 @@ mod tests {
         crate::logger::init_once();
 
         let tilesets = parse("1p1p1p2p2p2p3p3p3p5p ツモ5P ポン4p4p4p");
-        let res = dbg!(judge(&tilesets)).unwrap();
+        let res = dbg!(judge(&tilesets, &Ruleset::default())).unwrap();
         assert_eq!(
             res.to_string(),
-            "東場 東家 \n1p1p1p2p2p2p3p3p3p5p ポン4p4p4p ツモ5P\n(4p4p4p 1p1p1p 2p2p2p 3p3p3p 5p5P 待ち: 単騎)\n1翻 ドラ\n2翻 三暗刻\n2翻 対々和\n5翻 清一色\n10翻 24000点 倍満"
+            "東場 東家 \n1p1p1p2p2p2p3p3p3p5p ポン4p4p4p ツモ5P\n(4p4p4p 1p1p1p 2p2p2p 3p3p3p 5p5P 待ち: 単騎)\n1翻 赤ドラ\n2翻 三暗刻\n2翻 対々和\n5翻 清一色\n10翻 24000点 倍満"
         );
         assert_eq!(
             res.display_en().to_string(),
-            "East East \n1p1p1p2p2p2p3p3p3p5p Pon4p4p4p Tsumo5P\n(4p4p4p 1p1p1p 2p2p2p 3p3p3p 5p5P waiting: Single wait)\n1 Han Dora\n2 Han Three Concealed Pungs\n2 Han All Pungs\n5 Han Full Flush\n10 Han 24000 Points Baiman"
+            "East-round East-seat \n1p1p1p2p2p2p3p3p3p5p Pon4p4p4p Tsumo5P\n(4p4p4p 1p1p1p 2p2p2p 3p3p3p 5p5P waiting: Single wait)\n1 Han Akadora\n2 Han Three Concealed Pungs\n2 Han All Pungs\n5 Han Full Flush\n10 Han 24000 Points Baiman"
         );
     }
 
@@ -469,14 +980,14 @@ mod tests {
         let tilesets = parse("5s6s7s4m5m6m4p4p4p5p6p西西 ロン西");
         let tilesets = with_direction(tilesets, Direction::West, Direction::East);
 
-        let res = dbg!(judge(&tilesets)).unwrap();
+        let res = dbg!(judge(&tilesets, &Ruleset::default())).unwrap();
         assert_eq!(
             res.to_string(),
             "東場 西家 \n5s6s7s4m5m6m4p4p4p5p6p西西 ロン西\n(西西西 5s6s7s 4m5m6m 4p5p6p 4p4p 待ち: シャンポン)\n1翻 役牌\n1翻40符 1300点"
         );
         assert_eq!(
             res.display_en().to_string(),
-            "East West \n5s6s7s4m5m6m4p4p4p5p6p西西 Ron西\n(西西西 5s6s7s 4m5m6m 4p5p6p 4p4p waiting: Double set wait)\n1 Han Yakuhai\n1 Han 40 Minipoints 1300 Points"
+            "East-round West-seat \n5s6s7s4m5m6m4p4p4p5p6p西西 Ron西\n(西西西 5s6s7s 4m5m6m 4p5p6p 4p4p waiting: Double set wait)\n1 Han Yakuhai\n1 Han 40 Minipoints 1300 Points"
         );
     }
 
@@ -485,14 +996,14 @@ mod tests {
         crate::logger::init_once();
 
         let tilesets = parse("1s2s3s4s5s6s6s7s8s8s9s西西 ロン7s ドラ1s中6s2p");
-        let res = dbg!(judge(&tilesets)).unwrap();
+        let res = dbg!(judge(&tilesets, &Ruleset::default())).unwrap();
         assert_eq!(
             res.to_string(),
             "東場 東家 \nドラ1s6s2p中 1s2s3s4s5s6s6s7s8s8s9s西西 ロン7s\n(6s7s8s 1s2s3s 4s5s6s 7s8s9s 西西 待ち: カンチャン)\n2翻 一気通貫\n3翻 混一色\n3翻 ドラ\n8翻 24000点 倍満"
         );
         assert_eq!(
             res.display_en().to_string(),
-            "East East \nDora1s6s2p中 1s2s3s4s5s6s6s7s8s8s9s西西 Ron7s\n(6s7s8s 1s2s3s 4s5s6s 7s8s9s 西西 waiting: Closed wait)\n2 Han Pure Straight\n3 Han Half Flush\n3 Han Dora\n8 Han 24000 Points Baiman"
+            "East-round East-seat \nDora1s6s2p中 1s2s3s4s5s6s6s7s8s8s9s西西 Ron7s\n(6s7s8s 1s2s3s 4s5s6s 7s8s9s 西西 waiting: Closed wait)\n2 Han Pure Straight\n3 Han Half Flush\n3 Han Dora\n8 Han 24000 Points Baiman"
         );
     }
 
@@ -501,14 +1012,14 @@ mod tests {
         crate::logger::init_once();
 
         let tilesets = parse("1m2m3m7m7m4s5s6s8s8s西西西 ツモ7m");
-        let res = dbg!(judge(&tilesets)).unwrap();
+        let res = dbg!(judge(&tilesets, &Ruleset::default())).unwrap();
         assert_eq!(
             res.to_string(),
             "東場 東家 \n4s5s6s8s8s1m2m3m7m7m西西西 ツモ7m\n(7m7m7m 西西西 4s5s6s 1m2m3m 8s8s 待ち: シャンポン)\n1翻 門前清自摸和\n1翻40符 2000点"
         );
         assert_eq!(
             res.display_en().to_string(),
-            "East East \n4s5s6s8s8s1m2m3m7m7m西西西 Tsumo7m\n(7m7m7m 西西西 4s5s6s 1m2m3m 8s8s waiting: Double set wait)\n1 Han Fully Concealed Hand\n1 Han 40 Minipoints 2000 Points"
+            "East-round East-seat \n4s5s6s8s8s1m2m3m7m7m西西西 Tsumo7m\n(7m7m7m 西西西 4s5s6s 1m2m3m 8s8s waiting: Double set wait)\n1 Han Fully Concealed Hand\n1 Han 40 Minipoints 2000 Points"
         );
     }
 
@@ -525,14 +1036,14 @@ mod tests {
             },
             ..tilesets
         };
-        let res = dbg!(judge(&tilesets)).unwrap();
+        let res = dbg!(judge(&tilesets, &Ruleset::default())).unwrap();
         assert_eq!(
             res.to_string(),
-            "東場 東家 \nドラ9m9p9p東 1p1p2p3p4p4p5p6p6p7p7p8p9p ツモ5P\n(2p3p4p 4p5p6p 5P6p7p 7p8p9p 1p1p 待ち: 両面)\n1翻 立直\n1翻 門前清自摸和\n1翻 平和\n3翻 ドラ\n6翻 清一色\n12翻 36000点 三倍満"
+            "東場 東家 リーチ \nドラ9m9p9p東 1p1p2p3p4p4p5p6p6p7p7p8p9p ツモ5P\n(2p3p4p 4p5p6p 5P6p7p 7p8p9p 1p1p 待ち: 両面)\n1翻 立直\n1翻 門前清自摸和\n1翻 平和\n1翻 赤ドラ\n2翻 ドラ\n6翻 清一色\n12翻 36000点 三倍満"
         );
         assert_eq!(
             res.display_en().to_string(),
-            "East East \nDora9m9p9p東 1p1p2p3p4p4p5p6p6p7p7p8p9p Tsumo5P\n(2p3p4p 4p5p6p 5P6p7p 7p8p9p 1p1p waiting: Open wait)\n1 Han Riichi\n1 Han Fully Concealed Hand\n1 Han Pinfu\n3 Han Dora\n6 Han Full Flush\n12 Han 36000 Points Sanbaiman"
+            "East-round East-seat Riichi \nDora9m9p9p東 1p1p2p3p4p4p5p6p6p7p7p8p9p Tsumo5P\n(2p3p4p 4p5p6p 5P6p7p 7p8p9p 1p1p waiting: Open wait)\n1 Han Riichi\n1 Han Fully Concealed Hand\n1 Han Pinfu\n1 Han Akadora\n2 Han Dora\n6 Han Full Flush\n12 Han 36000 Points Sanbaiman"
         );
     }
 
@@ -541,14 +1052,14 @@ mod tests {
         crate::logger::init_once();
         let tilesets = parse("1s1s1s9s9s9p9p ロン9p 暗槓1p1p1p1p ポン1m1m1m ドラ8s9p");
         let tilesets = with_direction(tilesets, Direction::West, Direction::East);
-        let res = dbg!(judge(&tilesets)).unwrap();
+        let res = dbg!(judge(&tilesets, &Ruleset::default())).unwrap();
         assert_eq!(
             res.to_string(),
             "東場 西家 \nドラ8s9p 1s1s1s9s9s9p9p ポン1m1m1m 暗槓1p1p1p1p ロン9p\n(1m1m1m 9p9p9p 1s1s1s 1p1p1p1p 9s9s 待ち: シャンポン)\n13翻 清老頭\n32000点 役満"
         );
         assert_eq!(
             res.display_en().to_string(),
-            "East West \nDora8s9p 1s1s1s9s9s9p9p Pon1m1m1m ConcealedKong1p1p1p1p Ron9p\n(1m1m1m 9p9p9p 1s1s1s 1p1p1p1p 9s9s waiting: Double set wait)\n13 Han All Terminals\n32000 Points Yakuman"
+            "East-round West-seat \nDora8s9p 1s1s1s9s9s9p9p Pon1m1m1m ConcealedKong1p1p1p1p Ron9p\n(1m1m1m 9p9p9p 1s1s1s 1p1p1p1p 9s9s waiting: Double set wait)\n13 Han All Terminals\n32000 Points Yakuman"
         );
     }
 
@@ -556,14 +1067,14 @@ mod tests {
     fn judge_luyise() {
         crate::logger::init_once();
         let tilesets = parse("2s2s2s2s3s4s4s6s6s6s8s8s8s ロン3s");
-        let res = dbg!(judge(&tilesets)).unwrap();
+        let res = dbg!(judge(&tilesets, &Ruleset::default())).unwrap();
         assert_eq!(
             res.to_string(),
             "東場 東家 \n2s2s2s2s3s4s4s6s6s6s8s8s8s ロン3s\n(6s6s6s 8s8s8s 2s3s4s 2s3s4s 2s2s 待ち: カンチャン)\n13翻 緑一色\n48000点 役満"
         );
         assert_eq!(
             res.display_en().to_string(),
-            "East East \n2s2s2s2s3s4s4s6s6s6s8s8s8s Ron3s\n(6s6s6s 8s8s8s 2s3s4s 2s3s4s 2s2s waiting: Closed wait)\n13 Han All Green\n48000 Points Yakuman"
+            "East-round East-seat \n2s2s2s2s3s4s4s6s6s6s8s8s8s Ron3s\n(6s6s6s 8s8s8s 2s3s4s 2s3s4s 2s2s waiting: Closed wait)\n13 Han All Green\n48000 Points Yakuman"
         );
     }
 
@@ -571,14 +1082,14 @@ mod tests {
     fn judge_hunlaotou() {
         crate::logger::init_once();
         let tilesets = parse("1m1m9m9m1s1s1s東東東 ロン9m ポン白白白");
-        let res = dbg!(judge(&tilesets)).unwrap();
+        let res = dbg!(judge(&tilesets, &Ruleset::default())).unwrap();
         assert_eq!(
             res.to_string(),
             "東場 東家 \n1s1s1s1m1m9m9m東東東 ポン白白白 ロン9m\n(白白白 9m9m9m 1s1s1s 東東東 1m1m 待ち: シャンポン)\n2翻 混老頭\n2翻 対々和\n3翻 役牌\n7翻 18000点 跳満"
         );
         assert_eq!(
             res.display_en().to_string(),
-            "East East \n1s1s1s1m1m9m9m東東東 Pon白白白 Ron9m\n(白白白 9m9m9m 1s1s1s 東東東 1m1m waiting: Double set wait)\n2 Han All Terminals and Honors\n2 Han All Pungs\n3 Han Yakuhai\n7 Han 18000 Points Haneman"
+            "East-round East-seat \n1s1s1s1m1m9m9m東東東 Pon白白白 Ron9m\n(白白白 9m9m9m 1s1s1s 東東東 1m1m waiting: Double set wait)\n2 Han All Terminals and Honors\n2 Han All Pungs\n3 Han Yakuhai\n7 Han 18000 Points Haneman"
         );
     }
 
@@ -596,14 +1107,14 @@ mod tests {
             ..tilesets
         };
 
-        let res = dbg!(judge(&tilesets)).unwrap();
+        let res = dbg!(judge(&tilesets, &Ruleset::default())).unwrap();
         assert_eq!(
             res.to_string(),
-            "東場 東家 \nドラ4m 2s2s5s6s7s8s9s3m4m5m1p1p1p ツモ7s\n(1p1p1p 5s6s7s 7s8s9s 3m4m5m 2s2s 待ち: ペンチャン)\n1翻 立直\n1翻 門前清自摸和\n1翻 ドラ\n3翻40符 7700点"
+            "東場 東家 リーチ \nドラ4m 2s2s5s6s7s8s9s3m4m5m1p1p1p ツモ7s\n(1p1p1p 5s6s7s 7s8s9s 3m4m5m 2s2s 待ち: ペンチャン)\n1翻 立直\n1翻 門前清自摸和\n1翻 ドラ\n3翻40符 7700点"
         );
         assert_eq!(
             res.display_en().to_string(),
-            "East East \nDora4m 2s2s5s6s7s8s9s3m4m5m1p1p1p Tsumo7s\n(1p1p1p 5s6s7s 7s8s9s 3m4m5m 2s2s waiting: Edge wait)\n1 Han Riichi\n1 Han Fully Concealed Hand\n1 Han Dora\n3 Han 40 Minipoints 7700 Points"
+            "East-round East-seat Riichi \nDora4m 2s2s5s6s7s8s9s3m4m5m1p1p1p Tsumo7s\n(1p1p1p 5s6s7s 7s8s9s 3m4m5m 2s2s waiting: Edge wait)\n1 Han Riichi\n1 Han Fully Concealed Hand\n1 Han Dora\n3 Han 40 Minipoints 7700 Points"
         );
     }
 
@@ -611,14 +1122,14 @@ mod tests {
     fn judge_true_yiman() {
         crate::logger::init_once();
         let tilesets = parse("2m2m2m3m3m3m4m4m4m5m5m5m1m ツモ1m ドラ2m3m4m");
-        let res = dbg!(judge(&tilesets)).unwrap();
+        let res = dbg!(judge(&tilesets, &Ruleset::default())).unwrap();
         assert_eq!(
             res.to_string(),
             "東場 東家 \nドラ2m3m4m 1m2m2m2m3m3m3m4m4m4m5m5m5m ツモ1m\n(2m2m2m 3m3m3m 4m4m4m 5m5m5m 1m1m 待ち: 単騎)\n13翻 四暗刻単騎\n48000点 役満"
         );
         assert_eq!(
             res.display_en().to_string(),
-            "East East \nDora2m3m4m 1m2m2m2m3m3m3m4m4m4m5m5m5m Tsumo1m\n(2m2m2m 3m3m3m 4m4m4m 5m5m5m 1m1m waiting: Single wait)\n13 Han Four Concealed Pungs (Single)\n48000 Points Yakuman"
+            "East-round East-seat \nDora2m3m4m 1m2m2m2m3m3m3m4m4m4m5m5m5m Tsumo1m\n(2m2m2m 3m3m3m 4m4m4m 5m5m5m 1m1m waiting: Single wait)\n13 Han Four Concealed Pungs (Single)\n48000 Points Yakuman"
         );
     }
 
@@ -626,14 +1137,14 @@ mod tests {
     fn judge_pinghe_zipai() {
         crate::logger::init_once();
         let tilesets = parse("1p2p2p3p3p4p6p6p7p7p8p南南 ロン8p");
-        let res = dbg!(judge(&tilesets)).unwrap();
+        let res = dbg!(judge(&tilesets, &Ruleset::default())).unwrap();
         assert_eq!(
             res.to_string(),
             "東場 東家 \n1p2p2p3p3p4p6p6p7p7p8p南南 ロン8p\n(6p7p8p 1p2p3p 2p3p4p 6p7p8p 南南 待ち: 両面)\n1翻 平和\n1翻 一盃口\n3翻 混一色\n5翻 12000点 満貫"
         );
         assert_eq!(
             res.display_en().to_string(),
-            "East East \n1p2p2p3p3p4p6p6p7p7p8p南南 Ron8p\n(6p7p8p 1p2p3p 2p3p4p 6p7p8p 南南 waiting: Open wait)\n1 Han Pinfu\n1 Han Pure Double Chow\n3 Han Half Flush\n5 Han 12000 Points Mangan"
+            "East-round East-seat \n1p2p2p3p3p4p6p6p7p7p8p南南 Ron8p\n(6p7p8p 1p2p3p 2p3p4p 6p7p8p 南南 waiting: Open wait)\n1 Han Pinfu\n1 Han Pure Double Chow\n3 Han Half Flush\n5 Han 12000 Points Mangan"
         );
     }
 
@@ -641,14 +1152,14 @@ mod tests {
     fn judge_pinghe_zipai_ng() {
         crate::logger::init_once();
         let tilesets = parse("1p2p2p3p3p4p6p6p7p7p8p東東 ロン8p");
-        let res = dbg!(judge(&tilesets)).unwrap();
+        let res = dbg!(judge(&tilesets, &Ruleset::default())).unwrap();
         assert_eq!(
             res.to_string(),
             "東場 東家 \n1p2p2p3p3p4p6p6p7p7p8p東東 ロン8p\n(6p7p8p 1p2p3p 2p3p4p 6p7p8p 東東 待ち: 両面)\n1翻 一盃口\n3翻 混一色\n4翻40符 12000点 満貫"
         );
         assert_eq!(
             res.display_en().to_string(),
-            "East East \n1p2p2p3p3p4p6p6p7p7p8p東東 Ron8p\n(6p7p8p 1p2p3p 2p3p4p 6p7p8p 東東 waiting: Open wait)\n1 Han Pure Double Chow\n3 Han Half Flush\n4 Han 40 Minipoints 12000 Points Mangan"
+            "East-round East-seat \n1p2p2p3p3p4p6p6p7p7p8p東東 Ron8p\n(6p7p8p 1p2p3p 2p3p4p 6p7p8p 東東 waiting: Open wait)\n1 Han Pure Double Chow\n3 Han Half Flush\n4 Han 40 Minipoints 12000 Points Mangan"
         );
     }
 
@@ -656,14 +1167,143 @@ mod tests {
     fn judge_dora_yiman() {
         crate::logger::init_once();
         let tilesets = parse("1p1p1p2p2p2p3p3p3p4p5p6p5p ツモ5p ドラ1p2p3p4p5p6p");
-        let res = dbg!(judge(&tilesets)).unwrap();
+        let res = dbg!(judge(&tilesets, &Ruleset::default())).unwrap();
         assert_eq!(
             res.to_string(),
             "東場 東家 \nドラ1p2p3p4p5p6p 1p1p1p2p2p2p3p3p3p4p5p5p6p ツモ5p\n(1p1p1p 2p2p2p 3p3p3p 4p5p6p 5p5p 待ち: 単騎)\n1翻 門前清自摸和\n2翻 三暗刻\n6翻 清一色\n14翻 ドラ\n23翻 48000点 役満"
         );
         assert_eq!(
             res.display_en().to_string(),
-            "East East \nDora1p2p3p4p5p6p 1p1p1p2p2p2p3p3p3p4p5p5p6p Tsumo5p\n(1p1p1p 2p2p2p 3p3p3p 4p5p6p 5p5p waiting: Single wait)\n1 Han Fully Concealed Hand\n2 Han Three Concealed Pungs\n6 Han Full Flush\n14 Han Dora\n23 Han 48000 Points Yakuman"
+            "East-round East-seat \nDora1p2p3p4p5p6p 1p1p1p2p2p2p3p3p3p4p5p5p6p Tsumo5p\n(1p1p1p 2p2p2p 3p3p3p 4p5p6p 5p5p waiting: Single wait)\n1 Han Fully Concealed Hand\n2 Han Three Concealed Pungs\n6 Han Full Flush\n14 Han Dora\n23 Han 48000 Points Yakuman"
+        );
+    }
+
+    #[test]
+    fn judge_payment() {
+        crate::logger::init_once();
+        let tilesets = parse("1m2m3m7m7m4s5s6s8s8s西西西 ツモ7m");
+        let tilesets = with_direction(tilesets, Direction::South, Direction::East);
+        let res = dbg!(judge(&tilesets, &Ruleset::default())).unwrap();
+        assert_eq!(
+            res.payment().kind,
+            PaymentKind::ChildZimo {
+                parent: 700,
+                child: 400
+            }
+        );
+        assert_eq!(res.payment().total(), 1500);
+
+        let tilesets = parse("5s6s7s4m5m6m4p4p4p5p6p西西 ロン西");
+        let tilesets = with_direction(tilesets, Direction::West, Direction::East);
+        let res = dbg!(judge(&tilesets, &Ruleset::default())).unwrap();
+        assert_eq!(res.payment().kind, PaymentKind::Ronghe(1300));
+    }
+
+    #[test]
+    fn judge_benchang_gongtuo() {
+        crate::logger::init_once();
+        let tilesets = parse("1m2m3m7m7m4s5s6s8s8s西西西 ツモ7m");
+        let tilesets = Tilesets {
+            context: Context {
+                player: Direction::South,
+                benchang: 2,
+                gongtuo: 1,
+                ..Context::default()
+            },
+            ..tilesets
+        };
+        let res = dbg!(judge(&tilesets, &Ruleset::default())).unwrap();
+        assert_eq!(
+            res.to_string(),
+            "東場 南家 2本場 供託1 \n4s5s6s8s8s1m2m3m7m7m西西西 ツモ7m\n(7m7m7m 西西西 4s5s6s 1m2m3m 8s8s 待ち: シャンポン)\n1翻 門前清自摸和\n1翻40符 1300点\n2本場 供託1000点 600/900点 合計3100点"
+        );
+        assert_eq!(
+            res.display_en().to_string(),
+            "East-round South-seat Honba2 Deposit1 \n4s5s6s8s8s1m2m3m7m7m西西西 Tsumo7m\n(7m7m7m 西西西 4s5s6s 1m2m3m 8s8s waiting: Double set wait)\n1 Han Fully Concealed Hand\n1 Han 40 Minipoints 1300 Points\n2 Honba 1000 Deposit 600/900 Points Total 3100 Points"
+        );
+    }
+
+    #[test]
+    fn judge_ruleset() {
+        crate::logger::init_once();
+
+        let no_kiriage = Ruleset {
+            kiriage_manguan: false,
+            ..Ruleset::default()
+        };
+        let tilesets = parse("2m3m4m2p3p4p2s3s4s5s6s8s8s ロン7s");
+        let tilesets = with_direction(tilesets, Direction::South, Direction::East);
+        let res = dbg!(judge(&tilesets, &Ruleset::default())).unwrap();
+        assert_eq!(res.payment().total(), 8000);
+        let res = dbg!(judge(&tilesets, &no_kiriage)).unwrap();
+        assert_eq!(res.payment().total(), 7700);
+
+        let no_kuitan = Ruleset {
+            kuitan: false,
+            ..Ruleset::default()
+        };
+        let tilesets = parse("2m3m4m2p3p4p5s6s7s8s ロン8s ポン4s4s4s");
+        assert!(judge(&tilesets, &Ruleset::default()).is_some());
+        assert!(judge(&tilesets, &no_kuitan).is_none());
+
+        let no_kazoe = Ruleset {
+            kazoe_yiman: false,
+            ..Ruleset::default()
+        };
+        let tilesets = parse("1p1p1p2p2p2p3p3p3p4p5p6p5p ツモ5p ドラ1p2p3p4p5p6p");
+        let res = dbg!(judge(&tilesets, &no_kazoe)).unwrap();
+        assert_eq!(
+            res.total().display_full(true, &no_kazoe).to_string(),
+            "23翻 36000点 三倍満"
+        );
+
+        let double_yiman = Ruleset {
+            double_yiman: true,
+            ..Ruleset::default()
+        };
+        let tilesets = parse("2m2m2m3m3m3m4m4m4m5m5m5m1m ツモ1m");
+        let res = dbg!(judge(&tilesets, &double_yiman)).unwrap();
+        assert_eq!(
+            res.to_string(),
+            "東場 東家 \n1m2m2m2m3m3m3m4m4m4m5m5m5m ツモ1m\n(2m2m2m 3m3m3m 4m4m4m 5m5m5m 1m1m 待ち: 単騎)\n26翻 四暗刻単騎\n96000点 ダブル役満"
+        );
+
+        let no_akadora = Ruleset {
+            akadora: 0,
+            ..Ruleset::default()
+        };
+        let tilesets = parse("1p1p1p2p2p2p3p3p3p5p ツモ5P ポン4p4p4p");
+        let res = dbg!(judge(&tilesets, &no_akadora)).unwrap();
+        assert!(res.forms().iter().all(|form| !form.is_dora()));
+    }
+
+    #[test]
+    fn judge_uradora() {
+        use crate::context::Lizhi;
+        crate::logger::init_once();
+
+        let tilesets = parse("2s2s1p1p1p3m4m5m5s6s7s8s9s ツモ7s ドラ表示3m 裏ドラ表示1s9p");
+        let res = dbg!(judge(&tilesets, &Ruleset::default())).unwrap();
+        assert_eq!(
+            res.to_string(),
+            "東場 東家 \nドラ4m 裏ドラ表示1s9p 2s2s5s6s7s8s9s3m4m5m1p1p1p ツモ7s\n(1p1p1p 5s6s7s 7s8s9s 3m4m5m 2s2s 待ち: ペンチャン)\n1翻 門前清自摸和\n1翻 ドラ\n2翻40符 3900点"
+        );
+
+        let tilesets = Tilesets {
+            context: Context {
+                lizhi: Lizhi::Lizhi,
+                ..Context::default()
+            },
+            ..tilesets
+        };
+        let res = dbg!(judge(&tilesets, &Ruleset::default())).unwrap();
+        assert_eq!(
+            res.to_string(),
+            "東場 東家 リーチ \nドラ4m 裏ドラ表示1s9p 2s2s5s6s7s8s9s3m4m5m1p1p1p ツモ7s\n(1p1p1p 5s6s7s 7s8s9s 3m4m5m 2s2s 待ち: ペンチャン)\n1翻 立直\n1翻 門前清自摸和\n1翻 ドラ\n5翻 裏ドラ\n8翻 24000点 倍満"
+        );
+        assert_eq!(
+            res.display_en().to_string(),
+            "East-round East-seat Riichi \nDora4m UradoraIndicator1s9p 2s2s5s6s7s8s9s3m4m5m1p1p1p Tsumo7s\n(1p1p1p 5s6s7s 7s8s9s 3m4m5m 2s2s waiting: Edge wait)\n1 Han Riichi\n1 Han Fully Concealed Hand\n1 Han Dora\n5 Han Uradora\n8 Han 24000 Points Baiman"
         );
     }
 
@@ -671,6 +1311,290 @@ mod tests {
     fn judge_dora_only() {
         crate::logger::init_once();
         let tilesets = parse("2m3m4m2s2s4s5s6p7p8p ツモ6s チー3m1m2m ドラ2m");
-        assert!(judge(&tilesets).is_none());
+        assert!(judge(&tilesets, &Ruleset::default()).is_none());
+    }
+
+    #[test]
+    fn context_round_trip() {
+        let tilesets = parse("南場 西家 リーチ 一発 ドラ表示1m 2m3m4m4p5p6p7s8s9s3s3s5m6m ロン7m");
+        let judge = judge(&tilesets, &Ruleset::default()).unwrap();
+
+        // 判定結果の最初の 2 行をつなげると元の手牌が得られる。
+        let ja = judge.to_string();
+        let lines: Vec<_> = ja.lines().take(2).collect();
+        assert_eq!(lines[0], "南場 西家 リーチ 一発 ");
+        let restored = parse(&lines.join(" "));
+        assert_eq!(ja, judge_restored(&restored));
+
+        let en = judge.display_en().to_string();
+        let lines: Vec<_> = en.lines().take(2).collect();
+        assert_eq!(lines[0], "South-round West-seat Riichi Ippatsu ");
+        assert_eq!(ja, judge_restored(&parse(&lines.join(" "))));
+    }
+
+    fn judge_restored(tilesets: &Tilesets) -> String {
+        judge(tilesets, &Ruleset::default()).unwrap().to_string()
+    }
+
+    #[test]
+    fn explanation() {
+        use crate::explanation::Reason;
+        use crate::tiles::Tiles;
+
+        let tilesets = parse("リーチ 2m3m4m4p5p6p7s8s9s東東5m6m ロン7m");
+        let res = judge(&tilesets, &Ruleset::default()).unwrap();
+        let explanation = res.explanation();
+        assert_eq!(explanation.established.len(), 1);
+        assert_eq!(explanation.established[0].form, Form::Lizhi);
+        let pinghe = (explanation.rejected.iter())
+            .find(|rejected| rejected.forms == [Form::Pinghe])
+            .unwrap();
+        assert_eq!(pinghe.reason, Reason::FanpaiQuetou("東".parse().unwrap()));
+        assert_eq!(
+            pinghe.reason.display_en().to_string(),
+            "pair is a value tile (東)"
+        );
+        assert!(explanation
+            .display_en()
+            .to_string()
+            .contains("Pinfu rejected: pair is a value tile (東)\n"));
+        assert!(explanation
+            .to_string()
+            .contains("平和 不成立: 雀頭の東が役牌です。\n"));
+
+        let tilesets = parse("1p1p2p2p3p3p4p4p5p5p6p6p7p ツモ7p");
+        let res = judge(&tilesets, &Ruleset::default()).unwrap();
+        let melds: Vec<Tiles> = ["1p2p3p", "1p2p3p", "5p6p7p", "5p6p7p"]
+            .iter()
+            .map(|tiles| tiles.parse().unwrap())
+            .collect();
+        assert_eq!(res.explanation().melds(Form::Liangbeigou), Some(&melds[..]));
+        assert_eq!(
+            res.explanation().melds(Form::Qingyise(true)).unwrap().len(),
+            5
+        );
+        let forms: Vec<_> = (res.explanation().established.iter())
+            .map(|established| established.form)
+            .collect();
+        assert_eq!(&forms, res.forms());
+
+        // 役満があれば他の役は数えない。
+        let tilesets = parse("1p1p1p2p2p2p3p3p3p4p4p4p5p ツモ5p");
+        let res = judge(&tilesets, &Ruleset::default()).unwrap();
+        assert!(res.explanation().rejected.iter().any(|rejected| {
+            rejected.forms == [Form::Menqianqingzimohu] && rejected.reason == Reason::Yiman
+        }));
+        assert_eq!(
+            res.explanation().melds(Form::Sianke(true)).unwrap().len(),
+            4
+        );
+    }
+
+    #[test]
+    fn ranked() {
+        let tilesets = parse("2m2m3m3m4m4m5m5m6m6m7m7m8m ロン8m");
+        let ruleset = Ruleset::default();
+        let ranked = judge_ranked(&tilesets, &ruleset);
+        assert!(ranked.len() >= 3);
+
+        // 先頭は judge の結果と同じ。
+        let best = &ranked[0];
+        assert_eq!(best.beaten_by, None);
+        assert_eq!(
+            best.judge.forms(),
+            judge(&tilesets, &ruleset).unwrap().forms()
+        );
+        assert!(best.judge.forms().contains(&Form::Liangbeigou));
+
+        // 七対子としても解釈できるが、二盃口の方が翻数が多い。
+        let qiduizi = (ranked.iter())
+            .find(|i| i.judge.forms().contains(&Form::Qiduizi))
+            .unwrap();
+        assert!(qiduizi.judge.agari_tilesets().is_none());
+        assert_eq!(qiduizi.judge.total().fu, 25);
+        assert_eq!(qiduizi.beaten_by, Some(Preference::Fan));
+        assert_eq!(Preference::Fan.display_en().to_string(), "more han");
+
+        // 順位の通りに並んでいる。
+        for pair in ranked.windows(2) {
+            assert_ne!(compare(&pair[0].judge, &pair[1].judge), Ordering::Less);
+        }
+
+        let tilesets = parse("1m1m1m2m3m4m5m6m7m8m9m9m9m ツモ2m");
+        let ranked = judge_ranked(&tilesets, &ruleset);
+        assert_eq!(ranked[0].judge.forms(), &[Form::Jiulianbaodeng(true)]);
+        assert_eq!(ranked[1].beaten_by, Some(Preference::Yiman));
+        assert!(ranked[1].judge.agari_tilesets().is_some());
+
+        assert!(judge_ranked(&parse("1m2m3m4p5p6p7s8s9s東東南南 ロン南"), &ruleset).is_empty());
+    }
+
+    #[test]
+    fn try_judge_errors() {
+        let ruleset = Ruleset::default();
+        assert!(try_judge(&parse("1m2m3m4p5p6p7s8s9s3s3s5m6m ロン7m"), &ruleset).is_ok());
+
+        let err = try_judge(&parse("1m2m3m4p5p6p7s8s9s東東5m6m ロン9m"), &ruleset).unwrap_err();
+        assert!(matches!(err, JudgeError::NotAgari { shanten: 0, .. }));
+        assert_eq!(
+            err.to_string(),
+            "アガリの形になっていません。待ち牌は 4m7m です。"
+        );
+        let err = try_judge(&parse("1m2m3m4p5p6p7s8s9s東南5m6m ロン9m"), &ruleset).unwrap_err();
+        assert_eq!(
+            err.display_en().to_string(),
+            "Not a Winning Hand (1-shanten)"
+        );
+
+        // 鳴いた断么九は喰いタンなしでは役にならない。
+        let kuitan_nashi = Ruleset {
+            kuitan: false,
+            ..ruleset
+        };
+        let err = try_judge(
+            &parse("4p5p6p6s7s8s3s3s5m6m ポン2m2m2m ロン7m ドラ3s"),
+            &kuitan_nashi,
+        )
+        .unwrap_err();
+        match &err {
+            JudgeError::NoYaku { doras, hints } => {
+                assert_eq!(doras, &[Form::Dora(2)]);
+                let conditions: Vec<_> = hints.iter().map(|hint| hint.condition).collect();
+                assert_eq!(conditions, vec![Condition::Menqian, Condition::Kuitan]);
+                assert!(hints[0].forms.contains(&Form::Duanyaojiu));
+            }
+            _ => panic!("unexpected error: {}", err),
+        }
+        assert_eq!(
+            err.to_string(),
+            "役がありません (ドラのみ) 。\n門前なら断么九がつきます。\n喰いタンありなら断么九がつきます。"
+        );
+        assert_eq!(
+            err.display_en().to_string(),
+            "No Yaku (Dora Only)\nwould be All Simples if concealed\nwould be All Simples if open tanyao were allowed"
+        );
+
+        // フィールドを書き換えて作った牌集合も確かめる。
+        let mut tilesets = parse("1m1m1m1m2m3m4p5p6p7s8s9s東 ロン東");
+        tilesets.last = "1m".parse().unwrap();
+        assert!(matches!(
+            try_judge(&tilesets, &ruleset),
+            Err(JudgeError::InvalidTiles(
+                TilesetsError::InvalidNumSameTiles(_)
+            ))
+        ));
+    }
+
+    #[test]
+    fn each_last() {
+        use crate::tilesets::parse_tilesets;
+
+        let (context, tilesets) =
+            parse_tilesets("2m3m4m5m6m7m4p5p6p3s3s7s8s9s", Context::default()).unwrap();
+        let cands = TingpaiTilesets::each_last(context, tilesets).unwrap();
+        let choices = judge_each_last(&cands, None, &Ruleset::default());
+        assert_eq!(choices.choices.len(), 26);
+
+        let best = choices.best().unwrap();
+        assert_eq!(
+            (best.last.to_string(), best.is_zimo),
+            ("9s".to_string(), true)
+        );
+        let worst = choices.worst().unwrap();
+        assert_eq!(
+            (worst.last.to_string(), worst.is_zimo),
+            ("3s".to_string(), true)
+        );
+
+        // 嵌張や単騎の待ちとみなすと平和がつかない。
+        let ronghe_5p = (choices.choices.iter())
+            .find(|c| c.last.to_string() == "5p" && !c.is_zimo)
+            .unwrap();
+        assert!(matches!(ronghe_5p.result, Err(JudgeError::NoYaku { .. })));
+        assert_eq!(ronghe_5p.to_string(), "ロン5p: 役がありません。");
+
+        let display = choices.to_string();
+        assert!(display.ends_with(
+            "最高 ツモ9s: 2翻20符 2000点 門前清自摸和・平和\n最低 ツモ3s: 1翻30符 1500点 門前清自摸和"
+        ));
+        assert!(choices
+            .display_en()
+            .to_string()
+            .contains("\nRon9s: 1 Han 30 Minipoints 1500 Points Pinfu\n"));
+
+        let ron_only = judge_each_last(&cands, Some(false), &Ruleset::default());
+        assert!(ron_only.choices.iter().all(|c| !c.is_zimo));
+
+        let (context, tilesets) = parse_tilesets("2m3m4m ロン5m", Context::default()).unwrap();
+        assert!(matches!(
+            TingpaiTilesets::each_last(context, tilesets),
+            Err(TilesetsError::LastTileSpecified)
+        ));
+        let (context, tilesets) = parse_tilesets("2m3m4m", Context::default()).unwrap();
+        assert!(matches!(
+            TingpaiTilesets::each_last(context, tilesets),
+            Err(TilesetsError::InvalidNumTiles(3))
+        ));
+    }
+
+    #[test]
+    fn renhe() {
+        use crate::ruleset::Renhe;
+
+        let tilesets = parse("南家 人和 1m2m3m4p5p6p7s8s9s東東西西 ロン西");
+        let rule = |renhe| Ruleset {
+            renhe,
+            ..Ruleset::default()
+        };
+
+        assert!(judge(&tilesets, &rule(Renhe::None)).is_none());
+        assert!(matches!(
+            try_judge(&tilesets, &rule(Renhe::None)),
+            Err(JudgeError::NoYaku { .. })
+        ));
+
+        let res = judge(&tilesets, &rule(Renhe::Manguan)).unwrap();
+        assert_eq!(res.forms(), &[Form::Renhe]);
+        assert_eq!(res.payment().total(), 8000);
+        assert!(res.to_string().contains("\n5翻 人和\n"));
+        assert!(res.display_en().to_string().contains("Blessing of Man"));
+
+        let res = judge(&tilesets, &rule(Renhe::Beiman)).unwrap();
+        assert_eq!(res.payment().total(), 16000);
+
+        let res = judge(&tilesets, &rule(Renhe::Yiman)).unwrap();
+        assert_eq!(res.payment().total(), 32000);
+        assert!(res.total().is_true_yiman());
+
+        // 満貫・倍満の人和は他の役に翻数を足さず、人和だけの場合と比べて高い方を採る。
+        let tilesets = parse("南家 人和 2m3m4m4p5p6p3s4s5s6s7s5m5m ロン8s ドラ5m");
+        let res = judge(&tilesets, &rule(Renhe::Manguan)).unwrap();
+        assert_eq!(res.forms(), &[Form::Renhe]);
+        assert_eq!(res.payment().total(), 8000);
+
+        let ranked = judge_ranked(&tilesets, &rule(Renhe::Manguan));
+        assert_eq!(ranked.len(), 2);
+        assert!(!ranked[1].judge.forms().contains(&Form::Renhe));
+        assert_eq!(ranked[1].judge.total().fan, 4);
+
+        let res = judge(&tilesets, &rule(Renhe::Beiman)).unwrap();
+        assert_eq!(res.forms(), &[Form::Renhe]);
+        assert_eq!(res.payment().total(), 16000);
+
+        // 他の役だけで人和より高ければ、人和はつかない。
+        let tilesets = parse("南家 人和 2m3m4m4p5p6p3s4s5s6s7s5m5m ロン8s ドラ5m4m3m");
+        let res = judge(&tilesets, &rule(Renhe::Manguan)).unwrap();
+        assert!(!res.forms().contains(&Form::Renhe));
+        assert_eq!(res.payment().total(), 12000);
+        assert!(res
+            .explanation()
+            .to_string()
+            .contains("人和は他の役と複合しません。"));
+
+        // 役満の人和は他の役満と複合する。
+        let tilesets = parse("南家 人和 1m1m1m2m3m4m5m6m7m8m9m9m9m ロン1m");
+        let res = judge(&tilesets, &rule(Renhe::Yiman)).unwrap();
+        assert!(res.forms().contains(&Form::Renhe));
+        assert_eq!(res.total().yiman, 2);
     }
 }
@@ -1,12 +1,33 @@
 pub mod agaritilesets;
+pub mod compact;
 pub mod context;
+pub mod explanation;
 pub mod form;
+pub mod fu;
 pub mod judge;
+#[cfg(feature = "majsoul")]
+pub mod majsoul;
+#[cfg(feature = "mjai")]
+pub mod mjai;
+pub mod paipu;
+pub mod payment;
+pub mod round;
+pub mod ruleset;
+#[cfg(feature = "serde")]
+mod serialize;
+pub mod shanten;
+pub mod span;
+#[cfg(feature = "tenhou")]
+pub mod tenhou;
 pub mod tile;
 pub mod tiles;
 pub mod tileset;
 pub mod tilesets;
+pub mod tingpai;
+pub mod ukeire;
 mod utils;
+pub mod wall;
+pub mod zhenting;
 
 #[cfg(test)]
 pub mod logger {
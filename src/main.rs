@@ -0,0 +1,323 @@
+//! 手牌を判定して点数を表示するコマンドラインツール。
+//!
+//! 手牌は牌集合の表記 (例: `123m456p789s東東 ロン東`) で与える。 `南家 リーチ` のようなコンテキストの
+//! 表記を混ぜてもよい。手牌を与えなければ標準入力から一行に一つずつ読み込んでまとめて判定する。
+
+use mahjong::context::{Context, Direction, Lizhi};
+use mahjong::judge::{try_judge, Judge, JudgeError};
+use mahjong::ruleset::{Renhe, Ruleset};
+use mahjong::tilesets::{parse_tilesets, ParseErrors, Tilesets};
+use std::io::{self, BufRead};
+use std::process;
+use thiserror::Error;
+
+const USAGE: &str = "\
+使い方: mahjong [オプション] [手牌...]
+
+手牌には「南場 西家 リーチ 一発 海底」のようなコンテキストの表記を混ぜてもよい。
+手牌を与えなければ標準入力から一行に一つずつ読み込む。
+
+オプション:
+    --place <風>        場風 (東南西北または East など) 。既定は東。
+    --player <風>       自風。既定は東。
+    --riichi            立直
+    --double-riichi     ダブル立直
+    --ippatsu           一発 (立直と一緒に指定する)
+    --dora <牌>         ドラ表示牌 (例: 5m東)
+    --uradora <牌>      裏ドラ表示牌
+    --haitei            海底撈月
+    --houtei            河底撈魚
+    --rinshan           嶺上開花
+    --chankan           槍槓
+    --tenhou            天和
+    --chiihou           地和
+    --renhou <扱い>     人和 (満貫・倍満・役満のいずれかとして数える)
+    --honba <数>        本場
+    --kyotaku <数>      供託されているリーチ棒の本数
+    --en                英語で表示する
+    --json              JSON で出力する
+    -h, --help          この説明を表示する";
+
+type Result<T> = std::result::Result<T, CliError>;
+
+#[derive(Debug, Error)]
+enum CliError {
+    #[error("不明なオプションです: {0}")]
+    UnknownOption(String),
+
+    #[error("{0} には値が必要です。")]
+    MissingValue(String),
+
+    #[error("{0} の値が変です: {1}")]
+    InvalidValue(String, String),
+
+    #[error("一発は立直と一緒に指定してください。")]
+    IppatsuWithoutLizhi,
+
+    #[error("{0}")]
+    Parse(#[from] ParseErrors),
+
+    #[error("{0}")]
+    Judge(#[from] JudgeError),
+
+    #[error("JSON での出力には json フィーチャーが必要です。")]
+    JsonUnsupported,
+
+    #[error("入力を読めません: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// コマンドラインで指定された設定。
+#[derive(Debug, Default)]
+struct Options {
+    context: Context,
+    doras: Option<String>,
+    uradoras: Option<String>,
+    ruleset: Ruleset,
+    en: bool,
+    json: bool,
+    help: bool,
+
+    /// 手牌。空なら標準入力から読む。
+    hand: Vec<String>,
+}
+
+impl Options {
+    fn parse<I: IntoIterator<Item = String>>(args: I) -> Result<Options> {
+        let mut options = Options::default();
+        let mut lizhi = Lizhi::None;
+        let mut ippatsu = false;
+        let mut args = args.into_iter();
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| CliError::MissingValue(arg.clone()))
+            };
+
+            match arg.as_str() {
+                "--place" | "--player" => {
+                    let value = value()?;
+                    let dir: Direction = (value.parse())
+                        .map_err(|_| CliError::InvalidValue(arg.clone(), value.clone()))?;
+                    if arg == "--place" {
+                        options.context.place = dir;
+                    } else {
+                        options.context.player = dir;
+                    }
+                }
+                "--honba" | "--kyotaku" => {
+                    let value = value()?;
+                    let num: u32 = (value.parse())
+                        .map_err(|_| CliError::InvalidValue(arg.clone(), value.clone()))?;
+                    if arg == "--honba" {
+                        options.context.benchang = num;
+                    } else {
+                        options.context.gongtuo = num;
+                    }
+                }
+                "--dora" => options.doras = Some(value()?),
+                "--uradora" => options.uradoras = Some(value()?),
+                "--riichi" => lizhi = Lizhi::Lizhi,
+                "--double-riichi" => lizhi = Lizhi::DoubleLizhi,
+                "--ippatsu" => ippatsu = true,
+                "--haitei" => options.context.situation.haidimoyue = true,
+                "--houtei" => options.context.situation.hedilaoyu = true,
+                "--rinshan" => options.context.situation.lingshangkaihua = true,
+                "--chankan" => options.context.situation.chenggang = true,
+                "--tenhou" => options.context.situation.tianhe = true,
+                "--chiihou" => options.context.situation.dihe = true,
+                "--renhou" => {
+                    let value = value()?;
+                    options.ruleset.renhe = match value.as_str() {
+                        "満貫" | "Mangan" => Renhe::Manguan,
+                        "倍満" | "Baiman" => Renhe::Beiman,
+                        "役満" | "Yakuman" => Renhe::Yiman,
+                        _ => return Err(CliError::InvalidValue(arg, value)),
+                    };
+                    options.context.situation.renhe = true;
+                }
+                "--en" => options.en = true,
+                "--json" => options.json = true,
+                "-h" | "--help" => options.help = true,
+                _ if arg.starts_with("--") => return Err(CliError::UnknownOption(arg)),
+                _ => options.hand.push(arg),
+            }
+        }
+
+        options.context.lizhi = match (lizhi, ippatsu) {
+            (lizhi, false) => lizhi,
+            (Lizhi::Lizhi, true) => Lizhi::LizhiIppatsu,
+            (Lizhi::DoubleLizhi, true) => Lizhi::DoubleLizhiIppatsu,
+            _ => return Err(CliError::IppatsuWithoutLizhi),
+        };
+
+        if options.json && !cfg!(feature = "json") {
+            return Err(CliError::JsonUnsupported);
+        }
+
+        Ok(options)
+    }
+
+    /// 一つの手牌を判定する。
+    fn judge(&self, hand: &str) -> Result<Judge> {
+        let mut hand = hand.to_string();
+        if let Some(doras) = &self.doras {
+            hand += &format!(" ドラ表示{}", doras);
+        }
+
+        if let Some(uradoras) = &self.uradoras {
+            hand += &format!(" 裏ドラ表示{}", uradoras);
+        }
+
+        let (context, tilesets) = parse_tilesets(&hand, self.context.clone())?;
+        let tilesets = Tilesets::new(context, tilesets).map_err(JudgeError::from)?;
+        Ok(try_judge(&tilesets, &self.ruleset)?)
+    }
+
+    /// 判定結果または失敗を出力する形にする。
+    fn render(&self, result: &Result<Judge>) -> String {
+        if self.json {
+            return render_json(result);
+        }
+
+        match result {
+            Ok(judge) if self.en => judge.display_en().to_string(),
+            Ok(judge) => judge.to_string(),
+            Err(CliError::Judge(err)) if self.en => err.display_en().to_string(),
+            Err(err) => format!("エラー: {}", err),
+        }
+    }
+}
+
+#[cfg(feature = "json")]
+fn render_json(result: &Result<Judge>) -> String {
+    let value = match result {
+        Ok(judge) => serde_json::to_value(judge).expect("judge can be serialized."),
+        Err(err) => serde_json::json!({ "error": err.to_string() }),
+    };
+
+    value.to_string()
+}
+
+#[cfg(not(feature = "json"))]
+fn render_json(_: &Result<Judge>) -> String {
+    unreachable!("--json is rejected without the json feature.")
+}
+
+fn run() -> Result<bool> {
+    let options = Options::parse(std::env::args().skip(1))?;
+    if options.help {
+        println!("{}", USAGE);
+        return Ok(true);
+    }
+
+    if !options.hand.is_empty() {
+        let result = options.judge(&options.hand.join(" "));
+        println!("{}", options.render(&result));
+        return Ok(result.is_ok());
+    }
+
+    // 一行に一つの手牌をまとめて判定する。
+    let mut succeeded = true;
+    let stdin = io::stdin();
+    for line in stdin.lock().lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let result = options.judge(&line);
+        succeeded &= result.is_ok();
+        println!("{}", options.render(&result));
+        if !options.json {
+            println!();
+        }
+    }
+
+    Ok(succeeded)
+}
+
+fn main() {
+    match run() {
+        Ok(true) => {}
+        Ok(false) => process::exit(1),
+        Err(err) => {
+            eprintln!("エラー: {}", err);
+            eprintln!("{}", USAGE);
+            process::exit(2);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(args: &str) -> Options {
+        Options::parse(args.split_whitespace().map(String::from)).unwrap()
+    }
+
+    #[test]
+    fn parse_options() {
+        let opts =
+            options("--place 南 --player West --riichi --ippatsu --dora 5m --haitei 1m ロン1m");
+        assert_eq!(opts.context.place, Direction::South);
+        assert_eq!(opts.context.player, Direction::West);
+        assert_eq!(opts.context.lizhi, Lizhi::LizhiIppatsu);
+        assert!(opts.context.situation.haidimoyue);
+        assert_eq!(opts.doras.as_deref(), Some("5m"));
+        assert_eq!(opts.hand, vec!["1m", "ロン1m"]);
+
+        let parse = |args: &str| Options::parse(args.split_whitespace().map(String::from));
+        assert!(matches!(
+            parse("--ippatsu"),
+            Err(CliError::IppatsuWithoutLizhi)
+        ));
+        assert!(matches!(parse("--place"), Err(CliError::MissingValue(_))));
+        assert!(matches!(
+            parse("--place 中"),
+            Err(CliError::InvalidValue(..))
+        ));
+        assert!(matches!(parse("--foo"), Err(CliError::UnknownOption(_))));
+
+        let opts = options("--player 南 --renhou 倍満");
+        assert_eq!(opts.ruleset.renhe, Renhe::Beiman);
+        assert!(opts.context.situation.renhe);
+        assert!(matches!(
+            parse("--renhou 跳満"),
+            Err(CliError::InvalidValue(..))
+        ));
+    }
+
+    #[test]
+    fn judge_hand() {
+        let opts = options("--riichi --dora 4m");
+        let judge = opts.judge("2m3m4m4p5p6p7s8s9s3s3s5m6m ロン7m").unwrap();
+        // 立直・平和・ドラ 1
+        assert_eq!(judge.total().fan, 3);
+        assert!(opts.render(&Ok(judge)).contains("立直"));
+
+        let opts = options("--en");
+        let result = opts.judge("1m2m3m4p5p6p7s8s9s東東南南 ロン南");
+        assert!(matches!(
+            result,
+            Err(CliError::Judge(JudgeError::NoYaku { .. }))
+        ));
+        assert_eq!(
+            opts.render(&result),
+            "No Yaku\nwould be Fully Concealed Hand with tsumo\nwould be Riichi with riichi"
+        );
+
+        let judge = opts
+            .judge("南家 リーチ 2m3m4m4p5p6p7s8s9s3s3s5m6m ロン7m")
+            .unwrap();
+        assert_eq!(judge.tilesets().context.player, Direction::South);
+        assert_eq!(judge.total().fan, 2);
+
+        assert!(matches!(
+            opts.judge("1m2m3m ロン1x"),
+            Err(CliError::Parse(_))
+        ));
+    }
+}
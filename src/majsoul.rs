@@ -0,0 +1,522 @@
+//! 雀魂の牌譜を読み込む。
+//!
+//! 雀魂の牌譜を復号した JSON に対応する。牌譜は `RecordNewRound` や `RecordDiscardTile` などの記録
+//! の列で、それぞれ `{"name": ".lq.RecordNewRound", "data": {...}}` の形をしている。記録の列だけの配列
+//! か、 `head` (対局の情報) と `records` (記録の列) を持つオブジェクトを読める。三人麻雀には対応しな
+//! い。
+
+use crate::context::Direction;
+use crate::paipu::{Event, HuleRecord, Paipu, RoundRecord};
+use crate::ruleset::{Renhe, Ruleset};
+use crate::tile::Tile;
+use num_traits::FromPrimitive;
+use serde_json::Value;
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, MajsoulError>;
+
+#[derive(Debug, Error)]
+pub enum MajsoulError {
+    /// JSON として読めない。
+    #[error("JSON を読めません: {0}")]
+    Json(#[from] serde_json::Error),
+
+    /// JSON の構造が想定と違う。
+    #[error("牌譜の形式が変です: {0}")]
+    InvalidFormat(String),
+
+    /// 記録に必要な項目がない。
+    #[error("{0} に {1} がありません。")]
+    FieldNotFound(String, &'static str),
+
+    /// 牌の表記が読めない。
+    #[error("不明な牌です: {0}")]
+    UnknownTile(String),
+
+    /// 席の番号が 0 から 3 でない。
+    #[error("{0} の席の番号が変です: {1}")]
+    InvalidSeat(String, u32),
+
+    /// 点数や翻数が大きすぎて計算できない。
+    #[error("{0} の点数が大きすぎます。")]
+    InvalidPoint(String),
+
+    /// 三人麻雀の牌譜。
+    #[error("三人麻雀には対応していません。")]
+    Sanma,
+}
+
+/// 雀魂 (段位戦) のルール。切り上げ満貫と人和はなく、ダブル役満と数え役満がある。
+pub fn ruleset() -> Ruleset {
+    Ruleset {
+        kiriage_manguan: false,
+        kuitan: true,
+        kazoe_yiman: true,
+        double_yiman: true,
+        akadora: 3,
+        renhe: Renhe::None,
+    }
+}
+
+/// 牌の表記 (例: "1m", "0p", "7z") を牌にする。 0 は赤ドラの 5 を表す。
+fn tile(s: &str) -> Result<Tile> {
+    let unknown = || MajsoulError::UnknownTile(s.to_string());
+
+    match s.strip_prefix('0') {
+        Some(suit) => (format!("5{}", suit).parse::<Tile>())
+            .ok()
+            .and_then(|tile| tile.with_red(true).ok())
+            .ok_or_else(unknown),
+        None => s.parse().map_err(|_| unknown()),
+    }
+}
+
+/// 一つの記録。
+struct Record<'a> {
+    name: &'a str,
+    data: &'a Value,
+}
+
+impl<'a> Record<'a> {
+    fn get(&self, key: &'static str) -> Result<&'a Value> {
+        (self.data.get(key)).ok_or_else(|| MajsoulError::FieldNotFound(self.name.to_string(), key))
+    }
+
+    fn number(&self, key: &'static str) -> Result<u32> {
+        (self.get(key)?.as_u64())
+            .map(|v| v as u32)
+            .ok_or_else(|| MajsoulError::FieldNotFound(self.name.to_string(), key))
+    }
+
+    /// 席の番号。 0 から 3 でなければエラーとなる。
+    fn seat(&self) -> Result<usize> {
+        match self.number("seat")? {
+            seat @ 0..=3 => Ok(seat as usize),
+            seat => Err(MajsoulError::InvalidSeat(self.name.to_string(), seat)),
+        }
+    }
+
+    fn flag(&self, key: &'static str) -> bool {
+        self.data.get(key).and_then(Value::as_bool).unwrap_or(false)
+    }
+
+    fn tile(&self, key: &'static str) -> Result<Tile> {
+        let value = self.get(key)?;
+        tile(value.as_str().unwrap_or_default())
+    }
+
+    /// 牌の配列。項目がなければ空になる。
+    fn tiles(&self, key: &'static str) -> Result<Vec<Tile>> {
+        match self.data.get(key) {
+            Some(Value::Array(tiles)) => (tiles.iter())
+                .map(|t| tile(t.as_str().unwrap_or_default()))
+                .collect(),
+            Some(Value::String(t)) => Ok(vec![tile(t)?]),
+            _ => Ok(Vec::new()),
+        }
+    }
+}
+
+/// `{"name": ..., "data": ...}` を記録にする。名前の `.lq.` は取り除く。
+fn record(value: &Value) -> Result<Record<'_>> {
+    let name = (value.get("name").and_then(Value::as_str))
+        .ok_or_else(|| MajsoulError::InvalidFormat(format!("記録の名前がありません: {}", value)))?;
+    let data =
+        (value.get("data")).ok_or_else(|| MajsoulError::FieldNotFound(name.to_string(), "data"))?;
+
+    Ok(Record {
+        name: name.trim_start_matches(".lq."),
+        data,
+    })
+}
+
+/// 読み込み中の局の状態。
+struct RoundState {
+    round: RoundRecord,
+
+    /// これまでにめくられたドラ表示牌の数。
+    num_doras: usize,
+
+    /// 最後に牌を捨てた (または加槓した) 人の席。ロンの放銃者になる。
+    last_seat: usize,
+}
+
+impl RoundState {
+    fn new(record: &Record) -> Result<RoundState> {
+        let dealer = record.number("ju")? as usize % 4;
+        let mut hands: [Vec<Tile>; 4] = Default::default();
+        for (hand, key) in hands
+            .iter_mut()
+            .zip(&["tiles0", "tiles1", "tiles2", "tiles3"])
+        {
+            *hand = record.tiles(key)?;
+        }
+
+        if hands[3].is_empty() {
+            return Err(MajsoulError::Sanma);
+        }
+
+        // 親の配牌は 14 枚で、最後の 1 枚を最初のツモとみなす。
+        let mut events = Vec::new();
+        if hands[dealer].len() == 14 {
+            let tile = hands[dealer].pop().expect("the hand must not be empty.");
+            events.push(Event::Mopai { seat: dealer, tile });
+        }
+
+        let mut dora_indicators = record.tiles("doras")?;
+        if dora_indicators.is_empty() {
+            dora_indicators = record.tiles("dora")?;
+        }
+
+        let place =
+            Direction::from_u32(record.number("chang")? % 4).expect("place must be less than 4.");
+
+        Ok(RoundState {
+            num_doras: dora_indicators.len(),
+            round: RoundRecord {
+                place,
+                dealer,
+                benchang: record.number("ben")?,
+                gongtuo: record.number("liqibang").unwrap_or(0),
+                hands,
+                dora_indicators,
+                events,
+            },
+            last_seat: dealer,
+        })
+    }
+
+    /// 記録に新しいドラ表示牌があれば出来事に加える。
+    fn reveal_doras(&mut self, record: &Record) -> Result<()> {
+        let doras = record.tiles("doras")?;
+        for &indicator in doras.iter().skip(self.num_doras) {
+            self.round.events.push(Event::Dora(indicator));
+        }
+        self.num_doras = self.num_doras.max(doras.len());
+
+        Ok(())
+    }
+
+    fn apply(&mut self, record: &Record) -> Result<()> {
+        match record.name {
+            "RecordDealTile" => {
+                self.round.events.push(Event::Mopai {
+                    seat: record.seat()?,
+                    tile: record.tile("tile")?,
+                });
+                self.reveal_doras(record)?;
+            }
+
+            "RecordDiscardTile" => {
+                let seat = record.seat()?;
+                self.round.events.push(Event::Dapai {
+                    seat,
+                    tile: record.tile("tile")?,
+                    is_lizhi: record.flag("is_liqi") || record.flag("is_wliqi"),
+                });
+                self.last_seat = seat;
+                self.reveal_doras(record)?;
+            }
+
+            "RecordChiPengGang" => {
+                let seat = record.seat()?;
+                let tiles = record.tiles("tiles")?;
+                let froms: Vec<usize> = (record.get("froms")?.as_array())
+                    .map(|froms| {
+                        (froms.iter())
+                            .map(|f| f.as_u64().unwrap_or(0) as usize)
+                            .collect()
+                    })
+                    .unwrap_or_default();
+
+                // 他の人から来た牌が鳴いた牌になる。
+                let pos = (froms.iter().position(|&from| from != seat))
+                    .filter(|&pos| pos < tiles.len())
+                    .ok_or_else(|| MajsoulError::FieldNotFound(record.name.to_string(), "froms"))?;
+                let called = tiles[pos];
+                let others = (tiles.iter().enumerate())
+                    .filter(|&(i, _)| i != pos)
+                    .map(|(_, &t)| t)
+                    .collect();
+
+                self.round.events.push(match record.number("type")? {
+                    0 => Event::Chi {
+                        seat,
+                        tiles: others,
+                        called,
+                    },
+                    1 => Event::Peng {
+                        seat,
+                        tiles: others,
+                        called,
+                    },
+                    _ => Event::Minggang {
+                        seat,
+                        tiles: others,
+                        called,
+                    },
+                });
+            }
+
+            "RecordAnGangAddGang" => {
+                let seat = record.seat()?;
+                let tile = record.tile("tiles")?;
+
+                // 暗槓では牌の種類だけが記録されているので、赤ドラは手牌から探してもらう。
+                self.round.events.push(match record.number("type")? {
+                    3 => Event::Angang {
+                        seat,
+                        tiles: vec![tile; 4],
+                    },
+                    _ => Event::Jiagang { seat, tile },
+                });
+                self.last_seat = seat;
+                self.reveal_doras(record)?;
+            }
+
+            "RecordHule" => {
+                let hules = (record.get("hules")?.as_array())
+                    .ok_or_else(|| MajsoulError::FieldNotFound(record.name.to_string(), "hules"))?;
+                for hule in hules {
+                    let hule = Record {
+                        name: record.name,
+                        data: hule,
+                    };
+                    let event = Event::Hule(hule_record(&hule, self.last_seat)?);
+                    self.round.events.push(event);
+                }
+            }
+
+            "RecordNoTile" | "RecordLiuJu" => self.round.events.push(Event::Liuju),
+
+            "RecordBaBei" => return Err(MajsoulError::Sanma),
+
+            _ => {}
+        }
+
+        Ok(())
+    }
+}
+
+/// `RecordHule` の中の一つのアガリを読む。 `last_seat` は最後に牌を捨てた人の席。
+fn hule_record(hule: &Record, last_seat: usize) -> Result<HuleRecord> {
+    let seat = hule.seat()?;
+    let is_zimo = hule.flag("zimo");
+    let is_yiman = hule.flag("yiman");
+    let count = hule.number("count")?;
+    let invalid_point = || MajsoulError::InvalidPoint(hule.name.to_string());
+
+    let value = if !is_zimo {
+        Some(hule.number("point_rong")?)
+    } else if hule
+        .number("qinjia")
+        .map_or(false, |dealer| dealer as usize == seat)
+    {
+        hule.number("point_zimo_xian")?.checked_mul(3)
+    } else {
+        let qin = hule.number("point_zimo_qin")?;
+        (hule.number("point_zimo_xian")?.checked_mul(2)).and_then(|xian| xian.checked_add(qin))
+    };
+    let value = value.ok_or_else(invalid_point)?;
+
+    let yakus = match hule.data.get("fans").and_then(Value::as_array) {
+        Some(fans) => (fans.iter())
+            .map(|fan| {
+                let val = fan.get("val").and_then(Value::as_u64).unwrap_or(0) as u32;
+                let name = match (fan.get("name").and_then(Value::as_str), fan.get("id")) {
+                    (Some(name), _) => name.to_string(),
+                    (None, Some(id)) => format!("役 {}", id),
+                    (None, None) => String::new(),
+                };
+                let val = if is_yiman {
+                    val.checked_mul(13)
+                } else {
+                    Some(val)
+                };
+                Ok((name, val.ok_or_else(invalid_point)?))
+            })
+            .collect::<Result<_>>()?,
+        None => Vec::new(),
+    };
+
+    Ok(HuleRecord {
+        seat,
+        from: if is_zimo { None } else { Some(last_seat) },
+        tile: hule.tile("hu_tile")?,
+        uradora_indicators: hule.tiles("li_doras")?,
+        yakus,
+        fan: if is_yiman { 0 } else { count },
+        fu: if is_yiman {
+            None
+        } else {
+            Some(hule.number("fu")?)
+        },
+        yiman: if is_yiman { count } else { 0 },
+        value: Some(value),
+    })
+}
+
+/// 対局の情報からプレイヤーの名前を席順に並べる。
+fn names(head: &Value) -> Vec<String> {
+    let mut names = vec![String::new(); 4];
+    for account in (head.get("accounts").and_then(Value::as_array))
+        .into_iter()
+        .flatten()
+    {
+        let seat = account.get("seat").and_then(Value::as_u64).unwrap_or(0) as usize;
+        if let (Some(name), Some(nickname)) = (
+            names.get_mut(seat),
+            account.get("nickname").and_then(Value::as_str),
+        ) {
+            *name = nickname.to_string();
+        }
+    }
+
+    names
+}
+
+/// 雀魂の牌譜を復号した JSON を読む。
+pub fn parse_json(json: &str) -> Result<Paipu> {
+    let value: Value = serde_json::from_str(json)?;
+
+    let (head, records) = match &value {
+        Value::Array(records) => (None, records),
+        Value::Object(_) => {
+            let records = (["records", "actions", "data"].iter())
+                .find_map(|key| value.get(key).and_then(Value::as_array))
+                .ok_or_else(|| MajsoulError::InvalidFormat("記録の列がありません。".to_string()))?;
+            (value.get("head"), records)
+        }
+        _ => {
+            return Err(MajsoulError::InvalidFormat(
+                "配列かオブジェクトではありません。".to_string(),
+            ))
+        }
+    };
+
+    let mut paipu = Paipu {
+        names: head.map(names).unwrap_or_default(),
+        ruleset: ruleset(),
+        rounds: Vec::new(),
+    };
+
+    let mut state: Option<RoundState> = None;
+    for value in records {
+        let record = record(value)?;
+
+        if record.name == "RecordNewRound" {
+            paipu.rounds.extend(state.take().map(|s| s.round));
+            state = Some(RoundState::new(&record)?);
+        } else if let Some(state) = state.as_mut() {
+            state.apply(&record)?;
+        }
+    }
+
+    paipu.rounds.extend(state.map(|s| s.round));
+
+    Ok(paipu)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paipu::{discrepancies, rescore};
+
+    /// 東 1 局で親が 5p をツモ切りし、南家が単騎待ちでロンする。
+    const JSON: &str = r#"{
+        "head": {"accounts": [{"seat": 0, "nickname": "A"}, {"seat": 1, "nickname": "B"},
+                              {"seat": 2, "nickname": "C"}, {"seat": 3, "nickname": "D"}]},
+        "records": [
+            {"name": ".lq.RecordNewRound", "data": {
+                "chang": 0, "ju": 0, "ben": 0, "liqibang": 0, "doras": ["4z"],
+                "tiles0": ["1m","1m","9m","9p","9s","1z","2z","3z","4z","5z","6z","7z","7z","5p"],
+                "tiles1": ["2m","3m","4m","6m","7m","8m","2s","3s","4s","4s","5s","6s","5p"],
+                "tiles2": ["0p","5p","7m","8m","9m","1p","2p","3p","7s","8s","9s","5z","6z"],
+                "tiles3": ["1p","3p","7p","9p","1s","7s","9s","1z","2z","3z","4z","5z","6z"]}},
+            {"name": ".lq.RecordDiscardTile", "data": {"seat": 0, "tile": "5p", "moqie": true}},
+            {"name": ".lq.RecordHule", "data": {"hules": [{
+                "seat": 1, "zimo": false, "hu_tile": "5p", "qinjia": 0, "yiman": false,
+                "count": 1, "fu": 40, "point_rong": 1300, "point_zimo_qin": 0,
+                "point_zimo_xian": 0, "fans": [{"name": "断幺九", "val": 1, "id": 14}],
+                "li_doras": []}]}}
+        ]
+    }"#;
+
+    #[test]
+    fn parse() {
+        let paipu = parse_json(JSON).unwrap();
+        assert_eq!(paipu.names, vec!["A", "B", "C", "D"]);
+        assert_eq!(paipu.rounds.len(), 1);
+
+        let round = &paipu.rounds[0];
+        assert_eq!(round.hands[0].len(), 13);
+        assert_eq!(round.hands[2][0].to_string(), "5P");
+        assert_eq!(round.dora_indicators[0].to_string(), "北");
+
+        let res = rescore(&paipu);
+        assert_eq!(res.len(), 1);
+        assert!(res[0].is_consistent(), "{}", res[0]);
+        assert_eq!(res[0].record.from, Some(0));
+    }
+
+    #[test]
+    fn discrepancy() {
+        let paipu = parse_json(&JSON.replace(r#""fu": 40"#, r#""fu": 30"#)).unwrap();
+        let res = discrepancies(&paipu);
+        assert_eq!(res.len(), 1);
+        assert_eq!(
+            res[0].to_string(),
+            "東1局0本場 南家 ロン: 記録 1翻30符 1300点 / 判定 1翻40符 1300点"
+        );
+    }
+
+    #[test]
+    fn invalid_seat() {
+        let json = JSON.replace(
+            r#"{"seat": 0, "tile": "5p", "moqie": true}"#,
+            r#"{"seat": 6, "tile": "5p", "moqie": true}"#,
+        );
+        assert_ne!(json, JSON);
+        assert!(matches!(
+            parse_json(&json),
+            Err(MajsoulError::InvalidSeat(_, 6))
+        ));
+    }
+
+    #[test]
+    fn invalid_point() {
+        let json = JSON.replace(r#""zimo": false"#, r#""zimo": true"#).replace(
+            r#""point_zimo_xian": 0"#,
+            r#""point_zimo_xian": 4294967295"#,
+        );
+        assert!(matches!(
+            parse_json(&json),
+            Err(MajsoulError::InvalidPoint(_))
+        ));
+
+        let json = JSON
+            .replace(r#""yiman": false"#, r#""yiman": true"#)
+            .replace(r#""val": 1"#, r#""val": 4294967295"#);
+        assert!(matches!(
+            parse_json(&json),
+            Err(MajsoulError::InvalidPoint(_))
+        ));
+    }
+
+    #[test]
+    fn tile_notation() {
+        assert_eq!(tile("0m").unwrap().to_string(), "5M");
+        assert_eq!(tile("7z").unwrap().to_string(), "中");
+        assert!(tile("0z").is_err());
+        assert!(tile("x").is_err());
+    }
+
+    #[test]
+    fn sanma() {
+        let json = JSON.replace(
+            r#""tiles3": ["1p","3p","7p","9p","1s","7s","9s","1z","2z","3z","4z","5z","6z"]"#,
+            r#""tiles3": []"#,
+        );
+        assert!(matches!(parse_json(&json), Err(MajsoulError::Sanma)));
+    }
+}
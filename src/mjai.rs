@@ -0,0 +1,1073 @@
+//! MJAI プロトコルに対応する。
+//!
+//! MJAI は麻雀 AI (bot) が使う JSON Lines のプロトコルで、局の出来事を一行に一つの JSON で表す。こ
+//! こでは MJAI の牌の表記と牌の相互変換、出来事の読み書き、出来事の列からのプレイヤーの視点の再現、
+//! アガリの検証を行う。 bot を実際に対局させる仕組みは `harness` にある。
+
+pub mod harness;
+
+use crate::context::{Context, Direction, HuleProgress, Lizhi, Situation};
+use crate::judge::{judge, Judge};
+use crate::paipu::{Event, HuleRecord, Paipu, RoundRecord};
+use crate::round::RoundError;
+use crate::ruleset::Ruleset;
+use crate::tile::Tile;
+use crate::tiles::{remove_tile, Tiles};
+use crate::tilesets::{TilesetsError, TingpaiTilesets};
+use num_traits::FromPrimitive;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, MjaiError>;
+
+#[derive(Debug, Error)]
+pub enum MjaiError {
+    /// 牌の表記が読めない。
+    #[error("不明な牌です: {0}")]
+    UnknownTile(String),
+
+    /// 伏せられた牌 ("?") の中身が必要になった。
+    #[error("伏せられた牌があります。")]
+    HiddenTile,
+
+    /// 手牌にない牌を使おうとした。
+    #[error("{0} 番目の席の手牌に {1} がありません。")]
+    TileNotFound(usize, Tile),
+
+    /// 加槓しようとしたポンがない。
+    #[error("{0} 番目の席に {1} のポンがありません。")]
+    PengNotFound(usize, Tile),
+
+    /// その出来事はここでは使えない。
+    #[error("ここでは使えない出来事です: {0}")]
+    UnexpectedEvent(String),
+
+    /// 牌集合の集合を作れなかった。
+    #[error("{0}")]
+    Tilesets(#[from] TilesetsError),
+
+    /// 役がないか、アガリの形になっていない。
+    #[error("アガれません。")]
+    NoYaku,
+
+    /// JSON として読めない。
+    #[error("JSON を読めません: {0}")]
+    Json(#[from] serde_json::Error),
+
+    /// bot との入出力に失敗した。
+    #[error("bot との入出力に失敗しました: {0}")]
+    Io(#[from] std::io::Error),
+
+    /// bot が応答せずに終了した。
+    #[error("bot が応答しませんでした。")]
+    NoResponse,
+
+    /// bot が不正な行動をとった。
+    #[error("{0} 番目の席の bot の行動は不正です: {1}")]
+    IllegalResponse(usize, String),
+
+    /// 席の番号が 0 から 3 でない。
+    #[error("{0} 番目の席はありません。")]
+    InvalidSeat(usize),
+
+    /// 局の進行に失敗した。
+    #[error("{0}")]
+    Round(#[from] RoundError),
+}
+
+/// 字牌の MJAI での表記。東南西北白發中の順。
+const ZIPAI_NAMES: [&str; 7] = ["E", "S", "W", "N", "P", "F", "C"];
+
+/// MJAI の牌の表記 (例: "1m", "5mr", "E", "P") を牌にする。 "r" は赤ドラを表す。
+pub fn parse_tile(s: &str) -> Result<Tile> {
+    let unknown = || MjaiError::UnknownTile(s.to_string());
+
+    if let Some(pos) = ZIPAI_NAMES.iter().position(|&name| name == s) {
+        return Ok(Tile::from_index(27 + pos));
+    }
+
+    let (body, is_red) = match s.strip_suffix('r') {
+        Some(body) => (body, true),
+        None => (s, false),
+    };
+
+    let mut chars = body.chars();
+    let (number, suit) = match (chars.next(), chars.next(), chars.next()) {
+        (Some(number), Some(suit), None) => (number, suit),
+        _ => return Err(unknown()),
+    };
+
+    let number = (number.to_digit(10))
+        .filter(|n| (1..=9).contains(n))
+        .ok_or_else(unknown)? as usize;
+    let base = match suit {
+        's' => 0,
+        'm' => 9,
+        'p' => 18,
+        _ => return Err(unknown()),
+    };
+
+    let tile = Tile::from_index(base + number - 1);
+    if is_red {
+        (tile.order())
+            .filter(|o| o.number() == 5)
+            .and_then(|_| tile.with_red(true).ok())
+            .ok_or_else(unknown)
+    } else {
+        Ok(tile)
+    }
+}
+
+/// 牌を MJAI の表記にする。
+pub fn tile_name(tile: Tile) -> String {
+    let index = tile.index();
+    if index >= 27 {
+        return ZIPAI_NAMES[index - 27].to_string();
+    }
+
+    let suit = ['s', 'm', 'p'][index / 9];
+    let red = if tile.is_red() { "r" } else { "" };
+    format!("{}{}{}", index % 9 + 1, suit, red)
+}
+
+/// `serde` で牌を MJAI の表記として読み書きする。
+mod pai {
+    use crate::tile::Tile;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(tile: &Tile, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&super::tile_name(*tile))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Tile, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        super::parse_tile(&s).map_err(de::Error::custom)
+    }
+}
+
+/// `serde` で伏せられているかもしれない牌を読み書きする。伏せられた牌 "?" は None となる。
+mod maybe_pai {
+    use crate::tile::Tile;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(tile: &Option<Tile>, serializer: S) -> Result<S::Ok, S::Error> {
+        match tile {
+            Some(tile) => super::pai::serialize(tile, serializer),
+            None => serializer.serialize_str("?"),
+        }
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Option<Tile>, D::Error> {
+        match String::deserialize(deserializer)?.as_str() {
+            "?" => Ok(None),
+            s => super::parse_tile(s).map(Some).map_err(de::Error::custom),
+        }
+    }
+}
+
+/// `serde` で牌の並びを読み書きする。
+mod pais {
+    use crate::tile::Tile;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(tiles: &[Tile], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(tiles.iter().map(|&tile| super::tile_name(tile)))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Vec<Tile>, D::Error> {
+        (Vec::<String>::deserialize(deserializer)?.iter())
+            .map(|s| super::parse_tile(s).map_err(de::Error::custom))
+            .collect()
+    }
+}
+
+/// `serde` で配牌を読み書きする。他家の配牌は "?" で伏せられている。
+mod tehais {
+    use crate::tile::Tile;
+    use serde::ser::SerializeSeq;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(
+        tehais: &[Vec<Option<Tile>>],
+        serializer: S,
+    ) -> Result<S::Ok, S::Error> {
+        let mut seq = serializer.serialize_seq(Some(tehais.len()))?;
+        for tehai in tehais {
+            let names: Vec<String> = (tehai.iter())
+                .map(|tile| tile.map_or_else(|| "?".to_string(), super::tile_name))
+                .collect();
+            seq.serialize_element(&names)?;
+        }
+        seq.end()
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(
+        deserializer: D,
+    ) -> Result<Vec<Vec<Option<Tile>>>, D::Error> {
+        (Vec::<Vec<String>>::deserialize(deserializer)?.iter())
+            .map(|tehai| {
+                (tehai.iter())
+                    .map(|s| match s.as_str() {
+                        "?" => Ok(None),
+                        s => super::parse_tile(s).map(Some).map_err(de::Error::custom),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// `serde` で場風を "E", "S", "W", "N" として読み書きする。
+mod bakaze {
+    use crate::context::Direction;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    const NAMES: [&str; 4] = ["E", "S", "W", "N"];
+    const DIRECTIONS: [Direction; 4] = [
+        Direction::East,
+        Direction::South,
+        Direction::West,
+        Direction::North,
+    ];
+
+    pub fn serialize<S: Serializer>(dir: &Direction, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(NAMES[*dir as usize])
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Direction, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        (NAMES.iter().position(|&name| name == s))
+            .map(|pos| DIRECTIONS[pos])
+            .ok_or_else(|| de::Error::custom(format!("不明な方角です: {}", s)))
+    }
+}
+
+/// MJAI の出来事。 bot の行動も同じ形で表す。
+///
+/// プレイヤーは起家を 0 とした席の番号で表す。
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum MjaiEvent {
+    /// 対局の開始。 `id` は受け取る bot の席。
+    StartGame {
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        id: Option<usize>,
+
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        names: Vec<String>,
+    },
+
+    /// 局の開始。親の配牌も 13 枚で、最初のツモは `Tsumo` で伝えられる。
+    StartKyoku {
+        #[serde(with = "bakaze")]
+        bakaze: Direction,
+
+        /// 何局目か (1 から 4) 。
+        kyoku: u32,
+        honba: u32,
+        kyotaku: u32,
+        oya: usize,
+
+        #[serde(with = "pai")]
+        dora_marker: Tile,
+
+        #[serde(with = "tehais")]
+        tehais: Vec<Vec<Option<Tile>>>,
+
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        scores: Vec<i32>,
+    },
+
+    /// ツモ。他家のツモは伏せられる。
+    Tsumo {
+        actor: usize,
+
+        #[serde(with = "maybe_pai")]
+        pai: Option<Tile>,
+    },
+
+    /// 打牌。
+    Dahai {
+        actor: usize,
+
+        #[serde(with = "pai")]
+        pai: Tile,
+
+        #[serde(default)]
+        tsumogiri: bool,
+    },
+
+    /// チー。 `target` は鳴かれた人、 `consumed` は手牌から出した牌。
+    Chi {
+        actor: usize,
+        target: usize,
+
+        #[serde(with = "pai")]
+        pai: Tile,
+
+        #[serde(with = "pais")]
+        consumed: Vec<Tile>,
+    },
+
+    /// ポン。
+    Pon {
+        actor: usize,
+        target: usize,
+
+        #[serde(with = "pai")]
+        pai: Tile,
+
+        #[serde(with = "pais")]
+        consumed: Vec<Tile>,
+    },
+
+    /// 大明槓。
+    Daiminkan {
+        actor: usize,
+        target: usize,
+
+        #[serde(with = "pai")]
+        pai: Tile,
+
+        #[serde(with = "pais")]
+        consumed: Vec<Tile>,
+    },
+
+    /// 暗槓。
+    Ankan {
+        actor: usize,
+
+        #[serde(with = "pais")]
+        consumed: Vec<Tile>,
+    },
+
+    /// 加槓。 `pai` は加えた牌、 `consumed` はポンしていた牌。
+    Kakan {
+        actor: usize,
+
+        #[serde(with = "pai")]
+        pai: Tile,
+
+        #[serde(with = "pais")]
+        consumed: Vec<Tile>,
+    },
+
+    /// 新しいドラ表示牌。
+    Dora {
+        #[serde(with = "pai")]
+        dora_marker: Tile,
+    },
+
+    /// 立直の宣言。続く打牌が立直宣言牌となる。
+    Reach { actor: usize },
+
+    /// 立直の成立。
+    ReachAccepted { actor: usize },
+
+    /// アガリ。ツモなら `target` は `actor` と同じになる。
+    Hora {
+        actor: usize,
+        target: usize,
+
+        #[serde(with = "pai")]
+        pai: Tile,
+
+        #[serde(default, with = "pais", skip_serializing_if = "Vec::is_empty")]
+        uradora_markers: Vec<Tile>,
+
+        /// 役の名前と翻数。
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        yakus: Vec<(String, u32)>,
+
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        fu: Option<u32>,
+
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        fan: Option<u32>,
+
+        /// 点数。本場と供託の分は含まない。ツモなら支払いの合計とする。
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        hora_points: Option<u32>,
+
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        deltas: Vec<i32>,
+
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        scores: Vec<i32>,
+    },
+
+    /// 流局。
+    Ryukyoku {
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        deltas: Vec<i32>,
+
+        #[serde(default, skip_serializing_if = "Vec::is_empty")]
+        scores: Vec<i32>,
+    },
+
+    /// 局の終了。
+    EndKyoku {},
+
+    /// 対局の終了。
+    EndGame {},
+
+    /// 何もしない (bot の応答) 。
+    #[serde(rename = "none")]
+    Pass {},
+}
+
+impl FromStr for MjaiEvent {
+    type Err = MjaiError;
+
+    fn from_str(s: &str) -> Result<MjaiEvent> {
+        Ok(serde_json::from_str(s)?)
+    }
+}
+
+impl fmt::Display for MjaiEvent {
+    fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
+        let json = serde_json::to_string(self).map_err(|_| fmt::Error)?;
+        write!(b, "{}", json)
+    }
+}
+
+/// 席の番号が 0 から 3 であることを確かめる。
+fn check_seat(seat: usize) -> Result<()> {
+    if seat < 4 {
+        Ok(())
+    } else {
+        Err(MjaiError::InvalidSeat(seat))
+    }
+}
+
+/// 出来事に含まれる席の番号。
+fn event_seats(event: &MjaiEvent) -> Vec<usize> {
+    match event {
+        MjaiEvent::Tsumo { actor, .. }
+        | MjaiEvent::Dahai { actor, .. }
+        | MjaiEvent::Ankan { actor, .. }
+        | MjaiEvent::Kakan { actor, .. }
+        | MjaiEvent::Reach { actor }
+        | MjaiEvent::ReachAccepted { actor } => vec![*actor],
+        MjaiEvent::Chi { actor, target, .. }
+        | MjaiEvent::Pon { actor, target, .. }
+        | MjaiEvent::Daiminkan { actor, target, .. }
+        | MjaiEvent::Hora { actor, target, .. } => vec![*actor, *target],
+        _ => Vec::new(),
+    }
+}
+
+/// アガリの役満の数。役の一覧があれば 13 翻ごとに一つと数え、なければ翻数から求める。
+///
+/// 役の一覧がなければ、 13 翻以上の数え役満も役満として数えることになる。
+fn hora_yiman(yakus: &[(String, u32)], fan: Option<u32>) -> u32 {
+    if yakus.is_empty() {
+        fan.map_or(0, |fan| fan / 13)
+    } else {
+        yakus.iter().map(|&(_, fan)| fan / 13).sum()
+    }
+}
+
+/// 全ての牌が見えている MJAI の出来事の列 (サーバーのログなど) を牌譜にする。
+///
+/// `paipu::rescore` で判定し直すと、アガリが正しいかどうかを検証できる。アガリに点数が含まれていな
+/// ければ点数は比べない。翻数が含まれていない場合は、判定結果の `result` だけが意味を持つ。
+pub fn to_paipu(events: &[MjaiEvent], ruleset: &Ruleset) -> Result<Paipu> {
+    let mut paipu = Paipu {
+        ruleset: *ruleset,
+        ..Paipu::default()
+    };
+    let mut round: Option<RoundRecord> = None;
+    let mut reach = [false; 4];
+
+    for event in events {
+        if let MjaiEvent::StartGame { names, .. } = event {
+            paipu.names = names.clone();
+            continue;
+        }
+
+        if let MjaiEvent::StartKyoku {
+            bakaze,
+            honba,
+            kyotaku,
+            oya,
+            dora_marker,
+            tehais,
+            ..
+        } = event
+        {
+            paipu.rounds.extend(round.take());
+            reach = [false; 4];
+            check_seat(*oya)?;
+
+            let mut hands: [Vec<Tile>; 4] = Default::default();
+            for (hand, tehai) in hands.iter_mut().zip(tehais) {
+                *hand = (tehai.iter())
+                    .map(|tile| tile.ok_or(MjaiError::HiddenTile))
+                    .collect::<Result<_>>()?;
+            }
+
+            round = Some(RoundRecord {
+                place: *bakaze,
+                dealer: *oya,
+                benchang: *honba,
+                gongtuo: *kyotaku,
+                hands,
+                dora_indicators: vec![*dora_marker],
+                events: Vec::new(),
+            });
+            continue;
+        }
+
+        let round = match round.as_mut() {
+            Some(round) => round,
+            None => continue,
+        };
+
+        for seat in event_seats(event) {
+            check_seat(seat)?;
+        }
+
+        let converted = match event {
+            MjaiEvent::Tsumo { actor, pai } => Event::Mopai {
+                seat: *actor,
+                tile: pai.ok_or(MjaiError::HiddenTile)?,
+            },
+            MjaiEvent::Dahai { actor, pai, .. } => Event::Dapai {
+                seat: *actor,
+                tile: *pai,
+                is_lizhi: std::mem::take(&mut reach[*actor]),
+            },
+            MjaiEvent::Chi {
+                actor,
+                pai,
+                consumed,
+                ..
+            } => Event::Chi {
+                seat: *actor,
+                tiles: consumed.clone(),
+                called: *pai,
+            },
+            MjaiEvent::Pon {
+                actor,
+                pai,
+                consumed,
+                ..
+            } => Event::Peng {
+                seat: *actor,
+                tiles: consumed.clone(),
+                called: *pai,
+            },
+            MjaiEvent::Daiminkan {
+                actor,
+                pai,
+                consumed,
+                ..
+            } => Event::Minggang {
+                seat: *actor,
+                tiles: consumed.clone(),
+                called: *pai,
+            },
+            MjaiEvent::Ankan { actor, consumed } => Event::Angang {
+                seat: *actor,
+                tiles: consumed.clone(),
+            },
+            MjaiEvent::Kakan { actor, pai, .. } => Event::Jiagang {
+                seat: *actor,
+                tile: *pai,
+            },
+            MjaiEvent::Dora { dora_marker } => Event::Dora(*dora_marker),
+            MjaiEvent::Reach { actor } => {
+                reach[*actor] = true;
+                continue;
+            }
+            MjaiEvent::Hora {
+                actor,
+                target,
+                pai,
+                uradora_markers,
+                yakus,
+                fu,
+                fan,
+                hora_points,
+                ..
+            } => {
+                let yiman = hora_yiman(yakus, *fan);
+                Event::Hule(HuleRecord {
+                    seat: *actor,
+                    from: if actor == target { None } else { Some(*target) },
+                    tile: *pai,
+                    uradora_indicators: uradora_markers.clone(),
+                    yakus: yakus.clone(),
+                    fan: if yiman > 0 { 0 } else { fan.unwrap_or(0) },
+                    fu: if yiman > 0 { None } else { *fu },
+                    yiman,
+                    value: *hora_points,
+                })
+            }
+            MjaiEvent::Ryukyoku { .. } => Event::Liuju,
+            _ => continue,
+        };
+
+        round.events.push(converted);
+    }
+
+    paipu.rounds.extend(round);
+
+    Ok(paipu)
+}
+
+/// 出来事の列から再現した、一人のプレイヤーから見た局の状態。
+#[derive(Debug, Clone, Default)]
+pub struct View {
+    /// 自分の席。
+    pub id: usize,
+
+    pub bakaze: Direction,
+    pub kyoku: u32,
+    pub honba: u32,
+    pub kyotaku: u32,
+    pub oya: usize,
+    pub dora_markers: Vec<Tile>,
+
+    /// 自分の手牌。ツモった牌も含む。
+    pub hand: Vec<Tile>,
+
+    /// 自分のポン。
+    pub pengs: Vec<Tiles>,
+
+    /// 自分のチー。
+    pub chis: Vec<Tiles>,
+
+    /// 自分の明槓。加槓も含む。
+    pub minggangs: Vec<Tiles>,
+
+    /// 自分の暗槓。
+    pub angangs: Vec<Tiles>,
+
+    /// 席ごとの捨て牌。鳴かれた牌も含む。
+    pub discards: [Vec<Tile>; 4],
+
+    /// 席ごとに立直が成立しているかどうか。
+    pub reached: [bool; 4],
+
+    /// 自分の立直の状態。
+    lizhi: Lizhi,
+
+    /// 自分の一発が有効かどうか。
+    ippatsu: bool,
+
+    /// 立直を宣言して打牌を待っている席。
+    declared: [bool; 4],
+
+    /// 誰かがツモった回数。
+    num_tsumo: usize,
+
+    is_lingshang: bool,
+    next_is_lingshang: bool,
+    is_first_go_around: bool,
+
+    /// 直前の加槓。槍槓の判定に使う。
+    last_kakan: Option<Tile>,
+}
+
+impl View {
+    pub fn new(id: usize) -> View {
+        View {
+            id,
+            ..View::default()
+        }
+    }
+
+    /// 出来事を一つ反映する。
+    pub fn update(&mut self, event: &MjaiEvent) -> Result<()> {
+        let id = self.id;
+
+        match event {
+            MjaiEvent::StartGame {
+                id: Some(new_id), ..
+            } => self.id = *new_id,
+
+            MjaiEvent::StartKyoku {
+                bakaze,
+                kyoku,
+                honba,
+                kyotaku,
+                oya,
+                dora_marker,
+                tehais,
+                ..
+            } => {
+                let hand = (tehais.get(id).into_iter().flatten())
+                    .map(|tile| tile.ok_or(MjaiError::HiddenTile))
+                    .collect::<Result<_>>()?;
+
+                *self = View {
+                    id,
+                    bakaze: *bakaze,
+                    kyoku: *kyoku,
+                    honba: *honba,
+                    kyotaku: *kyotaku,
+                    oya: *oya,
+                    dora_markers: vec![*dora_marker],
+                    hand,
+                    is_first_go_around: true,
+                    ..View::default()
+                };
+            }
+
+            MjaiEvent::Tsumo { actor, pai } => {
+                if *actor == id {
+                    self.hand.push(pai.ok_or(MjaiError::HiddenTile)?);
+                }
+                self.num_tsumo += 1;
+                self.is_lingshang = self.next_is_lingshang;
+                self.next_is_lingshang = false;
+                self.last_kakan = None;
+            }
+
+            MjaiEvent::Dahai { actor, pai, .. } => {
+                let is_lizhi = std::mem::take(&mut self.declared[*actor % 4]);
+                if *actor == id {
+                    self.remove(*pai)?;
+                    self.ippatsu = is_lizhi;
+                    if is_lizhi {
+                        self.lizhi = if self.is_first_go_around && self.discards[id].is_empty() {
+                            Lizhi::DoubleLizhi
+                        } else {
+                            Lizhi::Lizhi
+                        };
+                    }
+                }
+
+                self.discards[*actor % 4].push(*pai);
+                self.is_lingshang = false;
+                if self.discards.iter().all(|d| !d.is_empty()) {
+                    self.is_first_go_around = false;
+                }
+            }
+
+            MjaiEvent::Chi {
+                actor,
+                pai,
+                consumed,
+                ..
+            }
+            | MjaiEvent::Pon {
+                actor,
+                pai,
+                consumed,
+                ..
+            }
+            | MjaiEvent::Daiminkan {
+                actor,
+                pai,
+                consumed,
+                ..
+            } => {
+                if *actor == id {
+                    let mut set = self.take(consumed)?;
+                    set.push(*pai);
+                    let set = Tiles::new(set);
+
+                    match event {
+                        MjaiEvent::Chi { .. } => self.chis.push(set),
+                        MjaiEvent::Pon { .. } => self.pengs.push(set),
+                        _ => self.minggangs.push(set),
+                    }
+                }
+
+                self.interrupt();
+                self.next_is_lingshang = matches!(event, MjaiEvent::Daiminkan { .. });
+            }
+
+            MjaiEvent::Ankan { actor, consumed } => {
+                if *actor == id {
+                    let set = self.take(consumed)?;
+                    self.angangs.push(Tiles::new(set));
+                }
+
+                self.interrupt();
+                self.next_is_lingshang = true;
+            }
+
+            MjaiEvent::Kakan { actor, pai, .. } => {
+                if *actor == id {
+                    self.remove(*pai)?;
+                    let pos = (self.pengs.iter())
+                        .position(|peng| peng.first() == *pai)
+                        .ok_or(MjaiError::PengNotFound(id, *pai))?;
+                    let mut gang = self.pengs.remove(pos).into_inner();
+                    gang.push(*pai);
+                    self.minggangs.push(Tiles::new(gang));
+                }
+
+                self.interrupt();
+                self.next_is_lingshang = true;
+                self.last_kakan = Some(*pai);
+            }
+
+            MjaiEvent::Dora { dora_marker } => self.dora_markers.push(*dora_marker),
+
+            MjaiEvent::Reach { actor } => self.declared[*actor % 4] = true,
+
+            MjaiEvent::ReachAccepted { actor } => {
+                self.reached[*actor % 4] = true;
+                self.kyotaku += 1;
+            }
+
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// 自分の手牌から牌を一枚取り除く。赤ドラかどうかまで一致する牌があればそちらを優先する。
+    fn remove(&mut self, tile: Tile) -> Result<Tile> {
+        remove_tile(&mut self.hand, tile).ok_or(MjaiError::TileNotFound(self.id, tile))
+    }
+
+    fn take(&mut self, tiles: &[Tile]) -> Result<Vec<Tile>> {
+        tiles.iter().map(|&tile| self.remove(tile)).collect()
+    }
+
+    /// 鳴きや槓によって一発と最初の一巡が消える。
+    fn interrupt(&mut self) {
+        self.ippatsu = false;
+        self.is_first_go_around = false;
+    }
+
+    /// 自分の手牌から `last` を除いた牌集合の集合を作る。偶然役は含まない。
+    pub fn tingpai_tilesets(&self, last: Option<Tile>) -> TingpaiTilesets {
+        let mut hand = self.hand.clone();
+        if let Some(last) = last {
+            remove_tile(&mut hand, last);
+        }
+
+        let lizhi = self.lizhi.with_ippatsu(self.ippatsu);
+
+        let player = Direction::from_usize((self.id + 4 - self.oya) % 4).unwrap_or_default();
+
+        TingpaiTilesets {
+            context: Context {
+                lizhi,
+                situation: Situation::default(),
+                place: self.bakaze,
+                player,
+                player_name: String::new(),
+                benchang: self.honba,
+                gongtuo: self.kyotaku,
+                discards: self.discards[self.id % 4].clone(),
+                passed: Vec::new(),
+                passed_after_lizhi: Vec::new(),
+            },
+            hand: Tiles::new(hand),
+            pengs: self.pengs.clone(),
+            chis: self.chis.clone(),
+            minggangs: self.minggangs.clone(),
+            angangs: self.angangs.clone(),
+            doras: (self.dora_markers.iter())
+                .map(|t| t.wrapping_next())
+                .collect(),
+            uradoras: Tiles::new(Vec::new()),
+        }
+    }
+
+    /// 自分のアガリを判定する。 `uradora_markers` は立直していなければ無視される。
+    pub fn judge_hora(
+        &self,
+        pai: Tile,
+        is_zimo: bool,
+        uradora_markers: &[Tile],
+        ruleset: &Ruleset,
+    ) -> Result<Judge> {
+        let mut tingpai = self.tingpai_tilesets(if is_zimo { Some(pai) } else { None });
+
+        tingpai.context.situation = HuleProgress {
+            is_zimo,
+            is_parent: tingpai.context.is_parent(),
+            is_lingshang: self.is_lingshang,
+            is_haidi: self.num_tsumo >= 70,
+            is_chenggang: !is_zimo && self.last_kakan == Some(pai),
+            is_first: self.is_first_go_around && self.discards[self.id % 4].is_empty(),
+        }
+        .situation();
+
+        if self.lizhi != Lizhi::None {
+            tingpai.uradoras = (uradora_markers.iter())
+                .map(|t| t.wrapping_next())
+                .collect();
+        }
+
+        let tilesets = tingpai.with_last(pai, is_zimo)?;
+        judge(&tilesets, ruleset).ok_or(MjaiError::NoYaku)
+    }
+
+    /// 自分のアガリの出来事を検証する。
+    pub fn validate_hora(&self, event: &MjaiEvent, ruleset: &Ruleset) -> Result<Judge> {
+        match event {
+            MjaiEvent::Hora {
+                actor,
+                target,
+                pai,
+                uradora_markers,
+                ..
+            } if *actor == self.id => {
+                self.judge_hora(*pai, actor == target, uradora_markers, ruleset)
+            }
+            _ => Err(MjaiError::UnexpectedEvent(event.to_string())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paipu::rescore;
+
+    fn tile(tile: &str) -> Tile {
+        tile.parse().unwrap()
+    }
+
+    fn events(lines: &str) -> Vec<MjaiEvent> {
+        lines.lines().map(|line| line.parse().unwrap()).collect()
+    }
+
+    /// 東 1 局で親が 5p をツモ切りし、南家が単騎待ちでロンする。
+    const LOG: &str = r#"{"type":"start_game","names":["A","B","C","D"]}
+{"type":"start_kyoku","bakaze":"E","kyoku":1,"honba":0,"kyotaku":0,"oya":0,"dora_marker":"N","tehais":[["1m","1m","9m","9p","9s","E","S","W","N","P","F","C","C"],["2m","3m","4m","6m","7m","8m","2s","3s","4s","4s","5s","6s","5p"],["5pr","5p","7m","8m","9m","1p","2p","3p","7s","8s","9s","P","F"],["1p","3p","7p","9p","1s","7s","9s","E","S","W","N","P","F"]]}
+{"type":"tsumo","actor":0,"pai":"5p"}
+{"type":"dahai","actor":0,"pai":"5p","tsumogiri":true}
+{"type":"hora","actor":1,"target":0,"pai":"5p","fu":40,"fan":1,"hora_points":1300}"#;
+
+    #[test]
+    fn tile_names() {
+        for name in &["1m", "9s", "5pr", "E", "P", "C"] {
+            assert_eq!(tile_name(parse_tile(name).unwrap()), *name);
+        }
+
+        assert_eq!(parse_tile("5mr").unwrap().to_string(), "5M");
+        assert_eq!(parse_tile("F").unwrap(), tile("發"));
+        assert!(parse_tile("4mr").is_err());
+        assert!(parse_tile("0m").is_err());
+        assert!(parse_tile("?").is_err());
+    }
+
+    #[test]
+    fn event_json() {
+        let event: MjaiEvent = r#"{"type":"dahai","actor":2,"pai":"5mr","tsumogiri":false}"#
+            .parse()
+            .unwrap();
+        assert_eq!(
+            event,
+            MjaiEvent::Dahai {
+                actor: 2,
+                pai: tile("5M"),
+                tsumogiri: false,
+            }
+        );
+        assert_eq!(
+            event.to_string(),
+            r#"{"type":"dahai","actor":2,"pai":"5mr","tsumogiri":false}"#
+        );
+
+        let event: MjaiEvent = r#"{"type":"tsumo","actor":1,"pai":"?"}"#.parse().unwrap();
+        assert_eq!(
+            event,
+            MjaiEvent::Tsumo {
+                actor: 1,
+                pai: None
+            }
+        );
+
+        assert_eq!(
+            MjaiEvent::Pass {}.to_string(),
+            r#"{"type":"none"}"#.to_string()
+        );
+    }
+
+    #[test]
+    fn validate_log() {
+        let paipu = to_paipu(&events(LOG), &Ruleset::default()).unwrap();
+        assert_eq!(paipu.names, vec!["A", "B", "C", "D"]);
+
+        let res = rescore(&paipu);
+        assert_eq!(res.len(), 1);
+        assert!(res[0].is_consistent(), "{}", res[0]);
+    }
+
+    #[test]
+    fn validate_log_partial() {
+        let hora =
+            r#"{"type":"hora","actor":1,"target":0,"pai":"5p","fu":40,"fan":1,"hora_points":1300}"#;
+
+        // 点数がなくても、翻数と符数が合えば一致とみなす。
+        let log = LOG.replace(
+            hora,
+            r#"{"type":"hora","actor":1,"target":0,"pai":"5p","fu":40,"fan":1}"#,
+        );
+        let paipu = to_paipu(&events(&log), &Ruleset::default()).unwrap();
+        let res = rescore(&paipu);
+        assert_eq!(res[0].record.value, None);
+        assert!(res[0].is_consistent(), "{}", res[0]);
+
+        // 役満の数は役の一覧か翻数から求める。
+        for yiman in &[
+            r#"{"type":"hora","actor":1,"target":0,"pai":"5p","fan":26,"hora_points":64000}"#,
+            r#"{"type":"hora","actor":1,"target":0,"pai":"5p","yakus":[["大四喜",26]],"fan":26}"#,
+        ] {
+            let paipu = to_paipu(&events(&LOG.replace(hora, yiman)), &Ruleset::default()).unwrap();
+            match paipu.rounds[0].events.last() {
+                Some(Event::Hule(record)) => {
+                    assert_eq!(record.yiman, 2);
+                    assert_eq!(record.fan, 0);
+                }
+                event => panic!("unexpected event: {:?}", event),
+            }
+        }
+
+        for invalid in &[
+            r#"{"type":"hora","actor":4,"target":0,"pai":"5p"}"#,
+            r#"{"type":"hora","actor":1,"target":7,"pai":"5p"}"#,
+        ] {
+            assert!(matches!(
+                to_paipu(&events(&LOG.replace(hora, invalid)), &Ruleset::default()),
+                Err(MjaiError::InvalidSeat(_))
+            ));
+        }
+        let log = LOG.replace(
+            r#""actor":0,"pai":"5p","tsumogiri""#,
+            r#""actor":5,"pai":"5p","tsumogiri""#,
+        );
+        assert!(matches!(
+            to_paipu(&events(&log), &Ruleset::default()),
+            Err(MjaiError::InvalidSeat(5))
+        ));
+    }
+
+    #[test]
+    fn view() {
+        let events = events(LOG);
+        let mut view = View::new(1);
+        for event in &events[..4] {
+            view.update(event).unwrap();
+        }
+
+        assert_eq!(view.hand.len(), 13);
+        assert_eq!(view.discards[0], vec![tile("5p")]);
+
+        let judge = view.validate_hora(&events[4], &Ruleset::default()).unwrap();
+        assert_eq!(judge.total().fan, 1);
+        assert_eq!(judge.total().fu, 40);
+
+        // 他の人のアガリは検証できない。
+        let other = View::new(2);
+        assert!(other
+            .validate_hora(&events[4], &Ruleset::default())
+            .is_err());
+    }
+}
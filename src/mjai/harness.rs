@@ -0,0 +1,652 @@
+//! MJAI の bot を手元で対局させる。
+//!
+//! bot には自分に見える出来事だけを JSON の配列として一行で送り、行動を一行の JSON で受け取る。局の
+//! 進行は `round::Round` が行う。
+
+use super::{MjaiError, MjaiEvent, Result};
+use crate::context::Direction;
+use crate::form::Point;
+use crate::judge::judge;
+use crate::payment::Payment;
+use crate::round::{Action, Phase, Round, RoundResult};
+use crate::ruleset::Ruleset;
+use crate::tile::Tile;
+use crate::wall::Wall;
+use num_traits::FromPrimitive;
+use rand::Rng;
+use std::io::{BufRead, BufReader, Write};
+use std::process::{Child, ChildStdin, ChildStdout, Command, Stdio};
+
+/// 対局する bot 。
+pub trait Agent {
+    /// 前回の応答の後に起きた出来事を受け取り、行動を返す。行動を求められていないときの応答は無視
+    /// される。
+    fn react(&mut self, events: &[MjaiEvent]) -> Result<MjaiEvent>;
+}
+
+/// 子プロセスとして動く bot 。標準入出力で通信する。
+pub struct Process {
+    child: Child,
+    stdin: ChildStdin,
+    stdout: BufReader<ChildStdout>,
+}
+
+impl Process {
+    /// `command` を起動する。標準入出力は上書きされる。
+    pub fn spawn(command: &mut Command) -> Result<Process> {
+        let mut child = command
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()?;
+        let stdin = child.stdin.take().expect("stdin is piped.");
+        let stdout = BufReader::new(child.stdout.take().expect("stdout is piped."));
+
+        Ok(Process {
+            child,
+            stdin,
+            stdout,
+        })
+    }
+}
+
+impl Agent for Process {
+    fn react(&mut self, events: &[MjaiEvent]) -> Result<MjaiEvent> {
+        writeln!(self.stdin, "{}", serde_json::to_string(events)?)?;
+        self.stdin.flush()?;
+
+        let mut line = String::new();
+        if self.stdout.read_line(&mut line)? == 0 {
+            return Err(MjaiError::NoResponse);
+        }
+
+        line.trim().parse()
+    }
+}
+
+impl Drop for Process {
+    fn drop(&mut self) {
+        let _ = self.child.kill();
+        let _ = self.child.wait();
+    }
+}
+
+/// 4 人の bot による対局。席の番号は起家を 0 とする。
+pub struct Harness {
+    agents: Vec<Box<dyn Agent>>,
+    ruleset: Ruleset,
+
+    /// まだ bot に送っていない出来事。
+    queues: [Vec<MjaiEvent>; 4],
+
+    /// 伏せられていない全ての出来事。
+    log: Vec<MjaiEvent>,
+
+    scores: [i32; 4],
+    bakaze: Direction,
+    kyoku: u32,
+    honba: u32,
+    kyotaku: u32,
+
+    /// 親が交代した回数。
+    num_rotations: u32,
+}
+
+impl Harness {
+    /// 持ち点 25000 点で対局を用意する。 `agents` はちょうど 4 人でなければならない。
+    pub fn new(agents: Vec<Box<dyn Agent>>, ruleset: &Ruleset) -> Harness {
+        assert_eq!(agents.len(), 4, "mahjong needs exactly 4 agents.");
+
+        Harness {
+            agents,
+            ruleset: *ruleset,
+            queues: Default::default(),
+            log: Vec::new(),
+            scores: [25000; 4],
+            bakaze: Direction::East,
+            kyoku: 1,
+            honba: 0,
+            kyotaku: 0,
+            num_rotations: 0,
+        }
+    }
+
+    /// 持ち点。
+    pub fn scores(&self) -> [i32; 4] {
+        self.scores
+    }
+
+    /// 伏せられていない全ての出来事。 `mjai::to_paipu` で牌譜にできる。
+    pub fn log(&self) -> &[MjaiEvent] {
+        &self.log
+    }
+
+    /// 親が `num_rotations` 回交代するまで対局する (4 なら東風戦) 。最終的な持ち点を返す。
+    pub fn run_game<R: Rng + ?Sized>(
+        &mut self,
+        rng: &mut R,
+        num_rotations: u32,
+    ) -> Result<[i32; 4]> {
+        self.broadcast(MjaiEvent::StartGame {
+            id: None,
+            names: Vec::new(),
+        });
+
+        while self.num_rotations < num_rotations {
+            let wall = Wall::new(rng, &self.ruleset);
+            self.run_kyoku(wall)?;
+        }
+
+        self.broadcast(MjaiEvent::EndGame {});
+        for seat in 0..4 {
+            self.ask(seat)?;
+        }
+
+        Ok(self.scores)
+    }
+
+    /// `wall` を使って一局を行う。
+    pub fn run_kyoku(&mut self, wall: Wall) -> Result<RoundResult> {
+        let mut round = Round::new(wall, self.bakaze, self.honba, self.kyotaku, &self.ruleset);
+
+        // 親の配牌は最初のツモを除いた 13 枚として送る。
+        let drawn = match round.phase() {
+            Phase::Dapai { drawn, .. } => *drawn,
+            _ => None,
+        };
+        let tehais = (0..4)
+            .map(|seat| {
+                let mut hand = round.player(self.direction(seat)).hand.clone();
+                if let Some(drawn) = drawn.filter(|_| seat == self.oya()) {
+                    remove_exact(&mut hand, drawn);
+                }
+                hand.into_iter().map(Some).collect()
+            })
+            .collect();
+
+        let dora_indicators = round.wall().dora_indicators();
+        self.broadcast(MjaiEvent::StartKyoku {
+            bakaze: self.bakaze,
+            kyoku: self.kyoku,
+            honba: self.honba,
+            kyotaku: self.kyotaku,
+            oya: self.oya(),
+            dora_marker: dora_indicators.first(),
+            tehais,
+            scores: self.scores.to_vec(),
+        });
+
+        let mut num_doras = 1;
+        let result = loop {
+            // 槓ドラ
+            let dora_indicators = round.wall().dora_indicators();
+            for &dora_marker in &dora_indicators.inner()[num_doras..] {
+                self.broadcast(MjaiEvent::Dora { dora_marker });
+            }
+            num_doras = dora_indicators.len();
+
+            match round.phase().clone() {
+                Phase::Dapai { player, drawn } => {
+                    let seat = self.seat(player);
+                    if let Some(tile) = drawn {
+                        self.broadcast(MjaiEvent::Tsumo {
+                            actor: seat,
+                            pai: Some(tile),
+                        });
+                    }
+                    self.turn(&mut round, player, drawn)?;
+                }
+                Phase::Response {
+                    player,
+                    tile,
+                    is_lizhi,
+                    ..
+                } => self.respond(&mut round, player, tile, is_lizhi)?,
+                Phase::Finished(result) => break result,
+            }
+        };
+
+        self.finish(&round, &result);
+        Ok(result)
+    }
+
+    /// 手番の bot に打牌などをさせる。
+    fn turn(&mut self, round: &mut Round, player: Direction, drawn: Option<Tile>) -> Result<()> {
+        let seat = self.seat(player);
+        let legal = round.legal_actions(player);
+        let mut lizhi = false;
+
+        let (action, response) = loop {
+            let response = self.ask(seat)?;
+            let action = match &response {
+                MjaiEvent::Reach { actor } if *actor == seat && !lizhi => {
+                    if !legal.iter().any(|a| matches!(a, Action::Lizhi(_))) {
+                        return Err(MjaiError::IllegalResponse(seat, response.to_string()));
+                    }
+                    lizhi = true;
+                    self.broadcast(response);
+                    continue;
+                }
+                MjaiEvent::Dahai { actor, pai, .. } if *actor == seat && lizhi => {
+                    Action::Lizhi(*pai)
+                }
+                MjaiEvent::Dahai { actor, pai, .. } if *actor == seat => Action::Dapai(*pai),
+                MjaiEvent::Hora { actor, .. } if *actor == seat && !lizhi => Action::Zimo,
+                MjaiEvent::Ankan { actor, consumed } if *actor == seat && !lizhi => {
+                    match consumed.first() {
+                        Some(&tile) => Action::Angang(tile),
+                        None => return Err(MjaiError::IllegalResponse(seat, response.to_string())),
+                    }
+                }
+                MjaiEvent::Kakan { actor, pai, .. } if *actor == seat && !lizhi => {
+                    Action::Jiagang(*pai)
+                }
+                _ => return Err(MjaiError::IllegalResponse(seat, response.to_string())),
+            };
+            break (action, response);
+        };
+
+        round
+            .act(player, action)
+            .map_err(|_| MjaiError::IllegalResponse(seat, response.to_string()))?;
+
+        let me = round.player(player);
+        match action {
+            Action::Dapai(pai) | Action::Lizhi(pai) => {
+                let pai = *me.discards.last().unwrap_or(&pai);
+                self.broadcast(MjaiEvent::Dahai {
+                    actor: seat,
+                    pai,
+                    tsumogiri: drawn.map_or(false, |t| t == pai && t.is_red() == pai.is_red()),
+                });
+            }
+            Action::Angang(_) => {
+                let consumed = me.angangs.last().map(|t| t.inner().clone());
+                self.broadcast(MjaiEvent::Ankan {
+                    actor: seat,
+                    consumed: consumed.unwrap_or_default(),
+                });
+            }
+            Action::Jiagang(pai) => {
+                let mut consumed = me
+                    .minggangs
+                    .last()
+                    .map_or_else(Vec::new, |t| t.inner().clone());
+                let pai = remove_exact(&mut consumed, pai).unwrap_or(pai);
+                self.broadcast(MjaiEvent::Kakan {
+                    actor: seat,
+                    pai,
+                    consumed,
+                });
+            }
+            _ => {}
+        }
+
+        Ok(())
+    }
+
+    /// 打牌 (または加槓) に対して、鳴き・ロンのできる bot に応答させる。
+    fn respond(
+        &mut self,
+        round: &mut Round,
+        discarder: Direction,
+        tile: Tile,
+        is_lizhi: bool,
+    ) -> Result<()> {
+        let target = self.seat(discarder);
+        let mut responses = Vec::new();
+
+        let mut player = discarder.next();
+        while player != discarder {
+            let legal = round.legal_actions(player);
+            if legal.len() > 1 {
+                let seat = self.seat(player);
+                let response = self.ask(seat)?;
+                let illegal = || MjaiError::IllegalResponse(seat, response.to_string());
+                let action = match &response {
+                    MjaiEvent::Pass {} => Action::Pass,
+                    MjaiEvent::Hora { actor, .. } if *actor == seat => Action::Ronghe,
+                    MjaiEvent::Pon { actor, .. } if *actor == seat => Action::Peng,
+                    MjaiEvent::Daiminkan { actor, .. } if *actor == seat => Action::Minggang,
+                    MjaiEvent::Chi {
+                        actor, consumed, ..
+                    } if *actor == seat && consumed.len() == 2 => {
+                        Action::Chi(consumed[0], consumed[1])
+                    }
+                    _ => return Err(illegal()),
+                };
+                if !legal.contains(&action) {
+                    return Err(illegal());
+                }
+                responses.push((player, action));
+            }
+            player = player.next();
+        }
+
+        round.respond(responses.clone())?;
+
+        let caller = match round.phase() {
+            Phase::Finished(RoundResult::Hule(_)) => return Ok(()),
+            Phase::Dapai { player, .. } => Some(*player),
+            _ => None,
+        };
+
+        if is_lizhi {
+            self.scores[target] -= 1000;
+            self.broadcast(MjaiEvent::ReachAccepted { actor: target });
+        }
+
+        let caller = match caller {
+            Some(caller) => caller,
+            None => return Ok(()),
+        };
+        let action = match responses.iter().find(|&&(p, _)| p == caller) {
+            Some(&(_, action)) => action,
+            None => return Ok(()),
+        };
+
+        let me = round.player(caller);
+        let meld = match action {
+            Action::Chi(..) => me.chis.last(),
+            Action::Peng => me.pengs.last(),
+            Action::Minggang => me.minggangs.last(),
+            _ => return Ok(()),
+        };
+
+        let mut consumed = meld.map_or_else(Vec::new, |t| t.inner().clone());
+        let pai = remove_exact(&mut consumed, tile).unwrap_or(tile);
+        let actor = self.seat(caller);
+        self.broadcast(match action {
+            Action::Chi(..) => MjaiEvent::Chi {
+                actor,
+                target,
+                pai,
+                consumed,
+            },
+            Action::Peng => MjaiEvent::Pon {
+                actor,
+                target,
+                pai,
+                consumed,
+            },
+            _ => MjaiEvent::Daiminkan {
+                actor,
+                target,
+                pai,
+                consumed,
+            },
+        });
+
+        Ok(())
+    }
+
+    /// 局の結果を送り、持ち点と次の局を決める。
+    fn finish(&mut self, round: &Round, result: &RoundResult) {
+        let mut deltas = [0; 4];
+
+        let dealer_continues = match result {
+            RoundResult::Hule(hules) => {
+                let mut judges = Vec::new();
+                for hule in hules {
+                    let judge = judge(&hule.tilesets, &self.ruleset)
+                        .expect("round finishes only with valid hules.");
+                    let payment = judge.payment();
+
+                    let mut hule_deltas = [0; 4];
+                    for (seat, delta) in hule_deltas.iter_mut().enumerate() {
+                        let payer = self.direction(seat);
+                        if payer == hule.player {
+                            *delta += payment.total() as i32;
+                        } else if hule.from.map_or(true, |from| from == payer) {
+                            *delta -= payment.amount(hule.player, payer) as i32;
+                        }
+                    }
+
+                    for (delta, d) in deltas.iter_mut().zip(&hule_deltas) {
+                        *delta += d;
+                    }
+                    judges.push((hule, judge, hule_deltas));
+                }
+
+                for (score, delta) in self.scores.iter_mut().zip(&deltas) {
+                    *score += delta;
+                }
+
+                for (hule, judge, hule_deltas) in judges {
+                    let actor = self.seat(hule.player);
+                    let tilesets = &hule.tilesets;
+                    let &Point { fan, fu, yiman } = judge.total();
+
+                    self.broadcast(MjaiEvent::Hora {
+                        actor,
+                        target: hule.from.map_or(actor, |from| self.seat(from)),
+                        pai: tilesets.last,
+                        uradora_markers: tilesets.uradora_indicators().into_inner(),
+                        yakus: (judge.forms().iter())
+                            .map(|form| (form.name().to_string(), form.point(&self.ruleset).fan))
+                            .collect(),
+                        fu: Some(fu),
+                        fan: Some(if yiman > 0 { 13 * yiman } else { fan }),
+                        hora_points: Some(
+                            Payment::new(
+                                *judge.total(),
+                                tilesets.context.is_parent(),
+                                tilesets.is_zimo,
+                                &self.ruleset,
+                            )
+                            .total(),
+                        ),
+                        deltas: hule_deltas.to_vec(),
+                        scores: self.scores.to_vec(),
+                    });
+                }
+
+                hules.iter().any(|hule| hule.player == Direction::East)
+            }
+            RoundResult::Liuju {
+                tingpais,
+                liuju_manguans,
+            } => {
+                let num = tingpais.len() as i32;
+                if !liuju_manguans.is_empty() {
+                    // 流し満貫があれば、ノーテン罰符の代わりに満貫のツモアガリと同じ支払いをする。
+                    for liuju_manguan in liuju_manguans {
+                        let payment = liuju_manguan.payment;
+                        for (seat, delta) in deltas.iter_mut().enumerate() {
+                            let payer = self.direction(seat);
+                            if payer == liuju_manguan.player {
+                                *delta += payment.total() as i32;
+                            } else {
+                                *delta -= payment.amount(liuju_manguan.player, payer) as i32;
+                            }
+                        }
+                    }
+                } else if 0 < num && num < 4 {
+                    for (seat, delta) in deltas.iter_mut().enumerate() {
+                        *delta = if tingpais.contains(&self.direction(seat)) {
+                            3000 / num
+                        } else {
+                            -3000 / (4 - num)
+                        };
+                    }
+                }
+
+                for (score, delta) in self.scores.iter_mut().zip(&deltas) {
+                    *score += delta;
+                }
+                self.broadcast(MjaiEvent::Ryukyoku {
+                    deltas: deltas.to_vec(),
+                    scores: self.scores.to_vec(),
+                });
+
+                tingpais.contains(&Direction::East)
+            }
+        };
+
+        self.broadcast(MjaiEvent::EndKyoku {});
+
+        let is_hule = matches!(result, RoundResult::Hule(_));
+        self.kyotaku = if is_hule { 0 } else { round.gongtuo() };
+        self.honba = if is_hule && !dealer_continues {
+            0
+        } else {
+            self.honba + 1
+        };
+
+        if !dealer_continues {
+            self.num_rotations += 1;
+            self.kyoku += 1;
+            if self.kyoku > 4 {
+                self.kyoku = 1;
+                self.bakaze = self.bakaze.next();
+            }
+        }
+    }
+
+    /// 親の席。
+    fn oya(&self) -> usize {
+        (self.kyoku - 1) as usize % 4
+    }
+
+    /// 自風が `dir` の人の席。
+    fn seat(&self, dir: Direction) -> usize {
+        (self.oya() + dir as usize) % 4
+    }
+
+    /// 席 `seat` の人の自風。
+    fn direction(&self, seat: usize) -> Direction {
+        Direction::from_usize((seat + 4 - self.oya()) % 4).expect("seat must be less than 4.")
+    }
+
+    /// 出来事を記録し、各 bot に見える形にして送る準備をする。
+    fn broadcast(&mut self, event: MjaiEvent) {
+        for (seat, queue) in self.queues.iter_mut().enumerate() {
+            queue.push(mask(&event, seat));
+        }
+        self.log.push(event);
+    }
+
+    /// たまっている出来事を送り、 bot の応答を受け取る。
+    fn ask(&mut self, seat: usize) -> Result<MjaiEvent> {
+        let events = std::mem::take(&mut self.queues[seat]);
+        self.agents[seat].react(&events)
+    }
+}
+
+/// 席 `seat` の bot から見えない牌を伏せる。
+fn mask(event: &MjaiEvent, seat: usize) -> MjaiEvent {
+    match event {
+        MjaiEvent::StartGame { names, .. } => MjaiEvent::StartGame {
+            id: Some(seat),
+            names: names.clone(),
+        },
+        MjaiEvent::StartKyoku {
+            bakaze,
+            kyoku,
+            honba,
+            kyotaku,
+            oya,
+            dora_marker,
+            tehais,
+            scores,
+        } => MjaiEvent::StartKyoku {
+            bakaze: *bakaze,
+            kyoku: *kyoku,
+            honba: *honba,
+            kyotaku: *kyotaku,
+            oya: *oya,
+            dora_marker: *dora_marker,
+            tehais: (tehais.iter().enumerate())
+                .map(|(s, tehai)| {
+                    if s == seat {
+                        tehai.clone()
+                    } else {
+                        vec![None; tehai.len()]
+                    }
+                })
+                .collect(),
+            scores: scores.clone(),
+        },
+        MjaiEvent::Tsumo { actor, .. } if *actor != seat => MjaiEvent::Tsumo {
+            actor: *actor,
+            pai: None,
+        },
+        event => event.clone(),
+    }
+}
+
+/// 赤ドラかどうかまで一致する牌を優先して一枚取り除く。
+fn remove_exact(tiles: &mut Vec<Tile>, tile: Tile) -> Option<Tile> {
+    let pos = (tiles.iter())
+        .position(|&t| t == tile && t.is_red() == tile.is_red())
+        .or_else(|| tiles.iter().position(|&t| t == tile))?;
+    Some(tiles.remove(pos))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mjai::to_paipu;
+    use crate::paipu::rescore;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    /// ツモった牌をそのまま捨て、鳴かない bot 。
+    struct Tsumogiri {
+        id: usize,
+    }
+
+    impl Agent for Tsumogiri {
+        fn react(&mut self, events: &[MjaiEvent]) -> Result<MjaiEvent> {
+            for event in events {
+                if let MjaiEvent::StartGame { id: Some(id), .. } = event {
+                    self.id = *id;
+                }
+            }
+
+            match events.last() {
+                Some(&MjaiEvent::Tsumo {
+                    actor,
+                    pai: Some(pai),
+                }) if actor == self.id => Ok(MjaiEvent::Dahai {
+                    actor,
+                    pai,
+                    tsumogiri: true,
+                }),
+                _ => Ok(MjaiEvent::Pass {}),
+            }
+        }
+    }
+
+    #[test]
+    fn tsumogiri_game() {
+        let agents: Vec<Box<dyn Agent>> = (0..4)
+            .map(|_| Box::new(Tsumogiri { id: 0 }) as Box<dyn Agent>)
+            .collect();
+        let mut harness = Harness::new(agents, &Ruleset::default());
+        let mut rng = StdRng::seed_from_u64(0);
+
+        let scores = harness.run_game(&mut rng, 4).unwrap();
+        assert_eq!(scores.iter().sum::<i32>(), 100000);
+
+        // 記録からも同じ点数が判定できる。
+        let paipu = to_paipu(harness.log(), &Ruleset::default()).unwrap();
+        assert!(paipu.rounds.len() >= 4);
+        assert!(rescore(&paipu).iter().all(|res| res.is_consistent()));
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn process() {
+        let mut bot = Process::spawn(
+            Command::new("sh").args(["-c", r#"while read l; do echo '{"type":"none"}'; done"#]),
+        )
+        .unwrap();
+
+        let events = [MjaiEvent::StartGame {
+            id: Some(0),
+            names: Vec::new(),
+        }];
+        assert_eq!(bot.react(&events).unwrap(), MjaiEvent::Pass {});
+        assert_eq!(bot.react(&[]).unwrap(), MjaiEvent::Pass {});
+    }
+}
@@ -0,0 +1,893 @@
+//! 牌譜を定義し、牌譜に記録されたアガリを判定し直す。
+//!
+//! 天鳳などの形式ごとの読み込みはそれぞれのモジュールで行い、ここで定義する共通の形式に変換する。牌譜
+//! の出来事を順に再現して、アガった時点の牌集合の集合とコンテキストを作り、判定結果を牌譜に記録され
+//! ている翻数・符数・点数と比べる。
+
+use crate::context::{Context, Direction, HuleProgress, Lizhi};
+use crate::form::Point;
+use crate::judge::{judge, Judge};
+use crate::payment::Payment;
+use crate::ruleset::Ruleset;
+use crate::tile::Tile;
+use crate::tiles::{remove_tile, Tiles};
+use crate::tilesets::{TilesetsError, TingpaiTilesets};
+use log::debug;
+use num_traits::FromPrimitive;
+use std::fmt;
+use thiserror::Error;
+
+/// 配牌後のツモ山の枚数。
+const NUM_LIVE_TILES: usize = 136 - 14 - 13 * 4;
+
+/// 牌譜。
+#[derive(Debug, Clone, Default)]
+pub struct Paipu {
+    /// 席順 (起家から順) に並んだプレイヤーの名前。
+    pub names: Vec<String>,
+
+    /// この対局のルール。
+    pub ruleset: Ruleset,
+
+    /// 局ごとの記録。
+    pub rounds: Vec<RoundRecord>,
+}
+
+/// 一局の記録。
+///
+/// プレイヤーは起家を 0 とした席の番号 (0 から 3) で表す。
+#[derive(Debug, Clone, Default)]
+pub struct RoundRecord {
+    /// 場風。
+    pub place: Direction,
+
+    /// 親の席。東 1 局なら 0 となる。
+    pub dealer: usize,
+
+    /// 本場。
+    pub benchang: u32,
+
+    /// 局の開始時に供託されているリーチ棒の本数。
+    pub gongtuo: u32,
+
+    /// 席ごとの配牌。
+    pub hands: [Vec<Tile>; 4],
+
+    /// 局の開始時のドラ表示牌。
+    pub dora_indicators: Vec<Tile>,
+
+    /// 局の出来事。
+    pub events: Vec<Event>,
+}
+
+/// 局の中の一つの出来事。
+#[derive(Debug, Clone)]
+pub enum Event {
+    /// ツモ。槓の後のツモは嶺上牌のツモとなる。
+    Mopai { seat: usize, tile: Tile },
+
+    /// 打牌。立直宣言牌なら `is_lizhi` が true となる。
+    Dapai {
+        seat: usize,
+        tile: Tile,
+        is_lizhi: bool,
+    },
+
+    /// チー。 `tiles` は手牌から出した 2 枚、 `called` は鳴いた牌。
+    Chi {
+        seat: usize,
+        tiles: Vec<Tile>,
+        called: Tile,
+    },
+
+    /// ポン。 `tiles` は手牌から出した 2 枚、 `called` は鳴いた牌。
+    Peng {
+        seat: usize,
+        tiles: Vec<Tile>,
+        called: Tile,
+    },
+
+    /// 大明槓。 `tiles` は手牌から出した 3 枚、 `called` は鳴いた牌。
+    Minggang {
+        seat: usize,
+        tiles: Vec<Tile>,
+        called: Tile,
+    },
+
+    /// 暗槓。 `tiles` は手牌から出した 4 枚。
+    Angang { seat: usize, tiles: Vec<Tile> },
+
+    /// 加槓。 `tile` は手牌から加えた牌。
+    Jiagang { seat: usize, tile: Tile },
+
+    /// 新しいドラ表示牌がめくられた。
+    Dora(Tile),
+
+    /// アガリ。ダブロンの場合は続けて記録される。
+    Hule(HuleRecord),
+
+    /// 流局。
+    Liuju,
+}
+
+impl Event {
+    /// 出来事を起こした人の席。アガリならアガった人の席となる。
+    pub fn seat(&self) -> Option<usize> {
+        match self {
+            Event::Mopai { seat, .. }
+            | Event::Dapai { seat, .. }
+            | Event::Chi { seat, .. }
+            | Event::Peng { seat, .. }
+            | Event::Minggang { seat, .. }
+            | Event::Angang { seat, .. }
+            | Event::Jiagang { seat, .. } => Some(*seat),
+            Event::Hule(record) => Some(record.seat),
+            Event::Dora(_) | Event::Liuju => None,
+        }
+    }
+}
+
+/// 牌譜に記録されているアガリ。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HuleRecord {
+    /// アガった人の席。
+    pub seat: usize,
+
+    /// 放銃した人の席。ツモなら None となる。
+    pub from: Option<usize>,
+
+    /// アガリ牌。
+    pub tile: Tile,
+
+    /// 裏ドラ表示牌。立直していなければ空でよい。
+    pub uradora_indicators: Vec<Tile>,
+
+    /// 記録されている役の名前と翻数。役満の翻数は 13 とする。
+    pub yakus: Vec<(String, u32)>,
+
+    /// 記録されている翻数。役満なら 0 とする。
+    pub fan: u32,
+
+    /// 記録されている符数。記録されていなければ None となる。
+    pub fu: Option<u32>,
+
+    /// 記録されている役満の数。
+    pub yiman: u32,
+
+    /// 記録されている点数。本場と供託の分は含まない。ツモなら支払いの合計とする。記録されていなければ
+    /// None となる。
+    pub value: Option<u32>,
+}
+
+impl HuleRecord {
+    pub fn is_zimo(&self) -> bool {
+        self.from.is_none()
+    }
+}
+
+/// 牌譜を再現できなかった理由。
+#[derive(Debug, Error)]
+pub enum RescoreError {
+    /// 手牌にない牌を使おうとした。
+    #[error("{0} 番目の席の手牌に {1} がありません。")]
+    TileNotFound(usize, Tile),
+
+    /// 加槓しようとしたポンがない。
+    #[error("{0} 番目の席に {1} のポンがありません。")]
+    PengNotFound(usize, Tile),
+
+    /// 牌集合の集合を作れなかった。
+    #[error("{0}")]
+    Tilesets(#[from] TilesetsError),
+
+    /// 席や親の番号が 0 から 3 でない。
+    #[error("{0} 番目の席はありません。")]
+    InvalidSeat(usize),
+
+    /// 役がない。
+    #[error("役がありません。")]
+    NoYaku,
+}
+
+/// 牌譜に記録されたアガリを判定し直した結果。
+#[derive(Debug)]
+pub struct Rescore {
+    /// 牌譜の中で何番目の局か。
+    pub round: usize,
+
+    /// その局の場風。
+    pub place: Direction,
+
+    /// その局の親の席。
+    pub dealer: usize,
+
+    /// その局の本場。
+    pub benchang: u32,
+
+    /// 牌譜に記録されているアガリ。
+    pub record: HuleRecord,
+
+    /// 判定し直した結果。
+    pub result: Result<Judge, RescoreError>,
+}
+
+impl Rescore {
+    /// アガった人の自風。
+    pub fn player(&self) -> Direction {
+        seat_direction(self.record.seat, self.dealer)
+    }
+
+    /// 判定結果の点数。本場と供託の分は含まない。ツモなら支払いの合計となる。
+    pub fn value(&self) -> Option<u32> {
+        let judge = self.result.as_ref().ok()?;
+        let tilesets = judge.tilesets();
+        let payment = Payment::new(
+            *judge.total(),
+            tilesets.context.is_parent(),
+            tilesets.is_zimo,
+            judge.ruleset(),
+        );
+
+        Some(payment.total())
+    }
+
+    /// 判定結果の翻数・符数・点数が牌譜の記録と一致しているかどうか。
+    ///
+    /// 役満では役満の数と点数だけを比べる。符数は牌譜に記録されている場合だけ比べる。
+    pub fn is_consistent(&self) -> bool {
+        let judge = match &self.result {
+            Ok(judge) => judge,
+            Err(_) => return false,
+        };
+
+        let total = judge.total();
+        let record = &self.record;
+        let point_matches = if record.yiman > 0 || total.yiman > 0 {
+            record.yiman == total.yiman
+        } else {
+            record.fan == total.fan && record.fu.map_or(true, |fu| fu == total.fu)
+        };
+
+        point_matches
+            && record
+                .value
+                .map_or(true, |value| self.value() == Some(value))
+    }
+
+    pub fn display_en(&self) -> RescoreDisplayEn<'_> {
+        RescoreDisplayEn(self)
+    }
+}
+
+/// 記録された翻数・符数を表示用の `Point` にする。
+fn recorded_point(record: &HuleRecord) -> Point {
+    if record.yiman > 0 {
+        Point::new_multiple_yiman(record.yiman)
+    } else {
+        Point::with_fu(record.fan, record.fu.unwrap_or(0))
+    }
+}
+
+impl fmt::Display for Rescore {
+    fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            b,
+            "{}{}局{}本場 {}家 {}: 記録 {}{} / ",
+            self.place,
+            self.dealer + 1,
+            self.benchang,
+            self.player(),
+            if self.record.is_zimo() {
+                "ツモ"
+            } else {
+                "ロン"
+            },
+            recorded_point(&self.record),
+            (self.record.value).map_or_else(String::new, |value| format!(" {}点", value)),
+        )?;
+
+        match &self.result {
+            Ok(judge) => write!(b, "判定 {} {}点", judge.total(), self.value().unwrap_or(0)),
+            Err(e) => write!(b, "判定できません ({})", e),
+        }
+    }
+}
+
+pub struct RescoreDisplayEn<'a>(&'a Rescore);
+
+impl fmt::Display for RescoreDisplayEn<'_> {
+    fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
+        let RescoreDisplayEn(rescore) = self;
+        write!(
+            b,
+            "{} {} {} Honba {} {}: Recorded {}{} / ",
+            rescore.place.display_en(),
+            rescore.dealer + 1,
+            rescore.benchang,
+            rescore.player().display_en(),
+            if rescore.record.is_zimo() {
+                "Tsumo"
+            } else {
+                "Ron"
+            },
+            recorded_point(&rescore.record).display_en(),
+            (rescore.record.value).map_or_else(String::new, |value| format!(" {}", value)),
+        )?;
+
+        match &rescore.result {
+            Ok(judge) => write!(
+                b,
+                "Judged {} {}",
+                judge.total().display_en(),
+                rescore.value().unwrap_or(0)
+            ),
+            Err(_) => write!(b, "Cannot Judge"),
+        }
+    }
+}
+
+/// 牌譜に記録された全てのアガリを判定し直す。
+pub fn rescore(paipu: &Paipu) -> Vec<Rescore> {
+    let mut res = Vec::new();
+
+    for (index, round) in paipu.rounds.iter().enumerate() {
+        let mut replay = Replay::new(round, &paipu.ruleset);
+        for event in &round.events {
+            if let Event::Hule(record) = event {
+                res.push(Rescore {
+                    round: index,
+                    place: round.place,
+                    dealer: round.dealer,
+                    benchang: round.benchang,
+                    record: record.clone(),
+                    result: replay.hule(record),
+                });
+            } else if replay.error.is_none() {
+                if let Err(e) = replay.apply(event) {
+                    debug!("牌譜を再現できません: {}", e);
+                    replay.error = Some(e);
+                }
+            }
+        }
+    }
+
+    res
+}
+
+/// 牌譜に記録されたアガリのうち、判定し直した結果が記録と一致しないものを列挙する。
+pub fn discrepancies(paipu: &Paipu) -> Vec<Rescore> {
+    rescore(paipu)
+        .into_iter()
+        .filter(|rescore| !rescore.is_consistent())
+        .collect()
+}
+
+/// 席の番号と親の席から自風を求める。番号は 4 で割った余りを使う。
+fn seat_direction(seat: usize, dealer: usize) -> Direction {
+    Direction::from_usize((seat % 4 + 4 - dealer % 4) % 4).expect("seat must be less than 4.")
+}
+
+/// 席の番号が 0 から 3 であることを確かめる。
+fn check_seat(seat: usize) -> Result<(), RescoreError> {
+    if seat < 4 {
+        Ok(())
+    } else {
+        Err(RescoreError::InvalidSeat(seat))
+    }
+}
+
+/// 再現中のプレイヤーの状態。
+#[derive(Debug, Clone, Default)]
+struct Player {
+    hand: Vec<Tile>,
+    pengs: Vec<Tiles>,
+    chis: Vec<Tiles>,
+    minggangs: Vec<Tiles>,
+    angangs: Vec<Tiles>,
+    discards: Vec<Tile>,
+    lizhi: Lizhi,
+    ippatsu: bool,
+}
+
+/// 一局の再現。
+struct Replay<'a> {
+    round: &'a RoundRecord,
+    ruleset: &'a Ruleset,
+    players: Vec<Player>,
+    dora_indicators: Vec<Tile>,
+
+    /// ツモった回数。嶺上牌のツモも含む (嶺上牌をツモるとツモ山の最後の牌が王牌に移るため) 。
+    num_mopai: usize,
+
+    /// 最後のツモが嶺上牌かどうか。
+    is_lingshang: bool,
+
+    /// 次のツモが嶺上牌かどうか。
+    next_is_lingshang: bool,
+
+    /// 鳴きがなく、最初の一巡の途中かどうか。
+    is_first_go_around: bool,
+
+    /// 直前の加槓。槍槓の判定に使う。
+    last_jiagang: Option<Tile>,
+
+    /// 立直宣言牌が通るのを待っている席。
+    pending_lizhi: Option<usize>,
+
+    gongtuo: u32,
+
+    /// 再現に失敗した理由。失敗した後のアガリは全てこのエラーになる。
+    error: Option<RescoreError>,
+}
+
+impl<'a> Replay<'a> {
+    fn new(round: &'a RoundRecord, ruleset: &'a Ruleset) -> Replay<'a> {
+        let players = (round.hands.iter())
+            .map(|hand| Player {
+                hand: hand.clone(),
+                ..Player::default()
+            })
+            .collect();
+
+        Replay {
+            round,
+            ruleset,
+            players,
+            dora_indicators: round.dora_indicators.clone(),
+            num_mopai: 0,
+            is_lingshang: false,
+            next_is_lingshang: false,
+            is_first_go_around: true,
+            last_jiagang: None,
+            pending_lizhi: None,
+            gongtuo: round.gongtuo,
+            error: check_seat(round.dealer).err(),
+        }
+    }
+
+    /// アガリ以外の出来事を再現する。
+    fn apply(&mut self, event: &Event) -> Result<(), RescoreError> {
+        // 立直宣言牌でロンされなかったので、リーチ棒が供託される。
+        if self.pending_lizhi.take().is_some() {
+            self.gongtuo += 1;
+        }
+
+        if let Some(seat) = event.seat() {
+            check_seat(seat)?;
+        }
+
+        match event {
+            Event::Mopai { seat, tile } => {
+                self.players[*seat].hand.push(*tile);
+                self.num_mopai += 1;
+                self.is_lingshang = self.next_is_lingshang;
+                self.next_is_lingshang = false;
+                self.last_jiagang = None;
+            }
+
+            Event::Dapai {
+                seat,
+                tile,
+                is_lizhi,
+            } => {
+                let is_first = self.is_first_go_around;
+                let p = &mut self.players[*seat];
+                remove_tile(&mut p.hand, *tile).ok_or(RescoreError::TileNotFound(*seat, *tile))?;
+                p.ippatsu = false;
+
+                if *is_lizhi {
+                    p.lizhi = if is_first && p.discards.is_empty() {
+                        Lizhi::DoubleLizhi
+                    } else {
+                        Lizhi::Lizhi
+                    };
+                    p.ippatsu = true;
+                    self.pending_lizhi = Some(*seat);
+                }
+
+                p.discards.push(*tile);
+                self.is_lingshang = false;
+
+                // 親から数えて 4 回打牌されたら最初の一巡は終わる。
+                if self.players.iter().all(|p| !p.discards.is_empty()) {
+                    self.is_first_go_around = false;
+                }
+            }
+
+            Event::Chi {
+                seat,
+                tiles,
+                called,
+            } => {
+                let set = self.take_tiles(*seat, tiles, Some(*called))?;
+                self.players[*seat].chis.push(set);
+                self.interrupt();
+            }
+
+            Event::Peng {
+                seat,
+                tiles,
+                called,
+            } => {
+                let set = self.take_tiles(*seat, tiles, Some(*called))?;
+                self.players[*seat].pengs.push(set);
+                self.interrupt();
+            }
+
+            Event::Minggang {
+                seat,
+                tiles,
+                called,
+            } => {
+                let set = self.take_tiles(*seat, tiles, Some(*called))?;
+                self.players[*seat].minggangs.push(set);
+                self.interrupt();
+                self.next_is_lingshang = true;
+            }
+
+            Event::Angang { seat, tiles } => {
+                let set = self.take_tiles(*seat, tiles, None)?;
+                self.players[*seat].angangs.push(set);
+                self.interrupt();
+                self.next_is_lingshang = true;
+            }
+
+            Event::Jiagang { seat, tile } => {
+                let p = &mut self.players[*seat];
+                remove_tile(&mut p.hand, *tile).ok_or(RescoreError::TileNotFound(*seat, *tile))?;
+
+                let pos = (p.pengs.iter())
+                    .position(|peng| peng.first() == *tile)
+                    .ok_or(RescoreError::PengNotFound(*seat, *tile))?;
+                let mut gang = p.pengs.remove(pos).into_inner();
+                gang.push(*tile);
+                p.minggangs.push(Tiles::new(gang));
+
+                self.interrupt();
+                self.next_is_lingshang = true;
+                self.last_jiagang = Some(*tile);
+            }
+
+            Event::Dora(indicator) => self.dora_indicators.push(*indicator),
+
+            Event::Hule(_) | Event::Liuju => {}
+        }
+
+        Ok(())
+    }
+
+    /// 手牌から鳴きや暗槓に使う牌を取り出して、面子を作る。
+    fn take_tiles(
+        &mut self,
+        seat: usize,
+        tiles: &[Tile],
+        called: Option<Tile>,
+    ) -> Result<Tiles, RescoreError> {
+        let hand = &mut self.players[seat].hand;
+        let mut set = Vec::with_capacity(4);
+
+        for &tile in tiles {
+            set.push(remove_tile(hand, tile).ok_or(RescoreError::TileNotFound(seat, tile))?);
+        }
+        set.extend(called);
+
+        Ok(Tiles::new(set))
+    }
+
+    /// 鳴きや槓によって一発と最初の一巡が消える。
+    fn interrupt(&mut self) {
+        for p in self.players.iter_mut() {
+            p.ippatsu = false;
+        }
+        self.is_first_go_around = false;
+    }
+
+    /// 記録されたアガリの牌集合の集合を作って判定する。
+    fn hule(&self, record: &HuleRecord) -> Result<Judge, RescoreError> {
+        if let Some(e) = &self.error {
+            return Err(match e {
+                RescoreError::TileNotFound(seat, tile) => RescoreError::TileNotFound(*seat, *tile),
+                RescoreError::PengNotFound(seat, tile) => RescoreError::PengNotFound(*seat, *tile),
+                RescoreError::InvalidSeat(seat) => RescoreError::InvalidSeat(*seat),
+                _ => unreachable!("replaying events only fails on missing tiles or seats."),
+            });
+        }
+
+        let seat = record.seat;
+        check_seat(seat)?;
+        record.from.map_or(Ok(()), check_seat)?;
+        let is_zimo = record.is_zimo();
+        let p = &self.players[seat];
+
+        let mut hand = p.hand.clone();
+        if is_zimo {
+            remove_tile(&mut hand, record.tile)
+                .ok_or(RescoreError::TileNotFound(seat, record.tile))?;
+        }
+
+        let player = seat_direction(seat, self.round.dealer);
+        let situation = HuleProgress {
+            is_zimo,
+            is_parent: player == Direction::East,
+            is_lingshang: self.is_lingshang,
+            is_haidi: self.num_mopai >= NUM_LIVE_TILES,
+            is_chenggang: !is_zimo && self.last_jiagang == Some(record.tile),
+            is_first: self.is_first_go_around && p.discards.is_empty(),
+        }
+        .situation();
+        let lizhi = p.lizhi.with_ippatsu(p.ippatsu);
+
+        let doras = (self.dora_indicators.iter())
+            .map(|t| t.wrapping_next())
+            .collect();
+        let uradoras = if lizhi != Lizhi::None {
+            (record.uradora_indicators.iter())
+                .map(|t| t.wrapping_next())
+                .collect()
+        } else {
+            Tiles::new(Vec::new())
+        };
+
+        let tingpai = TingpaiTilesets {
+            context: Context {
+                lizhi,
+                situation,
+                place: self.round.place,
+                player,
+                player_name: String::new(),
+                benchang: self.round.benchang,
+                gongtuo: self.gongtuo,
+                discards: p.discards.clone(),
+                passed: Vec::new(),
+                passed_after_lizhi: Vec::new(),
+            },
+            hand: Tiles::new(hand),
+            pengs: p.pengs.clone(),
+            chis: p.chis.clone(),
+            minggangs: p.minggangs.clone(),
+            angangs: p.angangs.clone(),
+            doras,
+            uradoras,
+        };
+
+        let tilesets = tingpai.with_last(record.tile, is_zimo)?;
+        judge(&tilesets, self.ruleset).ok_or(RescoreError::NoYaku)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tiles(tiles: &str) -> Vec<Tile> {
+        tiles.parse::<Tiles>().unwrap().into_inner()
+    }
+
+    fn tile(tile: &str) -> Tile {
+        tile.parse().unwrap()
+    }
+
+    /// 東 1 局で親が 5p をツモ切りし、南家が単騎待ちでロンする。
+    fn paipu(record: HuleRecord) -> Paipu {
+        let round = RoundRecord {
+            place: Direction::East,
+            dealer: 0,
+            benchang: 0,
+            gongtuo: 0,
+            hands: [
+                tiles("11m9m9p9s東南西北白發中中"),
+                tiles("234678m234456s5p"),
+                tiles("5p5p789m123p789s白發"),
+                tiles("1379p179s東南西北白發"),
+            ],
+            dora_indicators: tiles("北"),
+            events: vec![
+                Event::Mopai {
+                    seat: 0,
+                    tile: tile("5p"),
+                },
+                Event::Dapai {
+                    seat: 0,
+                    tile: tile("5p"),
+                    is_lizhi: false,
+                },
+                Event::Hule(record),
+            ],
+        };
+
+        Paipu {
+            names: Vec::new(),
+            ruleset: Ruleset::default(),
+            rounds: vec![round],
+        }
+    }
+
+    fn record(fan: u32, value: u32) -> HuleRecord {
+        HuleRecord {
+            seat: 1,
+            from: Some(0),
+            tile: tile("5p"),
+            uradora_indicators: Vec::new(),
+            yakus: vec![("断幺九".to_string(), fan)],
+            fan,
+            fu: Some(40),
+            yiman: 0,
+            value: Some(value),
+        }
+    }
+
+    #[test]
+    fn consistent() {
+        let res = rescore(&paipu(record(1, 1300)));
+        assert_eq!(res.len(), 1);
+        assert!(res[0].is_consistent(), "{}", res[0]);
+        assert_eq!(res[0].player(), Direction::South);
+        assert_eq!(
+            res[0].to_string(),
+            "東1局0本場 南家 ロン: 記録 1翻40符 1300点 / 判定 1翻40符 1300点"
+        );
+        assert!(discrepancies(&paipu(record(1, 1300))).is_empty());
+    }
+
+    #[test]
+    fn inconsistent() {
+        let res = discrepancies(&paipu(record(2, 2600)));
+        assert_eq!(res.len(), 1);
+        assert_eq!(
+            res[0].display_en().to_string(),
+            "East 1 0 Honba South Ron: Recorded 2 Han 40 Minipoints 2600 / \
+             Judged 1 Han 40 Minipoints 1300"
+        );
+    }
+
+    #[test]
+    fn tile_not_found() {
+        let mut paipu = paipu(record(1, 1300));
+        paipu.rounds[0].hands[0] = tiles("11m9m9p9s東南西北白發中中");
+        paipu.rounds[0].events[0] = Event::Mopai {
+            seat: 0,
+            tile: tile("1s"),
+        };
+
+        let res = rescore(&paipu);
+        match &res[0].result {
+            Err(RescoreError::TileNotFound(0, t)) if *t == tile("5p") => {}
+            res => panic!("unexpected result: {:?}", res),
+        }
+        assert!(!res[0].is_consistent());
+    }
+
+    #[test]
+    fn invalid_seat() {
+        let invalid_seat = |paipu: &Paipu| match rescore(paipu)[0].result {
+            Err(RescoreError::InvalidSeat(seat)) => seat,
+            ref res => panic!("unexpected result: {:?}", res),
+        };
+
+        let mut hule = record(1, 1300);
+        hule.seat = 5;
+        assert_eq!(invalid_seat(&paipu(hule)), 5);
+
+        let mut dealer = paipu(record(1, 1300));
+        dealer.rounds[0].dealer = 9;
+        assert_eq!(invalid_seat(&dealer), 9);
+        assert!(!rescore(&dealer)[0].to_string().is_empty());
+
+        let mut mopai = paipu(record(1, 1300));
+        mopai.rounds[0].events[0] = Event::Mopai {
+            seat: 6,
+            tile: tile("5p"),
+        };
+        assert_eq!(invalid_seat(&mopai), 6);
+    }
+
+    #[test]
+    fn double_lizhi_ippatsu_zimo() {
+        let round = RoundRecord {
+            place: Direction::South,
+            dealer: 1,
+            benchang: 1,
+            gongtuo: 0,
+            hands: [
+                tiles("234678m234456s5p"),
+                tiles("11m9m9p9s東南西北白發中中"),
+                tiles("5p5p789m123p789s白發"),
+                tiles("1379p179s東南西北白發"),
+            ],
+            dora_indicators: tiles("北"),
+            events: vec![
+                Event::Mopai {
+                    seat: 1,
+                    tile: tile("1p"),
+                },
+                Event::Dapai {
+                    seat: 1,
+                    tile: tile("1p"),
+                    is_lizhi: false,
+                },
+                Event::Mopai {
+                    seat: 2,
+                    tile: tile("2p"),
+                },
+                Event::Dapai {
+                    seat: 2,
+                    tile: tile("2p"),
+                    is_lizhi: false,
+                },
+                Event::Mopai {
+                    seat: 3,
+                    tile: tile("3p"),
+                },
+                Event::Dapai {
+                    seat: 3,
+                    tile: tile("3p"),
+                    is_lizhi: false,
+                },
+                Event::Mopai {
+                    seat: 0,
+                    tile: tile("4p"),
+                },
+                Event::Dapai {
+                    seat: 0,
+                    tile: tile("4p"),
+                    is_lizhi: true,
+                },
+                Event::Mopai {
+                    seat: 1,
+                    tile: tile("1p"),
+                },
+                Event::Dapai {
+                    seat: 1,
+                    tile: tile("1p"),
+                    is_lizhi: false,
+                },
+                Event::Mopai {
+                    seat: 2,
+                    tile: tile("2p"),
+                },
+                Event::Dapai {
+                    seat: 2,
+                    tile: tile("2p"),
+                    is_lizhi: false,
+                },
+                Event::Mopai {
+                    seat: 3,
+                    tile: tile("3p"),
+                },
+                Event::Dapai {
+                    seat: 3,
+                    tile: tile("3p"),
+                    is_lizhi: false,
+                },
+                Event::Mopai {
+                    seat: 0,
+                    tile: tile("5p"),
+                },
+                Event::Hule(HuleRecord {
+                    seat: 0,
+                    from: None,
+                    tile: tile("5p"),
+                    uradora_indicators: tiles("中"),
+                    yakus: Vec::new(),
+                    fan: 5,
+                    fu: Some(30),
+                    yiman: 0,
+                    value: Some(8000),
+                }),
+            ],
+        };
+
+        let paipu = Paipu {
+            rounds: vec![round],
+            ..Paipu::default()
+        };
+
+        let res = rescore(&paipu);
+        let judge = res[0].result.as_ref().unwrap();
+        // 最初の一巡の打牌での立直なのでダブル立直になる。
+        assert_eq!(judge.tilesets().context.lizhi, Lizhi::DoubleLizhiIppatsu);
+        assert_eq!(judge.tilesets().context.gongtuo, 1);
+        assert_eq!(judge.tilesets().context.player, Direction::North);
+        assert!(res[0].is_consistent(), "{}", res[0]);
+    }
+}
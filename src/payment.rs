@@ -0,0 +1,261 @@
+//! 点数の支払いを定義する。
+
+use crate::context::Direction;
+use crate::form::Point;
+use crate::ruleset::Ruleset;
+use crate::utils::ceil_at;
+use std::fmt;
+
+/// 一本場あたりの点数。
+const BENCHANG_VALUE: u32 = 300;
+
+/// リーチ棒一本あたりの点数。
+const GONGTUO_VALUE: u32 = 1000;
+
+/// 誰がいくら支払うか。
+///
+/// ツモの場合は支払う人ごとに百点単位の切り上げを行うので、合計がロンのときの点数と一致するとは限ら
+/// ない。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Payment {
+    /// 支払い方と一人あたりの点数。本場の分も含む。
+    pub kind: PaymentKind,
+
+    /// 本場。
+    pub benchang: u32,
+
+    /// 供託されていたリーチ棒の本数。
+    pub gongtuo: u32,
+}
+
+/// 支払い方。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum PaymentKind {
+    /// ロン。放銃した人が一人で支払う。
+    Ronghe(u32),
+
+    /// 親のツモ。子がそれぞれ同じ額を支払う。
+    ParentZimo(u32),
+
+    /// 子のツモ。
+    ChildZimo {
+        /// 親が支払う額。
+        parent: u32,
+
+        /// 子がそれぞれ支払う額。
+        child: u32,
+    },
+}
+
+impl Payment {
+    /// 翻数・符数と親かどうか、ツモかどうかから支払いを計算する。
+    pub fn new(point: Point, is_parent: bool, is_zimo: bool, ruleset: &Ruleset) -> Payment {
+        let base = point.base_value(ruleset);
+
+        let kind = match (is_zimo, is_parent) {
+            (false, _) => PaymentKind::Ronghe(point.value(is_parent, ruleset)),
+            (true, true) => PaymentKind::ParentZimo(ceil_at(base * 2, 100)),
+            (true, false) => PaymentKind::ChildZimo {
+                parent: ceil_at(base * 2, 100),
+                child: ceil_at(base, 100),
+            },
+        };
+
+        Payment {
+            kind,
+            benchang: 0,
+            gongtuo: 0,
+        }
+    }
+
+    /// 本場を設定する。一本場につきロンなら 300 点、ツモなら一人 100 点ずつが加算される。
+    pub fn with_benchang(self, benchang: u32) -> Payment {
+        // 既に設定されていた本場の分は差し引いておく。
+        let old = self.benchang;
+        let fix = |value: u32, num_payers: u32| {
+            value - old * BENCHANG_VALUE / num_payers + benchang * BENCHANG_VALUE / num_payers
+        };
+
+        let kind = match self.kind {
+            PaymentKind::Ronghe(value) => PaymentKind::Ronghe(fix(value, 1)),
+            PaymentKind::ParentZimo(each) => PaymentKind::ParentZimo(fix(each, 3)),
+            PaymentKind::ChildZimo { parent, child } => PaymentKind::ChildZimo {
+                parent: fix(parent, 3),
+                child: fix(child, 3),
+            },
+        };
+
+        Payment {
+            kind,
+            benchang,
+            ..self
+        }
+    }
+
+    /// 供託されていたリーチ棒の本数を設定する。これはアガった人が全て受け取る。
+    pub fn with_gongtuo(self, gongtuo: u32) -> Payment {
+        Payment { gongtuo, ..self }
+    }
+
+    /// アガった人が受け取る合計の点数。本場と供託の分も含む。
+    pub fn total(self) -> u32 {
+        let paid = match self.kind {
+            PaymentKind::Ronghe(value) => value,
+            PaymentKind::ParentZimo(each) => each * 3,
+            PaymentKind::ChildZimo { parent, child } => parent + child * 2,
+        };
+
+        paid + self.gongtuo * GONGTUO_VALUE
+    }
+
+    /// `winner` がアガったときに `payer` が支払う点数。本場の分も含む。
+    ///
+    /// ロンの場合は `payer` を放銃した人とみなす。 `payer` が `winner` と同じなら 0 となる。
+    pub fn amount(self, winner: Direction, payer: Direction) -> u32 {
+        if winner == payer {
+            return 0;
+        }
+
+        match self.kind {
+            PaymentKind::Ronghe(value) => value,
+            PaymentKind::ParentZimo(each) => each,
+            PaymentKind::ChildZimo { parent, .. } if payer == Direction::East => parent,
+            PaymentKind::ChildZimo { child, .. } => child,
+        }
+    }
+
+    /// 本場・供託のいずれかがあるかどうか。
+    pub fn has_bonus(self) -> bool {
+        self.benchang > 0 || self.gongtuo > 0
+    }
+
+    pub fn display_en(self) -> PaymentDisplayEn {
+        PaymentDisplayEn(self)
+    }
+}
+
+impl fmt::Display for Payment {
+    fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
+        if self.benchang > 0 {
+            write!(b, "{}本場 ", self.benchang)?;
+        }
+
+        if self.gongtuo > 0 {
+            write!(b, "供託{}点 ", self.gongtuo * GONGTUO_VALUE)?;
+        }
+
+        match self.kind {
+            PaymentKind::Ronghe(value) => write!(b, "{}点", value)?,
+            PaymentKind::ParentZimo(each) => write!(b, "{}点オール", each)?,
+            PaymentKind::ChildZimo { parent, child } => write!(b, "{}/{}点", child, parent)?,
+        }
+
+        if self.has_bonus() {
+            write!(b, " 合計{}点", self.total())?;
+        }
+
+        Ok(())
+    }
+}
+
+pub struct PaymentDisplayEn(Payment);
+
+impl fmt::Display for PaymentDisplayEn {
+    fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
+        let PaymentDisplayEn(payment) = self;
+
+        if payment.benchang > 0 {
+            write!(b, "{} Honba ", payment.benchang)?;
+        }
+
+        if payment.gongtuo > 0 {
+            write!(b, "{} Deposit ", payment.gongtuo * GONGTUO_VALUE)?;
+        }
+
+        match payment.kind {
+            PaymentKind::Ronghe(value) => write!(b, "{} Points", value)?,
+            PaymentKind::ParentZimo(each) => write!(b, "{} Points All", each)?,
+            PaymentKind::ChildZimo { parent, child } => write!(b, "{}/{} Points", child, parent)?,
+        }
+
+        if payment.has_bonus() {
+            write!(b, " Total {} Points", payment.total())?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ronghe() {
+        let payment = Payment::new(Point::with_fu(3, 30), false, false, &Ruleset::default());
+        assert_eq!(payment.kind, PaymentKind::Ronghe(3900));
+        assert_eq!(payment.total(), 3900);
+        assert_eq!(payment.amount(Direction::South, Direction::West), 3900);
+        assert_eq!(payment.amount(Direction::South, Direction::South), 0);
+        assert_eq!(payment.to_string(), "3900点");
+    }
+
+    #[test]
+    fn child_zimo() {
+        let payment = Payment::new(Point::with_fu(3, 30), false, true, &Ruleset::default());
+        assert_eq!(
+            payment.kind,
+            PaymentKind::ChildZimo {
+                parent: 2000,
+                child: 1000
+            }
+        );
+        assert_eq!(payment.total(), 4000);
+        assert_eq!(payment.amount(Direction::South, Direction::East), 2000);
+        assert_eq!(payment.amount(Direction::South, Direction::North), 1000);
+        assert_eq!(payment.to_string(), "1000/2000点");
+        assert_eq!(payment.display_en().to_string(), "1000/2000 Points");
+    }
+
+    #[test]
+    fn parent_zimo() {
+        let payment = Payment::new(Point::with_fu(1, 30), true, true, &Ruleset::default());
+        assert_eq!(payment.kind, PaymentKind::ParentZimo(500));
+        assert_eq!(payment.total(), 1500);
+        assert_eq!(payment.to_string(), "500点オール");
+        assert_eq!(payment.display_en().to_string(), "500 Points All");
+
+        let payment = Payment::new(Point::new_yiman(), true, true, &Ruleset::default());
+        assert_eq!(payment.kind, PaymentKind::ParentZimo(16000));
+        assert_eq!(payment.total(), 48000);
+    }
+
+    #[test]
+    fn benchang_gongtuo() {
+        let payment = Payment::new(Point::with_fu(3, 30), false, false, &Ruleset::default())
+            .with_benchang(2)
+            .with_gongtuo(1);
+        assert_eq!(payment.kind, PaymentKind::Ronghe(4500));
+        assert_eq!(payment.total(), 5500);
+        assert_eq!(payment.to_string(), "2本場 供託1000点 4500点 合計5500点");
+        assert_eq!(
+            payment.display_en().to_string(),
+            "2 Honba 1000 Deposit 4500 Points Total 5500 Points"
+        );
+
+        let payment =
+            Payment::new(Point::with_fu(3, 30), false, true, &Ruleset::default()).with_benchang(1);
+        assert_eq!(
+            payment.kind,
+            PaymentKind::ChildZimo {
+                parent: 2100,
+                child: 1100
+            }
+        );
+        assert_eq!(payment.total(), 4300);
+        assert_eq!(payment.amount(Direction::West, Direction::East), 2100);
+        assert_eq!(payment.amount(Direction::West, Direction::South), 1100);
+    }
+}
@@ -0,0 +1,1221 @@
+//! 一局の進行を扱う。
+//!
+//! 配牌からツモ・打牌・鳴きを経てアガリか流局に至るまでを管理する。アガった場合は、そのまま
+//! `judge::judge` に渡せる `Tilesets` を作る。
+
+use crate::context::{Context, Direction, HuleProgress, Lizhi, Situation};
+use crate::form::Point;
+use crate::judge::judge;
+use crate::payment::Payment;
+use crate::ruleset::Ruleset;
+use crate::shanten::shanten;
+use crate::tile::Tile;
+use crate::tiles::{remove_tile, Tiles};
+use crate::tilesets::{Tilesets, TingpaiTilesets};
+use crate::tingpai::{is_tingpai, machi_tiles};
+use crate::wall::Wall;
+use crate::zhenting::zhenting;
+use log::debug;
+use std::fmt;
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, RoundError>;
+
+#[derive(Debug, Error)]
+pub enum RoundError {
+    /// 局は既に終わっている。
+    #[error("局は既に終わっています。")]
+    Finished,
+
+    /// 打牌などを待っているところに鳴きやロンをしようとした、またはその逆。
+    #[error("今はその種類の行動をとることはできません。")]
+    InvalidPhase,
+
+    /// その人はその行動をとれない。
+    #[error("{0}家は今 {1} をすることはできません。")]
+    IllegalAction(Direction, Action),
+}
+
+/// プレイヤーのとる行動。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// 打牌。
+    Dapai(Tile),
+
+    /// 立直して打牌する。
+    Lizhi(Tile),
+
+    /// 暗槓。
+    Angang(Tile),
+
+    /// 加槓。
+    Jiagang(Tile),
+
+    /// ツモ。
+    Zimo,
+
+    /// チー。手牌から出す 2 枚を指定する。
+    Chi(Tile, Tile),
+
+    /// ポン。
+    Peng,
+
+    /// 明槓 (大明槓) 。
+    Minggang,
+
+    /// ロン。
+    Ronghe,
+
+    /// 何もしない。
+    Pass,
+}
+
+impl Action {
+    /// 鳴き・ロンの優先順位。大きいほど優先される。
+    fn priority(self) -> u32 {
+        match self {
+            Action::Ronghe => 3,
+            Action::Peng | Action::Minggang => 2,
+            Action::Chi(_, _) => 1,
+            _ => 0,
+        }
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Action::Dapai(tile) => write!(b, "打{}", tile),
+            Action::Lizhi(tile) => write!(b, "立直打{}", tile),
+            Action::Angang(tile) => write!(b, "暗槓{}", tile),
+            Action::Jiagang(tile) => write!(b, "加槓{}", tile),
+            Action::Zimo => write!(b, "ツモ"),
+            Action::Chi(x, y) => write!(b, "チー{}{}", x, y),
+            Action::Peng => write!(b, "ポン"),
+            Action::Minggang => write!(b, "明槓"),
+            Action::Ronghe => write!(b, "ロン"),
+            Action::Pass => write!(b, "パス"),
+        }
+    }
+}
+
+/// 局の中での一人のプレイヤーの状態。
+#[derive(Debug, Clone, Default)]
+pub struct Player {
+    /// 手牌。ツモった牌も含む。
+    pub hand: Vec<Tile>,
+
+    /// ポン。
+    pub pengs: Vec<Tiles>,
+
+    /// チー。
+    pub chis: Vec<Tiles>,
+
+    /// 明槓。加槓も含む。
+    pub minggangs: Vec<Tiles>,
+
+    /// 暗槓。
+    pub angangs: Vec<Tiles>,
+
+    /// 捨て牌。鳴かれた牌も含む。
+    pub discards: Vec<Tile>,
+
+    /// 最後に自分が打牌してから見逃した牌。
+    pub passed: Vec<Tile>,
+
+    /// 立直してから見逃した牌。
+    pub passed_after_lizhi: Vec<Tile>,
+
+    /// 立直の状態。一発は `ippatsu` で管理する。
+    pub lizhi: Lizhi,
+
+    /// 一発が有効かどうか。
+    pub ippatsu: bool,
+
+    /// 捨て牌を鳴かれたかどうか。
+    pub discards_called: bool,
+
+    /// 鳴いた直後に、喰い替えとなるので捨てられない牌。打牌すると空になる。
+    pub kuikae: Vec<Tile>,
+}
+
+impl Player {
+    /// 副露をしたかどうか。
+    pub fn did_fulou(&self) -> bool {
+        !self.pengs.is_empty() || !self.chis.is_empty() || !self.minggangs.is_empty()
+    }
+
+    /// 副露や暗槓によって確定している面子の数。
+    pub fn num_mianzis(&self) -> usize {
+        self.pengs.len() + self.chis.len() + self.minggangs.len() + self.angangs.len()
+    }
+
+    /// 流し満貫の条件を満たしているかどうか。捨て牌が全て幺九牌で、一枚も鳴かれていなければ真。
+    pub fn is_liuju_manguan(&self) -> bool {
+        !self.discards.is_empty()
+            && !self.discards_called
+            && self.discards.iter().all(|tile| tile.is_yaojiu())
+    }
+
+    fn count(&self, tile: Tile) -> usize {
+        self.hand.iter().filter(|&&t| t == tile).count()
+    }
+
+    /// 一発を考慮した立直の状態。
+    fn lizhi_with_ippatsu(&self) -> Lizhi {
+        self.lizhi.with_ippatsu(self.ippatsu)
+    }
+}
+
+/// 局の進行状況。
+#[derive(Debug, Clone)]
+pub enum Phase {
+    /// `player` がツモった (または鳴いた) 後で、打牌などを待っている。
+    Dapai {
+        player: Direction,
+
+        /// ツモった牌。鳴いた後なら None 。
+        drawn: Option<Tile>,
+    },
+
+    /// `player` が捨てた (または加槓した) `tile` に対する鳴き・ロンを待っている。
+    Response {
+        player: Direction,
+        tile: Tile,
+
+        /// 立直宣言牌かどうか。
+        is_lizhi: bool,
+
+        /// 加槓した牌かどうか。このときは槍槓のロンしかできない。
+        is_jiagang: bool,
+    },
+
+    /// 局が終わった。
+    Finished(RoundResult),
+}
+
+/// 局の結果。
+#[derive(Debug, Clone)]
+pub enum RoundResult {
+    /// アガリ。ダブロンなどでは複数になる。
+    Hule(Vec<Hule>),
+
+    /// 荒牌流局。
+    Liuju {
+        /// 聴牌していた人。
+        tingpais: Vec<Direction>,
+
+        /// 流し満貫になった人。
+        liuju_manguans: Vec<LiujuManguan>,
+    },
+}
+
+/// 流し満貫。
+///
+/// 荒牌流局したときに、捨て牌が全て幺九牌で一枚も鳴かれていなければ満貫のツモアガリと同じ点数を受
+/// け取る。本場と供託は受け取らない。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LiujuManguan {
+    /// 流し満貫になった人。
+    pub player: Direction,
+
+    /// 支払い。
+    pub payment: Payment,
+}
+
+impl LiujuManguan {
+    pub fn new(player: Direction, ruleset: &Ruleset) -> LiujuManguan {
+        let is_parent = player == Direction::East;
+        LiujuManguan {
+            player,
+            payment: Payment::new(Point::new_manguan(), is_parent, true, ruleset),
+        }
+    }
+
+    pub fn display_en(&self) -> LiujuManguanDisplayEn<'_> {
+        LiujuManguanDisplayEn(self)
+    }
+}
+
+impl fmt::Display for LiujuManguan {
+    fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
+        write!(b, "{}家 流し満貫 {}", self.player, self.payment)
+    }
+}
+
+pub struct LiujuManguanDisplayEn<'a>(&'a LiujuManguan);
+
+impl fmt::Display for LiujuManguanDisplayEn<'_> {
+    fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
+        let LiujuManguanDisplayEn(liuju_manguan) = self;
+        write!(
+            b,
+            "{}-seat Nagashi Mangan {}",
+            liuju_manguan.player.display_en(),
+            liuju_manguan.payment.display_en()
+        )
+    }
+}
+
+/// 一人のアガリ。
+#[derive(Debug, Clone)]
+pub struct Hule {
+    /// アガった人。
+    pub player: Direction,
+
+    /// 放銃した人。ツモなら None 。
+    pub from: Option<Direction>,
+
+    /// 判定に使う牌集合の集合。
+    pub tilesets: Tilesets,
+}
+
+/// 一局。
+#[derive(Debug, Clone)]
+pub struct Round {
+    wall: Wall,
+    place: Direction,
+    benchang: u32,
+    gongtuo: u32,
+    ruleset: Ruleset,
+    players: [Player; 4],
+    phase: Phase,
+
+    /// 誰も鳴いていない最初の一巡の間かどうか。
+    is_first_go_around: bool,
+
+    /// 直前のツモが嶺上牌かどうか。
+    is_lingshang: bool,
+}
+
+impl Round {
+    /// 山から配牌を行い、親が最初のツモをした状態の局を作る。
+    pub fn new(
+        mut wall: Wall,
+        place: Direction,
+        benchang: u32,
+        gongtuo: u32,
+        ruleset: &Ruleset,
+    ) -> Round {
+        let mut players: [Player; 4] = Default::default();
+
+        // 4 枚ずつ 3 回配ってから、 1 枚ずつ配る。
+        for &num in &[4, 4, 4, 1] {
+            for player in players.iter_mut() {
+                for _ in 0..num {
+                    let tile = wall.draw().expect("wall must have enough tiles to deal.");
+                    player.hand.push(tile);
+                }
+            }
+        }
+
+        let mut round = Round {
+            wall,
+            place,
+            benchang,
+            gongtuo,
+            ruleset: *ruleset,
+            players,
+            phase: Phase::Finished(RoundResult::Liuju {
+                tingpais: Vec::new(),
+                liuju_manguans: Vec::new(),
+            }),
+            is_first_go_around: true,
+            is_lingshang: false,
+        };
+
+        round.draw(Direction::East);
+        round
+    }
+
+    /// 今の進行状況。
+    pub fn phase(&self) -> &Phase {
+        &self.phase
+    }
+
+    /// 山。
+    pub fn wall(&self) -> &Wall {
+        &self.wall
+    }
+
+    /// 場風。
+    pub fn place(&self) -> Direction {
+        self.place
+    }
+
+    /// 供託されているリーチ棒の本数。
+    pub fn gongtuo(&self) -> u32 {
+        self.gongtuo
+    }
+
+    /// 自風が `player` であるプレイヤー。
+    pub fn player(&self, player: Direction) -> &Player {
+        &self.players[player as usize]
+    }
+
+    /// `player` が今とれる行動を列挙する。鳴き・ロンを待っているときは `Action::Pass` も含む。
+    pub fn legal_actions(&self, player: Direction) -> Vec<Action> {
+        match self.phase {
+            Phase::Dapai { player: p, drawn } if p == player => self.legal_dapais(player, drawn),
+            Phase::Response {
+                player: p,
+                tile,
+                is_jiagang,
+                ..
+            } if p != player => self.legal_responses(player, p, tile, is_jiagang),
+            _ => Vec::new(),
+        }
+    }
+
+    /// 手番の人が行動する。打牌・立直・暗槓・加槓・ツモのいずれかでなければならない。
+    ///
+    /// 打牌した後は `Round::respond()` で他家の鳴き・ロンを受け付ける。
+    pub fn act(&mut self, player: Direction, action: Action) -> Result<()> {
+        let drawn = match self.phase {
+            Phase::Dapai { player: p, drawn } if p == player => drawn,
+            Phase::Dapai { .. } => return Err(RoundError::IllegalAction(player, action)),
+            Phase::Response { .. } => return Err(RoundError::InvalidPhase),
+            Phase::Finished(_) => return Err(RoundError::Finished),
+        };
+
+        if !self.legal_dapais(player, drawn).contains(&action) {
+            return Err(RoundError::IllegalAction(player, action));
+        }
+
+        debug!("{}家: {}", player, action);
+        match action {
+            Action::Dapai(tile) | Action::Lizhi(tile) => {
+                let is_lizhi = matches!(action, Action::Lizhi(_));
+                let is_first = self.is_first_go_around && self.player(player).discards.is_empty();
+                let p = &mut self.players[player as usize];
+                let tile = remove_tile(&mut p.hand, tile).expect("legal action.");
+
+                // 立直後の打牌で一発は消える。
+                p.ippatsu = is_lizhi;
+                if is_lizhi {
+                    p.lizhi = if is_first {
+                        Lizhi::DoubleLizhi
+                    } else {
+                        Lizhi::Lizhi
+                    };
+                }
+
+                p.discards.push(tile);
+                p.passed.clear();
+                p.kuikae.clear();
+                self.is_lingshang = false;
+                self.phase = Phase::Response {
+                    player,
+                    tile,
+                    is_lizhi,
+                    is_jiagang: false,
+                };
+            }
+            Action::Angang(tile) => {
+                let p = &mut self.players[player as usize];
+                let gang: Tiles = (0..4)
+                    .map(|_| remove_tile(&mut p.hand, tile).expect("legal action."))
+                    .collect();
+                p.angangs.push(gang);
+                self.clear_ippatsu();
+                self.is_first_go_around = false;
+                self.wall.reveal_dora_indicator();
+                self.draw_lingshang(player);
+            }
+            Action::Jiagang(tile) => {
+                let p = &mut self.players[player as usize];
+                let tile = remove_tile(&mut p.hand, tile).expect("legal action.");
+                let idx = (p.pengs.iter())
+                    .position(|peng| peng.first() == tile)
+                    .expect("legal action.");
+                let mut gang = p.pengs.remove(idx).into_inner();
+                gang.push(tile);
+                p.minggangs.push(Tiles::new(gang));
+                self.clear_ippatsu();
+                self.phase = Phase::Response {
+                    player,
+                    tile,
+                    is_lizhi: false,
+                    is_jiagang: true,
+                };
+            }
+            Action::Zimo => {
+                let last = drawn.expect("legal action.");
+                let tilesets = self
+                    .hule_tilesets(player, last, true, false)
+                    .expect("legal action.");
+                self.phase = Phase::Finished(RoundResult::Hule(vec![Hule {
+                    player,
+                    from: None,
+                    tilesets,
+                }]));
+            }
+            _ => unreachable!("legal_dapais() does not contain responses."),
+        }
+
+        Ok(())
+    }
+
+    /// 打牌 (または加槓) に対する他家の行動をまとめて受け付ける。
+    ///
+    /// 指定されなかった人はパスしたものとみなす。ロン・ポン (明槓) ・チーの順に優先し、ロンが複数あ
+    /// ればダブロンとなる。誰も鳴かなければ次の人がツモる。
+    pub fn respond(&mut self, responses: Vec<(Direction, Action)>) -> Result<()> {
+        let (discarder, tile, is_lizhi, is_jiagang) = match self.phase {
+            Phase::Response {
+                player,
+                tile,
+                is_lizhi,
+                is_jiagang,
+            } => (player, tile, is_lizhi, is_jiagang),
+            Phase::Dapai { .. } => return Err(RoundError::InvalidPhase),
+            Phase::Finished(_) => return Err(RoundError::Finished),
+        };
+
+        for &(player, action) in &responses {
+            if !self.legal_actions(player).contains(&action) {
+                return Err(RoundError::IllegalAction(player, action));
+            }
+        }
+
+        // ロン
+        let mut rongs = Vec::new();
+        let mut player = discarder.next();
+        while player != discarder {
+            if responses.contains(&(player, Action::Ronghe)) {
+                rongs.push(player);
+            }
+            player = player.next();
+        }
+
+        if !rongs.is_empty() {
+            let mut hules = Vec::new();
+            for (i, &player) in rongs.iter().enumerate() {
+                debug!("{}家: ロン", player);
+                let mut tilesets = self
+                    .hule_tilesets(player, tile, false, is_jiagang)
+                    .expect("legal action.");
+
+                // 本場と供託は放銃者から見て最初の人だけが受け取る。
+                if i > 0 {
+                    tilesets.context.benchang = 0;
+                    tilesets.context.gongtuo = 0;
+                }
+
+                hules.push(Hule {
+                    player,
+                    from: Some(discarder),
+                    tilesets,
+                });
+            }
+
+            self.phase = Phase::Finished(RoundResult::Hule(hules));
+            return Ok(());
+        }
+
+        // 見逃した牌を記録する。
+        for (idx, p) in self.players.iter_mut().enumerate() {
+            if idx == discarder as usize {
+                continue;
+            }
+
+            p.passed.push(tile);
+            if p.lizhi != Lizhi::None {
+                p.passed_after_lizhi.push(tile);
+            }
+        }
+
+        if is_jiagang {
+            self.wall.reveal_dora_indicator();
+            self.draw_lingshang(discarder);
+            return Ok(());
+        }
+
+        // 立直が成立する。
+        if is_lizhi {
+            self.gongtuo += 1;
+        }
+
+        let call = (responses.into_iter())
+            .filter(|&(_, action)| action.priority() > 0)
+            .max_by_key(|&(_, action)| action.priority());
+
+        match call {
+            Some((player, action)) => {
+                debug!("{}家: {}", player, action);
+                self.players[discarder as usize].discards_called = true;
+                self.call(player, action, tile);
+            }
+            None => {
+                if discarder == Direction::North {
+                    self.is_first_go_around = false;
+                }
+
+                let next = discarder.next();
+                if self.wall.remaining() == 0 {
+                    self.finish_liuju();
+                } else {
+                    self.draw(next);
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// 手番の人の打牌などを列挙する。
+    fn legal_dapais(&self, player: Direction, drawn: Option<Tile>) -> Vec<Action> {
+        let p = self.player(player);
+        let mut res = Vec::new();
+
+        if let Some(last) = drawn {
+            if self.hule_tilesets(player, last, true, false).is_some() {
+                res.push(Action::Zimo);
+            }
+        }
+
+        let can_gang = drawn.is_some()
+            && self.wall.num_gangs() < crate::wall::NUM_LINGSHANG
+            && self.wall.remaining() > 0;
+
+        if p.lizhi != Lizhi::None {
+            // 立直後はツモ切りしかできない。暗槓は待ちが変わらない場合のみできる。
+            let last = drawn.expect("lizhi player cannot call.");
+            res.push(Action::Dapai(last));
+
+            if can_gang && p.count(last) == 4 && self.keeps_machis_after_angang(player, last) {
+                res.push(Action::Angang(last));
+            }
+
+            return res;
+        }
+
+        for tile in distinct_tiles(&p.hand) {
+            if !p.kuikae.contains(&tile) {
+                res.push(Action::Dapai(tile));
+            }
+        }
+
+        if !p.did_fulou() && self.wall.remaining() >= 4 {
+            for tile in distinct_tiles(&p.hand) {
+                let mut hand = p.hand.clone();
+                remove_tile(&mut hand, tile);
+                if shanten(&Tiles::new(hand), p.num_mianzis()) == 0 {
+                    res.push(Action::Lizhi(tile));
+                }
+            }
+        }
+
+        if can_gang {
+            for tile in distinct_tiles(&p.hand) {
+                if p.count(tile) == 4 && !res.contains(&Action::Angang(tile)) {
+                    res.push(Action::Angang(tile));
+                }
+
+                if p.pengs.iter().any(|peng| peng.first() == tile) {
+                    res.push(Action::Jiagang(tile));
+                }
+            }
+        }
+
+        res
+    }
+
+    /// 他家の打牌に対してとれる行動を列挙する。
+    fn legal_responses(
+        &self,
+        player: Direction,
+        discarder: Direction,
+        tile: Tile,
+        is_jiagang: bool,
+    ) -> Vec<Action> {
+        let p = self.player(player);
+        let mut res = vec![Action::Pass];
+
+        if self
+            .hule_tilesets(player, tile, false, is_jiagang)
+            .is_some()
+        {
+            res.push(Action::Ronghe);
+        }
+
+        // 槍槓以外の鳴きはできない。また立直後や河底牌も鳴けない。
+        if is_jiagang || p.lizhi != Lizhi::None || self.wall.remaining() == 0 {
+            return res;
+        }
+
+        // 鳴いた後に喰い替えにならない打牌ができなければ鳴けない。
+        let can_dapai = |used: &[Tile], action: Action| {
+            let mut hand = p.hand.clone();
+            for &t in used {
+                remove_tile(&mut hand, t);
+            }
+            let kuikae = kuikae_tiles(action, tile);
+            hand.iter().any(|t| !kuikae.contains(t))
+        };
+
+        if p.count(tile) >= 2 && can_dapai(&[tile, tile], Action::Peng) {
+            res.push(Action::Peng);
+        }
+
+        if p.count(tile) >= 3 && self.wall.num_gangs() < crate::wall::NUM_LINGSHANG {
+            res.push(Action::Minggang);
+        }
+
+        // チーは上家の打牌に対してのみできる。
+        if discarder.next() == player {
+            let prev = tile.prev();
+            let next = tile.next();
+            let candidates = [
+                (prev.and_then(Tile::prev), prev),
+                (prev, next),
+                (next, next.and_then(Tile::next)),
+            ];
+
+            for &(x, y) in &candidates {
+                if let (Some(x), Some(y)) = (x, y) {
+                    if let (Some(x), Some(y)) = (find_tile(&p.hand, x), find_tile(&p.hand, y)) {
+                        if can_dapai(&[x, y], Action::Chi(x, y)) {
+                            res.push(Action::Chi(x, y));
+                        }
+                    }
+                }
+            }
+        }
+
+        res
+    }
+
+    /// 鳴きを行う。
+    fn call(&mut self, player: Direction, action: Action, tile: Tile) {
+        self.clear_ippatsu();
+        self.is_first_go_around = false;
+
+        let p = &mut self.players[player as usize];
+        let mut remove = |t: Tile| remove_tile(&mut p.hand, t).expect("legal action.");
+
+        match action {
+            Action::Chi(x, y) => {
+                let chi = Tiles::new(vec![remove(x), remove(y), tile]);
+                p.chis.push(chi);
+            }
+            Action::Peng => {
+                let peng = Tiles::new(vec![remove(tile), remove(tile), tile]);
+                p.pengs.push(peng);
+            }
+            Action::Minggang => {
+                let gang = Tiles::new(vec![remove(tile), remove(tile), remove(tile), tile]);
+                p.minggangs.push(gang);
+                self.wall.reveal_dora_indicator();
+                self.draw_lingshang(player);
+                return;
+            }
+            _ => unreachable!("only calls can reach here."),
+        }
+        p.kuikae = kuikae_tiles(action, tile);
+
+        self.phase = Phase::Dapai {
+            player,
+            drawn: None,
+        };
+    }
+
+    /// ツモ山からツモる。
+    fn draw(&mut self, player: Direction) {
+        let tile = self.wall.draw().expect("wall must not be empty.");
+        self.players[player as usize].hand.push(tile);
+        self.is_lingshang = false;
+        self.phase = Phase::Dapai {
+            player,
+            drawn: Some(tile),
+        };
+    }
+
+    /// 嶺上牌をツモる。
+    fn draw_lingshang(&mut self, player: Direction) {
+        let tile = self
+            .wall
+            .draw_lingshang()
+            .expect("lingshang must be available for a legal gang.");
+        self.players[player as usize].hand.push(tile);
+        self.is_lingshang = true;
+        self.phase = Phase::Dapai {
+            player,
+            drawn: Some(tile),
+        };
+    }
+
+    fn clear_ippatsu(&mut self) {
+        for p in self.players.iter_mut() {
+            p.ippatsu = false;
+        }
+    }
+
+    fn finish_liuju(&mut self) {
+        let players = [
+            Direction::East,
+            Direction::South,
+            Direction::West,
+            Direction::North,
+        ];
+        let tingpais = (players.iter().copied())
+            .filter(|&player| is_tingpai(&self.tingpai_tilesets(player, None)))
+            .collect();
+        let liuju_manguans = (players.iter().copied())
+            .filter(|&player| self.player(player).is_liuju_manguan())
+            .map(|player| LiujuManguan::new(player, &self.ruleset))
+            .collect();
+
+        debug!("流局しました。");
+        self.phase = Phase::Finished(RoundResult::Liuju {
+            tingpais,
+            liuju_manguans,
+        });
+    }
+
+    /// 暗槓しても待ちが変わらないかどうか。
+    fn keeps_machis_after_angang(&self, player: Direction, tile: Tile) -> bool {
+        let before = machi_tiles(&self.tingpai_tilesets(player, Some(tile)));
+
+        let mut after = self.tingpai_tilesets(player, Some(tile));
+        let mut hand = after.hand.clone().into_inner();
+        let gang: Tiles = (0..3)
+            .filter_map(|_| remove_tile(&mut hand, tile))
+            .collect();
+        after.hand = Tiles::new(hand);
+        after.angangs.push(gang);
+
+        before == machi_tiles(&after)
+    }
+
+    /// `player` の手牌から `last` を除いた (13 枚の) 牌集合の集合を作る。
+    fn tingpai_tilesets(&self, player: Direction, last: Option<Tile>) -> TingpaiTilesets {
+        let p = self.player(player);
+        let mut hand = p.hand.clone();
+        if let Some(last) = last {
+            remove_tile(&mut hand, last);
+        }
+
+        let doras = (self.wall.dora_indicators().iter())
+            .map(|t| t.wrapping_next())
+            .collect();
+        let uradoras = if p.lizhi != Lizhi::None {
+            (self.wall.uradora_indicators().iter())
+                .map(|t| t.wrapping_next())
+                .collect()
+        } else {
+            Tiles::new(Vec::new())
+        };
+
+        TingpaiTilesets {
+            context: self.context(player),
+            hand: Tiles::new(hand),
+            pengs: p.pengs.clone(),
+            chis: p.chis.clone(),
+            minggangs: p.minggangs.clone(),
+            angangs: p.angangs.clone(),
+            doras,
+            uradoras,
+        }
+    }
+
+    /// `player` から見た状況。偶然役は含まない。
+    fn context(&self, player: Direction) -> Context {
+        let p = self.player(player);
+        Context {
+            lizhi: p.lizhi_with_ippatsu(),
+            situation: Situation::default(),
+            place: self.place,
+            player,
+            player_name: String::new(),
+            benchang: self.benchang,
+            gongtuo: self.gongtuo,
+            discards: p.discards.clone(),
+            passed: p.passed.clone(),
+            passed_after_lizhi: p.passed_after_lizhi.clone(),
+        }
+    }
+
+    /// アガリの牌集合の集合を作る。役がないかフリテンのロンなど、アガれない場合は None となる。
+    fn hule_tilesets(
+        &self,
+        player: Direction,
+        last: Tile,
+        is_zimo: bool,
+        is_chenggang: bool,
+    ) -> Option<Tilesets> {
+        let mut tingpai = self.tingpai_tilesets(player, if is_zimo { Some(last) } else { None });
+
+        if !is_zimo && zhenting(&tingpai).is_some() {
+            return None;
+        }
+
+        tingpai.context.situation = HuleProgress {
+            is_zimo,
+            is_parent: player == Direction::East,
+            is_lingshang: self.is_lingshang,
+            is_haidi: self.wall.remaining() == 0,
+            is_chenggang,
+            is_first: self.is_first_go_around && self.player(player).discards.is_empty(),
+        }
+        .situation();
+
+        let tilesets = tingpai.with_last(last, is_zimo).ok()?;
+        judge(&tilesets, &self.ruleset).map(|_| tilesets)
+    }
+}
+
+/// チー・ポンの直後に喰い替えとなる牌。鳴いた牌と、チーならその両面の反対側の牌 (筋) になる。
+fn kuikae_tiles(action: Action, tile: Tile) -> Vec<Tile> {
+    let mut res = vec![tile];
+    if let Action::Chi(x, y) = action {
+        let mut chi = [x, y, tile];
+        chi.sort_by_key(|t| t.index());
+        let suji = if chi[0] == tile {
+            chi[2].next()
+        } else if chi[2] == tile {
+            chi[0].prev()
+        } else {
+            None
+        };
+        res.extend(suji);
+    }
+
+    res
+}
+
+/// 同じ牌を重複させずに列挙する。赤ドラとそうでない牌は区別する。
+fn distinct_tiles(tiles: &[Tile]) -> Vec<Tile> {
+    let mut res: Vec<Tile> = Vec::new();
+    for &tile in tiles {
+        if !res
+            .iter()
+            .any(|&t| t == tile && t.is_red() == tile.is_red())
+        {
+            res.push(tile);
+        }
+    }
+
+    res
+}
+
+/// 同じ牌を探す。赤ドラでない牌があればそちらを優先する。
+fn find_tile(tiles: &[Tile], tile: Tile) -> Option<Tile> {
+    (tiles.iter().copied())
+        .filter(|&t| t == tile)
+        .min_by_key(|t| t.is_red())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wall::NUM_TILES;
+
+    fn tiles(tiles: &str) -> Vec<Tile> {
+        tiles.parse::<Tiles>().unwrap().into_inner()
+    }
+
+    /// 配牌とツモ順を指定して山を作る。
+    ///
+    /// `hands` は東南西北の順の配牌、 `draws` は親の最初のツモからの空白区切りのツモ順で、残りは適
+    /// 当に埋める。
+    fn wall(hands: [&str; 4], draws: &str) -> Wall {
+        Wall::from_tiles(wall_tiles(hands, draws)).unwrap()
+    }
+
+    /// `wall` と同じ順に牌を並べる。嶺上牌などを差し替えたいときに使う。
+    fn wall_tiles(hands: [&str; 4], draws: &str) -> Vec<Tile> {
+        let hands: Vec<Vec<Tile>> = hands.iter().map(|h| tiles(h)).collect();
+        let draws: Vec<Tile> = draws
+            .split_whitespace()
+            .map(|t| t.parse().unwrap())
+            .collect();
+
+        let mut rest = Wall::all_tiles(&Ruleset::default());
+        for tile in hands.iter().flatten().chain(draws.iter()) {
+            remove_tile(&mut rest, *tile).unwrap();
+        }
+
+        let mut res = Vec::with_capacity(NUM_TILES);
+        for &(start, num) in &[(0, 4), (4, 4), (8, 4), (12, 1)] {
+            for hand in &hands {
+                res.extend_from_slice(&hand[start..start + num]);
+            }
+        }
+
+        res.extend(draws);
+        res.extend(rest);
+        res
+    }
+
+    fn round(hands: [&str; 4], draws: &str) -> Round {
+        Round::new(
+            wall(hands, draws),
+            Direction::East,
+            0,
+            0,
+            &Ruleset::default(),
+        )
+    }
+
+    const HANDS: [&str; 4] = [
+        "1m1m9m9p9s東南西北白發中中",
+        "4p6p1s1s2s3s東南西北白發中",
+        "5p5p7m8m9m1p2p3p7s8s9s白發",
+        "2m3m4m6m7m8m2s3s4s4s5s6s5p",
+    ];
+
+    #[test]
+    fn deal() {
+        let round = round(HANDS, "5p");
+        assert_eq!(round.player(Direction::East).hand.len(), 14);
+        assert_eq!(round.player(Direction::South).hand.len(), 13);
+        assert_eq!(round.wall().remaining(), NUM_TILES - 14 - 53);
+        assert!(matches!(
+            round.phase(),
+            Phase::Dapai {
+                player: Direction::East,
+                drawn: Some(_),
+            }
+        ));
+    }
+
+    #[test]
+    fn call_priority() {
+        let mut round = round(HANDS, "5p");
+        let tile = "5p".parse().unwrap();
+        round.act(Direction::East, Action::Dapai(tile)).unwrap();
+
+        let chi = Action::Chi("4p".parse().unwrap(), "6p".parse().unwrap());
+        assert!(round.legal_actions(Direction::South).contains(&chi));
+        assert!(round.legal_actions(Direction::West).contains(&Action::Peng));
+        assert!(round
+            .legal_actions(Direction::North)
+            .contains(&Action::Ronghe));
+
+        // ロンはポンやチーより優先される。
+        let mut ronghe = round.clone();
+        ronghe
+            .respond(vec![
+                (Direction::South, chi),
+                (Direction::West, Action::Peng),
+                (Direction::North, Action::Ronghe),
+            ])
+            .unwrap();
+
+        match ronghe.phase() {
+            Phase::Finished(RoundResult::Hule(hules)) => {
+                assert_eq!(hules.len(), 1);
+                assert_eq!(hules[0].player, Direction::North);
+                assert_eq!(hules[0].from, Some(Direction::East));
+                assert!(judge(&hules[0].tilesets, &Ruleset::default()).is_some());
+            }
+            phase => panic!("unexpected phase: {:?}", phase),
+        }
+
+        // ポンはチーより優先される。
+        round
+            .respond(vec![
+                (Direction::South, chi),
+                (Direction::West, Action::Peng),
+            ])
+            .unwrap();
+        assert!(matches!(
+            round.phase(),
+            Phase::Dapai {
+                player: Direction::West,
+                drawn: None,
+            }
+        ));
+        assert_eq!(round.player(Direction::West).pengs.len(), 1);
+
+        // 見逃した北家は同巡内フリテンになる。
+        assert_eq!(round.player(Direction::North).passed, vec![tile]);
+    }
+
+    #[test]
+    fn kuikae() {
+        let hands = [HANDS[0], "2p3p4p6p7p8p1s1s東南西北白", HANDS[2], HANDS[3]];
+        let tile = |t: &str| t.parse::<Tile>().unwrap();
+        let mut round = round(hands, "5p");
+        round
+            .act(Direction::East, Action::Dapai(tile("5p")))
+            .unwrap();
+
+        // 5p を 3p4p でチーすると、 5p と筋の 2p は捨てられない。
+        let mut chi = round.clone();
+        let action = Action::Chi(tile("3p"), tile("4p"));
+        chi.respond(vec![(Direction::South, action)]).unwrap();
+        let dapais = chi.legal_actions(Direction::South);
+        assert!(!dapais.contains(&Action::Dapai(tile("2p"))));
+        assert!(dapais.contains(&Action::Dapai(tile("8p"))));
+        assert!(chi
+            .act(Direction::South, Action::Dapai(tile("2p")))
+            .is_err());
+        chi.act(Direction::South, Action::Dapai(tile("8p")))
+            .unwrap();
+        assert!(chi.player(Direction::South).kuikae.is_empty());
+
+        // 6p7p でチーすると 8p が筋になる。
+        let action = Action::Chi(tile("6p"), tile("7p"));
+        round.respond(vec![(Direction::South, action)]).unwrap();
+        let dapais = round.legal_actions(Direction::South);
+        assert!(!dapais.contains(&Action::Dapai(tile("8p"))));
+        assert!(dapais.contains(&Action::Dapai(tile("2p"))));
+
+        // 鳴いた後に喰い替えの牌しか残らないなら鳴けない。
+        let mut round = self::round(HANDS, "5p");
+        round.players[Direction::South as usize].hand = tiles("3p4p5p5p");
+        round.phase = Phase::Response {
+            player: Direction::East,
+            tile: tile("2p"),
+            is_lizhi: false,
+            is_jiagang: false,
+        };
+        let action = Action::Chi(tile("3p"), tile("4p"));
+        assert!(!round.legal_actions(Direction::South).contains(&action));
+    }
+
+    #[test]
+    fn illegal_action() {
+        let mut round = round(HANDS, "5p");
+        assert!(round
+            .act(Direction::South, Action::Dapai("4p".parse().unwrap()))
+            .is_err());
+        assert!(round.act(Direction::East, Action::Zimo).is_err());
+        assert!(round.respond(Vec::new()).is_err());
+    }
+
+    #[test]
+    fn lizhi_ippatsu_zimo() {
+        let mut round = round(
+            [
+                "2m3m4m6m7m8m2s3s4s4s5s6s5p",
+                "1p3p5p7p9p1s3s5s7s9s東東南",
+                "1p3p5p7p9p1s3s5s7s9s東南南",
+                "1m9m1p9p1s9s白白發發中中北",
+            ],
+            "1m 西 西 西 5p",
+        );
+
+        round
+            .act(Direction::East, Action::Lizhi("1m".parse().unwrap()))
+            .unwrap();
+        round.respond(Vec::new()).unwrap();
+        assert_eq!(round.gongtuo(), 1);
+
+        for &player in &[Direction::South, Direction::West, Direction::North] {
+            let tile = "西".parse().unwrap();
+            round.act(player, Action::Dapai(tile)).unwrap();
+            round.respond(Vec::new()).unwrap();
+        }
+
+        assert_eq!(
+            round.legal_actions(Direction::East),
+            vec![Action::Zimo, Action::Dapai("5p".parse().unwrap())]
+        );
+        round.act(Direction::East, Action::Zimo).unwrap();
+
+        match round.phase() {
+            Phase::Finished(RoundResult::Hule(hules)) => {
+                let context = &hules[0].tilesets.context;
+                assert_eq!(context.lizhi, Lizhi::DoubleLizhiIppatsu);
+                assert_eq!(context.gongtuo, 1);
+                assert!(context.situation.is_empty());
+            }
+            phase => panic!("unexpected phase: {:?}", phase),
+        }
+    }
+
+    #[test]
+    fn angang_first_go_around() {
+        let hands = [
+            "1m1m1m2p3p4p5s6s7s東東白白",
+            "1p3p5p7p9p1s3s5s7s9s南南南",
+            "1p3p5p7p9p1s3s5s7s9s西西西",
+            "2m3m4m6m7m8m2s3s4s8s8s9s9s",
+        ];
+        let mut tiles = wall_tiles(hands, "1m");
+
+        // 最初の嶺上牌を白にする。
+        let pos = tiles.iter().rposition(|t| t.to_string() == "白").unwrap();
+        tiles.swap(pos, NUM_TILES - 14);
+        let wall = Wall::from_tiles(tiles).unwrap();
+        let mut round = Round::new(wall, Direction::East, 0, 0, &Ruleset::default());
+
+        // 第一ツモでの暗槓は天和を消す。
+        round
+            .act(Direction::East, Action::Angang("1m".parse().unwrap()))
+            .unwrap();
+        assert!(!round.is_first_go_around);
+        assert!(round.legal_actions(Direction::East).contains(&Action::Zimo));
+        round.act(Direction::East, Action::Zimo).unwrap();
+
+        match round.phase() {
+            Phase::Finished(RoundResult::Hule(hules)) => {
+                let situation = &hules[0].tilesets.context.situation;
+                assert!(situation.lingshangkaihua);
+                assert!(!situation.tianhe);
+                assert!(judge(&hules[0].tilesets, &Ruleset::default()).is_some());
+            }
+            phase => panic!("unexpected phase: {:?}", phase),
+        }
+    }
+
+    #[test]
+    fn liuju() {
+        use rand::rngs::StdRng;
+        use rand::SeedableRng;
+
+        let wall = Wall::new(&mut StdRng::seed_from_u64(42), &Ruleset::default());
+        let mut round = Round::new(wall, Direction::East, 0, 0, &Ruleset::default());
+
+        // 全員がツモ切りを続ける。
+        loop {
+            match *round.phase() {
+                Phase::Dapai {
+                    player,
+                    drawn: Some(tile),
+                } => round.act(player, Action::Dapai(tile)).unwrap(),
+                Phase::Response { .. } => round.respond(Vec::new()).unwrap(),
+                _ => break,
+            }
+        }
+
+        assert!(matches!(
+            round.phase(),
+            Phase::Finished(RoundResult::Liuju { .. })
+        ));
+        assert_eq!(round.wall().remaining(), 0);
+        assert_eq!(round.player(Direction::North).discards.len(), 17);
+    }
+
+    #[test]
+    fn liuju_manguan() {
+        let mut player = Player {
+            discards: tiles("1m9p東白"),
+            ..Player::default()
+        };
+        assert!(player.is_liuju_manguan());
+
+        player.discards_called = true;
+        assert!(!player.is_liuju_manguan());
+
+        player.discards_called = false;
+        player.discards.push("5s".parse().unwrap());
+        assert!(!player.is_liuju_manguan());
+        assert!(!Player::default().is_liuju_manguan());
+
+        let child = LiujuManguan::new(Direction::South, &Ruleset::default());
+        assert_eq!(child.payment.total(), 8000);
+        assert_eq!(child.to_string(), "南家 流し満貫 2000/4000点");
+        assert_eq!(
+            child.display_en().to_string(),
+            "South-seat Nagashi Mangan 2000/4000 Points"
+        );
+
+        let parent = LiujuManguan::new(Direction::East, &Ruleset::default());
+        assert_eq!(parent.payment.total(), 12000);
+        assert_eq!(parent.to_string(), "東家 流し満貫 4000点オール");
+    }
+}
@@ -0,0 +1,61 @@
+//! ルールの違いを定義する。
+
+/// 判定に使うルール。
+///
+/// `Ruleset::default()` は一般的なルール (切り上げ満貫・喰いタンあり・数え役満あり・ダブル役満なし・
+/// 赤ドラあり・人和なし) になっている。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(default))]
+pub struct Ruleset {
+    /// 切り上げ満貫。 4翻30符と3翻60符を満貫として扱う。
+    pub kiriage_manguan: bool,
+
+    /// 喰いタン。副露していても断么九を認める。
+    pub kuitan: bool,
+
+    /// 数え役満。 13翻以上を役満として扱う。なしの場合は三倍満どまりとなる。
+    pub kazoe_yiman: bool,
+
+    /// ダブル役満。四暗刻単騎・国士無双13面待ち・純正九蓮宝燈・大四喜をダブル役満として扱う。
+    pub double_yiman: bool,
+
+    /// 山に入れる赤ドラ (赤い 5) の枚数。一枚につき一翻のドラとして数える。 0 なら赤ドラなし。
+    ///
+    /// 筒子・萬子・索子の順に一枚ずつ割り振るので、 3 なら各色 1 枚、 4 なら筒子だけ 2 枚になる。
+    pub akadora: u32,
+
+    /// 人和の扱い。
+    pub renhe: Renhe,
+}
+
+/// 人和をどう扱うか。ルールによって満貫・倍満・役満のいずれかとするか、役として認めない。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum Renhe {
+    /// 人和なし。
+    #[default]
+    None,
+
+    /// 満貫。
+    Manguan,
+
+    /// 倍満。
+    Beiman,
+
+    /// 役満。
+    Yiman,
+}
+
+impl Default for Ruleset {
+    fn default() -> Ruleset {
+        Ruleset {
+            kiriage_manguan: true,
+            kuitan: true,
+            kazoe_yiman: true,
+            double_yiman: false,
+            akadora: 3,
+            renhe: Renhe::None,
+        }
+    }
+}
@@ -0,0 +1,300 @@
+//! `serde` による直列化を実装する。
+//!
+//! 牌・牌のかたまり・牌集合は `FromStr` で読める短い文字列 (例: "5M", "東", "ポン1m1m1m") として表す。
+//! 判定結果は役・翻数・符数・支払い・手牌の分解を含む構造として表す。判定結果を読み込むときは、含ま
+//! れている牌集合の集合とルールからもう一度判定をやり直す。牌集合の集合を読み込むときは、文字列から
+//! 読むときと同じように検証する。
+
+use crate::agaritilesets::{AgariTilesets, MachiKind};
+use crate::context::Context;
+use crate::explanation::Explanation;
+use crate::form::{Form, Point};
+use crate::fu::FuBreakdown;
+use crate::judge::{judge_all, Judge};
+use crate::payment::Payment;
+use crate::ruleset::Ruleset;
+use crate::tile::Tile;
+use crate::tiles::Tiles;
+use crate::tileset::{Tag, Tileset};
+use crate::tilesets::Tilesets;
+use serde::de::{self, Deserialize, Deserializer};
+use serde::ser::{Serialize, Serializer};
+use std::fmt::Display;
+use std::str::FromStr;
+
+fn serialize_display<T: Display, S: Serializer>(
+    value: &T,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
+
+fn deserialize_from_str<'de, T, D>(deserializer: D) -> Result<T, D::Error>
+where
+    T: FromStr,
+    T::Err: Display,
+    D: Deserializer<'de>,
+{
+    let s = String::deserialize(deserializer)?;
+    s.parse().map_err(de::Error::custom)
+}
+
+impl Serialize for Tile {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_display(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Tile {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Tile, D::Error> {
+        deserialize_from_str(deserializer)
+    }
+}
+
+impl Serialize for Tiles {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_display(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Tiles {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Tiles, D::Error> {
+        deserialize_from_str(deserializer)
+    }
+}
+
+impl Serialize for Tileset {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serialize_display(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Tileset {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Tileset, D::Error> {
+        deserialize_from_str(deserializer)
+    }
+}
+
+/// 牌集合の集合。 `Tilesets` の `Serialize` と同じ形をしている。
+#[derive(serde::Deserialize)]
+struct TilesetsRepr {
+    context: Context,
+    is_zimo: bool,
+    last: Tile,
+    hand: Tiles,
+    pengs: Vec<Tiles>,
+    chis: Vec<Tiles>,
+    minggangs: Vec<Tiles>,
+    angangs: Vec<Tiles>,
+    doras: Tiles,
+    uradoras: Tiles,
+}
+
+impl<'de> Deserialize<'de> for Tilesets {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Tilesets, D::Error> {
+        let repr = TilesetsRepr::deserialize(deserializer)?;
+        let last = if repr.is_zimo { Tag::Zimo } else { Tag::Ronghe };
+        let uradora_indicators = (repr.uradoras.iter())
+            .map(|tile| tile.wrapping_prev())
+            .collect();
+
+        // 牌の数や鳴きの形などを確かめるために、牌集合の列から作り直す。
+        let tilesets = vec![
+            (Tag::Hand, repr.hand),
+            (last, Tiles::new(vec![repr.last])),
+            (Tag::Dora, repr.doras),
+            (Tag::UradoraIndicator, uradora_indicators),
+        ]
+        .into_iter()
+        .chain(repr.pengs.into_iter().map(|tiles| (Tag::Peng, tiles)))
+        .chain(repr.chis.into_iter().map(|tiles| (Tag::Chi, tiles)))
+        .chain(
+            repr.minggangs
+                .into_iter()
+                .map(|tiles| (Tag::Minggang, tiles)),
+        )
+        .chain(repr.angangs.into_iter().map(|tiles| (Tag::Angang, tiles)))
+        .map(|(tag, tiles)| Tileset::new(tag, tiles))
+        .collect::<Result<_, _>>()
+        .map_err(de::Error::custom)?;
+
+        Tilesets::new(repr.context, tilesets).map_err(de::Error::custom)
+    }
+}
+
+/// 判定結果の中の一つの役。
+#[derive(serde::Serialize, serde::Deserialize)]
+struct FormRepr {
+    form: Form,
+    fan: u32,
+    yiman: u32,
+}
+
+/// 手牌の分解。
+#[derive(serde::Serialize, serde::Deserialize)]
+struct DecompositionRepr {
+    quetou: Tiles,
+    anshuns: Vec<Tiles>,
+    ankes: Vec<Tiles>,
+    mingshuns: Vec<Tiles>,
+    mingkes: Vec<Tiles>,
+    machi: MachiKind,
+}
+
+impl DecompositionRepr {
+    fn new(agari: &AgariTilesets) -> DecompositionRepr {
+        DecompositionRepr {
+            quetou: agari.quetou().clone(),
+            anshuns: agari.anshuns().cloned().collect(),
+            ankes: agari.ankes().cloned().collect(),
+            mingshuns: agari.mingshuns().cloned().collect(),
+            mingkes: agari.mingkes().cloned().collect(),
+            machi: agari.machi(),
+        }
+    }
+}
+
+/// 判定結果。
+#[derive(serde::Serialize, serde::Deserialize)]
+struct JudgeRepr {
+    forms: Vec<FormRepr>,
+    fan: u32,
+    fu: u32,
+    yiman: u32,
+
+    /// 点数 (本場・供託を含まない、ロンのときの点数) 。
+    value: u32,
+    payment: Payment,
+
+    /// 手牌の分解。七対子や国士無双などでは None となる。
+    decomposition: Option<DecompositionRepr>,
+
+    /// 成立した役と成立しなかった役の候補。読み込むときは判定をやり直すので使わない。
+    #[serde(default)]
+    explanation: Explanation,
+
+    /// 符の内訳。読み込むときは使わない。
+    #[serde(default)]
+    fu_breakdown: FuBreakdown,
+    tilesets: Tilesets,
+    ruleset: Ruleset,
+}
+
+impl Serialize for Judge {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let ruleset = self.ruleset();
+        let &Point { fan, fu, yiman } = self.total();
+
+        let forms = (self.forms().iter())
+            .map(|&form| {
+                let point = form.point(ruleset);
+                FormRepr {
+                    form,
+                    fan: point.fan,
+                    yiman: point.yiman,
+                }
+            })
+            .collect();
+
+        JudgeRepr {
+            forms,
+            fan,
+            fu,
+            yiman,
+            value: self
+                .total()
+                .value(self.tilesets().context.is_parent(), ruleset),
+            payment: self.payment(),
+            decomposition: self.agari_tilesets().map(DecompositionRepr::new),
+            explanation: self.explanation().clone(),
+            fu_breakdown: self.fu_breakdown().clone(),
+            tilesets: self.tilesets().clone(),
+            ruleset: *ruleset,
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Judge {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Judge, D::Error> {
+        let repr = JudgeRepr::deserialize(deserializer)?;
+        let forms: Vec<Form> = repr.forms.iter().map(|f| f.form).collect();
+
+        // 同じ役になる解釈を探す。
+        let found = judge_all(&repr.tilesets, &repr.ruleset)
+            .find(|judge| *judge.forms() == forms && judge.total().fu == repr.fu);
+
+        found.ok_or_else(|| de::Error::custom("判定結果が牌集合の集合と一致しません。"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::judge::judge;
+
+    fn parse(from: &str) -> Tilesets {
+        from.parse().unwrap()
+    }
+
+    #[test]
+    fn tile() {
+        let tile: Tile = "5M".parse().unwrap();
+        assert_eq!(serde_json::to_string(&tile).unwrap(), r#""5M""#);
+        assert_eq!(
+            serde_json::from_str::<Tile>(r#""東""#).unwrap().to_string(),
+            "東"
+        );
+        assert!(serde_json::from_str::<Tile>(r#""0m""#).is_err());
+
+        let tiles: Tiles = "123m東東".parse().unwrap();
+        assert_eq!(serde_json::to_string(&tiles).unwrap(), r#""1m2m3m東東""#);
+
+        let tileset: Tileset = "ポン1m1m1m".parse().unwrap();
+        let json = serde_json::to_string(&tileset).unwrap();
+        assert_eq!(serde_json::from_str::<Tileset>(&json).unwrap(), tileset);
+    }
+
+    #[test]
+    fn tilesets() {
+        let tilesets = parse("1m2m3m4p5p6p7s8s9s東東南南 ロン南");
+        let json = serde_json::to_string(&tilesets).unwrap();
+        let restored: Tilesets = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.to_string(), tilesets.to_string());
+
+        // 牌が足りない手牌や形の変な鳴きは読み込めない。
+        let mut json = serde_json::to_value(&tilesets).unwrap();
+        json["hand"] = "1m2m3m4p5p6p7s8s9s東東南".into();
+        assert!(serde_json::from_value::<Tilesets>(json.clone()).is_err());
+
+        json["pengs"] = serde_json::json!(["1m2m4m"]);
+        assert!(serde_json::from_value::<Tilesets>(json).is_err());
+
+        let tilesets = parse("ドラ表示北 裏ドラ表示中 1m2m3m4p5p6p7s8s9s東東南南 リーチ ツモ南");
+        let json = serde_json::to_string(&tilesets).unwrap();
+        let restored: Tilesets = serde_json::from_str(&json).unwrap();
+        assert_eq!(restored.to_string(), tilesets.to_string());
+    }
+
+    #[test]
+    fn judge_json() {
+        let tilesets = parse("2m3m4m4p5p6p7s8s9s3s3s5m6m ロン7m");
+        let judge = judge(&tilesets, &Ruleset::default()).unwrap();
+        let json = serde_json::to_value(&judge).unwrap();
+
+        assert_eq!(json["fan"], 1);
+        assert_eq!(json["fu"], 30);
+        assert_eq!(json["forms"][0]["form"], "Pinghe");
+        assert_eq!(json["payment"]["kind"]["Ronghe"], 1500);
+        assert_eq!(json["decomposition"]["quetou"], "3s3s");
+        assert_eq!(json["decomposition"]["machi"], "Liangmian");
+
+        let restored: Judge = serde_json::from_value(json.clone()).unwrap();
+        assert_eq!(restored.to_string(), judge.to_string());
+
+        // 牌集合の集合が変なら判定をやり直す前にエラーとなる。
+        let mut json = json;
+        json["tilesets"]["hand"] = "2m3m4m4p5p6p7s8s9s3s3s5m".into();
+        assert!(serde_json::from_value::<Judge>(json).is_err());
+    }
+}
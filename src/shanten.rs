@@ -0,0 +1,258 @@
+//! 向聴数を計算する。
+//!
+//! 向聴数は聴牌まであと何枚の有効牌が必要かを表す。聴牌なら 0 、アガリ形ならば -1 となる。
+
+use crate::tile::NUM_KINDS;
+use crate::tiles::Tiles;
+use log::debug;
+use std::cmp::min;
+
+/// 字牌の通し番号の始まり。
+const ZIPAI_START: usize = 27;
+
+/// 幺九牌の通し番号。
+const YAOJIU_INDICES: [usize; 13] = [0, 8, 9, 17, 18, 26, 27, 28, 29, 30, 31, 32, 33];
+
+/// 形ごとの向聴数。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Shanten {
+    /// 一般形 (4面子1雀頭) の向聴数。
+    pub regular: i32,
+
+    /// 七対子の向聴数。面子が確定している (副露・暗槓がある) ときは None 。
+    pub qiduizi: Option<i32>,
+
+    /// 国士無双の向聴数。面子が確定している (副露・暗槓がある) ときは None 。
+    pub kokushimuso: Option<i32>,
+}
+
+impl Shanten {
+    /// 手牌と、副露や暗槓によって既に確定している面子の数から向聴数を計算する。
+    ///
+    /// 手牌は 13 枚 (または 14 枚) から確定している面子 1 つにつき 3 枚を除いた枚数であるとする。
+    pub fn new(hand: &Tiles, num_mianzis: usize) -> Shanten {
+        let res = Shanten::from_counts(&hand.counts(), num_mianzis);
+        debug!(
+            "{} の向聴数: 一般形 {}, 七対子 {:?}, 国士無双 {:?}",
+            hand, res.regular, res.qiduizi, res.kokushimuso
+        );
+
+        res
+    }
+
+    /// 牌の種類ごとの枚数 (`Tiles::counts()`) から向聴数を計算する。
+    pub fn from_counts(counts: &[u8; NUM_KINDS], num_mianzis: usize) -> Shanten {
+        let regular = shanten_regular(counts, num_mianzis);
+
+        let (qiduizi, kokushimuso) = if num_mianzis == 0 {
+            (
+                Some(shanten_qiduizi(counts)),
+                Some(shanten_kokushimuso(counts)),
+            )
+        } else {
+            (None, None)
+        };
+
+        Shanten {
+            regular,
+            qiduizi,
+            kokushimuso,
+        }
+    }
+
+    /// 全ての形の中で最小の向聴数。
+    pub fn min(self) -> i32 {
+        (self.qiduizi.into_iter())
+            .chain(self.kokushimuso)
+            .fold(self.regular, min)
+    }
+
+    /// 聴牌しているかどうか。
+    pub fn is_tingpai(self) -> bool {
+        self.min() == 0
+    }
+
+    /// アガリ形になっているかどうか。
+    pub fn is_agari(self) -> bool {
+        self.min() == -1
+    }
+}
+
+/// 手牌と確定している面子の数から、全ての形の中で最小の向聴数を計算する。
+pub fn shanten(hand: &Tiles, num_mianzis: usize) -> i32 {
+    Shanten::new(hand, num_mianzis).min()
+}
+
+/// 一般形の向聴数を計算する。
+pub fn shanten_regular(counts: &[u8; NUM_KINDS], num_mianzis: usize) -> i32 {
+    let num_mianzis = num_mianzis as i32;
+    let mut calc = RegularCalculator {
+        counts: *counts,
+        best: 8,
+    };
+
+    // 雀頭がない場合
+    calc.search(0, num_mianzis, 0, false);
+
+    // 雀頭を一つ決め打つ場合
+    for i in 0..NUM_KINDS {
+        if calc.counts[i] >= 2 {
+            calc.counts[i] -= 2;
+            calc.search(0, num_mianzis, 0, true);
+            calc.counts[i] += 2;
+        }
+    }
+
+    calc.best
+}
+
+/// 七対子の向聴数を計算する。
+///
+/// 同じ牌を 4 枚使うことはできないので、対子が揃っていても種類が 7 種類に満たなければその分だけ向聴
+/// 数が増える。
+pub fn shanten_qiduizi(counts: &[u8; NUM_KINDS]) -> i32 {
+    let num_duizis = counts.iter().filter(|&&c| c >= 2).count() as i32;
+    let num_kinds = counts.iter().filter(|&&c| c >= 1).count() as i32;
+
+    6 - num_duizis + (7 - num_kinds).max(0)
+}
+
+/// 国士無双の向聴数を計算する。
+pub fn shanten_kokushimuso(counts: &[u8; NUM_KINDS]) -> i32 {
+    let num_kinds = (YAOJIU_INDICES.iter()).filter(|&&i| counts[i] >= 1).count() as i32;
+    let has_duizi = YAOJIU_INDICES.iter().any(|&i| counts[i] >= 2);
+
+    13 - num_kinds - if has_duizi { 1 } else { 0 }
+}
+
+/// 一般形の向聴数を求めるための探索。
+///
+/// 牌を前から順に面子・塔子・孤立牌として取り除いていき、向聴数の最小値を求める。
+struct RegularCalculator {
+    counts: [u8; NUM_KINDS],
+    best: i32,
+}
+
+impl RegularCalculator {
+    fn search(&mut self, start: usize, mianzis: i32, dazis: i32, has_quetou: bool) {
+        let i = match (start..NUM_KINDS).find(|&i| self.counts[i] > 0) {
+            Some(i) => i,
+            None => {
+                // 面子と塔子は合わせて 4 つまでしか意味がない。
+                let dazis = min(dazis, 4 - mianzis);
+                let shanten = 8 - 2 * mianzis - dazis - if has_quetou { 1 } else { 0 };
+                self.best = min(self.best, shanten);
+                return;
+            }
+        };
+
+        let is_shuzi = i < ZIPAI_START;
+        let order = i % 9;
+
+        // 刻子
+        if self.counts[i] >= 3 {
+            self.counts[i] -= 3;
+            self.search(i, mianzis + 1, dazis, has_quetou);
+            self.counts[i] += 3;
+        }
+
+        // 順子
+        if is_shuzi && order <= 6 && self.counts[i + 1] > 0 && self.counts[i + 2] > 0 {
+            self.take(&[i, i + 1, i + 2]);
+            self.search(i, mianzis + 1, dazis, has_quetou);
+            self.put(&[i, i + 1, i + 2]);
+        }
+
+        // 塔子。面子と合わせて 4 つを越えるものは数えても意味がない。
+        if mianzis + dazis < 4 {
+            // 対子
+            if self.counts[i] >= 2 {
+                self.counts[i] -= 2;
+                self.search(i, mianzis, dazis + 1, has_quetou);
+                self.counts[i] += 2;
+            }
+
+            // 両面・辺張
+            if is_shuzi && order <= 7 && self.counts[i + 1] > 0 {
+                self.take(&[i, i + 1]);
+                self.search(i, mianzis, dazis + 1, has_quetou);
+                self.put(&[i, i + 1]);
+            }
+
+            // 嵌張
+            if is_shuzi && order <= 6 && self.counts[i + 2] > 0 {
+                self.take(&[i, i + 2]);
+                self.search(i, mianzis, dazis + 1, has_quetou);
+                self.put(&[i, i + 2]);
+            }
+        }
+
+        // 残りは孤立牌として扱う。
+        let count = self.counts[i];
+        self.counts[i] = 0;
+        self.search(i + 1, mianzis, dazis, has_quetou);
+        self.counts[i] = count;
+    }
+
+    fn take(&mut self, indices: &[usize]) {
+        for &i in indices {
+            self.counts[i] -= 1;
+        }
+    }
+
+    fn put(&mut self, indices: &[usize]) {
+        for &i in indices {
+            self.counts[i] += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tiles(tiles: &str) -> Tiles {
+        tiles.parse().unwrap()
+    }
+
+    #[test]
+    fn regular() {
+        assert_eq!(shanten(&tiles("123m456p789s東東東南"), 0), 0);
+        assert_eq!(shanten(&tiles("123m456p789s東東東南南"), 0), -1);
+        assert_eq!(shanten(&tiles("123m456p78s東東東南西"), 0), 1);
+        assert_eq!(
+            shanten_regular(&tiles("147m258p369s東南西北").counts(), 0),
+            8
+        );
+        assert_eq!(shanten(&tiles("11123455678999m"), 0), -1);
+        assert_eq!(shanten(&tiles("1112345678999m"), 0), 0);
+    }
+
+    #[test]
+    fn with_mianzis() {
+        assert_eq!(shanten(&tiles("東"), 4), 0);
+        assert_eq!(shanten(&tiles("東東"), 4), -1);
+        assert_eq!(shanten(&tiles("2m3m東東"), 3), 0);
+        assert_eq!(shanten(&tiles("2m5m東南"), 3), 2);
+        assert_eq!(Shanten::new(&tiles("2m5m東南"), 3).qiduizi, None);
+    }
+
+    #[test]
+    fn qiduizi() {
+        let s = Shanten::new(&tiles("1133557799m1p1s東"), 0);
+        assert_eq!(s.qiduizi, Some(1));
+        assert_eq!(shanten(&tiles("113355m7799p1s東東"), 0), 0);
+        assert_eq!(shanten_qiduizi(&tiles("1111m2233p4455s東").counts()), 2);
+    }
+
+    #[test]
+    fn kokushimuso() {
+        assert_eq!(shanten(&tiles("19m19p19s東南西北白發中"), 0), 0);
+        assert_eq!(shanten(&tiles("19m19p19s東南西北白發中中"), 0), -1);
+        assert_eq!(
+            Shanten::new(&tiles("19m19p15s東南西北白發中"), 0).kokushimuso,
+            Some(1)
+        );
+        assert_eq!(shanten(&tiles("159m159p159s東南西北"), 0), 3);
+    }
+}
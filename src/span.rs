@@ -0,0 +1,64 @@
+//! 入力の中の位置を表す。パースのエラーがどこで起きたかを示すのに使う。
+
+use std::fmt;
+use std::ops::Range;
+
+/// 入力の中の範囲。バイト単位で数える。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Span {
+        Span { start, end }
+    }
+
+    /// 文字列全体を表す範囲。
+    pub fn whole(s: &str) -> Span {
+        Span::new(0, s.len())
+    }
+
+    /// `offset` バイトだけ後ろにずらす。
+    pub fn shift(self, offset: usize) -> Span {
+        Span::new(self.start + offset, self.end + offset)
+    }
+
+    /// `input` の中での文字単位の範囲に直す。
+    pub fn char_range(self, input: &str) -> Range<usize> {
+        let count = |pos: usize| input.char_indices().take_while(|&(i, _)| i < pos).count();
+        count(self.start)..count(self.end)
+    }
+}
+
+impl fmt::Display for Span {
+    fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
+        write!(b, "{}..{}", self.start, self.end)
+    }
+}
+
+/// 空白で区切られた語と、その開始位置 (バイト単位) を列挙する。
+pub(crate) fn words(s: &str) -> impl Iterator<Item = (usize, &str)> + '_ {
+    s.split_whitespace()
+        .map(move |word| (word.as_ptr() as usize - s.as_ptr() as usize, word))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn char_range() {
+        let input = "ロン1x";
+        let span = Span::new(7, 8);
+        assert_eq!(&input[span.start..span.end], "x");
+        assert_eq!(span.char_range(input), 3..4);
+    }
+
+    #[test]
+    fn words_with_offsets() {
+        let words: Vec<_> = words(" 東場  ロン1m").collect();
+        assert_eq!(words, vec![(1, "東場"), (9, "ロン1m")]);
+    }
+}
@@ -0,0 +1,1080 @@
+//! 天鳳の牌譜を読み込む。
+//!
+//! XML 形式 (mjlog) と tenhou.net/6 の JSON 形式に対応する。三人麻雀には対応しない。読み込んだ牌譜は
+//! `paipu::rescore` などで判定し直せる。
+
+use crate::context::Direction;
+use crate::paipu::{Event, HuleRecord, Paipu, RoundRecord};
+use crate::ruleset::{Renhe, Ruleset};
+use crate::tile::Tile;
+use num_traits::FromPrimitive;
+use serde_json::Value;
+use std::collections::VecDeque;
+use thiserror::Error;
+
+pub type Result<T> = std::result::Result<T, TenhouError>;
+
+#[derive(Debug, Error)]
+pub enum TenhouError {
+    /// XML として読めない。
+    #[error("XML の形式が変です: {0}")]
+    InvalidXml(String),
+
+    /// JSON の構造が想定と違う。
+    #[error("JSON の形式が変です: {0}")]
+    InvalidJson(String),
+
+    /// JSON として読めない。
+    #[error("JSON を読めません: {0}")]
+    Json(#[from] serde_json::Error),
+
+    /// タグに必要な属性がない。
+    #[error("{0} タグに {1} 属性がありません。")]
+    AttributeNotFound(String, &'static str),
+
+    /// 属性の値が読めない。
+    #[error("{0} 属性の値が変です: {1}")]
+    InvalidAttribute(&'static str, String),
+
+    /// 牌の番号が範囲外。
+    #[error("不明な牌です: {0}")]
+    UnknownTile(u32),
+
+    /// 鳴きの表記が読めない。
+    #[error("不明な鳴きです: {0}")]
+    UnknownMeld(String),
+
+    /// 三人麻雀の牌譜。
+    #[error("三人麻雀には対応していません。")]
+    Sanma,
+}
+
+/// 天鳳の役の番号ごとの名前。
+const YAKU_NAMES: [&str; 55] = [
+    "門前清自摸和",
+    "立直",
+    "一発",
+    "槍槓",
+    "嶺上開花",
+    "海底摸月",
+    "河底撈魚",
+    "平和",
+    "断幺九",
+    "一盃口",
+    "自風 東",
+    "自風 南",
+    "自風 西",
+    "自風 北",
+    "場風 東",
+    "場風 南",
+    "場風 西",
+    "場風 北",
+    "役牌 白",
+    "役牌 發",
+    "役牌 中",
+    "両立直",
+    "七対子",
+    "混全帯幺九",
+    "一気通貫",
+    "三色同順",
+    "三色同刻",
+    "三槓子",
+    "対々和",
+    "三暗刻",
+    "小三元",
+    "混老頭",
+    "二盃口",
+    "純全帯幺九",
+    "混一色",
+    "清一色",
+    "人和",
+    "天和",
+    "地和",
+    "大三元",
+    "四暗刻",
+    "四暗刻単騎",
+    "字一色",
+    "緑一色",
+    "清老頭",
+    "九蓮宝燈",
+    "純正九蓮宝燈",
+    "国士無双",
+    "国士無双１３面",
+    "大四喜",
+    "小四喜",
+    "四槓子",
+    "ドラ",
+    "裏ドラ",
+    "赤ドラ",
+];
+
+/// 天鳳のルール。切り上げ満貫とダブル役満と人和はなく、数え役満はある。
+pub fn ruleset(akadora: bool, kuitan: bool) -> Ruleset {
+    Ruleset {
+        kiriage_manguan: false,
+        kuitan,
+        kazoe_yiman: true,
+        double_yiman: false,
+        akadora: if akadora { 3 } else { 0 },
+        renhe: Renhe::None,
+    }
+}
+
+/// 場風を局の通し番号 (東 1 局が 0) から求める。
+fn place(kyoku: usize) -> Direction {
+    Direction::from_usize(kyoku / 4 % 4).expect("place must be less than 4.")
+}
+
+/// 牌の種類の番号 (萬子・筒子・索子・字牌の順) から牌を作る。
+fn tile_from_kind(kind: u32, is_red: bool) -> Tile {
+    let index = match kind / 9 {
+        0 => 9 + kind % 9,
+        1 => 18 + kind % 9,
+        2 => kind % 9,
+        _ => kind,
+    };
+
+    let tile = Tile::from_index(index as usize);
+    if is_red {
+        tile.with_red(true).expect("5 can be red.")
+    } else {
+        tile
+    }
+}
+
+/// mjlog の牌の番号 (0 から 135) を牌にする。 16, 52, 88 は赤ドラありのとき赤ドラとなる。
+fn mjlog_tile(id: u32, akadora: bool) -> Result<Tile> {
+    if id >= 136 {
+        return Err(TenhouError::UnknownTile(id));
+    }
+
+    let is_red = akadora && (id == 16 || id == 52 || id == 88);
+    Ok(tile_from_kind(id / 4, is_red))
+}
+
+/// JSON の牌の番号を牌にする。 11 から 19 が萬子、 21 から 29 が筒子、 31 から 39 が索子、 41 から
+/// 47 が字牌で、 51, 52, 53 がそれぞれの赤ドラとなる。
+fn json_tile(code: u32) -> Result<Tile> {
+    let (suit, number) = (code / 10, code % 10);
+    match (suit, number) {
+        (1..=3, 1..=9) => Ok(tile_from_kind((suit - 1) * 9 + number - 1, false)),
+        (4, 1..=7) => Ok(tile_from_kind(27 + number - 1, false)),
+        (5, 1..=3) => Ok(tile_from_kind((number - 1) * 9 + 4, true)),
+        _ => Err(TenhouError::UnknownTile(code)),
+    }
+}
+
+/// XML のタグ。
+struct Tag<'a> {
+    name: &'a str,
+    attrs: Vec<(&'a str, &'a str)>,
+}
+
+impl<'a> Tag<'a> {
+    fn get(&self, key: &str) -> Option<&'a str> {
+        (self.attrs.iter())
+            .find(|(k, _)| *k == key)
+            .map(|&(_, v)| v)
+    }
+
+    fn require(&self, key: &'static str) -> Result<&'a str> {
+        self.get(key)
+            .ok_or_else(|| TenhouError::AttributeNotFound(self.name.to_string(), key))
+    }
+
+    fn number(&self, key: &'static str) -> Result<u32> {
+        let value = self.require(key)?;
+        value
+            .parse()
+            .map_err(|_| TenhouError::InvalidAttribute(key, value.to_string()))
+    }
+
+    /// 席の番号。 0 から 3 でなければエラーとなる。
+    fn seat(&self, key: &'static str) -> Result<usize> {
+        match self.number(key)? {
+            seat @ 0..=3 => Ok(seat as usize),
+            seat => Err(TenhouError::InvalidAttribute(key, seat.to_string())),
+        }
+    }
+
+    /// カンマ区切りの数の並び。属性がなければ空になる。
+    fn numbers(&self, key: &'static str) -> Result<Vec<u32>> {
+        match self.get(key) {
+            None | Some("") => Ok(Vec::new()),
+            Some(value) => (value.split(','))
+                .map(|v| {
+                    v.parse()
+                        .map_err(|_| TenhouError::InvalidAttribute(key, value.to_string()))
+                })
+                .collect(),
+        }
+    }
+}
+
+/// mjlog をタグの並びに分ける。閉じタグや XML 宣言は読み飛ばす。
+fn tags(xml: &str) -> Result<Vec<Tag<'_>>> {
+    let mut res = Vec::new();
+    let mut rest = xml;
+
+    while let Some(start) = rest.find('<') {
+        let end = (rest[start..].find('>'))
+            .ok_or_else(|| TenhouError::InvalidXml("タグが閉じていません。".to_string()))?;
+        let body = rest[start + 1..start + end].trim_end_matches('/').trim();
+        rest = &rest[start + end + 1..];
+
+        if body.starts_with('/') || body.starts_with('?') || body.starts_with('!') {
+            continue;
+        }
+
+        let (name, mut attrs_str) = match body.find(char::is_whitespace) {
+            Some(pos) => (&body[..pos], body[pos..].trim_start()),
+            None => (body, ""),
+        };
+
+        let mut attrs = Vec::new();
+        while !attrs_str.is_empty() {
+            let eq = (attrs_str.find("=\""))
+                .ok_or_else(|| TenhouError::InvalidXml(format!("属性が変です: {}", body)))?;
+            let key = attrs_str[..eq].trim();
+            let value_len = (attrs_str[eq + 2..].find('"'))
+                .ok_or_else(|| TenhouError::InvalidXml(format!("属性が変です: {}", body)))?;
+            attrs.push((key, &attrs_str[eq + 2..eq + 2 + value_len]));
+            attrs_str = attrs_str[eq + 2 + value_len + 1..].trim_start();
+        }
+
+        res.push(Tag { name, attrs });
+    }
+
+    Ok(res)
+}
+
+/// `%E6%9D%B1` のようにパーセントエンコードされた名前を元に戻す。
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut res = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        let decoded = if bytes[i] == b'%' && i + 2 < bytes.len() {
+            std::str::from_utf8(&bytes[i + 1..i + 3])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+        } else {
+            None
+        };
+
+        match decoded {
+            Some(byte) => {
+                res.push(byte);
+                i += 3;
+            }
+            None => {
+                res.push(bytes[i]);
+                i += 1;
+            }
+        }
+    }
+
+    String::from_utf8_lossy(&res).into_owned()
+}
+
+/// mjlog の鳴きの番号を出来事にする。 `who` は鳴いた人の席。
+fn mjlog_meld(who: usize, m: u32, akadora: bool) -> Result<Event> {
+    let tile = |id: u32| mjlog_tile(id, akadora);
+
+    if m & 0x4 != 0 {
+        // チー
+        let t = m >> 10;
+        let (r, t) = (t % 3, t / 3);
+        let base = (t / 7) * 9 + t % 7;
+        let ids: Vec<u32> = (0..3)
+            .map(|i| (base + i) * 4 + ((m >> (3 + 2 * i)) & 0x3))
+            .collect();
+        let called = tile(ids[r as usize])?;
+        let tiles = (ids.iter().enumerate())
+            .filter(|&(i, _)| i != r as usize)
+            .map(|(_, &id)| tile(id))
+            .collect::<Result<_>>()?;
+
+        Ok(Event::Chi {
+            seat: who,
+            tiles,
+            called,
+        })
+    } else if m & 0x18 != 0 {
+        // ポンと加槓
+        let t = m >> 9;
+        let (r, t) = (t % 3, t / 3);
+        let unused = (m >> 5) & 0x3;
+        let ids: Vec<u32> = (0..4).filter(|&i| i != unused).map(|i| t * 4 + i).collect();
+
+        if m & 0x8 != 0 {
+            let called = tile(ids[r as usize])?;
+            let tiles = (ids.iter().enumerate())
+                .filter(|&(i, _)| i != r as usize)
+                .map(|(_, &id)| tile(id))
+                .collect::<Result<_>>()?;
+
+            Ok(Event::Peng {
+                seat: who,
+                tiles,
+                called,
+            })
+        } else {
+            Ok(Event::Jiagang {
+                seat: who,
+                tile: tile(t * 4 + unused)?,
+            })
+        }
+    } else if m & 0x20 != 0 {
+        // 北抜きは三人麻雀にしかない。
+        Err(TenhouError::Sanma)
+    } else {
+        // 暗槓と大明槓
+        let called_id = m >> 8;
+        let base = called_id / 4 * 4;
+        let from_who = m & 0x3;
+
+        if from_who == 0 {
+            let tiles = (0..4).map(|i| tile(base + i)).collect::<Result<_>>()?;
+            Ok(Event::Angang { seat: who, tiles })
+        } else {
+            let tiles = (0..4)
+                .map(|i| base + i)
+                .filter(|&id| id != called_id)
+                .map(tile)
+                .collect::<Result<_>>()?;
+
+            Ok(Event::Minggang {
+                seat: who,
+                tiles,
+                called: tile(called_id)?,
+            })
+        }
+    }
+}
+
+/// mjlog の AGARI タグを読む。
+fn mjlog_hule(tag: &Tag, akadora: bool) -> Result<HuleRecord> {
+    let seat = tag.seat("who")?;
+    let from = tag.seat("fromWho")?;
+    let ten = tag.numbers("ten")?;
+    if ten.len() < 2 {
+        return Err(TenhouError::InvalidAttribute("ten", format!("{:?}", ten)));
+    }
+
+    // 役は役の番号と翻数の組の列になっている。
+    let yaku = tag.numbers("yaku")?;
+    let fan = (yaku.chunks(2))
+        .try_fold(0u32, |sum, c| match c {
+            &[_, fan] => sum.checked_add(fan),
+            _ => None,
+        })
+        .ok_or_else(|| TenhouError::InvalidAttribute("yaku", format!("{:?}", yaku)))?;
+    let yakuman = tag.numbers("yakuman")?;
+    let name = |id: u32| {
+        YAKU_NAMES
+            .get(id as usize)
+            .map_or_else(|| format!("不明な役 {}", id), |name| name.to_string())
+    };
+
+    let mut yakus: Vec<(String, u32)> =
+        (yaku.chunks_exact(2)).map(|c| (name(c[0]), c[1])).collect();
+    yakus.extend(yakuman.iter().map(|&id| (name(id), 13)));
+
+    let uradora_indicators = (tag.numbers("doraHaiUra")?.into_iter())
+        .map(|id| mjlog_tile(id, akadora))
+        .collect::<Result<_>>()?;
+
+    Ok(HuleRecord {
+        seat,
+        from: if seat == from { None } else { Some(from) },
+        tile: mjlog_tile(tag.number("machi")?, akadora)?,
+        uradora_indicators,
+        fan: if yakuman.is_empty() { fan } else { 0 },
+        fu: if yakuman.is_empty() {
+            Some(ten[0])
+        } else {
+            None
+        },
+        yiman: yakuman.len() as u32,
+        value: Some(ten[1]),
+        yakus,
+    })
+}
+
+/// 天鳳の XML 形式の牌譜 (mjlog) を読む。
+pub fn parse_mjlog(xml: &str) -> Result<Paipu> {
+    let mut paipu = Paipu::default();
+    let mut akadora = true;
+    let mut round: Option<RoundRecord> = None;
+    let mut lizhi = [false; 4];
+
+    for tag in tags(xml)? {
+        let name = tag.name;
+        let (head, digits) = name.split_at(1.min(name.len()));
+        let is_tile_tag = !digits.is_empty() && digits.bytes().all(|b| b.is_ascii_digit());
+
+        if is_tile_tag {
+            let round = (round.as_mut()).ok_or_else(|| {
+                TenhouError::InvalidXml(format!("局の外に {} があります。", name))
+            })?;
+            let id = (digits.parse())
+                .map_err(|_| TenhouError::InvalidXml(format!("牌の番号が変です: {}", name)))?;
+            let tile = mjlog_tile(id, akadora)?;
+            let seat = |c: &str| "TUVW".find(c).or_else(|| "DEFG".find(c));
+
+            match head {
+                "T" | "U" | "V" | "W" => round.events.push(Event::Mopai {
+                    seat: seat(head).expect("head must be a seat."),
+                    tile,
+                }),
+                "D" | "E" | "F" | "G" => {
+                    let seat = seat(head).expect("head must be a seat.");
+                    round.events.push(Event::Dapai {
+                        seat,
+                        tile,
+                        is_lizhi: std::mem::take(&mut lizhi[seat]),
+                    });
+                }
+                _ => {}
+            }
+
+            continue;
+        }
+
+        match name {
+            "GO" => {
+                let kind = tag.number("type")?;
+                if kind & 0x10 != 0 {
+                    return Err(TenhouError::Sanma);
+                }
+
+                akadora = kind & 0x02 == 0;
+                paipu.ruleset = ruleset(akadora, kind & 0x04 == 0);
+            }
+
+            "UN" if paipu.names.is_empty() => {
+                paipu.names = (["n0", "n1", "n2", "n3"].iter())
+                    .map(|key| tag.get(key).map(percent_decode).unwrap_or_default())
+                    .collect();
+
+                if paipu.names[3].is_empty() {
+                    return Err(TenhouError::Sanma);
+                }
+            }
+
+            "INIT" => {
+                paipu.rounds.extend(round.take());
+                lizhi = [false; 4];
+
+                let seed = tag.numbers("seed")?;
+                if seed.len() < 6 {
+                    return Err(TenhouError::InvalidAttribute("seed", format!("{:?}", seed)));
+                }
+
+                let mut hands: [Vec<Tile>; 4] = Default::default();
+                for (hand, key) in hands.iter_mut().zip(&["hai0", "hai1", "hai2", "hai3"]) {
+                    *hand = (tag.numbers(key)?.into_iter())
+                        .map(|id| mjlog_tile(id, akadora))
+                        .collect::<Result<_>>()?;
+                }
+
+                round = Some(RoundRecord {
+                    place: place(seed[0] as usize),
+                    dealer: tag.seat("oya")?,
+                    benchang: seed[1],
+                    gongtuo: seed[2],
+                    hands,
+                    dora_indicators: vec![mjlog_tile(seed[5], akadora)?],
+                    events: Vec::new(),
+                });
+            }
+
+            "REACH" | "N" | "DORA" | "AGARI" | "RYUUKYOKU" => {
+                let round = (round.as_mut()).ok_or_else(|| {
+                    TenhouError::InvalidXml(format!("局の外に {} があります。", name))
+                })?;
+
+                match name {
+                    "REACH" if tag.number("step")? == 1 => {
+                        lizhi[tag.seat("who")?] = true;
+                    }
+                    "N" => {
+                        let who = tag.seat("who")?;
+                        round
+                            .events
+                            .push(mjlog_meld(who, tag.number("m")?, akadora)?);
+                    }
+                    "DORA" => round
+                        .events
+                        .push(Event::Dora(mjlog_tile(tag.number("hai")?, akadora)?)),
+                    "AGARI" => round.events.push(Event::Hule(mjlog_hule(&tag, akadora)?)),
+                    "RYUUKYOKU" => round.events.push(Event::Liuju),
+                    _ => {}
+                }
+            }
+
+            _ => {}
+        }
+    }
+
+    paipu.rounds.extend(round);
+
+    Ok(paipu)
+}
+
+fn invalid_json(message: &str) -> TenhouError {
+    TenhouError::InvalidJson(message.to_string())
+}
+
+fn json_u32(value: &Value) -> Result<u32> {
+    (value.as_u64())
+        .map(|v| v as u32)
+        .ok_or_else(|| invalid_json(&format!("数ではありません: {}", value)))
+}
+
+/// 席の番号。 0 から 3 でなければエラーとなる。
+fn json_seat(value: &Value) -> Result<usize> {
+    match value.as_u64() {
+        Some(seat @ 0..=3) => Ok(seat as usize),
+        _ => Err(invalid_json(&format!("席の番号が変です: {}", value))),
+    }
+}
+
+fn json_array(value: &Value) -> Result<&Vec<Value>> {
+    (value.as_array()).ok_or_else(|| invalid_json(&format!("配列ではありません: {}", value)))
+}
+
+fn json_tiles(value: &Value) -> Result<Vec<Tile>> {
+    json_array(value)?
+        .iter()
+        .map(|v| json_tile(json_u32(v)?))
+        .collect()
+}
+
+/// JSON の鳴きの表記 (例: "c275226", "45p4545", "393939a39") 。
+struct JsonMeld {
+    /// 鳴きの種類を表す文字。
+    kind: char,
+
+    /// 表記に含まれる牌。
+    tiles: Vec<Tile>,
+
+    /// 種類を表す文字の直後にある牌の位置。鳴いた牌 (加槓なら加えた牌) を表す。
+    pos: usize,
+}
+
+impl JsonMeld {
+    fn parse(s: &str) -> Result<JsonMeld> {
+        let unknown = || TenhouError::UnknownMeld(s.to_string());
+        let mut kind = None;
+        let mut codes = Vec::new();
+        let mut pos = 0;
+        let mut digits = String::new();
+
+        for c in s.chars() {
+            if c.is_ascii_digit() {
+                digits.push(c);
+                if digits.len() == 2 {
+                    codes.push(digits.parse::<u32>().map_err(|_| unknown())?);
+                    digits.clear();
+                }
+            } else if kind.is_none() && digits.is_empty() {
+                kind = Some(c);
+                pos = codes.len();
+            } else {
+                return Err(unknown());
+            }
+        }
+
+        let kind = kind.ok_or_else(unknown)?;
+        if !digits.is_empty() || pos >= codes.len() {
+            return Err(unknown());
+        }
+
+        let tiles = codes.into_iter().map(json_tile).collect::<Result<_>>()?;
+
+        Ok(JsonMeld { kind, tiles, pos })
+    }
+
+    /// 鳴いた牌。
+    fn called(&self) -> Tile {
+        self.tiles[self.pos]
+    }
+
+    /// 手牌から出した牌。
+    fn others(&self) -> Vec<Tile> {
+        (self.tiles.iter().enumerate())
+            .filter(|&(i, _)| i != self.pos)
+            .map(|(_, &t)| t)
+            .collect()
+    }
+
+    /// 鳴いた人から見て、鳴かれた人が何人右にいるか (上家なら 3) 。
+    fn offset(&self) -> usize {
+        match self.pos {
+            0 => 3,
+            1 => 2,
+            _ => 1,
+        }
+    }
+}
+
+/// ツモの列の先頭が `seat` の捨てた `tile` を鳴く表記ならそれを返す。
+fn json_call(front: Option<&Value>, caller: usize, seat: usize, tile: Tile) -> Option<JsonMeld> {
+    let meld = JsonMeld::parse(front?.as_str()?).ok()?;
+    let is_call = matches!(meld.kind, 'c' | 'p' | 'm');
+
+    if is_call && (caller + meld.offset()) % 4 == seat && meld.called() == tile {
+        Some(meld)
+    } else {
+        None
+    }
+}
+
+/// 点数の表記 (例: "30符1000点", "満貫2000-4000点", "50符1600点∀") を符数と点数の合計にする。
+fn json_value(s: &str) -> Result<(Option<u32>, u32)> {
+    let invalid = || invalid_json(&format!("点数の表記が変です: {}", s));
+
+    let (fu, rest) = match s.find('符') {
+        Some(pos) => (
+            Some(s[..pos].parse().map_err(|_| invalid())?),
+            &s[pos + '符'.len_utf8()..],
+        ),
+        None => (None, s.trim_start_matches(|c: char| !c.is_ascii_digit())),
+    };
+
+    let is_all = rest.ends_with('∀');
+    let values = (rest.trim_end_matches('∀').trim_end_matches('点').split('-'))
+        .map(|v| v.parse::<u32>().map_err(|_| invalid()))
+        .collect::<Result<Vec<_>>>()?;
+
+    let value = match values[..] {
+        [each] if is_all => each.checked_mul(3),
+        [value] => Some(value),
+        [child, parent] => child.checked_mul(2).and_then(|v| v.checked_add(parent)),
+        _ => None,
+    };
+
+    Ok((fu, value.ok_or_else(invalid)?))
+}
+
+/// 役の表記 (例: "立直(1飜)", "大三元(役満)") を名前と翻数にする。役満は 13 翻とする。
+fn json_yaku(s: &str) -> Result<(String, u32, bool)> {
+    let invalid = || invalid_json(&format!("役の表記が変です: {}", s));
+    let open = s.find('(').ok_or_else(invalid)?;
+    let name = s[..open].to_string();
+    let inner = s[open + 1..].trim_end_matches(')');
+
+    if inner.contains("役満") {
+        Ok((name, 13, true))
+    } else {
+        let fan = inner
+            .trim_end_matches('飜')
+            .parse()
+            .map_err(|_| invalid())?;
+        Ok((name, fan, false))
+    }
+}
+
+/// JSON のアガリの情報 (例: [1, 0, 1, "40符1300点", "断幺九(1飜)"]) を読む。
+fn json_hule(info: &Value, tile: Tile, uradora_indicators: &[Tile]) -> Result<HuleRecord> {
+    let info = json_array(info)?;
+    if info.len() < 4 {
+        return Err(invalid_json("アガリの情報が足りません。"));
+    }
+
+    let seat = json_seat(&info[0])?;
+    let from = json_seat(&info[1])?;
+    let (fu, value) = json_value(info[3].as_str().unwrap_or_default())?;
+
+    let mut yakus = Vec::new();
+    let mut fan: u32 = 0;
+    let mut yiman = 0;
+    for yaku in &info[4..] {
+        let (name, f, is_yiman) = json_yaku(yaku.as_str().unwrap_or_default())?;
+        if is_yiman {
+            yiman += 1;
+        } else {
+            fan = fan
+                .checked_add(f)
+                .ok_or_else(|| invalid_json("翻数が大きすぎます。"))?;
+        }
+        yakus.push((name, f));
+    }
+
+    Ok(HuleRecord {
+        seat,
+        from: if seat == from { None } else { Some(from) },
+        tile,
+        uradora_indicators: uradora_indicators.to_vec(),
+        yakus,
+        fan: if yiman > 0 { 0 } else { fan },
+        fu: if yiman > 0 { None } else { fu },
+        yiman,
+        value: Some(value),
+    })
+}
+
+/// tenhou.net/6 の JSON の一局を読む。
+///
+/// JSON には席ごとにツモと打牌の列しか記録されていないので、鳴きの表記を手がかりに手番を追いながら
+/// 出来事を並べ直す。
+fn json_round(log: &Value) -> Result<RoundRecord> {
+    let log = json_array(log)?;
+    if log.len() < 17 {
+        return Err(invalid_json("局の記録が足りません。"));
+    }
+
+    let head = json_array(&log[0])?;
+    if head.len() < 3 {
+        return Err(invalid_json("局の情報が足りません。"));
+    }
+
+    let kyoku = json_u32(&head[0])? as usize;
+    let dora_indicators = json_tiles(&log[2])?;
+    let uradora_indicators = json_tiles(&log[3])?;
+
+    let mut hands: [Vec<Tile>; 4] = Default::default();
+    let mut takes: Vec<VecDeque<&Value>> = Vec::new();
+    let mut discards: Vec<VecDeque<&Value>> = Vec::new();
+    for (seat, hand) in hands.iter_mut().enumerate() {
+        *hand = json_tiles(&log[4 + 3 * seat])?;
+        takes.push(json_array(&log[5 + 3 * seat])?.iter().collect());
+        discards.push(json_array(&log[6 + 3 * seat])?.iter().collect());
+    }
+
+    if hands[3].is_empty() {
+        return Err(TenhouError::Sanma);
+    }
+
+    let mut events = Vec::new();
+    let mut seat = kyoku % 4;
+    let mut needs_mopai = true;
+    let mut drawn: Option<Tile> = None;
+    let mut last: Option<Tile> = None;
+    let mut num_doras = 1;
+
+    let mut reveal_dora = |events: &mut Vec<Event>| {
+        if let Some(&indicator) = dora_indicators.get(num_doras) {
+            events.push(Event::Dora(indicator));
+        }
+        num_doras += 1;
+    };
+
+    loop {
+        if needs_mopai {
+            let tile = match takes[seat].pop_front() {
+                Some(take) => json_tile(json_u32(take)?)?,
+                None => break,
+            };
+            events.push(Event::Mopai { seat, tile });
+            drawn = Some(tile);
+            last = Some(tile);
+        }
+
+        // ツモアガリか流局なら打牌はない。
+        let discard = match discards[seat].pop_front() {
+            Some(discard) => discard,
+            None => break,
+        };
+
+        let tsumogiri = || drawn.ok_or_else(|| invalid_json("ツモ切りする牌がありません。"));
+        let (tile, is_lizhi) = match discard {
+            Value::Number(_) => match json_u32(discard)? {
+                60 => (tsumogiri()?, false),
+                code => (json_tile(code)?, false),
+            },
+            Value::String(s) if s.starts_with('r') => match s[1..].parse::<u32>() {
+                Ok(60) => (tsumogiri()?, true),
+                Ok(code) => (json_tile(code)?, true),
+                Err(_) => return Err(TenhouError::UnknownMeld(s.clone())),
+            },
+            Value::String(s) => {
+                let meld = JsonMeld::parse(s)?;
+                match meld.kind {
+                    'a' => events.push(Event::Angang {
+                        seat,
+                        tiles: meld.tiles.clone(),
+                    }),
+                    'k' => {
+                        events.push(Event::Jiagang {
+                            seat,
+                            tile: meld.called(),
+                        });
+                        last = Some(meld.called());
+                    }
+                    _ => return Err(TenhouError::UnknownMeld(s.clone())),
+                }
+
+                reveal_dora(&mut events);
+                needs_mopai = true;
+                continue;
+            }
+            _ => return Err(invalid_json(&format!("打牌が変です: {}", discard))),
+        };
+
+        events.push(Event::Dapai {
+            seat,
+            tile,
+            is_lizhi,
+        });
+        last = Some(tile);
+
+        // ポン・大明槓をチーより優先して、打牌を鳴いた人を探す。
+        let call = (1..4)
+            .map(|offset| (seat + offset) % 4)
+            .filter_map(|caller| {
+                json_call(takes[caller].front().copied(), caller, seat, tile)
+                    .map(|meld| (caller, meld))
+            })
+            .min_by_key(|(_, meld)| meld.kind == 'c');
+
+        match call {
+            Some((caller, meld)) => {
+                takes[caller].pop_front();
+                let (tiles, called) = (meld.others(), meld.called());
+                seat = caller;
+                drawn = None;
+                needs_mopai = false;
+
+                match meld.kind {
+                    'c' => events.push(Event::Chi {
+                        seat,
+                        tiles,
+                        called,
+                    }),
+                    'p' => events.push(Event::Peng {
+                        seat,
+                        tiles,
+                        called,
+                    }),
+                    _ => {
+                        events.push(Event::Minggang {
+                            seat,
+                            tiles,
+                            called,
+                        });
+
+                        // 大明槓の後の打牌の列には 0 が入っている。
+                        if discards[seat].front().and_then(|d| d.as_u64()) == Some(0) {
+                            discards[seat].pop_front();
+                        }
+                        reveal_dora(&mut events);
+                        needs_mopai = true;
+                    }
+                }
+            }
+            None => {
+                seat = (seat + 1) % 4;
+                needs_mopai = true;
+            }
+        }
+    }
+
+    let result = json_array(&log[16])?;
+    match result.first().and_then(Value::as_str) {
+        Some("和了") => {
+            // 点数の増減とアガリの情報が交互に並んでいる。
+            for info in result[1..].iter().skip(1).step_by(2) {
+                let is_zimo = match json_array(info)?.get(..2) {
+                    Some([winner, from]) => json_u32(winner)? == json_u32(from)?,
+                    _ => return Err(invalid_json("アガリの情報が足りません。")),
+                };
+                let tile = if is_zimo { drawn } else { last };
+                let tile = tile.ok_or_else(|| invalid_json("アガリ牌がありません。"))?;
+                events.push(Event::Hule(json_hule(info, tile, &uradora_indicators)?));
+            }
+        }
+        _ => events.push(Event::Liuju),
+    }
+
+    Ok(RoundRecord {
+        place: place(kyoku),
+        dealer: kyoku % 4,
+        benchang: json_u32(&head[1])?,
+        gongtuo: json_u32(&head[2])?,
+        hands,
+        dora_indicators: dora_indicators.into_iter().take(1).collect(),
+        events,
+    })
+}
+
+/// tenhou.net/6 の JSON 形式の牌譜を読む。
+pub fn parse_json(json: &str) -> Result<Paipu> {
+    let value: Value = serde_json::from_str(json)?;
+
+    let names = match value.get("name").and_then(Value::as_array) {
+        Some(names) => (names.iter())
+            .map(|name| name.as_str().unwrap_or_default().to_string())
+            .collect(),
+        None => Vec::new(),
+    };
+
+    let akadora = match value.get("rule") {
+        Some(rule) => ["aka", "aka51", "aka52", "aka53"]
+            .iter()
+            .any(|key| rule.get(key).and_then(Value::as_u64).unwrap_or(0) > 0),
+        None => true,
+    };
+
+    let log = value
+        .get("log")
+        .ok_or_else(|| invalid_json("log がありません。"))?;
+    let rounds = (json_array(log)?.iter())
+        .map(json_round)
+        .collect::<Result<_>>()?;
+
+    Ok(Paipu {
+        names,
+        ruleset: ruleset(akadora, true),
+        rounds,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::paipu::{discrepancies, rescore};
+
+    const MJLOG: &str = r#"<mjloggm ver="2.3"><SHUFFLE seed="" ref=""/><GO type="169" lobby="0"/><UN n0="%41" n1="B" n2="C" n3="D" dan="0,0,0,0" rate="1500,1500,1500,1500" sx="M,M,M,M"/><TAIKYOKU oya="0"/><INIT seed="0,0,0,1,2,122" ten="250,250,250,250" oya="0" hai0="0,1,32,68,104,108,112,116,120,124,128,132,133" hai1="4,8,12,20,24,28,76,80,84,85,89,92,53" hai2="52,54,25,29,33,36,40,44,96,100,105,125,129" hai3="37,45,60,69,72,97,106,109,113,117,121,126,130"/><T55/><D55/><AGARI ba="0,0" hai="4,8,12,20,24,28,53,55,76,80,84,85,89,92" machi="55" ten="40,1300,0" yaku="8,1" doraHai="122" who="1" fromWho="0" sc="250,-13,250,13,250,0,250,0" owari="237,-26.0,263,26.0,250,5.0,250,-5.0"/></mjloggm>"#;
+
+    const JSON: &str = r#"{"title":["",""],"name":["A","B","C","D"],"rule":{"disp":"般南喰赤","aka":1},"log":[[[0,0,0],[25000,25000,25000,25000],[44],[],
+        [11,11,19,29,39,41,42,43,44,45,46,47,47],[25],[60],
+        [12,13,14,16,17,18,32,33,34,34,35,36,25],[],[],
+        [25,25,17,18,19,21,22,23,37,38,39,45,46],[],[],
+        [21,23,27,29,31,37,39,41,42,43,44,45,46],[],[],
+        ["和了",[-1300,1300,0,0],[1,0,1,"40符1300点","断幺九(1飜)"]]]]}"#;
+
+    #[test]
+    fn mjlog() {
+        let paipu = parse_mjlog(MJLOG).unwrap();
+        assert_eq!(paipu.names, vec!["A", "B", "C", "D"]);
+        assert_eq!(paipu.ruleset.akadora, 3);
+        assert!(!paipu.ruleset.kiriage_manguan);
+        assert_eq!(paipu.rounds.len(), 1);
+        assert_eq!(paipu.rounds[0].hands[2][0].to_string(), "5P");
+
+        let res = rescore(&paipu);
+        assert_eq!(res.len(), 1);
+        assert!(res[0].is_consistent(), "{}", res[0]);
+    }
+
+    #[test]
+    fn mjlog_discrepancy() {
+        let paipu =
+            parse_mjlog(&MJLOG.replace(r#"ten="40,1300,0""#, r#"ten="40,2000,0""#)).unwrap();
+        let res = discrepancies(&paipu);
+        assert_eq!(res.len(), 1);
+        assert_eq!(
+            res[0].to_string(),
+            "東1局0本場 南家 ロン: 記録 1翻40符 2000点 / 判定 1翻40符 1300点"
+        );
+    }
+
+    #[test]
+    fn mjlog_invalid() {
+        // 壊れた記録でも panic せずにエラーとなる。
+        for (from, to) in &[
+            (r#"who="1" fromWho="0""#, r#"who="5" fromWho="0""#),
+            (r#"who="1" fromWho="0""#, r#"who="1" fromWho="4""#),
+            (
+                r#"ten="250,250,250,250" oya="0""#,
+                r#"ten="250,250,250,250" oya="9""#,
+            ),
+            ("<T55/>", "<T99999999999/>"),
+            (r#"yaku="8,1""#, r#"yaku="8""#),
+            (r#"yaku="8,1""#, r#"yaku="8,4294967295,1,1""#),
+        ] {
+            let xml = MJLOG.replace(from, to);
+            assert_ne!(xml, MJLOG);
+            assert!(matches!(
+                parse_mjlog(&xml),
+                Err(TenhouError::InvalidAttribute(..)) | Err(TenhouError::InvalidXml(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn meld() {
+        // 対面 (席 0) の 5p をポンする。手牌の赤ドラの 5p と 5p を使う。
+        match mjlog_meld(2, 21034, true).unwrap() {
+            Event::Peng {
+                seat: 2,
+                tiles,
+                called,
+            } => {
+                assert_eq!(tiles[0].to_string(), "5P");
+                assert_eq!(tiles[1].to_string(), "5p");
+                assert_eq!(called.to_string(), "5p");
+            }
+            event => panic!("unexpected event: {:?}", event),
+        }
+
+        // 上家の 2p をチーする。
+        match mjlog_meld(1, 22823, true).unwrap() {
+            Event::Chi {
+                seat: 1,
+                tiles,
+                called,
+            } => {
+                assert_eq!(tiles[0].to_string(), "1p");
+                assert_eq!(tiles[1].to_string(), "3p");
+                assert_eq!(called.to_string(), "2p");
+            }
+            event => panic!("unexpected event: {:?}", event),
+        }
+    }
+
+    #[test]
+    fn json() {
+        let paipu = parse_json(JSON).unwrap();
+        assert_eq!(paipu.names, vec!["A", "B", "C", "D"]);
+        assert_eq!(paipu.rounds[0].events.len(), 3);
+
+        let res = rescore(&paipu);
+        assert_eq!(res.len(), 1);
+        assert!(res[0].is_consistent(), "{}", res[0]);
+    }
+
+    #[test]
+    fn json_peng() {
+        // 親の 5p を対面がポンして發を切り、流局する。
+        let json = JSON
+            .replace(
+                "[25,25,17,18,19,21,22,23,37,38,39,45,46],[],[]",
+                r#"[25,25,17,18,19,21,22,23,37,38,39,45,46],["25p2525"],[46]"#,
+            )
+            .replace(
+                r#"["和了",[-1300,1300,0,0],[1,0,1,"40符1300点","断幺九(1飜)"]]"#,
+                r#"["流局",[0,0,0,0]]"#,
+            );
+
+        let paipu = parse_json(&json).unwrap();
+        let events = &paipu.rounds[0].events;
+        assert_eq!(events.len(), 5);
+        match &events[2] {
+            Event::Peng {
+                seat: 2, called, ..
+            } => assert_eq!(called.to_string(), "5p"),
+            event => panic!("unexpected event: {:?}", event),
+        }
+        match &events[3] {
+            Event::Dapai { seat: 2, tile, .. } => assert_eq!(tile.to_string(), "發"),
+            event => panic!("unexpected event: {:?}", event),
+        }
+        assert!(rescore(&paipu).is_empty());
+    }
+
+    #[test]
+    fn json_value_notation() {
+        assert_eq!(json_value("30符1000点").unwrap(), (Some(30), 1000));
+        assert_eq!(json_value("40符700-1300点").unwrap(), (Some(40), 2700));
+        assert_eq!(json_value("50符1600点∀").unwrap(), (Some(50), 4800));
+        assert_eq!(json_value("満貫2000-4000点").unwrap(), (None, 8000));
+        assert!(json_value("満貫").is_err());
+        assert!(json_value("40符4294967295-4294967295点").is_err());
+        assert!(json_value("50符4294967295点∀").is_err());
+    }
+}
@@ -2,6 +2,7 @@
 
 use crate::context::Context;
 use crate::context::Direction;
+use crate::span::Span;
 use std::cmp::Ordering;
 use std::convert::TryFrom;
 use std::fmt;
@@ -91,7 +92,51 @@ pub enum Zipai {
     Zhong,
 }
 
+/// 牌の種類の数 (赤ドラは区別しない) 。
+pub const NUM_KINDS: usize = 34;
+
 impl Tile {
+    /// 全ての種類の牌を一枚ずつ、索子・萬子・筒子・字牌の順に並べたもの。赤ドラは含まない。
+    pub fn all() -> impl Iterator<Item = Tile> {
+        (0..NUM_KINDS).map(Tile::from_index)
+    }
+
+    /// 牌の種類ごとの通し番号 (0..34) を返す。赤ドラかどうかは無視する。
+    ///
+    /// 索子 1-9 が 0..9 、萬子が 9..18 、筒子が 18..27 、字牌が東南西北白發中の順に 27..34 となる。
+    pub fn index(self) -> usize {
+        match self {
+            Tile::Suozi(o) => o.number() as usize - 1,
+            Tile::Wanzi(o) => o.number() as usize - 1 + 9,
+            Tile::Tongzi(o) => o.number() as usize - 1 + 18,
+            Tile::Zipai(z) => z as usize + 27,
+        }
+    }
+
+    /// 通し番号から牌を作る。 `Tile::index()` の逆。赤ドラではない牌を返す。
+    ///
+    /// `index` が範囲外なら panic! する。
+    pub fn from_index(index: usize) -> Tile {
+        const ZIPAIS: [Zipai; 7] = [
+            Zipai::East,
+            Zipai::South,
+            Zipai::West,
+            Zipai::North,
+            Zipai::Bai,
+            Zipai::Fa,
+            Zipai::Zhong,
+        ];
+
+        assert!(index < NUM_KINDS, "牌の番号が範囲外です: {}", index);
+        let order = || Order::new((index % 9) as u8 + 1).unwrap();
+        match index / 9 {
+            0 => Tile::Suozi(order()),
+            1 => Tile::Wanzi(order()),
+            2 => Tile::Tongzi(order()),
+            _ => Tile::Zipai(ZIPAIS[index - 27]),
+        }
+    }
+
     pub fn next(self) -> Option<Tile> {
         match self {
             Tile::Zipai(_) => None,
@@ -188,23 +233,21 @@ impl Tile {
 
     /// 風牌かどうか調べる。風牌は「東南西北」のどれか。
     pub fn is_feng(self) -> bool {
-        match self {
-            Tile::Zipai(Zipai::East) => true,
-            Tile::Zipai(Zipai::South) => true,
-            Tile::Zipai(Zipai::West) => true,
-            Tile::Zipai(Zipai::North) => true,
-            _ => false,
-        }
+        matches!(
+            self,
+            Tile::Zipai(Zipai::East)
+                | Tile::Zipai(Zipai::South)
+                | Tile::Zipai(Zipai::West)
+                | Tile::Zipai(Zipai::North)
+        )
     }
 
     /// 三元牌かどうか調べる。三元牌は「白發中」のどれか。
     pub fn is_sanyuan(self) -> bool {
-        match self {
-            Tile::Zipai(Zipai::Bai) => true,
-            Tile::Zipai(Zipai::Fa) => true,
-            Tile::Zipai(Zipai::Zhong) => true,
-            _ => false,
-        }
+        matches!(
+            self,
+            Tile::Zipai(Zipai::Bai) | Tile::Zipai(Zipai::Fa) | Tile::Zipai(Zipai::Zhong)
+        )
     }
 
     /// 緑一色を構成できる牌かどうか調べる。
@@ -244,20 +287,53 @@ impl Tile {
 pub enum ParseError {
     /// 文字列の長さが変。
     #[error("文字列の長さが変です。")]
-    InvalidStringLen,
+    InvalidStringLen(Span),
 
     /// 予期しない文字が出現した。
-    #[error("予期しない文字です: {}", 0)]
-    InvalidChar(char),
+    #[error("予期しない文字です: {0}")]
+    InvalidChar(char, Span),
 
     /// その他、牌をつくるときにエラーが起きた
-    #[error("牌を作成できませんでした: {}", 0)]
-    TileError(Error),
+    #[error("牌を作成できませんでした: {0}")]
+    TileError(Error, Span),
 }
 
-impl From<Error> for ParseError {
-    fn from(err: Error) -> ParseError {
-        ParseError::TileError(err)
+impl ParseError {
+    /// 入力の中でエラーが起きた範囲。
+    pub fn span(&self) -> Span {
+        match *self {
+            ParseError::InvalidStringLen(span)
+            | ParseError::InvalidChar(_, span)
+            | ParseError::TileError(_, span) => span,
+        }
+    }
+
+    /// 直し方の手がかり。
+    pub fn hint(&self) -> &'static str {
+        match self {
+            ParseError::InvalidStringLen(_) => "牌は 1m や 5M (赤ドラ) 、東のように書きます。",
+            ParseError::InvalidChar(..) => {
+                "番号は 1 から 9 、種類は s, m, p (赤ドラは S, M, P) で書きます。"
+            }
+            ParseError::TileError(..) => {
+                "番号は 1 から 9 です。字牌は東南西北白發中か 1z から 7z で書きます。"
+            }
+        }
+    }
+
+    /// 範囲を `span` に置き換える。
+    pub(crate) fn with_span(self, span: Span) -> ParseError {
+        match self {
+            ParseError::InvalidStringLen(_) => ParseError::InvalidStringLen(span),
+            ParseError::InvalidChar(ch, _) => ParseError::InvalidChar(ch, span),
+            ParseError::TileError(err, _) => ParseError::TileError(err, span),
+        }
+    }
+
+    /// 範囲を `offset` バイトだけ後ろにずらす。
+    pub(crate) fn shift(self, offset: usize) -> ParseError {
+        let span = self.span().shift(offset);
+        self.with_span(span)
     }
 }
 
@@ -280,13 +356,16 @@ impl FromStr for Tile {
 
         // 他の牌
         //------------------------------
-        let mut chars = from.chars();
+        let mut chars = from.char_indices();
         let (order, kind) = match (chars.next(), chars.next(), chars.next()) {
             (Some(order), Some(kind), None) => (order, kind),
-            _ => return Err(ParseError::InvalidStringLen),
+            _ => return Err(ParseError::InvalidStringLen(Span::whole(from))),
+        };
+        let invalid_char = |(pos, ch): (usize, char)| {
+            ParseError::InvalidChar(ch, Span::new(pos, pos + ch.len_utf8()))
         };
 
-        let (tile_constructor, is_red): (fn(Order) -> Tile, bool) = match kind {
+        let (tile_constructor, is_red): (fn(Order) -> Tile, bool) = match kind.1 {
             's' => (Tile::Suozi, false),
             'm' => (Tile::Wanzi, false),
             'p' => (Tile::Tongzi, false),
@@ -295,10 +374,13 @@ impl FromStr for Tile {
             'M' => (Tile::Wanzi, true),
             'P' => (Tile::Tongzi, true),
 
-            ch => return Err(ParseError::InvalidChar(ch)),
+            _ => return Err(invalid_char(kind)),
         };
 
-        let order = Order::new(order as u8 - b'0')?.with_red(is_red);
+        let number = order.1.to_digit(10).ok_or_else(|| invalid_char(order))?;
+        let order = Order::new(number as u8)
+            .map_err(|err| ParseError::TileError(err, Span::whole(from)))?
+            .with_red(is_red);
 
         Ok(tile_constructor(order))
     }
@@ -311,7 +393,7 @@ impl Order {
     ///
     /// もし `order` が範囲外になっていればエラーを返す。
     pub fn new(order: u8) -> Result<Order> {
-        if order < 1 || 9 < order {
+        if !(1..=9).contains(&order) {
             return Err(Error::InvalidOrder);
         }
 
@@ -355,6 +437,11 @@ impl Order {
         self.is_red
     }
 
+    /// 番号 (1-9) を取得する。
+    pub fn number(self) -> u8 {
+        self.order
+    }
+
     /// 中張牌かどうか調べる。
     pub fn is_zhongzhang(self) -> bool {
         self.order != 1 && self.order != 9
@@ -446,13 +533,13 @@ impl hash::Hash for Order {
 
 impl PartialEq<Direction> for Zipai {
     fn eq(&self, other: &Direction) -> bool {
-        match (self, other) {
-            (Zipai::East, Direction::East) => true,
-            (Zipai::South, Direction::South) => true,
-            (Zipai::West, Direction::West) => true,
-            (Zipai::North, Direction::North) => true,
-            _ => false,
-        }
+        matches!(
+            (self, other),
+            (Zipai::East, Direction::East)
+                | (Zipai::South, Direction::South)
+                | (Zipai::West, Direction::West)
+                | (Zipai::North, Direction::North)
+        )
     }
 }
 
@@ -629,6 +716,19 @@ mod tests {
         assert_eq!(bai.num_fan(&make_ctx(Direction::East, Direction::East)), 1);
     }
 
+    #[test]
+    fn index() {
+        for (index, tile) in Tile::all().enumerate() {
+            assert_eq!(tile.index(), index);
+        }
+
+        assert_eq!("1s".parse::<Tile>().unwrap().index(), 0);
+        assert_eq!("5M".parse::<Tile>().unwrap().index(), 13);
+        assert_eq!("9p".parse::<Tile>().unwrap().index(), 26);
+        assert_eq!(Tile::from_index(27), Tile::Zipai(Zipai::East));
+        assert_eq!(Tile::from_index(33), Tile::Zipai(Zipai::Zhong));
+    }
+
     #[test]
     fn order() {
         assert!("4s".parse::<Tile>().unwrap() < "4m".parse::<Tile>().unwrap());
@@ -1,4 +1,5 @@
-use crate::tile::{ParseError as ParseTileError, Tile};
+use crate::span::Span;
+use crate::tile::{ParseError as ParseTileError, Tile, NUM_KINDS};
 use std::fmt;
 use std::iter::FromIterator;
 use std::ops::Deref;
@@ -9,19 +10,19 @@ use thiserror::Error;
 #[derive(Debug, Error)]
 pub enum Error {
     /// アガリ牌が複数回指定されている。
-    #[error("アガリ牌が複数枚指定されています: {}", 0)]
+    #[error("アガリ牌が複数枚指定されています: {0}")]
     InvalidLastTile(Tiles),
 
     /// ポンの長さが変、または全ての牌が同じではない。
-    #[error("変なポンです: {}", 0)]
+    #[error("変なポンです: {0}")]
     InvalidPeng(Tiles),
 
     /// チーの長さが変、または牌の番号が連続していない。例 : 2s4s5s
-    #[error("変なチーです: {}", 0)]
+    #[error("変なチーです: {0}")]
     InvalidChi(Tiles),
 
     /// カンの長さが変、または全ての牌が同じではない。
-    #[error("変なカンです: {}", 0)]
+    #[error("変なカンです: {0}")]
     InvalidGang(Tiles),
 }
 
@@ -135,6 +136,16 @@ impl Tiles {
         Ok(self)
     }
 
+    /// 牌の種類ごとの枚数を数える。添字は `Tile::index()` に対応する。
+    pub fn counts(&self) -> [u8; NUM_KINDS] {
+        let mut counts = [0; NUM_KINDS];
+        for tile in self.inner() {
+            counts[tile.index()] += 1;
+        }
+
+        counts
+    }
+
     pub fn push(&mut self, tile: Tile) {
         self.0.push(tile);
         self.0.sort();
@@ -148,7 +159,10 @@ impl Tiles {
         self.0.remove(idx)
     }
 
-    pub fn drain<R: std::ops::RangeBounds<usize>>(&mut self, range: R) -> std::vec::Drain<Tile> {
+    pub fn drain<R: std::ops::RangeBounds<usize>>(
+        &mut self,
+        range: R,
+    ) -> std::vec::Drain<'_, Tile> {
         self.0.drain(range)
     }
 }
@@ -177,14 +191,44 @@ impl FromIterator<Tile> for Tiles {
 
 #[derive(Debug, Error)]
 pub enum ParseError {
-    #[error("牌のパースに失敗しました: {}", 0)]
+    #[error("牌のパースに失敗しました: {0}")]
     ParseTileError(#[source] ParseTileError),
 
     #[error("数字がついていない s, m, p, z が出現しています。")]
-    EmptyBroadcast,
+    EmptyBroadcast(Span),
 
     #[error("s, m, p, z がついていない数字が出現しています。")]
-    UnterminatedBroadcast,
+    UnterminatedBroadcast(Span),
+}
+
+impl ParseError {
+    /// 入力の中でエラーが起きた範囲。
+    pub fn span(&self) -> Span {
+        match self {
+            ParseError::ParseTileError(err) => err.span(),
+            ParseError::EmptyBroadcast(span) | ParseError::UnterminatedBroadcast(span) => *span,
+        }
+    }
+
+    /// 直し方の手がかり。
+    pub fn hint(&self) -> &'static str {
+        match self {
+            ParseError::ParseTileError(err) => err.hint(),
+            ParseError::EmptyBroadcast(_) => "s, m, p, z の前に番号を書きます。例: 123s",
+            ParseError::UnterminatedBroadcast(_) => "番号の後ろに s, m, p, z を書きます。例: 123s",
+        }
+    }
+
+    /// 範囲を `offset` バイトだけ後ろにずらす。
+    pub(crate) fn shift(self, offset: usize) -> ParseError {
+        match self {
+            ParseError::ParseTileError(err) => ParseError::ParseTileError(err.shift(offset)),
+            ParseError::EmptyBroadcast(span) => ParseError::EmptyBroadcast(span.shift(offset)),
+            ParseError::UnterminatedBroadcast(span) => {
+                ParseError::UnterminatedBroadcast(span.shift(offset))
+            }
+        }
+    }
 }
 
 impl From<ParseTileError> for ParseError {
@@ -201,34 +245,61 @@ impl FromStr for Tiles {
         let mut pending_numbers = Vec::new();
 
         // 文字列が残っている限り繰り返す
-        for ch in s.chars() {
+        for (pos, ch) in s.char_indices() {
+            let end = pos + ch.len_utf8();
             match ch {
-                '1'..='9' => pending_numbers.push(ch),
+                '1'..='9' => pending_numbers.push((pos, ch)),
                 's' | 'm' | 'p' | 'S' | 'M' | 'P' | 'z' => {
                     // pending_numbers が空の場合は許さないことにする。
                     if pending_numbers.is_empty() {
-                        return Err(ParseError::EmptyBroadcast);
+                        return Err(ParseError::EmptyBroadcast(Span::new(pos, end)));
                     }
                     // 繰り返しターミネーターなら pending_numbers にある数字をすべて変換する。
-                    for number in pending_numbers.drain(..) {
-                        res.push(format!("{}{}", number, ch).parse()?);
+                    for (number_pos, number) in pending_numbers.drain(..) {
+                        let tile = format!("{}{}", number, ch).parse().map_err(|err| {
+                            ParseTileError::with_span(err, Span::new(number_pos, end))
+                        })?;
+                        res.push(tile);
                     }
                 }
                 _ => {
                     // 繰り返しが解消されていない場合はエラーとする。
-                    if !pending_numbers.is_empty() {
-                        return Err(ParseError::UnterminatedBroadcast);
+                    if let Some(&(number_pos, _)) = pending_numbers.first() {
+                        return Err(ParseError::UnterminatedBroadcast(Span::new(
+                            number_pos, end,
+                        )));
                     }
                     // そうでない場合は直接パースする。
-                    res.push(ch.to_string().parse()?);
+                    res.push(
+                        ch.to_string()
+                            .parse()
+                            .map_err(|err: ParseTileError| err.shift(pos))?,
+                    );
                 }
             }
         }
 
+        // 最後に残った数字も繰り返しが解消されていない。
+        if let Some(&(number_pos, _)) = pending_numbers.first() {
+            return Err(ParseError::UnterminatedBroadcast(Span::new(
+                number_pos,
+                s.len(),
+            )));
+        }
+
         Ok(Tiles::new(res))
     }
 }
 
+/// 牌を一枚取り除く。赤ドラかどうかまで一致する牌があればそちらを優先する。
+pub(crate) fn remove_tile(tiles: &mut Vec<Tile>, tile: Tile) -> Option<Tile> {
+    let pos = (tiles.iter())
+        .position(|&t| t == tile && t.is_red() == tile.is_red())
+        .or_else(|| tiles.iter().position(|&t| t == tile))?;
+
+    Some(tiles.remove(pos))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -291,5 +362,11 @@ mod tests {
         assert!("あいうえおs".parse::<Tiles>().is_err());
         assert!("1sあいうえお".parse::<Tiles>().is_err());
         assert!("あいうえお".parse::<Tiles>().is_err());
+
+        let span = |s: &str| s.parse::<Tiles>().unwrap_err().span();
+        assert_eq!(span("1s2x"), Span::new(2, 4));
+        assert_eq!(span("1s0m"), Span::new(2, 3));
+        assert_eq!(span("東p"), Span::new(3, 4));
+        assert_eq!(span("1s23"), Span::new(2, 4));
     }
 }
@@ -6,6 +6,7 @@
 //! - Tileset (牌集合) : 牌のかたまりを種別ごとに分類し、ありえない集合をエラーにしたもの。
 //! - AgariTileset (アガリ牌集合) : Tileset をもとに役判定をし、手牌を分解したもの。
 
+use crate::span::Span;
 use crate::tiles::{Error as TilesError, ParseError as ParseTilesError, Tiles};
 use std::fmt;
 use std::str::FromStr;
@@ -15,7 +16,7 @@ pub type Result<T> = std::result::Result<T, Error>;
 
 #[derive(Debug, Error)]
 pub enum Error {
-    #[error("牌集合のエラーです: {}", 0)]
+    #[error("牌集合のエラーです: {0}")]
     TilesError(#[source] TilesError),
 }
 
@@ -61,6 +62,12 @@ pub enum Tag {
 
     /// ドラ。
     Dora,
+
+    /// ドラ表示牌。
+    DoraIndicator,
+
+    /// 裏ドラ表示牌。
+    UradoraIndicator,
 }
 
 impl Tileset {
@@ -90,6 +97,8 @@ impl fmt::Display for Tag {
             Tag::Minggang => write!(b, "明槓"),
             Tag::Angang => write!(b, "暗槓"),
             Tag::Dora => write!(b, "ドラ"),
+            Tag::DoraIndicator => write!(b, "ドラ表示"),
+            Tag::UradoraIndicator => write!(b, "裏ドラ表示"),
         }
     }
 }
@@ -104,19 +113,49 @@ impl fmt::Display for Tileset {
 
 #[derive(Debug, Error)]
 pub enum ParseError {
-    #[error("不明なアノテーションです: {}", 0)]
-    UnknownAnnotation(String),
+    #[error("不明なアノテーションです: {0}")]
+    UnknownAnnotation(String, Span),
 
-    #[error("妙な牌集合です: {}", 0)]
-    TilesetError(Error),
+    #[error("妙な牌集合です: {0}")]
+    TilesetError(Error, Span),
 
-    #[error("牌集合のパースに失敗しました: {}", 0)]
+    #[error("牌集合のパースに失敗しました: {0}")]
     ParseTilesError(#[source] ParseTilesError),
 }
 
-impl From<Error> for ParseError {
-    fn from(err: Error) -> ParseError {
-        ParseError::TilesetError(err)
+impl ParseError {
+    /// 入力の中でエラーが起きた範囲。
+    pub fn span(&self) -> Span {
+        match self {
+            ParseError::UnknownAnnotation(_, span) | ParseError::TilesetError(_, span) => *span,
+            ParseError::ParseTilesError(err) => err.span(),
+        }
+    }
+
+    /// 直し方の手がかり。
+    pub fn hint(&self) -> &'static str {
+        match self {
+            ParseError::UnknownAnnotation(..) => {
+                "使えるのはツモ, ロン, ポン, チー, 明槓, 暗槓, ドラ, ドラ表示, 裏ドラ表示 とその英語名です。"
+            }
+            ParseError::TilesetError(..) => {
+                "アガリ牌は 1 枚、ポンは同じ牌 3 枚、チーは連続した 3 枚、カンは同じ牌 4 枚です。"
+            }
+            ParseError::ParseTilesError(err) => err.hint(),
+        }
+    }
+
+    /// 範囲を `offset` バイトだけ後ろにずらす。
+    pub(crate) fn shift(self, offset: usize) -> ParseError {
+        match self {
+            ParseError::UnknownAnnotation(annot, span) => {
+                ParseError::UnknownAnnotation(annot, span.shift(offset))
+            }
+            ParseError::TilesetError(err, span) => {
+                ParseError::TilesetError(err, span.shift(offset))
+            }
+            ParseError::ParseTilesError(err) => ParseError::ParseTilesError(err.shift(offset)),
+        }
     }
 }
 
@@ -135,33 +174,29 @@ impl FromStr for Tileset {
             .take_while(|&ch| !"123456789東南西北白發中".contains(ch))
             .collect();
 
-        let (tag, rest) = match &*annot {
-            "ツモ" => (Tag::Zimo, &s[6..]),
-            "Tsumo" => (Tag::Zimo, &s[5..]),
-
-            "ロン" => (Tag::Ronghe, &s[6..]),
-            "Ron" => (Tag::Ronghe, &s[3..]),
-
-            "ポン" => (Tag::Peng, &s[6..]),
-            "Pon" => (Tag::Peng, &s[3..]),
-
-            "チー" => (Tag::Chi, &s[6..]),
-            "Chii" => (Tag::Chi, &s[4..]),
-
-            "明槓" => (Tag::Minggang, &s[6..]),
-            "Kong" => (Tag::Minggang, &s[4..]),
-
-            "暗槓" => (Tag::Angang, &s[6..]),
-            "ConcealedKong" => (Tag::Angang, &s[13..]),
-
-            "ドラ" => (Tag::Dora, &s[6..]),
-            "Dora" => (Tag::Dora, &s[4..]),
-
-            "" => (Tag::Hand, &*s),
-            _ => return Err(ParseError::UnknownAnnotation(annot)),
+        let tag = match &*annot {
+            "ツモ" | "Tsumo" => Tag::Zimo,
+            "ロン" | "Ron" => Tag::Ronghe,
+            "ポン" | "Pon" => Tag::Peng,
+            "チー" | "Chii" => Tag::Chi,
+            "明槓" | "Kong" => Tag::Minggang,
+            "暗槓" | "ConcealedKong" => Tag::Angang,
+            "ドラ" | "Dora" => Tag::Dora,
+            "ドラ表示" | "DoraIndicator" => Tag::DoraIndicator,
+            "裏ドラ表示" | "UradoraIndicator" => Tag::UradoraIndicator,
+            "" => Tag::Hand,
+            _ => {
+                let span = Span::new(0, annot.len());
+                return Err(ParseError::UnknownAnnotation(annot, span));
+            }
         };
 
-        Ok(Tileset::new(tag, rest.parse()?)?)
+        let rest = &s[annot.len()..];
+        let tiles = rest
+            .parse::<Tiles>()
+            .map_err(|err| err.shift(annot.len()))?;
+        Tileset::new(tag, tiles)
+            .map_err(|err| ParseError::TilesetError(err, Span::new(annot.len(), s.len())))
     }
 }
 
@@ -197,8 +232,20 @@ mod tests {
             Tileset::new(Tag::Angang, "1p1p1p1p".parse().unwrap()).unwrap()
         );
 
+        assert_eq!(
+            "ドラ表示9p東".parse::<Tileset>().unwrap(),
+            Tileset::new(Tag::DoraIndicator, "9p東".parse().unwrap()).unwrap()
+        );
+
+        assert_eq!(
+            "UradoraIndicator1s".parse::<Tileset>().unwrap(),
+            Tileset::new(Tag::UradoraIndicator, "1s".parse().unwrap()).unwrap()
+        );
+
         match "ポン1p2p3p".parse::<Tileset>() {
-            Err(ParseError::TilesetError(Error::TilesError(TilesError::InvalidPeng(_)))) => {}
+            Err(ParseError::TilesetError(Error::TilesError(TilesError::InvalidPeng(_)), span)) => {
+                assert_eq!(span, Span::new(6, 12));
+            }
             _ => panic!("should cause invalid peng error"),
         }
     }
@@ -1,11 +1,15 @@
 //! 牌集合を定義する。
 
-use crate::context::{Context, Lizhi};
+use crate::context::{Context, Lizhi, ParseContextError};
+use crate::form::Form;
+use crate::span::{self, Span};
 use crate::tile::{Tile, TileKind};
 use crate::tiles::Tiles;
 use crate::tileset::ParseError as ParseTilesetError;
 use crate::tileset::{Tag, Tileset};
+use crate::zhenting::{zhenting, Zhenting};
 use std::fmt;
+use std::str::FromStr;
 use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, TilesetsError>;
@@ -28,29 +32,88 @@ pub enum TilesetsError {
     #[error("アガリ牌が二回以上指定されています。")]
     LastTileSpecifiedMoreThanOnce,
 
-    /// ドラが 2 回以上指定された。
+    /// アガリ牌のない牌集合の集合にアガリ牌が指定された。
+    #[error("アガリ牌は指定できません。")]
+    LastTileSpecified,
+
+    /// ドラが 2 回以上指定された。ドラとドラ表示牌を両方指定した場合もこれになる。
     #[error("ドラが二回以上指定されています。")]
     DorasSpecifiedMoreThanOnce,
 
+    /// 裏ドラが 2 回以上指定された。
+    #[error("裏ドラが二回以上指定されています。")]
+    UradorasSpecifiedMoreThanOnce,
+
     /// 立直と副露が同時に行われている。
     #[error("立直と副露が同時に行われています。")]
     BothLizhiFulou,
 
     /// 赤ドラが多すぎる。
-    #[error("{} に対する赤ドラが {} 枚もあります。", 0, 1)]
+    #[error("{0} に対する赤ドラが {1} 枚もあります。")]
     InvalidNumRed(TileKind, u32),
 
     /// 手牌に同じ牌が多すぎる。
-    #[error("{} の数が多すぎます。", 0)]
+    #[error("{0} の数が多すぎます。")]
     InvalidNumSameTiles(Tile),
 
+    /// フリテンなのにロンしている。
+    #[error("{0}なのでロンできません。")]
+    Zhenting(Zhenting),
+
     /// 手牌の枚数が多すぎるか少なすぎる (多牌か少牌) 。
-    #[error("手牌の数が変です: {} 枚あります。", 0)]
+    #[error("手牌の数が変です: {0} 枚あります。")]
     InvalidNumTiles(u32),
+
+    /// 偶然役が手牌の状態と矛盾している。
+    #[error("{0}")]
+    Situation(SituationError),
+}
+
+/// 偶然役が手牌の状態と矛盾している理由。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Error)]
+pub enum SituationError {
+    /// ツモでしかつかない偶然役がロンでついている。
+    #[error("{}はツモでしかつきません。", .0.name())]
+    RequiresZimo(Form),
+
+    /// ロンでしかつかない偶然役がツモでついている。
+    #[error("{}はロンでしかつきません。", .0.name())]
+    RequiresRonghe(Form),
+
+    /// 槓をしていないのに嶺上開花がついている。
+    #[error("嶺上開花には槓子が必要です。")]
+    NoGang,
+
+    /// 子なのに天和がついている。
+    #[error("天和は親でしかつきません。")]
+    TianheAsChild,
+
+    /// 親なのに地和がついている。
+    #[error("地和は子でしかつきません。")]
+    DiheAsParent,
+
+    /// 親なのに人和がついている。
+    #[error("人和は子でしかつきません。")]
+    RenheAsParent,
+
+    /// 鳴きや暗槓をしたのに天和・地和・人和がついている。
+    #[error("{}は鳴きや暗槓をしているとつきません。", .0.name())]
+    HasCalls(Form),
+
+    /// 立直しているのに天和・地和・人和がついている。
+    #[error("{}は立直しているとつきません。", .0.name())]
+    WithLizhi(Form),
+
+    /// 同時にはつかない偶然役がついている。
+    #[error("{}と{}は同時につきません。", .0.name(), .1.name())]
+    Conflict(Form, Form),
 }
 
 /// 牌集合の集合。これをもとに判定を行う。
+///
+/// `Deserialize` は `serialize` モジュールで実装していて、読み込むときに `Tilesets::new` と同じ検証をする。
 #[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct Tilesets {
     /// コンテキスト (場風・自風やリーチの状態など) 。
     pub context: Context,
@@ -80,20 +143,120 @@ pub struct Tilesets {
     ///
     /// ドラ表示牌ではなくてその次の本来のドラの牌で表されている。
     pub doras: Tiles,
+
+    /// 裏ドラ。
+    ///
+    /// ドラと同様に、裏ドラ表示牌ではなくて本来の裏ドラの牌で表されている。
+    pub uradoras: Tiles,
 }
 
 impl Tilesets {
     /// 牌集合の集合を作る。
     pub fn new(context: Context, tilesets: Vec<Tileset>) -> Result<Tilesets> {
-        let cand = Tilesets::dispatch(context, tilesets)?;
+        let (last, tingpai) = TingpaiTilesets::dispatch(context, tilesets)?;
+        let (last, is_zimo) = last.ok_or(TilesetsError::LastTileNotFound)?;
 
-        cand.check_lizhi_fulou()?;
-        cand.check_num_same_tiles()?;
-        cand.check_num_tiles()?;
+        let cand = tingpai.with_last(last, is_zimo)?;
+        cand.check_zhenting()?;
 
         Ok(cand)
     }
 
+    /// アガリ牌を除いた牌集合の集合を得る。
+    pub fn without_last(&self) -> TingpaiTilesets {
+        TingpaiTilesets {
+            context: self.context.clone(),
+            hand: self.hand.clone(),
+            pengs: self.pengs.clone(),
+            chis: self.chis.clone(),
+            minggangs: self.minggangs.clone(),
+            angangs: self.angangs.clone(),
+            doras: self.doras.clone(),
+            uradoras: self.uradoras.clone(),
+        }
+    }
+
+    /// フリテンでロンしていないかを確かめる。
+    pub(crate) fn check_zhenting(&self) -> Result<()> {
+        if self.is_zimo {
+            return Ok(());
+        }
+
+        match zhenting(&self.without_last()) {
+            None => Ok(()),
+            Some(zhenting) => Err(TilesetsError::Zhenting(zhenting)),
+        }
+    }
+
+    /// 偶然役が手牌の状態と矛盾していないかを確かめる。
+    fn check_situation(&self) -> Result<()> {
+        use SituationError::*;
+        let situation = self.context.situation;
+        let error = |err| Err(TilesetsError::Situation(err));
+
+        for &form in &[
+            Form::Haidimoyue,
+            Form::Lingshangkaihua,
+            Form::Tianhe,
+            Form::Dihe,
+        ] {
+            if situation.contains(form) && !self.is_zimo {
+                return error(RequiresZimo(form));
+            }
+        }
+
+        for &form in &[Form::Hedilaoyu, Form::Chenggang, Form::Renhe] {
+            if situation.contains(form) && self.is_zimo {
+                return error(RequiresRonghe(form));
+            }
+        }
+
+        if situation.lingshangkaihua && self.minggangs.is_empty() && self.angangs.is_empty() {
+            return error(NoGang);
+        }
+
+        if situation.tianhe && !self.context.is_parent() {
+            return error(TianheAsChild);
+        }
+
+        if situation.dihe && self.context.is_parent() {
+            return error(DiheAsParent);
+        }
+
+        if situation.renhe && self.context.is_parent() {
+            return error(RenheAsParent);
+        }
+
+        for &form in &[Form::Tianhe, Form::Dihe, Form::Renhe] {
+            if !situation.contains(form) {
+                continue;
+            }
+
+            if self.did_fulou() || !self.angangs.is_empty() {
+                return error(HasCalls(form));
+            }
+
+            if self.context.lizhi != Lizhi::None {
+                return error(WithLizhi(form));
+            }
+        }
+
+        // 嶺上牌は海底牌にならず、海底牌の後には槓ができない。最初の一巡は海底にならず、加槓もできない。
+        let conflicts = [
+            (Form::Haidimoyue, Form::Lingshangkaihua),
+            (Form::Hedilaoyu, Form::Chenggang),
+            (Form::Hedilaoyu, Form::Renhe),
+            (Form::Chenggang, Form::Renhe),
+        ];
+        for &(x, y) in &conflicts {
+            if situation.contains(x) && situation.contains(y) {
+                return error(Conflict(x, y));
+            }
+        }
+
+        Ok(())
+    }
+
     /// 副露をしたかどうか。
     ///
     /// 副露とはポン・チー・明槓のいずれかである。
@@ -108,8 +271,256 @@ impl Tilesets {
         !self.did_fulou()
     }
 
-    /// 単純に牌集合の列を受け取って、整理した Tilesets を返す。
-    fn dispatch(context: Context, tilesets: Vec<Tileset>) -> Result<Tilesets> {
+    /// ドラ以外の全ての牌をまわすイテレータを得る。
+    pub fn tiles_without_doras<'a>(&'a self) -> impl Iterator<Item = Tile> + 'a {
+        use std::iter::once;
+        once(self.last)
+            .chain(self.hand.iter().copied())
+            .chain(self.pengs.iter().flat_map(|i| i.iter().copied()))
+            .chain(self.chis.iter().flat_map(|i| i.iter().copied()))
+            .chain(self.minggangs.iter().flat_map(|i| i.iter().copied()))
+            .chain(self.angangs.iter().flat_map(|i| i.iter().copied()))
+    }
+
+    /// 裏ドラ表示牌。
+    pub fn uradora_indicators(&self) -> Tiles {
+        self.uradoras
+            .iter()
+            .map(|tile| tile.wrapping_prev())
+            .collect()
+    }
+
+    pub fn display_en(&self) -> TilesetsDisplayEn<'_> {
+        TilesetsDisplayEn(self)
+    }
+}
+
+impl fmt::Display for Tilesets {
+    fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
+        if !self.doras.is_empty() {
+            write!(b, "ドラ{} ", self.doras)?;
+        }
+
+        if !self.uradoras.is_empty() {
+            write!(b, "裏ドラ表示{} ", self.uradora_indicators())?;
+        }
+
+        write!(b, "{}", self.hand)?;
+
+        for peng in &self.pengs {
+            write!(b, " ポン{}", peng)?;
+        }
+
+        for chi in &self.chis {
+            write!(b, " チー{}", chi)?;
+        }
+
+        for minggang in &self.minggangs {
+            write!(b, " 明槓{}", minggang)?;
+        }
+
+        for angang in &self.angangs {
+            write!(b, " 暗槓{}", angang)?;
+        }
+
+        if self.is_zimo {
+            write!(b, " ツモ{}", self.last)?;
+        } else {
+            write!(b, " ロン{}", self.last)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TilesetsDisplayEn<'a>(&'a Tilesets);
+
+impl fmt::Display for TilesetsDisplayEn<'_> {
+    fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
+        let TilesetsDisplayEn(tilesets) = self;
+        if !tilesets.doras.is_empty() {
+            write!(b, "Dora{} ", tilesets.doras)?;
+        }
+
+        if !tilesets.uradoras.is_empty() {
+            write!(b, "UradoraIndicator{} ", tilesets.uradora_indicators())?;
+        }
+
+        write!(b, "{}", tilesets.hand)?;
+
+        for peng in &tilesets.pengs {
+            write!(b, " Pon{}", peng)?;
+        }
+
+        for chi in &tilesets.chis {
+            write!(b, " Chii{}", chi)?;
+        }
+
+        for minggang in &tilesets.minggangs {
+            write!(b, " Kong{}", minggang)?;
+        }
+
+        for angang in &tilesets.angangs {
+            write!(b, " ConcealedKong{}", angang)?;
+        }
+
+        if tilesets.is_zimo {
+            write!(b, " Tsumo{}", tilesets.last)?;
+        } else {
+            write!(b, " Ron{}", tilesets.last)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// アガリ牌がまだ決まっていない牌集合の集合。
+///
+/// 手牌と副露を合わせて 13 枚 (槓子は 3 枚と数える) になっている。待ちの列挙などに使う。
+#[derive(Debug, Clone)]
+pub struct TingpaiTilesets {
+    /// コンテキスト (場風・自風やリーチの状態など) 。
+    pub context: Context,
+
+    /// 手牌。
+    pub hand: Tiles,
+
+    /// ポン。
+    pub pengs: Vec<Tiles>,
+
+    /// チー。
+    pub chis: Vec<Tiles>,
+
+    /// 明槓。
+    pub minggangs: Vec<Tiles>,
+
+    /// 暗槓。
+    pub angangs: Vec<Tiles>,
+
+    /// ドラ。本来のドラの牌で表されている。
+    pub doras: Tiles,
+
+    /// 裏ドラ。本来の裏ドラの牌で表されている。
+    pub uradoras: Tiles,
+}
+
+impl TingpaiTilesets {
+    /// アガリ牌のない牌集合の集合を作る。ツモやロンの牌集合が含まれている場合はエラーとなる。
+    pub fn new(context: Context, tilesets: Vec<Tileset>) -> Result<TingpaiTilesets> {
+        let (last, cand) = TingpaiTilesets::dispatch(context, tilesets)?;
+
+        if last.is_some() {
+            return Err(TilesetsError::LastTileSpecified);
+        }
+
+        cand.check_lizhi_fulou()?;
+        cand.check_num_same_tiles(None)?;
+        cand.check_num_tiles(0)?;
+
+        Ok(cand)
+    }
+
+    /// アガリ牌が区別されていない牌集合の列から、手牌の牌を一種類ずつアガリ牌とみなした候補を作る。
+    ///
+    /// 手牌と副露を合わせて 14 枚 (槓子は 3 枚と数える) でなければエラーとなる。赤ドラは別の種類の牌と
+    /// して扱う。
+    pub fn each_last(
+        context: Context,
+        tilesets: Vec<Tileset>,
+    ) -> Result<Vec<(Tile, TingpaiTilesets)>> {
+        let (last, whole) = TingpaiTilesets::dispatch(context, tilesets)?;
+
+        if last.is_some() {
+            return Err(TilesetsError::LastTileSpecified);
+        }
+
+        whole.check_lizhi_fulou()?;
+        whole.check_num_same_tiles(None)?;
+        if whole.num_tiles() != 14 {
+            return Err(TilesetsError::InvalidNumTiles(whole.num_tiles() as _));
+        }
+
+        // `Tile` の比較は赤ドラを区別しないので、赤ドラかどうかも見て重複を除く。
+        let mut lasts: Vec<Tile> = whole.hand.iter().copied().collect();
+        lasts.sort_by_key(|tile| (tile.index(), tile.is_red()));
+        lasts.dedup_by_key(|tile| (tile.index(), tile.is_red()));
+
+        let res = (lasts.into_iter())
+            .map(|last| {
+                let mut cand = whole.clone();
+                let index = (cand.hand.iter())
+                    .position(|&tile| tile == last && tile.is_red() == last.is_red())
+                    .expect("last is taken from the hand.");
+                cand.hand.remove(index);
+                (last, cand)
+            })
+            .collect();
+
+        Ok(res)
+    }
+
+    /// アガリ牌を加えて、判定に使える牌集合の集合にする。
+    ///
+    /// 同じ牌が 5 枚以上になる場合などはエラーとなる。
+    pub fn with_last(&self, last: Tile, is_zimo: bool) -> Result<Tilesets> {
+        self.check_lizhi_fulou()?;
+        self.check_num_same_tiles(Some(last))?;
+        self.check_num_tiles(1)?;
+
+        let res = Tilesets {
+            context: self.context.clone(),
+            is_zimo,
+            last,
+            hand: self.hand.clone(),
+            pengs: self.pengs.clone(),
+            chis: self.chis.clone(),
+            minggangs: self.minggangs.clone(),
+            angangs: self.angangs.clone(),
+            doras: self.doras.clone(),
+            uradoras: self.uradoras.clone(),
+        };
+        res.check_situation()?;
+
+        Ok(res)
+    }
+
+    /// 副露をしたかどうか。
+    pub fn did_fulou(&self) -> bool {
+        !self.pengs.is_empty() || !self.chis.is_empty() || !self.minggangs.is_empty()
+    }
+
+    /// 門前かどうか。
+    pub fn is_menqian(&self) -> bool {
+        !self.did_fulou()
+    }
+
+    /// 副露や暗槓によって既に確定している面子の数。
+    pub fn num_mianzis(&self) -> usize {
+        self.pengs.len() + self.chis.len() + self.minggangs.len() + self.angangs.len()
+    }
+
+    /// ドラ以外の全ての牌をまわすイテレータを得る。
+    pub fn tiles_without_doras<'a>(&'a self) -> impl Iterator<Item = Tile> + 'a {
+        (self.hand.iter().copied())
+            .chain(self.pengs.iter().flat_map(|i| i.iter().copied()))
+            .chain(self.chis.iter().flat_map(|i| i.iter().copied()))
+            .chain(self.minggangs.iter().flat_map(|i| i.iter().copied()))
+            .chain(self.angangs.iter().flat_map(|i| i.iter().copied()))
+    }
+
+    /// ドラ表示牌・裏ドラ表示牌を含めた全ての牌をまわすイテレータを得る。
+    pub(crate) fn tiles_all<'a>(&'a self) -> impl Iterator<Item = Tile> + 'a {
+        self.tiles_without_doras()
+            .chain(self.doras.iter().map(|tile| tile.wrapping_prev()))
+            .chain(self.uradoras.iter().map(|tile| tile.wrapping_prev()))
+    }
+
+    /// 単純に牌集合の列を受け取って整理する。アガリ牌があればそれも返す。
+    fn dispatch(
+        context: Context,
+        tilesets: Vec<Tileset>,
+    ) -> Result<(Option<(Tile, bool)>, TingpaiTilesets)> {
         fn set<T>(storage: &mut Option<T>, value: T, error: TilesetsError) -> Result<()> {
             if storage.is_none() {
                 *storage = Some(value);
@@ -130,6 +541,10 @@ impl Tilesets {
         let mut angangs = Vec::new();
 
         let mut doras = None;
+        let mut uradoras = None;
+
+        // 表示牌から本来のドラの牌にする。
+        let indicated = |tiles: Tiles| tiles.iter().map(|tile| tile.wrapping_next()).collect();
 
         for tileset in tilesets {
             match tileset.tag {
@@ -159,31 +574,43 @@ impl Tilesets {
                     tileset.tiles,
                     TilesetsError::DorasSpecifiedMoreThanOnce,
                 )?,
+                Tag::DoraIndicator => set(
+                    &mut doras,
+                    indicated(tileset.tiles),
+                    TilesetsError::DorasSpecifiedMoreThanOnce,
+                )?,
+                Tag::UradoraIndicator => set(
+                    &mut uradoras,
+                    indicated(tileset.tiles),
+                    TilesetsError::UradorasSpecifiedMoreThanOnce,
+                )?,
             }
         }
 
         // Get one tile as last.  Its length is already checked in Tileset::new().
-        let last = last
-            .ok_or(TilesetsError::LastTileNotFound)?
-            .into_inner()
-            .into_iter()
-            .next()
-            .expect("last tile must have at least one tile.");
-        let is_zimo = is_zimo.expect("last was some but is_zimo is none.");
+        let last = last.map(|last| {
+            let last = (last.into_inner().into_iter())
+                .next()
+                .expect("last tile must have at least one tile.");
+            let is_zimo = is_zimo.expect("last was some but is_zimo is none.");
+            (last, is_zimo)
+        });
         let hand = hand.ok_or(TilesetsError::HandNotFound)?;
         let doras = doras.unwrap_or_else(|| Tiles::new(Vec::new()));
+        let uradoras = uradoras.unwrap_or_else(|| Tiles::new(Vec::new()));
 
-        Ok(Tilesets {
+        let tingpai = TingpaiTilesets {
             context,
-            is_zimo,
-            last,
             hand,
             pengs,
             chis,
             minggangs,
             angangs,
             doras,
-        })
+            uradoras,
+        };
+
+        Ok((last, tingpai))
     }
 
     /// 立直と副露が同時に起きていないかを確かめる。
@@ -199,29 +626,12 @@ impl Tilesets {
         Ok(())
     }
 
-    /// ドラ以外の全ての牌をまわすイテレータを得る。
-    pub fn tiles_without_doras<'a>(&'a self) -> impl Iterator<Item = Tile> + 'a {
-        use std::iter::once;
-        once(self.last)
-            .chain(self.hand.iter().copied())
-            .chain(self.pengs.iter().flat_map(|i| i.iter().copied()))
-            .chain(self.chis.iter().flat_map(|i| i.iter().copied()))
-            .chain(self.minggangs.iter().flat_map(|i| i.iter().copied()))
-            .chain(self.angangs.iter().flat_map(|i| i.iter().copied()))
-    }
-
-    /// ドラを含めた全ての牌をまわすイテレータを得る。
-    fn tiles_all<'a>(&'a self) -> impl Iterator<Item = Tile> + 'a {
-        self.tiles_without_doras()
-            .chain(self.doras.iter().map(|tile| tile.wrapping_prev()))
-    }
-
     /// 同じ牌の数を確認。同じ牌は 4 枚しかないはず。
-    fn check_num_same_tiles(&self) -> Result<()> {
+    fn check_num_same_tiles(&self, last: Option<Tile>) -> Result<()> {
         use std::collections::HashMap;
         let mut nums = HashMap::new();
 
-        for tile in self.tiles_all() {
+        for tile in self.tiles_all().chain(last) {
             *nums.entry(tile).or_insert(0) += 1;
         }
 
@@ -231,37 +641,34 @@ impl Tilesets {
         }
     }
 
-    /// 牌の数を確認。必ず 14 枚のはず。
-    fn check_num_tiles(&self) -> Result<()> {
-        let last = 1;
-        let hand = self.hand.len();
-        let pengs = self.pengs.len() * 3;
-        let chis = self.chis.len() * 3;
-
-        // 槓は実際は 4 枚あるが、枚数確認では 3 枚と扱う。
-        let minggangs = self.minggangs.len() * 3;
-        let angangs = self.angangs.len() * 3;
+    /// 手牌と副露の牌の数。槓は実際は 4 枚あるが、枚数確認では 3 枚と扱う。
+    fn num_tiles(&self) -> usize {
+        self.hand.len() + self.num_mianzis() * 3
+    }
 
-        let tiles = last + hand + pengs + chis + minggangs + angangs;
+    /// 牌の数を確認。アガリ牌 `last` 枚と合わせて 13 + `last` 枚のはず。
+    fn check_num_tiles(&self, last: usize) -> Result<()> {
+        let tiles = last + self.num_tiles();
 
-        if tiles != 14 {
+        if tiles != 13 + last {
             return Err(TilesetsError::InvalidNumTiles(tiles as _));
         }
 
         Ok(())
     }
-
-    pub fn display_en(&self) -> TilesetsDisplayEn {
-        TilesetsDisplayEn(self)
-    }
 }
 
-impl fmt::Display for Tilesets {
+impl fmt::Display for TingpaiTilesets {
     fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
         if !self.doras.is_empty() {
             write!(b, "ドラ{} ", self.doras)?;
         }
 
+        if !self.uradoras.is_empty() {
+            let indicators: Tiles = self.uradoras.iter().map(|t| t.wrapping_prev()).collect();
+            write!(b, "裏ドラ表示{} ", indicators)?;
+        }
+
         write!(b, "{}", self.hand)?;
 
         for peng in &self.pengs {
@@ -280,78 +687,138 @@ impl fmt::Display for Tilesets {
             write!(b, " 暗槓{}", angang)?;
         }
 
-        if self.is_zimo {
-            write!(b, " ツモ{}", self.last)?;
-        } else {
-            write!(b, " ロン{}", self.last)?;
-        }
-
         Ok(())
     }
 }
 
-#[derive(Debug, Clone, Copy)]
-pub struct TilesetsDisplayEn<'a>(&'a Tilesets);
+#[derive(Debug, Error)]
+pub enum ParseError {
+    #[error("牌集合のパースに失敗しました: {0}")]
+    ParseTilesetError(#[source] ParseTilesetError),
 
-impl fmt::Display for TilesetsDisplayEn<'_> {
-    fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
-        let TilesetsDisplayEn(tilesets) = self;
-        if !tilesets.doras.is_empty() {
-            write!(b, "Dora{} ", tilesets.doras)?;
-        }
+    #[error("牌集合の生成に失敗しました: {0}")]
+    TilesetsError(#[source] TilesetsError, Span),
 
-        write!(b, "{}", tilesets.hand)?;
+    #[error("コンテキストの表記が変です: {0}")]
+    ParseContextError(#[source] ParseContextError, Span),
+}
 
-        for peng in &tilesets.pengs {
-            write!(b, " Pon{}", peng)?;
+impl ParseError {
+    /// 入力の中でエラーが起きた範囲。
+    pub fn span(&self) -> Span {
+        match self {
+            ParseError::ParseTilesetError(err) => err.span(),
+            ParseError::TilesetsError(_, span) | ParseError::ParseContextError(_, span) => *span,
         }
+    }
 
-        for chi in &tilesets.chis {
-            write!(b, " Chii{}", chi)?;
+    /// 直し方の手がかり。
+    pub fn hint(&self) -> &'static str {
+        match self {
+            ParseError::ParseTilesetError(err) => err.hint(),
+            ParseError::TilesetsError(TilesetsError::Situation(_), _) => {
+                "海底・嶺上・天和・地和はツモ、河底・槍槓・人和はロンのときだけ書けます。"
+            }
+            ParseError::TilesetsError(..) => {
+                "手牌とアガリ牌を合わせて 14 枚 (カンがあればその分多く) にし、同じ牌は 4 枚までにします。"
+            }
+            ParseError::ParseContextError(ParseContextError::IppatsuWithoutLizhi, _) => {
+                "一発はリーチの後ろに書きます。"
+            }
+            ParseError::ParseContextError(..) => "例: 東場 南家 リーチ 一発 2本場 供託1",
         }
+    }
+}
 
-        for minggang in &tilesets.minggangs {
-            write!(b, " Kong{}", minggang)?;
-        }
+impl From<ParseTilesetError> for ParseError {
+    fn from(x: ParseTilesetError) -> ParseError {
+        ParseError::ParseTilesetError(x)
+    }
+}
 
-        for angang in &tilesets.angangs {
-            write!(b, " ConcealedKong{}", angang)?;
-        }
+/// 手牌全体をパースしたときに見つかったエラーの一覧。一度のパースでできるだけ多くのエラーを集める。
+#[derive(Debug, Error)]
+pub struct ParseErrors(pub Vec<ParseError>);
 
-        if tilesets.is_zimo {
-            write!(b, " Tsumo{}", tilesets.last)?;
-        } else {
-            write!(b, " Ron{}", tilesets.last)?;
+impl fmt::Display for ParseErrors {
+    fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
+        for (i, err) in self.0.iter().enumerate() {
+            if i > 0 {
+                writeln!(b)?;
+            }
+            write!(b, "{}: {} ({})", err.span(), err, err.hint())?;
         }
-
         Ok(())
     }
 }
 
-#[derive(Debug, Error)]
-pub enum ParseError {
-    #[error("牌集合のパースに失敗しました: {}", 0)]
-    ParseTilesetError(#[source] ParseTilesetError),
+impl From<ParseError> for ParseErrors {
+    fn from(err: ParseError) -> ParseErrors {
+        ParseErrors(vec![err])
+    }
+}
+
+/// 空白で区切られた牌集合とコンテキストの表記を読む。 `context` に表記の内容を書き足していく。
+///
+/// 読めない語があってもそこで止まらずに残りの語を読み、見つかったエラーをすべて返す。
+pub fn parse_tilesets(
+    s: &str,
+    mut context: Context,
+) -> std::result::Result<(Context, Vec<Tileset>), ParseErrors> {
+    let mut tilesets = Vec::new();
+    let mut errors = Vec::new();
+    for (offset, word) in span::words(s) {
+        match context.annotate(word) {
+            Ok(true) => continue,
+            Ok(false) => {}
+            Err(err) => {
+                let span = Span::new(offset, offset + word.len());
+                errors.push(ParseError::ParseContextError(err, span));
+                continue;
+            }
+        }
 
-    #[error("牌集合の生成に失敗しました: {}", 0)]
-    TilesetsError(#[source] TilesetsError),
+        match word.parse::<Tileset>() {
+            Ok(tileset) => tilesets.push(tileset),
+            Err(err) => errors.push(err.shift(offset).into()),
+        }
+    }
+
+    if !errors.is_empty() {
+        return Err(ParseErrors(errors));
+    }
+
+    Ok((context, tilesets))
 }
 
-impl From<ParseTilesetError> for ParseError {
-    fn from(x: ParseTilesetError) -> ParseError {
-        ParseError::ParseTilesetError(x)
+/// 空白で区切られた牌集合 (例: `ドラ5m 1m2m3m4p5p6p7s8s9s東東 ポン南南南 ロン東`) から牌集合の集合を作
+/// る。 `東場 南家 リーチ 一発` のようなコンテキストの表記を混ぜてもよい。 `Display` と `display_en` の
+/// 出力も読める。
+impl FromStr for Tilesets {
+    type Err = ParseErrors;
+
+    fn from_str(s: &str) -> std::result::Result<Tilesets, ParseErrors> {
+        let (context, tilesets) = parse_tilesets(s, Context::default())?;
+        Tilesets::new(context, tilesets)
+            .map_err(|err| ParseError::TilesetsError(err, Span::whole(s)).into())
     }
 }
 
-impl From<TilesetsError> for ParseError {
-    fn from(x: TilesetsError) -> ParseError {
-        ParseError::TilesetsError(x)
+/// `Tilesets` と同様だが、アガリ牌を含まない。
+impl FromStr for TingpaiTilesets {
+    type Err = ParseErrors;
+
+    fn from_str(s: &str) -> std::result::Result<TingpaiTilesets, ParseErrors> {
+        let (context, tilesets) = parse_tilesets(s, Context::default())?;
+        TingpaiTilesets::new(context, tilesets)
+            .map_err(|err| ParseError::TilesetsError(err, Span::whole(s)).into())
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::context::Direction;
 
     #[test]
     fn invalid_num_same_tiles() {
@@ -364,4 +831,199 @@ mod tests {
         )
         .is_err());
     }
+
+    #[test]
+    fn parse() {
+        let tilesets: Tilesets = "ドラ表示4m 1m2m3m4p5p6p東 ポン南南南 チー7s8s9s ロン東"
+            .parse()
+            .unwrap();
+        assert_eq!(tilesets.doras.to_string(), "5m");
+        assert_eq!(tilesets.pengs.len(), 1);
+        assert_eq!(tilesets.chis.len(), 1);
+        assert_eq!(tilesets.last.to_string(), "東");
+        assert!(!tilesets.is_zimo);
+
+        let errors = "1m2m3m ロン1x".parse::<Tilesets>().unwrap_err();
+        assert!(matches!(&errors.0[..], [ParseError::ParseTilesetError(_)]));
+        assert!(matches!(
+            &"1m2m3m4p5p6p東 ポン南南南 チー7s8s9s"
+                .parse::<Tilesets>()
+                .unwrap_err()
+                .0[..],
+            [ParseError::TilesetsError(
+                TilesetsError::LastTileNotFound,
+                _
+            )]
+        ));
+    }
+
+    #[test]
+    fn round_trip() {
+        for hand in &[
+            "1m2m3m4p5M6p7s8s9s東東南南 ロン南",
+            "ドラ5m6m 裏ドラ表示1p 2m3m4m4p5p6p3s3s5m6m 暗槓7s7s7s7s ツモ7m",
+            "3s 明槓中中中中 ポン白白白 チー1m2m3m チー7p8p9p ロン3s",
+        ] {
+            let tilesets: Tilesets = hand.parse().unwrap();
+            let ja = tilesets.to_string();
+            let en = tilesets.display_en().to_string();
+            assert_eq!(ja.parse::<Tilesets>().unwrap().to_string(), ja);
+            assert_eq!(en.parse::<Tilesets>().unwrap().to_string(), ja);
+        }
+
+        let tilesets: Tilesets = "南場 西家 リーチ 一発 1m2m3m4p5p6p7s8s9s東東南南 ロン南"
+            .parse()
+            .unwrap();
+        assert_eq!(tilesets.context.player, Direction::West);
+        assert_eq!(tilesets.context.lizhi, Lizhi::LizhiIppatsu);
+
+        let tingpai: TingpaiTilesets = "ドラ5m 2m3m4m4p5p6p7s8s9s3s3s5m6m".parse().unwrap();
+        let ja = tingpai.to_string();
+        assert!(ja.starts_with("ドラ5m "), "{}", ja);
+        assert_eq!(ja.parse::<TingpaiTilesets>().unwrap().to_string(), ja);
+    }
+
+    #[test]
+    fn parse_errors() {
+        // 読めない語がいくつあってもすべて報告する。
+        let input = "1m2m3m4p5p6p7s8s9s東 ポン1x1x1x 一発 ロン南南 ツモ東";
+        let errors = input.parse::<Tilesets>().unwrap_err();
+        let spans: Vec<_> = errors.0.iter().map(|err| err.span()).collect();
+        assert_eq!(
+            spans,
+            vec![Span::new(28, 30), Span::new(35, 41), Span::new(48, 54)]
+        );
+        assert_eq!(&input[spans[0].start..spans[0].end], "1x");
+        assert_eq!(&input[spans[1].start..spans[1].end], "一発");
+        assert_eq!(&input[spans[2].start..spans[2].end], "南南");
+        assert!(matches!(
+            errors.0[1],
+            ParseError::ParseContextError(ParseContextError::IppatsuWithoutLizhi, _)
+        ));
+        assert_eq!(errors.0[1].hint(), "一発はリーチの後ろに書きます。");
+        assert_eq!(errors.to_string().lines().count(), 3);
+
+        // 変な入力でも panic しない。
+        for input in &[
+            "",
+            " ",
+            "ツ",
+            "ツモ",
+            "!m",
+            "0m",
+            "10m",
+            "1m2",
+            "m",
+            "z8",
+            "8z",
+            "9z",
+            "1Z",
+            "5z5",
+            "ドラ表示",
+            "裏ドラ",
+            "ポン",
+            "１m",
+            "1ｍ",
+            "ロン東東 ロン",
+            "暗槓1m1m1m 1m2m",
+            "🀄 🀇",
+            "[",
+            "+",
+            "(",
+        ] {
+            assert!(input.parse::<Tilesets>().is_err(), "{}", input);
+            assert!(input.parse::<TingpaiTilesets>().is_err(), "{}", input);
+            assert!(crate::compact::parse(input).is_err(), "{}", input);
+        }
+    }
+
+    #[test]
+    fn situation() {
+        use SituationError::*;
+        let check = |s: &str| {
+            let (context, tilesets) = parse_tilesets(s, Context::default()).unwrap();
+            match Tilesets::new(context, tilesets) {
+                Ok(_) => None,
+                Err(TilesetsError::Situation(err)) => Some(err),
+                Err(err) => panic!("unexpected error: {}", err),
+            }
+        };
+
+        let menqian = "1m2m3m4p5p6p7s8s9s東東南南";
+        let gang = "1m2m3m4p5p6p7s8s9s東 暗槓南南南南";
+        assert_eq!(check(&format!("海底 {} ツモ南", menqian)), None);
+        assert_eq!(check(&format!("槍槓 {} ロン南", menqian)), None);
+        assert_eq!(check(&format!("嶺上 {} ツモ東", gang)), None);
+        assert_eq!(check(&format!("天和 {} ツモ南", menqian)), None);
+        assert_eq!(check(&format!("南家 地和 {} ツモ南", menqian)), None);
+
+        assert_eq!(
+            check(&format!("海底 {} ロン南", menqian)),
+            Some(RequiresZimo(Form::Haidimoyue))
+        );
+        assert_eq!(
+            check(&format!("槍槓 {} ツモ南", menqian)),
+            Some(RequiresRonghe(Form::Chenggang))
+        );
+        assert_eq!(check(&format!("嶺上 {} ツモ南", menqian)), Some(NoGang));
+        assert_eq!(
+            check(&format!("南家 天和 {} ツモ南", menqian)),
+            Some(TianheAsChild)
+        );
+        assert_eq!(
+            check(&format!("地和 {} ツモ南", menqian)),
+            Some(DiheAsParent)
+        );
+        assert_eq!(
+            check(&format!("南家 地和 {} ツモ東", gang)),
+            Some(HasCalls(Form::Dihe))
+        );
+        assert_eq!(
+            check(&format!("リーチ 天和 {} ツモ南", menqian)),
+            Some(WithLizhi(Form::Tianhe))
+        );
+        assert_eq!(
+            check(&format!("海底 嶺上 {} ツモ東", gang)),
+            Some(Conflict(Form::Haidimoyue, Form::Lingshangkaihua))
+        );
+        assert_eq!(
+            check(&format!("人和 {} ロン南", menqian)),
+            Some(RenheAsParent)
+        );
+        assert_eq!(
+            check(&format!("南家 人和 {} ツモ南", menqian)),
+            Some(RequiresRonghe(Form::Renhe))
+        );
+        assert_eq!(
+            check(&format!("河底 槍槓 {} ロン南", menqian)),
+            Some(Conflict(Form::Hedilaoyu, Form::Chenggang))
+        );
+        assert_eq!(
+            RequiresZimo(Form::Haidimoyue).to_string(),
+            "海底撈月はツモでしかつきません。"
+        );
+
+        let errors = format!("河底 {} ツモ南", menqian)
+            .parse::<Tilesets>()
+            .unwrap_err();
+        assert!(errors
+            .to_string()
+            .contains("河底撈魚はロンでしかつきません。"));
+    }
+
+    #[test]
+    fn each_last_red() {
+        // 赤ドラの 5m と普通の 5m は別の候補になり、それぞれの牌が取り除かれる。
+        let (context, tilesets) =
+            parse_tilesets("3m4m5m5M6m7m4p5p6p3s3s7s8s9s", Context::default()).unwrap();
+        let cands = TingpaiTilesets::each_last(context, tilesets).unwrap();
+        let fives: Vec<_> = (cands.iter())
+            .filter(|(last, _)| *last == "5m".parse().unwrap())
+            .map(|(last, cand)| (last.to_string(), cand.hand.iter().any(|t| t.is_red())))
+            .collect();
+        assert_eq!(
+            fives,
+            vec![("5m".to_string(), true), ("5M".to_string(), false)]
+        );
+    }
 }
@@ -0,0 +1,264 @@
+//! 聴牌している手牌の待ちを列挙する。
+
+use crate::judge::{judge, Judge};
+use crate::ruleset::Ruleset;
+use crate::shanten::Shanten;
+use crate::tile::{Tile, NUM_KINDS};
+use crate::tiles::Tiles;
+use crate::tilesets::TingpaiTilesets;
+use crate::zhenting::zhenting;
+use log::debug;
+use std::fmt;
+
+/// 待ち牌と、その牌でアガったときの判定。
+#[derive(Debug, Clone)]
+pub struct Machi {
+    /// 待ち牌。赤ドラではない牌で表されている。
+    pub tile: Tile,
+
+    /// ロンでアガったときの判定。役がないかフリテンなら None 。
+    pub ronghe: Option<Judge>,
+
+    /// ツモでアガったときの判定。役がなければ None 。
+    pub zimo: Option<Judge>,
+}
+
+impl Machi {
+    /// ロンかツモの少なくとも一方で役があるかどうか。
+    pub fn has_yaku(&self) -> bool {
+        self.ronghe.is_some() || self.zimo.is_some()
+    }
+
+    pub fn display_en(&self) -> MachiDisplayEn<'_> {
+        MachiDisplayEn(self)
+    }
+}
+
+impl fmt::Display for Machi {
+    fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
+        write!(b, "{}:", self.tile)?;
+
+        match &self.ronghe {
+            Some(judge) => write!(
+                b,
+                " ロン {}",
+                judge
+                    .total()
+                    .display_full(is_parent(judge), judge.ruleset())
+            )?,
+            None => write!(b, " ロン 役なし")?,
+        }
+
+        match &self.zimo {
+            Some(judge) => write!(
+                b,
+                " ツモ {}",
+                judge
+                    .total()
+                    .display_full(is_parent(judge), judge.ruleset())
+            )?,
+            None => write!(b, " ツモ 役なし")?,
+        }
+
+        Ok(())
+    }
+}
+
+pub struct MachiDisplayEn<'a>(&'a Machi);
+
+impl fmt::Display for MachiDisplayEn<'_> {
+    fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
+        let MachiDisplayEn(machi) = self;
+        write!(b, "{}:", machi.tile)?;
+
+        match &machi.ronghe {
+            Some(judge) => write!(
+                b,
+                " Ron {}",
+                judge
+                    .total()
+                    .display_full_en(is_parent(judge), judge.ruleset())
+            )?,
+            None => write!(b, " Ron No Yaku")?,
+        }
+
+        match &machi.zimo {
+            Some(judge) => write!(
+                b,
+                " Tsumo {}",
+                judge
+                    .total()
+                    .display_full_en(is_parent(judge), judge.ruleset())
+            )?,
+            None => write!(b, " Tsumo No Yaku")?,
+        }
+
+        Ok(())
+    }
+}
+
+fn is_parent(judge: &Judge) -> bool {
+    judge.tilesets().context.is_parent()
+}
+
+/// 聴牌している手牌の待ち牌を全て列挙し、それぞれについてロンとツモの判定を行う。
+///
+/// 聴牌していなければ空になる。状況役の指定などでロンかツモの一方しか成り立たないときは、もう一方の
+/// 判定が None になる。待ちの形は `machi_tiles` と同じだが、ここでは実際にアガれる牌だけを
+/// 返すので、手牌・副露とドラ表示牌で 4 枚とも見えている牌は含めない。フリテンの場合はロンの判定が
+/// None になる。
+pub fn machis(tingpai: &TingpaiTilesets, ruleset: &Ruleset) -> Vec<Machi> {
+    debug!("待ちを列挙します: {}", tingpai);
+    let is_zhenting = zhenting(tingpai).is_some();
+
+    let mut res = Vec::new();
+    for tile in machi_tiles(tingpai) {
+        // 海底摸月などの状況役はロンかツモの一方でしか成り立たないので、それぞれ別に判定する。
+        let ronghe = tingpai.with_last(tile, false);
+        let zimo = tingpai.with_last(tile, true);
+        if ronghe.is_err() && zimo.is_err() {
+            debug!("{} は待ちになりません。", tile);
+            continue;
+        }
+
+        let ronghe = (ronghe.ok())
+            .and_then(|ronghe| judge(&ronghe, ruleset))
+            .filter(|_| !is_zhenting);
+        let zimo = zimo.ok().and_then(|zimo| judge(&zimo, ruleset));
+        res.push(Machi { tile, ronghe, zimo });
+    }
+
+    res
+}
+
+/// 待ち牌を列挙する。役の有無やフリテンは問わない。
+///
+/// 自分の手牌・副露で 4 枚とも使っている牌 (純カラ) は待ちに含めない。ドラ表示牌など他から見えている
+/// 牌は数えないので、残りが全て表示牌になっていても待ちとして扱う。
+pub fn machi_tiles(tingpai: &TingpaiTilesets) -> Vec<Tile> {
+    let mut counts = tingpai.hand.counts();
+    let used = tingpai.tiles_without_doras().collect::<Tiles>().counts();
+    let num_mianzis = tingpai.num_mianzis();
+
+    let mut res = Vec::new();
+    for index in 0..NUM_KINDS {
+        if used[index] >= 4 {
+            continue;
+        }
+
+        // 向聴数で素早くアガリ形になるかを確かめる。
+        counts[index] += 1;
+        let is_agari = Shanten::from_counts(&counts, num_mianzis).is_agari();
+        counts[index] -= 1;
+
+        if is_agari {
+            res.push(Tile::from_index(index));
+        }
+    }
+
+    res
+}
+
+/// 聴牌しているかどうか。役がなくても形式上聴牌していれば真となる。
+pub fn is_tingpai(tingpai: &TingpaiTilesets) -> bool {
+    !machi_tiles(tingpai).is_empty()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::Context;
+
+    fn parse(from: &str) -> TingpaiTilesets {
+        from.parse().unwrap()
+    }
+
+    fn tiles(machis: &[Machi]) -> Vec<String> {
+        machis.iter().map(|m| m.tile.to_string()).collect()
+    }
+
+    #[test]
+    fn shuangpeng() {
+        let tingpai = parse("2m3m4m4p5p6p7s8s9s東東南南");
+        let machis = machis(&tingpai, &Ruleset::default());
+        assert_eq!(tiles(&machis), vec!["東", "南"]);
+
+        // 東場東家なので東はダブ東になる。
+        assert_eq!(machis[0].ronghe.as_ref().unwrap().total().fan, 2);
+
+        // 南は役がないのでロンできないが、ツモなら門前清自摸和がつく。
+        assert!(machis[1].ronghe.is_none());
+        assert_eq!(machis[1].zimo.as_ref().unwrap().total().fan, 1);
+        assert!(machis[1].has_yaku());
+        assert_eq!(machis[1].to_string(), "南: ロン 役なし ツモ 1翻40符 2000点");
+    }
+
+    #[test]
+    fn jiulianbaodeng() {
+        let tingpai = parse("1m1m1m2m3m4m5m6m7m8m9m9m9m");
+        let machis = machis(&tingpai, &Ruleset::default());
+        assert_eq!(machis.len(), 9);
+        assert!(machis
+            .iter()
+            .all(|m| m.ronghe.is_some() && m.zimo.is_some()));
+    }
+
+    #[test]
+    fn with_fulou() {
+        let tingpai = parse("2m3m4m4p5p6p7s8s東東 ポン白白白");
+        let machis = machis(&tingpai, &Ruleset::default());
+        assert_eq!(tiles(&machis), vec!["6s", "9s"]);
+        assert!(is_tingpai(&tingpai));
+    }
+
+    #[test]
+    fn chunkara() {
+        // 4s を全て自分で使っているので、単騎待ちにはならない。
+        let tingpai = parse("4s4s4s4s 暗槓1p1p1p1p ポン白白白 ポン中中中");
+        assert!(machis(&tingpai, &Ruleset::default()).is_empty());
+        assert!(!is_tingpai(&tingpai));
+
+        // 4 枚目がドラ表示牌でも、手牌と副露で使っていなければ待ちになる。
+        let tingpai = parse("ドラ表示1m 1m1m1m2m3m4m5m6m7m8m9m9m9m");
+        assert_eq!(machi_tiles(&tingpai).len(), 9);
+        assert!(is_tingpai(&tingpai));
+
+        // ただし 1m でアガることはできない。
+        let machis = machis(&tingpai, &Ruleset::default());
+        assert_eq!(machis.len(), 8);
+        assert!(machis.iter().all(|m| m.tile.to_string() != "1m"));
+    }
+
+    #[test]
+    fn with_situation() {
+        // 海底はツモでしか成り立たないが、待ちは残る。
+        let tingpai = parse("海底 2m3m4m4p5p6p7s8s9s東東南南");
+        let machis = machis(&tingpai, &Ruleset::default());
+        assert_eq!(tiles(&machis), vec!["東", "南"]);
+        assert!(machis.iter().all(|m| m.ronghe.is_none()));
+        assert_eq!(machis[1].zimo.as_ref().unwrap().total().fan, 2);
+
+        // 河底はロンでしか成り立たない。
+        let tingpai = parse("河底 2m3m4m4p5p6p7s8s9s東東南南");
+        let houtei = super::machis(&tingpai, &Ruleset::default());
+        assert_eq!(tiles(&houtei), vec!["東", "南"]);
+        assert!(houtei.iter().all(|m| m.zimo.is_none()));
+        assert_eq!(houtei[1].ronghe.as_ref().unwrap().total().fan, 1);
+    }
+
+    #[test]
+    fn not_tingpai() {
+        let tingpai = parse("1m4m7m2p5p8p3s6s9s東南西北");
+        assert!(machis(&tingpai, &Ruleset::default()).is_empty());
+        assert!(!is_tingpai(&tingpai));
+    }
+
+    #[test]
+    fn last_specified() {
+        let tilesets = vec![
+            "2m3m4m4p5p6p7s8s9s東東南".parse().unwrap(),
+            "ロン南".parse().unwrap(),
+        ];
+        assert!(TingpaiTilesets::new(Context::default(), tilesets).is_err());
+    }
+}
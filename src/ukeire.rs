@@ -0,0 +1,251 @@
+//! 受け入れ (牌効率) を計算する。
+//!
+//! 14 枚の手牌から何を切るとよいかを調べるために、打牌の候補ごとに向聴数と有効牌を列挙する。
+
+use crate::shanten::Shanten;
+use crate::tile::{Tile, NUM_KINDS};
+use crate::tiles::Tiles;
+use log::debug;
+use std::fmt;
+
+/// 有効牌とその残り枚数。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Youxiaopai {
+    /// 有効牌。赤ドラではない牌で表されている。
+    pub tile: Tile,
+
+    /// 見えていない残りの枚数。
+    pub remaining: u32,
+}
+
+/// 打牌の候補と、それを切ったときの向聴数・有効牌。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dapai {
+    /// 切る牌。
+    pub tile: Tile,
+
+    /// 切った後の向聴数。
+    pub shanten: i32,
+
+    /// 切った後に引くと向聴数が下がる牌。
+    pub youxiaopais: Vec<Youxiaopai>,
+}
+
+impl Dapai {
+    /// 有効牌の残り枚数の合計。
+    pub fn num_youxiaopais(&self) -> u32 {
+        self.youxiaopais.iter().map(|y| y.remaining).sum()
+    }
+
+    pub fn display_en(&self) -> DapaiDisplayEn<'_> {
+        DapaiDisplayEn(self)
+    }
+}
+
+impl fmt::Display for Dapai {
+    fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
+        write!(b, "打{} ", self.tile)?;
+
+        match self.shanten {
+            0 => write!(b, "聴牌")?,
+            n => write!(b, "{}向聴", n)?,
+        }
+
+        write!(b, " ")?;
+        for youxiaopai in &self.youxiaopais {
+            write!(b, "{}", youxiaopai.tile)?;
+        }
+
+        write!(b, " {}枚", self.num_youxiaopais())
+    }
+}
+
+pub struct DapaiDisplayEn<'a>(&'a Dapai);
+
+impl fmt::Display for DapaiDisplayEn<'_> {
+    fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
+        let DapaiDisplayEn(dapai) = self;
+        write!(b, "Discard {} ", dapai.tile)?;
+
+        match dapai.shanten {
+            0 => write!(b, "Tenpai")?,
+            n => write!(b, "{} Shanten", n)?,
+        }
+
+        write!(b, " ")?;
+        for youxiaopai in &dapai.youxiaopais {
+            write!(b, "{}", youxiaopai.tile)?;
+        }
+
+        write!(b, " {} Tiles", dapai.num_youxiaopais())
+    }
+}
+
+/// 14 枚の手牌 (から確定している面子の分を除いたもの) について、打牌の候補ごとに受け入れを計算す
+/// る。
+///
+/// `visible` には河・副露・ドラ表示牌など、手牌以外で見えている牌を全て渡す。自分の副露や暗槓の牌
+/// も含める。結果は向聴数が小さい順、有効牌が多い順に並ぶ。同じ種類の牌は一度だけ調べる。
+pub fn analyze(hand: &Tiles, num_mianzis: usize, visible: &Tiles) -> Vec<Dapai> {
+    let mut counts = hand.counts();
+    let seen = {
+        let mut seen = visible.counts();
+        for (s, c) in seen.iter_mut().zip(counts.iter()) {
+            *s += c;
+        }
+        seen
+    };
+
+    let mut res = Vec::new();
+    for index in 0..NUM_KINDS {
+        if counts[index] == 0 {
+            continue;
+        }
+
+        counts[index] -= 1;
+        let shanten = Shanten::from_counts(&counts, num_mianzis).min();
+        let youxiaopais = youxiaopais(&mut counts, num_mianzis, shanten, &seen);
+        counts[index] += 1;
+
+        res.push(Dapai {
+            tile: discard_tile(hand, index),
+            shanten,
+            youxiaopais,
+        });
+    }
+
+    res.sort_by_key(|d| (d.shanten, std::cmp::Reverse(d.num_youxiaopais())));
+    debug!("{} の受け入れ: {:?}", hand, res);
+
+    res
+}
+
+/// 13 枚の手牌について、向聴数を下げる牌を列挙する。
+fn youxiaopais(
+    counts: &mut [u8; NUM_KINDS],
+    num_mianzis: usize,
+    shanten: i32,
+    seen: &[u8; NUM_KINDS],
+) -> Vec<Youxiaopai> {
+    let mut res = Vec::new();
+
+    for (index, is_candidate) in candidates(counts, num_mianzis).iter().enumerate() {
+        if !is_candidate || counts[index] >= 4 {
+            continue;
+        }
+
+        counts[index] += 1;
+        let improved = Shanten::from_counts(counts, num_mianzis).min() < shanten;
+        counts[index] -= 1;
+
+        if improved {
+            res.push(Youxiaopai {
+                tile: Tile::from_index(index),
+                remaining: 4u32.saturating_sub(seen[index] as u32),
+            });
+        }
+    }
+
+    res
+}
+
+/// 有効牌になりうる牌の種類を求める。
+///
+/// 一般形では手牌のどれかと同じか 2 つ以内に隣接する牌しか有効牌にならない。面子が確定していない
+/// ときは国士無双のための幺九牌と、七対子で種類が足りないときの全ての牌も候補にする。
+fn candidates(counts: &[u8; NUM_KINDS], num_mianzis: usize) -> [bool; NUM_KINDS] {
+    let mut res = [false; NUM_KINDS];
+
+    for index in (0..NUM_KINDS).filter(|&i| counts[i] > 0) {
+        let tile = Tile::from_index(index);
+        let prev = tile.prev();
+        let next = tile.next();
+        let neighbors = [
+            Some(tile),
+            prev,
+            next,
+            prev.and_then(Tile::prev),
+            next.and_then(Tile::next),
+        ];
+
+        for neighbor in neighbors.iter().flatten() {
+            res[neighbor.index()] = true;
+        }
+    }
+
+    if num_mianzis == 0 {
+        let num_kinds = counts.iter().filter(|&&c| c > 0).count();
+        for (index, r) in res.iter_mut().enumerate() {
+            if num_kinds < 7 || Tile::from_index(index).is_yaojiu() {
+                *r = true;
+            }
+        }
+    }
+
+    res
+}
+
+/// 手牌の中から、通し番号が `index` の切る牌を選ぶ。赤ドラでない牌があればそちらを優先する。
+fn discard_tile(hand: &Tiles, index: usize) -> Tile {
+    (hand.iter().copied())
+        .filter(|tile| tile.index() == index)
+        .min_by_key(|tile| tile.is_red())
+        .expect("the hand must contain the tile to discard.")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn tiles(tiles: &str) -> Tiles {
+        tiles.parse().unwrap()
+    }
+
+    #[test]
+    fn tingpai() {
+        let res = analyze(&tiles("23m456p789s東東南南南西"), 0, &tiles(""));
+        assert_eq!(res[0].tile, "西".parse().unwrap());
+        assert_eq!(res[0].shanten, 0);
+        assert_eq!(res[0].num_youxiaopais(), 8);
+        assert_eq!(res[0].to_string(), "打西 聴牌 1m4m 8枚");
+        assert_eq!(
+            res[0].display_en().to_string(),
+            "Discard 西 Tenpai 1m4m 8 Tiles"
+        );
+    }
+
+    #[test]
+    fn visible() {
+        let res = analyze(&tiles("23m456p789s東東南南南西"), 0, &tiles("1m1m4m"));
+        assert_eq!(res[0].tile, "西".parse().unwrap());
+        assert_eq!(
+            res[0].youxiaopais,
+            vec![
+                Youxiaopai {
+                    tile: "1m".parse().unwrap(),
+                    remaining: 2,
+                },
+                Youxiaopai {
+                    tile: "4m".parse().unwrap(),
+                    remaining: 3,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn with_mianzis() {
+        let res = analyze(&tiles("13m9p東東"), 3, &tiles("白白白"));
+        assert_eq!(res[0].tile, "9p".parse().unwrap());
+        assert_eq!(res[0].shanten, 0);
+        assert_eq!(res[0].num_youxiaopais(), 4);
+    }
+
+    #[test]
+    fn kokushimuso() {
+        let res = analyze(&tiles("19m19p19s東南西北白發5m5m"), 0, &tiles(""));
+        assert_eq!(res[0].tile, "5m".parse().unwrap());
+        assert_eq!(res[0].shanten, 1);
+        assert_eq!(res[0].num_youxiaopais(), 4 + 12 * 3);
+    }
+}
@@ -0,0 +1,225 @@
+//! 山 (牌山) を定義する。
+
+use crate::ruleset::Ruleset;
+use crate::tile::{Tile, TileKind, NUM_KINDS};
+use crate::tiles::Tiles;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use thiserror::Error;
+
+/// 牌の総数。
+pub const NUM_TILES: usize = 136;
+
+/// 王牌の枚数。
+pub const NUM_DEAD_WALL: usize = 14;
+
+/// 嶺上牌の枚数。槓は一局に 4 回までしかできない。
+pub const NUM_LINGSHANG: usize = 4;
+
+pub type Result<T> = std::result::Result<T, WallError>;
+
+#[derive(Debug, Error)]
+pub enum WallError {
+    /// 牌の数が 136 枚ではない。
+    #[error("山の牌の数が変です: {0} 枚あります。")]
+    InvalidNumTiles(usize),
+
+    /// 同じ牌が 4 枚ではない。
+    #[error("山の {0} の数が変です: {1} 枚あります。")]
+    InvalidNumSameTiles(Tile, usize),
+}
+
+/// 山。
+///
+/// 生きている山 (ツモ山) と王牌からなる。王牌は先頭の 4 枚が嶺上牌で、その後ろにドラ表示牌と裏ドラ
+/// 表示牌が交互に 5 組並んでいる。
+#[derive(Debug, Clone)]
+pub struct Wall {
+    /// ツモ山。先頭から順にツモる。
+    live: Vec<Tile>,
+
+    /// 王牌。
+    dead: Vec<Tile>,
+
+    /// 嶺上牌をツモった回数。
+    num_lingshang: usize,
+
+    /// めくられているドラ表示牌の数。
+    num_dora_indicators: usize,
+}
+
+impl Wall {
+    /// ルールで決められた枚数の赤ドラを含む 136 枚の牌を並べる。
+    ///
+    /// 赤ドラは筒子・萬子・索子の順に一枚ずつ割り振る (`Ruleset::akadora`) 。
+    pub fn all_tiles(ruleset: &Ruleset) -> Vec<Tile> {
+        let num_red = |kind: TileKind| {
+            let index = match kind {
+                TileKind::Tongzi => 0,
+                TileKind::Wanzi => 1,
+                TileKind::Suozi => 2,
+                TileKind::Zipai => return 0,
+            };
+            (ruleset.akadora + 2 - index) / 3
+        };
+
+        let mut tiles = Vec::with_capacity(NUM_TILES);
+        for tile in Tile::all() {
+            for i in 0..4 {
+                let is_five = tile.order().map(|o| o.number()) == Some(5);
+                if is_five && i < num_red(tile.kind()) {
+                    tiles.push(tile.with_red(true).expect("5 can be red."));
+                } else {
+                    tiles.push(tile);
+                }
+            }
+        }
+
+        tiles
+    }
+
+    /// 牌を混ぜて新しい山を作る。
+    pub fn new<R: Rng + ?Sized>(rng: &mut R, ruleset: &Ruleset) -> Wall {
+        let mut tiles = Wall::all_tiles(ruleset);
+        tiles.shuffle(rng);
+        Wall::from_tiles(tiles).expect("all tiles must be valid.")
+    }
+
+    /// 並び順を指定して山を作る。
+    ///
+    /// 先頭から 122 枚がツモ山でこの順にツモられ、残りの 14 枚が王牌となる。牌譜の再現などに使う。
+    pub fn from_tiles(mut tiles: Vec<Tile>) -> Result<Wall> {
+        if tiles.len() != NUM_TILES {
+            return Err(WallError::InvalidNumTiles(tiles.len()));
+        }
+
+        let mut counts = [0; NUM_KINDS];
+        for tile in &tiles {
+            counts[tile.index()] += 1;
+        }
+
+        if let Some(index) = (0..NUM_KINDS).find(|&i| counts[i] != 4) {
+            return Err(WallError::InvalidNumSameTiles(
+                Tile::from_index(index),
+                counts[index],
+            ));
+        }
+
+        let dead = tiles.split_off(NUM_TILES - NUM_DEAD_WALL);
+
+        Ok(Wall {
+            live: tiles,
+            dead,
+            num_lingshang: 0,
+            num_dora_indicators: 1,
+        })
+    }
+
+    /// ツモ山から一枚ツモる。ツモ山が尽きていれば None となる。
+    pub fn draw(&mut self) -> Option<Tile> {
+        if self.live.is_empty() {
+            None
+        } else {
+            Some(self.live.remove(0))
+        }
+    }
+
+    /// 嶺上牌をツモる。既に 4 回ツモっていれば None となる。
+    ///
+    /// 王牌の枚数を保つため、ツモ山の最後の牌 (海底牌) が王牌に移る。
+    pub fn draw_lingshang(&mut self) -> Option<Tile> {
+        if self.num_lingshang >= NUM_LINGSHANG || self.live.is_empty() {
+            return None;
+        }
+
+        let tile = self.dead[self.num_lingshang];
+        self.num_lingshang += 1;
+        self.live.pop();
+
+        Some(tile)
+    }
+
+    /// 新しいドラ表示牌をめくる (槓ドラ) 。
+    pub fn reveal_dora_indicator(&mut self) {
+        self.num_dora_indicators = (self.num_dora_indicators + 1).min(NUM_LINGSHANG + 1);
+    }
+
+    /// めくられているドラ表示牌。
+    pub fn dora_indicators(&self) -> Tiles {
+        (0..self.num_dora_indicators)
+            .map(|i| self.dead[NUM_LINGSHANG + 2 * i])
+            .collect()
+    }
+
+    /// めくられているドラ表示牌に対応する裏ドラ表示牌。
+    pub fn uradora_indicators(&self) -> Tiles {
+        (0..self.num_dora_indicators)
+            .map(|i| self.dead[NUM_LINGSHANG + 2 * i + 1])
+            .collect()
+    }
+
+    /// ツモ山の残り枚数。
+    pub fn remaining(&self) -> usize {
+        self.live.len()
+    }
+
+    /// 嶺上牌をツモった回数。
+    pub fn num_gangs(&self) -> usize {
+        self.num_lingshang
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    #[test]
+    fn all_tiles() {
+        let num_red = |akadora: u32, kind: Option<TileKind>| {
+            let ruleset = Ruleset {
+                akadora,
+                ..Ruleset::default()
+            };
+            let tiles = Wall::all_tiles(&ruleset);
+            assert_eq!(tiles.len(), NUM_TILES);
+            (tiles.iter())
+                .filter(|t| t.is_red() && kind.map_or(true, |k| t.kind() == k))
+                .count()
+        };
+
+        assert_eq!(num_red(3, None), 3);
+        assert_eq!(num_red(0, None), 0);
+        assert_eq!(num_red(4, None), 4);
+        assert_eq!(num_red(4, Some(TileKind::Tongzi)), 2);
+        assert_eq!(num_red(4, Some(TileKind::Wanzi)), 1);
+    }
+
+    #[test]
+    fn draw() {
+        let mut wall = Wall::new(&mut StdRng::seed_from_u64(0), &Ruleset::default());
+        assert_eq!(wall.remaining(), NUM_TILES - NUM_DEAD_WALL);
+        assert_eq!(wall.dora_indicators().len(), 1);
+
+        wall.draw().unwrap();
+        wall.draw_lingshang().unwrap();
+        wall.reveal_dora_indicator();
+        assert_eq!(wall.remaining(), NUM_TILES - NUM_DEAD_WALL - 2);
+        assert_eq!(wall.dora_indicators().len(), 2);
+        assert_eq!(wall.uradora_indicators().len(), 2);
+        assert_eq!(wall.num_gangs(), 1);
+    }
+
+    #[test]
+    fn from_tiles() {
+        let mut tiles = Wall::all_tiles(&Ruleset::default());
+        assert!(Wall::from_tiles(tiles.clone()).is_ok());
+
+        tiles[0] = tiles[135];
+        assert!(Wall::from_tiles(tiles.clone()).is_err());
+
+        tiles.pop();
+        assert!(Wall::from_tiles(tiles).is_err());
+    }
+}
@@ -0,0 +1,166 @@
+//! フリテン (振聴) を判定する。
+
+use crate::context::Lizhi;
+use crate::tile::Tile;
+use crate::tilesets::TingpaiTilesets;
+use crate::tingpai::machi_tiles;
+use log::debug;
+use std::fmt;
+
+/// フリテンの種類。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Zhenting {
+    /// 捨て牌によるフリテン。自分の捨て牌に待ち牌がある。
+    Shepai,
+
+    /// 同巡内のフリテン。最後に自分が打牌してから待ち牌を見逃した。次の自分の打牌で解消する。
+    Tongxun,
+
+    /// 立直後のフリテン。立直してから待ち牌を見逃した。その局の間は解消しない。
+    Lizhi,
+}
+
+impl Zhenting {
+    pub fn display_en(self) -> ZhentingDisplayEn {
+        ZhentingDisplayEn(self)
+    }
+}
+
+impl fmt::Display for Zhenting {
+    fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Zhenting::Shepai => write!(b, "捨て牌フリテン"),
+            Zhenting::Tongxun => write!(b, "同巡内フリテン"),
+            Zhenting::Lizhi => write!(b, "立直後フリテン"),
+        }
+    }
+}
+
+pub struct ZhentingDisplayEn(Zhenting);
+
+impl fmt::Display for ZhentingDisplayEn {
+    fn fmt(&self, b: &mut fmt::Formatter) -> fmt::Result {
+        let ZhentingDisplayEn(zhenting) = self;
+        match zhenting {
+            Zhenting::Shepai => write!(b, "Permanent Furiten"),
+            Zhenting::Tongxun => write!(b, "Temporary Furiten"),
+            Zhenting::Lizhi => write!(b, "Riichi Furiten"),
+        }
+    }
+}
+
+/// 聴牌している手牌がフリテンかどうかを判定する。フリテンでなければ None となる。
+///
+/// 捨て牌や見逃した牌は `Context` に記録されているものを使う。複数のフリテンにあたる場合は、捨て牌
+/// によるもの・立直後のもの・同巡内のものの順に優先する。
+pub fn zhenting(tingpai: &TingpaiTilesets) -> Option<Zhenting> {
+    let context = &tingpai.context;
+    let machis = machi_tiles(tingpai);
+    let contains_machi = |tiles: &[Tile]| tiles.iter().any(|tile| machis.contains(tile));
+
+    let res = if contains_machi(&context.discards) {
+        Some(Zhenting::Shepai)
+    } else if context.lizhi != Lizhi::None && contains_machi(&context.passed_after_lizhi) {
+        Some(Zhenting::Lizhi)
+    } else if contains_machi(&context.passed) {
+        Some(Zhenting::Tongxun)
+    } else {
+        None
+    };
+
+    if let Some(zhenting) = res {
+        debug!("{} は{}です。", tingpai, zhenting);
+    }
+
+    res
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::context::Context;
+    use crate::tilesets::{Tilesets, TilesetsError};
+
+    fn parse(from: &str, context: Context) -> TingpaiTilesets {
+        let tilesets = from
+            .split_whitespace()
+            .map(|tileset| tileset.parse().unwrap())
+            .collect();
+
+        TingpaiTilesets::new(context, tilesets).unwrap()
+    }
+
+    fn tiles(tiles: &str) -> Vec<Tile> {
+        tiles.parse::<crate::tiles::Tiles>().unwrap().into_inner()
+    }
+
+    #[test]
+    fn shepai() {
+        let context = Context {
+            discards: tiles("9s1m"),
+            ..Context::default()
+        };
+        let tingpai = parse("2m3m4p5p6p7s8s9s東東東南南", context);
+        assert_eq!(zhenting(&tingpai), Some(Zhenting::Shepai));
+    }
+
+    #[test]
+    fn tongxun() {
+        let context = Context {
+            discards: tiles("9s"),
+            passed: tiles("4m"),
+            ..Context::default()
+        };
+        let tingpai = parse("2m3m4p5p6p7s8s9s東東東南南", context);
+        assert_eq!(zhenting(&tingpai), Some(Zhenting::Tongxun));
+        assert_eq!(Zhenting::Tongxun.to_string(), "同巡内フリテン");
+        assert_eq!(
+            Zhenting::Tongxun.display_en().to_string(),
+            "Temporary Furiten"
+        );
+    }
+
+    #[test]
+    fn lizhi() {
+        let context = Context {
+            lizhi: Lizhi::Lizhi,
+            passed_after_lizhi: tiles("1m"),
+            ..Context::default()
+        };
+        let tingpai = parse("2m3m4p5p6p7s8s9s東東東南南", context);
+        assert_eq!(zhenting(&tingpai), Some(Zhenting::Lizhi));
+    }
+
+    #[test]
+    fn not_zhenting() {
+        let context = Context {
+            discards: tiles("9s5m"),
+            passed: tiles("東"),
+            ..Context::default()
+        };
+        let tingpai = parse("2m3m4p5p6p7s8s9s東東東南南", context);
+        assert_eq!(zhenting(&tingpai), None);
+    }
+
+    #[test]
+    fn ronghe_on_zhenting() {
+        let context = Context {
+            discards: tiles("4m"),
+            ..Context::default()
+        };
+        let tilesets = |last: &str| {
+            vec![
+                "2m3m4p5p6p7s8s9s東東東南南".parse().unwrap(),
+                last.parse().unwrap(),
+            ]
+        };
+
+        match Tilesets::new(context.clone(), tilesets("ロン1m")) {
+            Err(TilesetsError::Zhenting(Zhenting::Shepai)) => {}
+            res => panic!("unexpected result: {:?}", res),
+        }
+
+        // ツモならアガれる。
+        assert!(Tilesets::new(context, tilesets("ツモ1m")).is_ok());
+    }
+}